@@ -0,0 +1,574 @@
+use crate::disassembler::format_operand;
+use crate::encoder::{
+    classify, condition_code, encode, encode_program, parse_number, parse_operand, Operand, CONDITION_NAMES,
+    REGISTERS_16, REGISTERS_32, REGISTERS_8,
+};
+use crate::memory::{check_code_size, CODE_BASE, CODE_SIZE, DATA_BASE};
+use crate::types::*;
+use std::collections::HashMap;
+
+/// 操作数中按关键字处理的单词（不区分大小写）
+const SIZE_KEYWORDS: [&str; 4] = ["BYTE", "WORD", "DWORD", "PTR"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Section {
+    Text,
+    Data,
+}
+
+/// 数据定义中的一项：数值、字符串或标签地址
+enum DataValue {
+    Number(i64),
+    Bytes(Vec<u8>),
+    Symbol(String),
+}
+
+/// 一条数据定义（db/dw/dd），地址在第一遍扫描时确定，标签值在最后回填
+struct DataDefinition {
+    address: u32,
+    size: usize,
+    values: Vec<DataValue>,
+    span: SourceSpan,
+}
+
+/// 代码段中的一条源指令
+struct SourceInstruction {
+    mnemonic: String,
+    operands: Vec<String>,
+    comment: Option<String>,
+    span: SourceSpan,
+}
+
+/// 汇编器：第一遍收集标签并为数据分配地址，第二遍规范化操作数并编码
+struct Assembler {
+    section: Section,
+    instructions: Vec<SourceInstruction>,
+    data: Vec<DataDefinition>,
+    data_size: u32,
+    /// 代码标签 → 所在指令的下标
+    code_labels: HashMap<String, usize>,
+    /// 数据标签 → 地址
+    data_labels: HashMap<String, u32>,
+    /// 尚未附着到指令上的代码标签
+    pending_labels: Vec<(String, SourceSpan)>,
+    errors: Vec<Diagnostic>,
+}
+
+/// 汇编 Intel 语法的源程序
+pub fn assemble(source: &str) -> AssemblyResult {
+    let mut assembler = Assembler {
+        section: Section::Text,
+        instructions: Vec::new(),
+        data: Vec::new(),
+        data_size: 0,
+        code_labels: HashMap::new(),
+        data_labels: HashMap::new(),
+        pending_labels: Vec::new(),
+        errors: Vec::new(),
+    };
+
+    let mut offset = 0;
+    for (number, line) in source.split('\n').enumerate() {
+        let span = SourceSpan {
+            start: offset,
+            end: offset + line.trim_end().len(),
+            line: number + 1,
+            column: 1,
+        };
+        assembler.scan_line(line, span);
+        offset += line.len() + 1;
+    }
+    assembler.finish()
+}
+
+/// 去掉注释（; 或 # 之后的内容，引号内除外），返回代码部分与注释
+fn split_comment(line: &str) -> (&str, Option<&str>) {
+    let mut quote = None;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            (None, ';' | '#') => return (&line[..i], Some(line[i + 1..].trim())),
+            _ => {}
+        }
+    }
+    (line, None)
+}
+
+/// 按逗号切分操作数，引号内的逗号除外
+fn split_operands(text: &str) -> Vec<String> {
+    let mut operands = Vec::new();
+    let mut current = String::new();
+    let mut quote = None;
+    for c in text.chars() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            (None, ',') => {
+                operands.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() || !operands.is_empty() {
+        operands.push(current.trim().to_string());
+    }
+    operands
+}
+
+fn is_identifier(text: &str) -> bool {
+    text.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_' || c == '.')
+        && text.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+}
+
+/// 数据定义伪指令对应的单元大小
+fn data_size(directive: &str) -> Option<usize> {
+    match directive.to_lowercase().as_str() {
+        "db" => Some(1),
+        "dw" => Some(2),
+        "dd" => Some(4),
+        _ => None,
+    }
+}
+
+/// 助记符的规范写法：统一别名与条件后缀，与反汇编器的输出一致
+fn canonical_mnemonic(mnemonic: &str) -> String {
+    let mnemonic = mnemonic.to_uppercase();
+    let alias = match mnemonic.as_str() {
+        "IRET" => "IRETD",
+        "PUSHA" => "PUSHAD",
+        "POPA" => "POPAD",
+        "PUSHF" => "PUSHFD",
+        "POPF" => "POPFD",
+        "SAL" => "SHL",
+        "LOOPZ" => "LOOPE",
+        "LOOPNZ" => "LOOPNE",
+        _ => "",
+    };
+    if !alias.is_empty() {
+        return alias.to_string();
+    }
+    for prefix in ["SET", "J"] {
+        if let Some(code) = mnemonic.strip_prefix(prefix).and_then(condition_code) {
+            return format!("{}{}", prefix, CONDITION_NAMES[code as usize]);
+        }
+    }
+    mnemonic
+}
+
+/// 以标签作为操作数的转移指令
+fn takes_label(mnemonic: &str) -> bool {
+    mnemonic.starts_with('J') || mnemonic.starts_with("LOOP") || mnemonic == "CALL"
+}
+
+impl Assembler {
+    fn scan_line(&mut self, line: &str, span: SourceSpan) {
+        let (code, comment) = split_comment(line);
+        let mut rest = code.trim();
+
+        // 行首的 "标签:"，一行可以有多个
+        while let Some((name, after)) = rest.split_once(':') {
+            let name = name.trim();
+            if !is_identifier(name) {
+                break;
+            }
+            self.define_label(name, span);
+            rest = after.trim();
+        }
+        if rest.is_empty() {
+            return;
+        }
+
+        let (word, operands) = match rest.split_once(char::is_whitespace) {
+            Some((word, operands)) => (word, operands.trim()),
+            None => (rest, ""),
+        };
+        match (word.to_lowercase().as_str(), operands.to_lowercase().as_str()) {
+            (".data", "") | ("section", ".data") | (".section", ".data") => {
+                self.section = Section::Data;
+                return;
+            }
+            (".text", "") | ("section", ".text") | (".section", ".text") => {
+                self.section = Section::Text;
+                return;
+            }
+            _ => {}
+        }
+
+        // 数据定义："db 1, 2"，或 NASM 风格不带冒号的 "msg db 'hi', 0"
+        if let Some(size) = data_size(word) {
+            return self.define_data(size, operands, span);
+        }
+        if let Some((directive, values)) = operands.split_once(char::is_whitespace).or(Some((operands, ""))) {
+            if let (Some(size), true) = (data_size(directive), is_identifier(word)) {
+                self.define_label(word, span);
+                return self.define_data(size, values.trim(), span);
+            }
+        }
+
+        if word.starts_with('.') {
+            self.errors.push(Diagnostic::error("E0501", format!("无法识别的伪指令 '{}'", word), span));
+            return;
+        }
+        if self.section == Section::Data {
+            self.errors.push(Diagnostic::error("E0501", "数据段中只能出现数据定义", span));
+            return;
+        }
+
+        let index = self.instructions.len();
+        for (label, _) in self.pending_labels.drain(..) {
+            self.code_labels.insert(label, index);
+        }
+        self.instructions.push(SourceInstruction {
+            mnemonic: canonical_mnemonic(word),
+            operands: split_operands(operands),
+            comment: comment.filter(|c| !c.is_empty()).map(str::to_string),
+            span,
+        });
+    }
+
+    fn define_label(&mut self, name: &str, span: SourceSpan) {
+        let defined = self.code_labels.contains_key(name)
+            || self.data_labels.contains_key(name)
+            || self.pending_labels.iter().any(|(label, _)| label == name);
+        if defined {
+            self.errors.push(Diagnostic::error("E0502", format!("标签 '{}' 重复定义", name), span));
+            return;
+        }
+        match self.section {
+            Section::Text => self.pending_labels.push((name.to_string(), span)),
+            Section::Data => {
+                self.data_labels.insert(name.to_string(), DATA_BASE + self.data_size);
+            }
+        }
+    }
+
+    fn define_data(&mut self, size: usize, text: &str, span: SourceSpan) {
+        if self.section != Section::Data {
+            self.errors.push(Diagnostic::error("E0501", "数据定义只能出现在 .data 段", span));
+            return;
+        }
+        let mut values = Vec::new();
+        let mut length = 0;
+        for item in split_operands(text) {
+            let quoted = item.len() >= 2
+                && ((item.starts_with('"') && item.ends_with('"')) || (item.starts_with('\'') && item.ends_with('\'')));
+            let value = if quoted && (size == 1 || item.len() == 3) {
+                // db 中的字符串逐字节展开；dw/dd 中只允许单个字符
+                let bytes = item.as_bytes()[1..item.len() - 1].to_vec();
+                match size {
+                    1 => DataValue::Bytes(bytes),
+                    _ => DataValue::Number(bytes[0] as i64),
+                }
+            } else if let Some(number) = parse_number(&item) {
+                let bits = size * 8;
+                if number < -(1 << (bits - 1)) || number >= (1 << bits) {
+                    self.errors.push(Diagnostic::error("E0505", format!("{} 超出 {} 位范围", number, bits), span));
+                    continue;
+                }
+                DataValue::Number(number)
+            } else if is_identifier(&item) {
+                DataValue::Symbol(item)
+            } else {
+                self.errors.push(Diagnostic::error("E0505", format!("无法解析数据 '{}'", item), span));
+                continue;
+            };
+            length += match &value {
+                DataValue::Bytes(bytes) => bytes.len(),
+                _ => size,
+            };
+            values.push(value);
+        }
+        if values.is_empty() && self.errors.last().is_none_or(|error| error.span != span) {
+            self.errors.push(Diagnostic::error("E0505", "数据定义缺少初值", span));
+        }
+        self.data.push(DataDefinition {
+            address: DATA_BASE + self.data_size,
+            size,
+            values,
+            span,
+        });
+        self.data_size += length as u32;
+    }
+
+    /// 规范化操作数：寄存器与长度说明转为大写，数字与字符常量转为十进制，数据标签替换为地址，
+    /// 内存操作数化简为 [基址+变址*比例+位移] 的标准形式。出错时返回诊断代码与信息
+    fn normalize_operand(&self, text: &str) -> Result<String, (&'static str, String)> {
+        let mut output = String::new();
+        let chars: Vec<char> = text.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            if c == '\'' || c == '"' {
+                let end = chars[i + 1..].iter().position(|&q| q == c).map(|p| i + 1 + p);
+                match end {
+                    Some(end) if end == i + 2 => output.push_str(&(chars[i + 1] as u32).to_string()),
+                    _ => return Err(("E0503", format!("无法解析字符常量 '{}'", text))),
+                }
+                i = end.unwrap() + 1;
+            } else if c.is_alphanumeric() || c == '_' || c == '.' {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '.') {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                let upper = word.to_uppercase();
+                if c.is_ascii_digit() {
+                    let value = parse_number(&word).ok_or_else(|| ("E0503", format!("无法解析数字 '{}'", word)))?;
+                    output.push_str(&value.to_string());
+                } else if is_register(&upper) || SIZE_KEYWORDS.contains(&upper.as_str()) {
+                    output.push_str(&upper);
+                } else if let Some(address) = self.data_labels.get(&word) {
+                    output.push_str(&address.to_string());
+                } else if self.code_labels.contains_key(&word) {
+                    return Err(("E0504", format!("代码标签 '{}' 只能用作跳转或调用的目标", word)));
+                } else {
+                    return Err(("E0504", format!("未定义的标签 '{}'", word)));
+                }
+            } else {
+                output.push(c);
+                i += 1;
+            }
+        }
+
+        let output = output.trim().to_string();
+        if !output.contains('[') {
+            return Ok(output);
+        }
+        let size = if output.starts_with("BYTE") {
+            Some("BYTE PTR")
+        } else if output.starts_with("DWORD") {
+            Some("DWORD PTR")
+        } else if output.starts_with("WORD") {
+            Some("WORD PTR")
+        } else {
+            None
+        };
+        match parse_operand(&output) {
+            Ok(memory @ Operand::Mem { .. }) => Ok(format_operand(&memory, size)),
+            _ => Err(("E0503", format!("无法解析内存操作数 '{}'", text))),
+        }
+    }
+
+    fn finish(mut self) -> AssemblyResult {
+        // 程序末尾的标签需要一条指令来承载
+        if let Some((_, span)) = self.pending_labels.first().cloned() {
+            let index = self.instructions.len();
+            for (label, _) in self.pending_labels.drain(..) {
+                self.code_labels.insert(label, index);
+            }
+            self.instructions.push(SourceInstruction {
+                mnemonic: "NOP".to_string(),
+                operands: Vec::new(),
+                comment: Some("空操作（标签占位）".to_string()),
+                span,
+            });
+        }
+
+        // 同一条指令上的多个标签：第一个附着在指令上，其余作为别名
+        let mut primary: HashMap<usize, String> = HashMap::new();
+        let mut labels: Vec<(&String, &usize)> = self.code_labels.iter().collect();
+        labels.sort_by_key(|(label, index)| (**index, self.instructions[**index].span.line, label.as_str()));
+        for (label, index) in labels {
+            primary.entry(*index).or_insert_with(|| label.clone());
+        }
+
+        let mut instructions = Vec::new();
+        let mut spans = Vec::new();
+        for (index, source) in self.instructions.iter().enumerate() {
+            let mut operands = Vec::new();
+            for operand in &source.operands {
+                let normalized = match self.code_labels.get(operand) {
+                    Some(target) if takes_label(&source.mnemonic) => Ok(primary[target].clone()),
+                    _ if takes_label(&source.mnemonic)
+                        && is_identifier(operand)
+                        && !is_register(operand)
+                        && !self.data_labels.contains_key(operand) =>
+                    {
+                        Err(("E0504", format!("未定义的标签 '{}'", operand)))
+                    }
+                    _ => self.normalize_operand(operand),
+                };
+                match normalized {
+                    Ok(operand) => operands.push(operand),
+                    Err((code, message)) => self.errors.push(Diagnostic::error(code, message, source.span)),
+                }
+            }
+            if operands.len() != source.operands.len() {
+                continue;
+            }
+
+            // 先单独编码以报告每条指令的错误，标签偏移由 encode_program 统一回填
+            let form = match encode(&source.mnemonic, &operands) {
+                Ok(encoding) => encoding.fields.first().map(|field| field.description.clone()).unwrap_or_default(),
+                Err(message) => {
+                    self.errors.push(Diagnostic::error("E0503", message, source.span));
+                    continue;
+                }
+            };
+            let memory = operands.iter().any(|operand| operand.contains('['));
+            let (instruction_type, cycles) = classify(&source.mnemonic, memory);
+            instructions.push(Instruction {
+                id: format!("{}_{}", source.mnemonic.to_lowercase(), instructions.len()),
+                instruction_type,
+                mnemonic: source.mnemonic.clone(),
+                operands,
+                machine_code: String::new(),
+                description: source.comment.clone().unwrap_or(form),
+                cycles,
+                label: primary.get(&index).cloned(),
+                encoding: Vec::new(),
+            });
+            spans.push(source.span);
+        }
+
+        if self.errors.is_empty() {
+            if let Err((index, message)) = encode_program(&mut instructions) {
+                self.errors.push(Diagnostic::error("E0503", message, spans[index]));
+            }
+        }
+
+        // 代码地址确定后回填数据中引用的标签
+        let mut code_addresses = Vec::with_capacity(instructions.len());
+        let mut address = CODE_BASE;
+        for instruction in &instructions {
+            code_addresses.push(address);
+            address += (instruction.machine_code.len() / 2) as u32;
+        }
+        // 错误标在第一条超出代码段的指令上
+        if let Err(message) = check_code_size((address - CODE_BASE) as u64) {
+            let ends = code_addresses.iter().skip(1).chain([&address]);
+            let index = ends.take_while(|end| (**end - CODE_BASE) as u64 <= CODE_SIZE).count();
+            self.errors.push(Diagnostic::error("E0506", message, spans[index]));
+        }
+        let mut bytes = Vec::with_capacity(self.data_size as usize);
+        for definition in &self.data {
+            debug_assert_eq!(DATA_BASE + bytes.len() as u32, definition.address);
+            for value in &definition.values {
+                let number = match value {
+                    DataValue::Bytes(text) => {
+                        bytes.extend_from_slice(text);
+                        continue;
+                    }
+                    DataValue::Number(number) => *number,
+                    DataValue::Symbol(name) => match (self.data_labels.get(name), self.code_labels.get(name)) {
+                        (Some(address), _) => *address as i64,
+                        (None, Some(index)) if self.errors.is_empty() => code_addresses[*index] as i64,
+                        (None, Some(_)) => 0,
+                        (None, None) => {
+                            self.errors.push(Diagnostic::error(
+                                "E0504",
+                                format!("未定义的标签 '{}'", name),
+                                definition.span,
+                            ));
+                            0
+                        }
+                    },
+                };
+                bytes.extend_from_slice(&(number as u32).to_le_bytes()[..definition.size]);
+            }
+        }
+
+        let mut symbols: Vec<AssemblySymbol> = self
+            .code_labels
+            .iter()
+            .filter(|(_, index)| **index < code_addresses.len())
+            .map(|(name, index)| AssemblySymbol {
+                name: name.clone(),
+                section: "text".to_string(),
+                address: code_addresses[*index] as u64,
+            })
+            .chain(self.data_labels.iter().map(|(name, address)| AssemblySymbol {
+                name: name.clone(),
+                section: "data".to_string(),
+                address: *address as u64,
+            }))
+            .collect();
+        symbols.sort_by(|a, b| (&a.section, a.address, &a.name).cmp(&(&b.section, b.address, &b.name)));
+
+        self.errors.sort_by_key(|error| error.span.start);
+        AssemblyResult {
+            success: self.errors.is_empty(),
+            instructions,
+            memory_image: MemoryImage {
+                base: DATA_BASE as u64,
+                bytes,
+            },
+            symbols,
+            errors: self.errors,
+        }
+    }
+}
+
+fn is_register(text: &str) -> bool {
+    let upper = text.to_uppercase();
+    [&REGISTERS_32[..], &REGISTERS_16[..], &REGISTERS_8[..]].iter().any(|registers| registers.contains(&upper.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::assembly_text;
+    use crate::cpu_simulator::{CPUSimulator, Simulator};
+    use crate::x86::X86;
+
+    fn errors(source: &str) -> Vec<(String, usize)> {
+        assemble(source).errors.iter().map(|error| (error.code.clone(), error.span.line)).collect()
+    }
+
+    #[test]
+    fn resolves_forward_labels_and_data_addresses() {
+        let source = "section .data\ncount: dd 3\nmsg db \"hi\", 0\ntable dd msg, done\n\
+                      section .text\nstart: mov ecx, [count]\njmp done\nnop\ndone: mov eax, msg ; 字符串地址\nhlt";
+        let result = assemble(source);
+        assert!(result.success, "{:?}", result.errors);
+        let listing: Vec<String> = result.instructions.iter().map(assembly_text).collect();
+        assert_eq!(listing, ["MOV    ECX, [8192]", "JMP    done", "NOP", "MOV    EAX, 8196", "HLT"]);
+        assert_eq!(result.instructions[3].description, "字符串地址");
+        // 第二遍回填：table 中是 msg 的数据地址与 done 的代码地址
+        assert_eq!(result.memory_image.base, DATA_BASE as u64);
+        assert_eq!(result.memory_image.bytes, [3, 0, 0, 0, b'h', b'i', 0, 0x04, 0x20, 0, 0, 12, 0, 0, 0]);
+        let symbols: Vec<(&str, &str, u64)> =
+            result.symbols.iter().map(|s| (s.name.as_str(), s.section.as_str(), s.address)).collect();
+        assert_eq!(
+            symbols,
+            [
+                ("count", "data", 0x2000),
+                ("msg", "data", 0x2004),
+                ("table", "data", 0x2007),
+                ("start", "text", 0),
+                ("done", "text", 12)
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_each_error_on_its_line() {
+        let source = "a: nop\na: nop\njmp nowhere\n.bss\nmov eax, a\nfoo eax\nsection .data\nx db 300\nnop";
+        let expected =
+            [("E0502", 2), ("E0504", 3), ("E0501", 4), ("E0504", 5), ("E0503", 6), ("E0505", 8), ("E0501", 9)];
+        let expected: Vec<(String, usize)> = expected.iter().map(|&(code, line)| (code.to_string(), line)).collect();
+        assert_eq!(errors(source), expected);
+        assert!(errors("db 1").iter().any(|(code, _)| code == "E0501"));
+    }
+
+    #[test]
+    fn assembled_programs_run_in_the_simulator() {
+        let source = "section .data\nvalues dd 5, 7, 9\nsum dd 0\nsection .text\n\
+                      mov ecx, 3\nmov esi, values\nnext: add eax, [esi]\nadd esi, 4\nloop next\nmov [sum], eax\nhlt";
+        let result = assemble(source);
+        assert!(result.success, "{:?}", result.errors);
+        let mut simulator = CPUSimulator::new(X86::default());
+        let length = result.instructions.len();
+        simulator.load_instructions(result.instructions).unwrap();
+        simulator.load_memory_image(result.memory_image);
+        while simulator.current_instruction_index < length {
+            simulator.step().unwrap();
+        }
+        assert_eq!(simulator.state.registers.general["EAX"], 21);
+        assert_eq!(simulator.state.memory.read(0x200C, 4, true).unwrap(), 21);
+    }
+}
@@ -0,0 +1,438 @@
+use crate::types::{
+    CacheAccess, CacheConfig, CacheLevelAccess, CacheLevelConfig, CacheLevelView, CacheLineView, CacheOperation,
+    CacheSetView, CacheView, MemoryState, ReplacementPolicy, WritePolicy,
+};
+
+/// 单级缓存的最大容量
+const MAX_CACHE_SIZE: u64 = 1 << 20;
+/// 最大相联度，伪 LRU 树的节点保存在一个 64 位整数中
+const MAX_ASSOCIATIVITY: u64 = 64;
+
+/// 检查缓存配置：各级的容量、行大小与相联度为 2 的幂，每组至少放得下一行
+pub fn check_config(config: &CacheConfig) -> Result<(), String> {
+    check_level("L1", &config.l1)?;
+    if let Some(l2) = &config.l2 {
+        check_level("L2", l2)?;
+    }
+    Ok(())
+}
+
+fn check_level(name: &str, config: &CacheLevelConfig) -> Result<(), String> {
+    for (what, value) in [("容量", config.size), ("行大小", config.line_size), ("相联度", config.associativity)]
+    {
+        if !value.is_power_of_two() {
+            return Err(format!("{} 的{} {} 不是 2 的幂", name, what, value));
+        }
+    }
+    if config.size > MAX_CACHE_SIZE || config.associativity > MAX_ASSOCIATIVITY {
+        return Err(format!("{} 的容量不能超过 {} 字节，相联度不能超过 {}", name, MAX_CACHE_SIZE, MAX_ASSOCIATIVITY));
+    }
+    if config.line_size < 4 || config.line_size * config.associativity > config.size {
+        return Err(format!("{} 的行大小至少为 4 字节，且行大小与相联度之积不能超过容量 {} 字节", name, config.size));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Line {
+    valid: bool,
+    dirty: bool,
+    tag: u64,
+    /// 最近一次访问与调入的时刻，用于 LRU 与 FIFO
+    last_used: u64,
+    inserted: u64,
+}
+
+/// 一级组相联缓存，只模拟标记与状态
+struct Cache {
+    name: &'static str,
+    config: CacheLevelConfig,
+    sets: Vec<Vec<Line>>,
+    /// 每组的伪 LRU 树：第 n 位为第 n 个节点（根为 1），指向下次替换的一侧
+    trees: Vec<u64>,
+    clock: u64,
+    /// 随机替换使用的伪随机数状态，复位后序列相同
+    seed: u64,
+    hits: u64,
+    misses: u64,
+    writebacks: u64,
+}
+
+impl Cache {
+    fn new(name: &'static str, config: CacheLevelConfig) -> Self {
+        let sets = (config.size / (config.line_size * config.associativity)) as usize;
+        Self {
+            name,
+            config,
+            sets: vec![vec![Line::default(); config.associativity as usize]; sets],
+            trees: vec![0; sets],
+            clock: 0,
+            seed: 0x2545_F491_4F6C_DD1D,
+            hits: 0,
+            misses: 0,
+            writebacks: 0,
+        }
+    }
+
+    /// 地址所在的组与标记
+    fn locate(&self, address: u64) -> (usize, u64) {
+        let (line, sets) = (address / self.config.line_size, self.sets.len() as u64);
+        ((line % sets) as usize, line / sets)
+    }
+
+    fn line_address(&self, set: usize, tag: u64) -> u64 {
+        (tag * self.sets.len() as u64 + set as u64) * self.config.line_size
+    }
+
+    fn find(&self, set: usize, tag: u64) -> Option<usize> {
+        self.sets[set].iter().position(|line| line.valid && line.tag == tag)
+    }
+
+    fn depth(&self) -> u32 {
+        self.config.associativity.trailing_zeros()
+    }
+
+    /// 访问一行：更新 LRU 时刻，并让伪 LRU 树上的节点都指向另一侧
+    fn touch(&mut self, set: usize, way: usize) {
+        self.clock += 1;
+        self.sets[set][way].last_used = self.clock;
+        let depth = self.depth();
+        let (tree, mut node) = (&mut self.trees[set], 1);
+        for level in (0..depth).rev() {
+            let bit = (way >> level) & 1;
+            match bit {
+                0 => *tree |= 1 << node,
+                _ => *tree &= !(1 << node),
+            }
+            node = 2 * node + bit;
+        }
+    }
+
+    /// 选出被替换的路：先用空行，否则按替换策略
+    fn victim(&mut self, set: usize) -> usize {
+        let lines = &self.sets[set];
+        if let Some(way) = lines.iter().position(|line| !line.valid) {
+            return way;
+        }
+        let oldest = |key: fn(&Line) -> u64| (0..lines.len()).min_by_key(|&way| key(&lines[way])).unwrap_or(0);
+        match self.config.replacement {
+            ReplacementPolicy::Lru => oldest(|line| line.last_used),
+            ReplacementPolicy::Fifo => oldest(|line| line.inserted),
+            ReplacementPolicy::Random => {
+                self.seed ^= self.seed << 13;
+                self.seed ^= self.seed >> 7;
+                self.seed ^= self.seed << 17;
+                (self.seed % lines.len() as u64) as usize
+            }
+            ReplacementPolicy::Plru => {
+                let (tree, mut node, mut way) = (self.trees[set], 1, 0);
+                for _ in 0..self.depth() {
+                    let bit = ((tree >> node) & 1) as usize;
+                    way = (way << 1) | bit;
+                    node = 2 * node + bit;
+                }
+                way
+            }
+        }
+    }
+
+    /// 调入一行，返回所在的路与被替换出的行（起始地址、是否为脏行）
+    fn fill(&mut self, set: usize, tag: u64) -> (usize, Option<(u64, bool)>) {
+        let way = self.victim(set);
+        let old = self.sets[set][way];
+        let evicted = old.valid.then(|| (self.line_address(set, old.tag), old.dirty));
+        self.clock += 1;
+        self.sets[set][way] = Line { valid: true, dirty: false, tag, last_used: self.clock, inserted: self.clock };
+        (way, evicted)
+    }
+
+    fn view(&self, memory: &MemoryState) -> CacheLevelView {
+        let sets = self
+            .sets
+            .iter()
+            .enumerate()
+            .map(|(index, lines)| CacheSetView {
+                index,
+                lines: lines
+                    .iter()
+                    .enumerate()
+                    .map(|(way, line)| {
+                        let address = self.line_address(index, line.tag);
+                        let bytes = match line.valid {
+                            true => (address..address + self.config.line_size).map(|a| memory.read_byte(a)).collect(),
+                            false => Vec::new(),
+                        };
+                        CacheLineView { way, valid: line.valid, dirty: line.dirty, tag: line.tag, address, bytes }
+                    })
+                    .collect(),
+            })
+            .collect();
+        let total = self.hits + self.misses;
+        CacheLevelView {
+            level: self.name.to_string(),
+            config: self.config,
+            hits: self.hits,
+            misses: self.misses,
+            hit_rate: if total == 0 { 0.0 } else { self.hits as f64 / total as f64 },
+            writebacks: self.writebacks,
+            sets,
+        }
+    }
+}
+
+/// 位于内存之前的缓存层次（L1 与可选的 L2）。缓存只决定访问的延迟，数据始终直接读写内存；
+/// 脏行写回由写缓冲完成，不计入访问延迟
+pub struct CacheHierarchy {
+    config: CacheConfig,
+    levels: Vec<Cache>,
+}
+
+impl Default for CacheHierarchy {
+    fn default() -> Self {
+        Self::new(CacheConfig::default())
+    }
+}
+
+impl CacheHierarchy {
+    pub fn new(config: CacheConfig) -> Self {
+        let mut levels = vec![Cache::new("L1", config.l1)];
+        levels.extend(config.l2.map(|l2| Cache::new("L2", l2)));
+        Self { config, levels }
+    }
+
+    pub fn config(&self) -> CacheConfig {
+        self.config
+    }
+
+    /// 访问 [address, address + size)；跨越 L1 缓存行时按行分开访问
+    pub fn access(&mut self, address: u64, size: u64, write: bool) -> Vec<CacheAccess> {
+        let line_size = self.config.l1.line_size;
+        let end = address + size.max(1);
+        (address / line_size..=(end - 1) / line_size)
+            .map(|line| {
+                let start = (line * line_size).max(address);
+                let (mut levels, mut memory) = (Vec::new(), false);
+                let operation = if write { CacheOperation::Write } else { CacheOperation::Read };
+                let latency = self.access_level(0, start, operation, &mut levels, &mut memory);
+                let size = ((line + 1) * line_size).min(end) - start;
+                CacheAccess { address: start, size, write, levels, memory, latency }
+            })
+            .collect()
+    }
+
+    /// 在第 level 级访问地址所在的行，返回所需的周期数；level 超出各级缓存时访问内存
+    fn access_level(
+        &mut self,
+        level: usize,
+        address: u64,
+        operation: CacheOperation,
+        report: &mut Vec<CacheLevelAccess>,
+        memory: &mut bool,
+    ) -> u64 {
+        let Some(cache) = self.levels.get_mut(level) else {
+            *memory = true;
+            return self.config.memory_latency;
+        };
+        let config = cache.config;
+        let (set, tag) = cache.locate(address);
+        let found = cache.find(set, tag);
+        match found {
+            Some(_) => cache.hits += 1,
+            None => cache.misses += 1,
+        }
+        let entry = report.len();
+        report.push(CacheLevelAccess {
+            level: cache.name.to_string(),
+            operation,
+            line_address: cache.line_address(set, tag),
+            set,
+            tag,
+            hit: found.is_some(),
+            evicted: None,
+            evicted_dirty: false,
+        });
+
+        let write = operation != CacheOperation::Read;
+        let mut latency = config.hit_latency;
+        let way = match found {
+            Some(way) => Some(way),
+            // 不命中时从下一级调入整行；写不命中且不按写分配时不调入
+            None if !write || config.write_allocate => {
+                latency += self.access_level(level + 1, address, CacheOperation::Read, report, memory);
+                let (way, evicted) = self.levels[level].fill(set, tag);
+                if let Some((victim, dirty)) = evicted {
+                    (report[entry].evicted, report[entry].evicted_dirty) = (Some(victim), dirty);
+                    if dirty {
+                        self.levels[level].writebacks += 1;
+                        self.access_level(level + 1, victim, CacheOperation::WriteBack, report, memory);
+                    }
+                }
+                Some(way)
+            }
+            None => None,
+        };
+        if let Some(way) = way {
+            self.levels[level].touch(set, way);
+        }
+        if write {
+            match (way, config.write_policy) {
+                (Some(way), WritePolicy::WriteBack) => self.levels[level].sets[set][way].dirty = true,
+                _ => latency += self.access_level(level + 1, address, operation, report, memory),
+            }
+        }
+        latency
+    }
+
+    pub fn view(&self, memory: &MemoryState) -> CacheView {
+        CacheView {
+            enabled: self.config.enabled,
+            memory_latency: self.config.memory_latency,
+            levels: self.levels.iter().map(|cache| cache.view(memory)).collect(),
+        }
+    }
+}
+
+/// 一次访问经过各级的情况，如 “0x3E8 读：L1 不命中，L2 命中”
+pub fn describe(access: &CacheAccess) -> String {
+    let mut steps: Vec<String> = access
+        .levels
+        .iter()
+        .map(|level| {
+            let result = match (level.operation, level.hit) {
+                (CacheOperation::WriteBack, _) => "接收写回".to_string(),
+                (_, true) => "命中".to_string(),
+                (_, false) => "不命中".to_string(),
+            };
+            match level.evicted {
+                Some(victim) if level.evicted_dirty => format!("{} {}，替换出脏行 0x{:X}", level.level, result, victim),
+                _ => format!("{} {}", level.level, result),
+            }
+        })
+        .collect();
+    if access.memory {
+        steps.push("访问内存".to_string());
+    }
+    let operation = if access.write { "写" } else { "读" };
+    format!("0x{:X} {}：{}", access.address, operation, steps.join("，"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 只有 L1 的缓存：行大小 16 字节，hit_latency 为 1，内存延迟 100
+    fn hierarchy(size: u64, associativity: u64, replacement: ReplacementPolicy) -> CacheHierarchy {
+        let l1 = CacheLevelConfig {
+            size,
+            line_size: 16,
+            associativity,
+            replacement,
+            write_policy: WritePolicy::WriteBack,
+            write_allocate: true,
+            hit_latency: 1,
+        };
+        CacheHierarchy::new(CacheConfig { enabled: true, l1, l2: None, memory_latency: 100 })
+    }
+
+    /// 读取一个字，返回 L1 的访问结果
+    fn read(cache: &mut CacheHierarchy, address: u64) -> CacheLevelAccess {
+        let accesses = cache.access(address, 4, false);
+        assert_eq!(accesses.len(), 1);
+        accesses[0].levels[0].clone()
+    }
+
+    #[test]
+    fn hit_and_miss() {
+        let mut cache = hierarchy(64, 2, ReplacementPolicy::Lru);
+        let miss = cache.access(0x20, 4, false).remove(0);
+        assert!(!miss.levels[0].hit && miss.memory);
+        assert_eq!((miss.levels[0].set, miss.levels[0].tag, miss.latency), (0, 1, 101));
+        // 同一行中的其他字节命中，不访问内存
+        let hit = cache.access(0x2C, 4, false).remove(0);
+        assert!(hit.levels[0].hit && !hit.memory);
+        assert_eq!(hit.latency, 1);
+        // 下一行映射到另一组
+        assert_eq!(read(&mut cache, 0x30).set, 1);
+        let view = cache.view(&MemoryState::default());
+        assert_eq!((view.levels[0].hits, view.levels[0].misses), (1, 2));
+    }
+
+    #[test]
+    fn access_across_lines() {
+        let mut cache = hierarchy(64, 2, ReplacementPolicy::Lru);
+        let accesses = cache.access(0x0C, 8, false);
+        let parts: Vec<_> = accesses.iter().map(|access| (access.address, access.size)).collect();
+        assert_eq!(parts, [(0x0C, 4), (0x10, 4)]);
+    }
+
+    #[test]
+    fn lru_replaces_least_recently_used() {
+        // 两路两组：0x00、0x20、0x40 都映射到第 0 组
+        let mut cache = hierarchy(64, 2, ReplacementPolicy::Lru);
+        read(&mut cache, 0x00);
+        read(&mut cache, 0x20);
+        assert!(read(&mut cache, 0x00).hit);
+        assert_eq!(read(&mut cache, 0x40).evicted, Some(0x20));
+        assert!(read(&mut cache, 0x00).hit);
+        assert!(!read(&mut cache, 0x20).hit);
+    }
+
+    #[test]
+    fn fifo_replaces_first_inserted() {
+        let mut cache = hierarchy(64, 2, ReplacementPolicy::Fifo);
+        read(&mut cache, 0x00);
+        read(&mut cache, 0x20);
+        assert!(read(&mut cache, 0x00).hit);
+        assert_eq!(read(&mut cache, 0x40).evicted, Some(0x00));
+    }
+
+    #[test]
+    fn plru_follows_tree() {
+        // 四路一组：依次调入四路后再访问第 0 路，树指向右半边中较早访问的第 2 路
+        let mut cache = hierarchy(64, 4, ReplacementPolicy::Plru);
+        for address in [0x00, 0x10, 0x20, 0x30, 0x00] {
+            read(&mut cache, address);
+        }
+        assert_eq!(read(&mut cache, 0x40).evicted, Some(0x20));
+    }
+
+    #[test]
+    fn dirty_line_written_back() {
+        let mut cache = hierarchy(64, 2, ReplacementPolicy::Lru);
+        let write = cache.access(0x00, 4, true).remove(0);
+        assert!(!write.levels[0].hit);
+        read(&mut cache, 0x20);
+        let access = cache.access(0x40, 4, false).remove(0);
+        assert_eq!(access.levels[0].evicted, Some(0x00));
+        assert!(access.levels[0].evicted_dirty);
+        // 写回由写缓冲完成，不计入延迟
+        assert_eq!(access.latency, 101);
+        let view = cache.view(&MemoryState::default());
+        assert_eq!(view.levels[0].writebacks, 1);
+        assert!(view.levels[0].sets[0].lines.iter().all(|line| !line.dirty));
+    }
+
+    #[test]
+    fn write_through_without_allocate() {
+        let config = hierarchy(64, 2, ReplacementPolicy::Lru).config();
+        let l1 = CacheLevelConfig { write_policy: WritePolicy::WriteThrough, write_allocate: false, ..config.l1 };
+        let mut cache = CacheHierarchy::new(CacheConfig { l1, ..config });
+        let write = cache.access(0x00, 4, true).remove(0);
+        assert!(write.memory);
+        assert_eq!(write.latency, 101);
+        // 写不命中没有调入该行
+        assert!(!read(&mut cache, 0x00).hit);
+        let write = cache.access(0x00, 4, true).remove(0);
+        assert!(write.levels[0].hit && write.memory);
+    }
+
+    #[test]
+    fn rejects_invalid_config() {
+        let mut config = CacheConfig::default();
+        assert!(check_config(&config).is_ok());
+        config.l1.associativity = 3;
+        assert!(check_config(&config).is_err());
+        config.l1 = CacheLevelConfig { associativity: 4, size: 32, ..config.l1 };
+        assert!(check_config(&config).is_err());
+    }
+}
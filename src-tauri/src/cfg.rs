@@ -0,0 +1,335 @@
+use crate::compiler::assembly_text;
+use crate::types::*;
+use std::collections::{BTreeSet, HashMap};
+
+/// 条件转移：成立时跳转，否则顺序执行
+fn is_conditional(mnemonic: &str) -> bool {
+    (mnemonic.starts_with('J') && mnemonic != "JMP") || mnemonic.starts_with("LOOP")
+}
+
+/// 结束基本块的指令：各种跳转、返回与停机
+fn ends_block(mnemonic: &str) -> bool {
+    mnemonic.starts_with('J') || mnemonic.starts_with("LOOP") || mnemonic == "RET" || mnemonic == "HLT"
+}
+
+/// 由编译得到的指令序列构建控制流图，并计算支配关系与自然循环
+pub fn build_cfg(instructions: &[Instruction]) -> ControlFlowGraph {
+    let labels: HashMap<&str, usize> = instructions
+        .iter()
+        .enumerate()
+        .filter_map(|(i, instruction)| instruction.label.as_deref().map(|label| (label, i)))
+        .collect();
+    let target = |instruction: &Instruction| {
+        instruction
+            .operands
+            .first()
+            .and_then(|operand| labels.get(operand.as_str()).copied())
+    };
+
+    // 块首：第一条指令、跳转或调用的目标、转移指令的下一条
+    let mut leaders = BTreeSet::new();
+    if !instructions.is_empty() {
+        leaders.insert(0);
+    }
+    for (i, instruction) in instructions.iter().enumerate() {
+        let mnemonic = instruction.mnemonic.as_str();
+        if ends_block(mnemonic) || mnemonic == "CALL" {
+            leaders.extend(target(instruction));
+        }
+        if ends_block(mnemonic) && i + 1 < instructions.len() {
+            leaders.insert(i + 1);
+        }
+    }
+    leaders.extend(labels.values().copied());
+
+    let starts: Vec<usize> = leaders.into_iter().collect();
+    let mut block_of = vec![0; instructions.len()];
+    let mut blocks: Vec<BasicBlock> = starts
+        .iter()
+        .enumerate()
+        .map(|(id, &start)| {
+            let end = starts.get(id + 1).map_or(instructions.len(), |next| *next) - 1;
+            block_of[start..=end].iter_mut().for_each(|b| *b = id);
+            BasicBlock {
+                id,
+                label: instructions[start].label.clone(),
+                function: String::new(),
+                start,
+                end,
+                instructions: instructions[start..=end].iter().map(assembly_text).collect(),
+                dominators: Vec::new(),
+                immediate_dominator: None,
+                is_loop_header: false,
+            }
+        })
+        .collect();
+
+    // 边：块尾的转移决定后继，块内的 CALL 产生调用边
+    let mut edges = Vec::new();
+    let mut entries = BTreeSet::new();
+    if !blocks.is_empty() {
+        entries.insert(0);
+    }
+    for block in &blocks {
+        let last = &instructions[block.end];
+        let next = (block.id + 1 < blocks.len()).then_some(block.id + 1);
+        let mut push = |to: Option<usize>, kind| {
+            if let Some(to) = to {
+                edges.push(CfgEdge { from: block.id, to, kind });
+            }
+        };
+        match last.mnemonic.as_str() {
+            "JMP" => push(target(last).map(|i| block_of[i]), CfgEdgeKind::Jump),
+            "RET" | "HLT" => {}
+            mnemonic if is_conditional(mnemonic) => {
+                push(target(last).map(|i| block_of[i]), CfgEdgeKind::Taken);
+                push(next, CfgEdgeKind::NotTaken);
+            }
+            _ => push(next, CfgEdgeKind::Fallthrough),
+        }
+
+        let mut callees = BTreeSet::new();
+        for instruction in &instructions[block.start..=block.end] {
+            if instruction.mnemonic == "CALL" {
+                callees.extend(target(instruction).map(|i| block_of[i]));
+            }
+        }
+        for callee in callees {
+            entries.insert(callee);
+            push(Some(callee), CfgEdgeKind::Call);
+        }
+    }
+
+    // 函数划分：入口块或调用目标开始一个新函数
+    let mut function = String::from("entry");
+    for block in &mut blocks {
+        if entries.contains(&block.id) {
+            function = block.label.clone().unwrap_or_else(|| format!("B{}", block.id));
+        }
+        block.function = function.clone();
+    }
+
+    let successors = |id: usize| {
+        edges
+            .iter()
+            .filter(move |edge| edge.from == id && edge.kind != CfgEdgeKind::Call)
+            .map(|edge| edge.to)
+    };
+    let dominators = compute_dominators(blocks.len(), &entries, &successors);
+    for (block, doms) in blocks.iter_mut().zip(&dominators) {
+        block.dominators = doms.iter().copied().collect();
+        // 直接支配者是除自身外支配集合最大的支配者
+        block.immediate_dominator = doms
+            .iter()
+            .filter(|&&d| d != block.id)
+            .max_by_key(|&&d| dominators[d].len())
+            .copied();
+    }
+
+    // 回边 u → v（v 支配 u）确定一个自然循环：v 加上不经过 v 能到达 u 的所有块
+    let mut loops = Vec::new();
+    for edge in edges.iter().filter(|edge| edge.kind != CfgEdgeKind::Call) {
+        if !dominators[edge.from].contains(&edge.to) {
+            continue;
+        }
+        let mut body = BTreeSet::from([edge.to]);
+        let mut worklist = vec![edge.from];
+        while let Some(id) = worklist.pop() {
+            if body.insert(id) {
+                worklist.extend(
+                    edges
+                        .iter()
+                        .filter(|e| e.to == id && e.kind != CfgEdgeKind::Call)
+                        .map(|e| e.from),
+                );
+            }
+        }
+        blocks[edge.to].is_loop_header = true;
+        loops.push(NaturalLoop {
+            header: edge.to,
+            back_edge_from: edge.from,
+            blocks: body.into_iter().collect(),
+        });
+    }
+
+    let dot = to_dot(&blocks, &edges);
+    ControlFlowGraph { blocks, edges, loops, dot }
+}
+
+/// 迭代求解支配集合：dom(n) = {n} ∪ ⋂ dom(p)，p 为 n 的前驱。
+/// 每个函数入口各自求解，从入口不可达的块支配集合为空。
+fn compute_dominators<I>(count: usize, entries: &BTreeSet<usize>, successors: &dyn Fn(usize) -> I) -> Vec<BTreeSet<usize>>
+where
+    I: Iterator<Item = usize>,
+{
+    let mut dominators = vec![BTreeSet::new(); count];
+    let mut assigned = vec![false; count];
+
+    for &entry in entries {
+        if assigned[entry] {
+            continue;
+        }
+        // 从入口可达、且尚未划入其他函数的块
+        let mut region = BTreeSet::new();
+        let mut worklist = vec![entry];
+        while let Some(id) = worklist.pop() {
+            if !assigned[id] && region.insert(id) {
+                worklist.extend(successors(id));
+            }
+        }
+        let predecessors: HashMap<usize, Vec<usize>> = region
+            .iter()
+            .map(|&id| (id, region.iter().copied().filter(|&p| successors(p).any(|s| s == id)).collect()))
+            .collect();
+
+        for &id in &region {
+            assigned[id] = true;
+            dominators[id] = if id == entry { BTreeSet::from([entry]) } else { region.clone() };
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &id in region.iter().filter(|&&id| id != entry) {
+                let mut new_set = predecessors[&id]
+                    .iter()
+                    .map(|p| dominators[*p].clone())
+                    .reduce(|a, b| a.intersection(&b).copied().collect())
+                    .unwrap_or_default();
+                new_set.insert(id);
+                if new_set != dominators[id] {
+                    dominators[id] = new_set;
+                    changed = true;
+                }
+            }
+        }
+    }
+    dominators
+}
+
+/// 生成 Graphviz DOT：每个函数一个子图，条件边标注 T/F，调用边为虚线
+fn to_dot(blocks: &[BasicBlock], edges: &[CfgEdge]) -> String {
+    let escape = |text: &str| text.replace('\\', "\\\\").replace('"', "\\\"");
+    let mut dot = String::from("digraph CFG {\n    node [shape=box, fontname=\"monospace\"];\n");
+
+    let mut functions: Vec<&str> = Vec::new();
+    for block in blocks {
+        if !functions.contains(&block.function.as_str()) {
+            functions.push(&block.function);
+        }
+    }
+    for (i, function) in functions.iter().enumerate() {
+        dot.push_str(&format!("    subgraph cluster_{} {{\n        label=\"{}\";\n", i, escape(function)));
+        for block in blocks.iter().filter(|b| b.function == *function) {
+            let mut label = match &block.label {
+                Some(name) => format!("B{} ({})\\l", block.id, escape(name)),
+                None => format!("B{}\\l", block.id),
+            };
+            for line in &block.instructions {
+                label.push_str(&escape(line));
+                label.push_str("\\l");
+            }
+            let style = if block.is_loop_header { ", style=bold" } else { "" };
+            dot.push_str(&format!("        B{} [label=\"{}\"{}];\n", block.id, label, style));
+        }
+        dot.push_str("    }\n");
+    }
+
+    for edge in edges {
+        let attributes = match edge.kind {
+            CfgEdgeKind::Fallthrough | CfgEdgeKind::Jump => String::new(),
+            CfgEdgeKind::Taken => " [label=\"T\", color=green]".to_string(),
+            CfgEdgeKind::NotTaken => " [label=\"F\", color=red]".to_string(),
+            CfgEdgeKind::Call => " [label=\"call\", style=dashed]".to_string(),
+        };
+        dot.push_str(&format!("    B{} -> B{}{};\n", edge.from, edge.to, attributes));
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 每行一条指令，"标签: 助记符 操作数, ..." 形式
+    fn program(source: &str) -> Vec<Instruction> {
+        source
+            .lines()
+            .map(|line| {
+                let (label, text) = match line.split_once(':') {
+                    Some((label, text)) => (Some(label.trim().to_string()), text.trim()),
+                    None => (None, line.trim()),
+                };
+                let (mnemonic, operands) = text.split_once(' ').unwrap_or((text, ""));
+                Instruction {
+                    id: mnemonic.to_string(),
+                    instruction_type: InstructionType::Control,
+                    mnemonic: mnemonic.to_string(),
+                    operands: operands.split(',').map(|o| o.trim().to_string()).filter(|o| !o.is_empty()).collect(),
+                    machine_code: String::new(),
+                    description: String::new(),
+                    cycles: 1,
+                    label,
+                    encoding: Vec::new(),
+                }
+            })
+            .collect()
+    }
+
+    fn edges(cfg: &ControlFlowGraph) -> Vec<(usize, usize, CfgEdgeKind)> {
+        cfg.edges.iter().map(|edge| (edge.from, edge.to, edge.kind)).collect()
+    }
+
+    fn immediate_dominators(cfg: &ControlFlowGraph) -> Vec<Option<usize>> {
+        cfg.blocks.iter().map(|block| block.immediate_dominator).collect()
+    }
+
+    #[test]
+    fn splits_an_if_else_into_a_diamond() {
+        let cfg = build_cfg(&program("CMP EAX, 0\nJE else\nMOV EBX, 1\nJMP end\nelse: MOV EBX, 2\nend: HLT"));
+        let ranges: Vec<(usize, usize)> = cfg.blocks.iter().map(|block| (block.start, block.end)).collect();
+        assert_eq!(ranges, [(0, 1), (2, 3), (4, 4), (5, 5)]);
+        assert_eq!(
+            edges(&cfg),
+            [
+                (0, 2, CfgEdgeKind::Taken),
+                (0, 1, CfgEdgeKind::NotTaken),
+                (1, 3, CfgEdgeKind::Jump),
+                (2, 3, CfgEdgeKind::Fallthrough)
+            ]
+        );
+        // 汇合点只被入口支配
+        assert_eq!(immediate_dominators(&cfg), [None, Some(0), Some(0), Some(0)]);
+        assert_eq!(cfg.blocks[3].dominators, [0, 3]);
+        assert!(cfg.loops.is_empty());
+    }
+
+    #[test]
+    fn finds_nested_natural_loops() {
+        let cfg = build_cfg(&program(
+            "MOV ECX, 0\nouter: CMP ECX, 3\nJGE done\nMOV EDX, 0\ninner: CMP EDX, 2\nJGE next\nINC EDX\nJMP inner\n\
+             next: INC ECX\nJMP outer\ndone: HLT",
+        ));
+        assert_eq!(cfg.blocks.len(), 7);
+        assert_eq!(immediate_dominators(&cfg), [None, Some(0), Some(1), Some(2), Some(3), Some(3), Some(1)]);
+        let loops: Vec<(usize, usize, Vec<usize>)> =
+            cfg.loops.iter().map(|l| (l.header, l.back_edge_from, l.blocks.clone())).collect();
+        assert_eq!(loops, [(3, 4, vec![3, 4]), (1, 5, vec![1, 2, 3, 4, 5])]);
+        let headers: Vec<usize> = cfg.blocks.iter().filter(|b| b.is_loop_header).map(|b| b.id).collect();
+        assert_eq!(headers, [1, 3]);
+        assert!(cfg.dot.contains("B1 -> B6 [label=\"T\", color=green];"));
+        assert!(cfg.dot.contains("B3 [label=\"B3 (inner)\\lCMP    EDX, 2\\lJGE    next\\l\", style=bold];"));
+    }
+
+    #[test]
+    fn calls_start_new_functions() {
+        let cfg = build_cfg(&program("CALL f\nHLT\nf: MOV EAX, 1\nRET"));
+        assert_eq!(edges(&cfg), [(0, 1, CfgEdgeKind::Call)]);
+        let functions: Vec<&str> = cfg.blocks.iter().map(|block| block.function.as_str()).collect();
+        assert_eq!(functions, ["B0", "f"]);
+        // 每个函数单独求支配关系
+        assert_eq!(cfg.blocks[1].dominators, [1]);
+        assert!(cfg.dot.contains("subgraph cluster_1 {\n        label=\"f\";"));
+        assert!(cfg.dot.contains("B0 -> B1 [label=\"call\", style=dashed];"));
+    }
+}
@@ -1,11 +1,25 @@
+use crate::ir::{IrBuilder, IrFunction, IrInstruction, IrOp, IrOperand, IrProgram};
+use crate::isa::Isa;
+use crate::memory::{check_code_size, DATA_BASE};
+use crate::mips::Mips;
+use crate::mips_codegen::MipsCodeGenerator;
+use crate::optimizer::Optimizer;
+use crate::regalloc::{allocate_registers, RegisterAllocation, ALLOCATABLE_REGISTERS};
+use crate::riscv::RiscV;
+use crate::riscv_codegen::RiscvCodeGenerator;
+use crate::semantic::SemanticAnalyzer;
 use crate::types::*;
+use crate::x86::X86;
 use regex::Regex;
+use std::collections::HashMap;
+use std::time::Instant;
 
 /// 简单的词法分析器
 pub struct Lexer {
     input: String,
     position: usize,
     tokens: Vec<Token>,
+    diagnostics: Vec<Diagnostic>,
 }
 
 #[derive(Debug, Clone)]
@@ -13,6 +27,19 @@ pub struct Token {
     pub token_type: TokenType,
     pub value: String,
     pub position: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Token {
+    pub fn span(&self) -> SourceSpan {
+        SourceSpan {
+            start: self.position,
+            end: self.position + self.value.len(),
+            line: self.line,
+            column: self.column,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -21,10 +48,12 @@ pub enum TokenType {
     Identifier,
     Number,
     String,
+    Char,
     Operator,
     Delimiter,
     Whitespace,
     Comment,
+    Preprocessor,
 }
 
 impl Lexer {
@@ -33,59 +62,90 @@ impl Lexer {
             input,
             position: 0,
             tokens: Vec::new(),
+            diagnostics: Vec::new(),
         }
     }
 
-    pub fn tokenize(&mut self) -> Result<Vec<Token>, String> {
-        let keywords = vec!["int", "float", "char", "void", "if", "else", "while", "for", "return", "include", "stdio"];
-        let operators = vec!["=", "+", "-", "*", "/", "%", "==", "!=", "<", ">", "<=", ">=", "&&", "||", "!"];
-        let delimiters = vec![";", ",", "(", ")", "{", "}", "[", "]"];
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
 
-        // 简单的正则表达式匹配
-        let patterns = vec![
-            (r"#\w+", TokenType::Keyword),
-            (r"\b(int|float|char|void|if|else|while|for|return|include|stdio)\b", TokenType::Keyword),
+    pub fn tokenize(&mut self) -> Vec<Token> {
+        // 简单的正则表达式匹配，均锚定在当前位置
+        let patterns: Vec<(Regex, TokenType)> = vec![
+            (r"#[^\n]*", TokenType::Preprocessor),
+            (r"\b(int|float|char|void|if|else|while|for|return)\b", TokenType::Keyword),
             (r"\b[a-zA-Z_][a-zA-Z0-9_]*\b", TokenType::Identifier),
-            (r"\b\d+\b", TokenType::Number),
-            (r#""[^"]*""#, TokenType::String),
-            (r"[+\-*/=<>!&|%]+", TokenType::Operator),
+            (r"\b\d+\.\d+\b|\b\d+\b", TokenType::Number),
+            (r#""[^"\n]*""#, TokenType::String),
+            (r"'(?:\\.|[^'\\\n])'", TokenType::Char),
+            // 注释在运算符之前匹配，否则 // 会被拆成两个除号
+            (r"//[^\n]*", TokenType::Comment),
+            (r"==|!=|<=|>=|&&|\|\||[+\-*/=<>!%]", TokenType::Operator),
             (r"[;,(){}\[\]]", TokenType::Delimiter),
             (r"\s+", TokenType::Whitespace),
-            (r"//.*", TokenType::Comment),
-        ];
-
-        let mut pos = 0;
-        while pos < self.input.len() {
-            let mut matched = false;
-            
-            for (pattern, token_type) in &patterns {
-                let regex = Regex::new(pattern).unwrap();
-                if let Some(mat) = regex.find_at(&self.input, pos) {
-                    if mat.start() == pos {
-                        let value = mat.as_str().to_string();
-                        
-                        // 跳过空白字符和注释
-                        if *token_type != TokenType::Whitespace && *token_type != TokenType::Comment {
-                            self.tokens.push(Token {
-                                token_type: token_type.clone(),
-                                value,
-                                position: pos,
-                            });
-                        }
-                        
-                        pos = mat.end();
-                        matched = true;
-                        break;
+        ]
+        .into_iter()
+        .map(|(pattern, token_type)| (Regex::new(&format!(r"\A(?:{})", pattern)).unwrap(), token_type))
+        .collect();
+
+        let mut line = 1;
+        let mut line_start = 0;
+        while self.position < self.input.len() {
+            let pos = self.position;
+            let rest = &self.input[pos..];
+            let column = self.input[line_start..pos].chars().count() + 1;
+            let span = |len: usize| SourceSpan {
+                start: pos,
+                end: pos + len,
+                line,
+                column,
+            };
+
+            let matched = patterns
+                .iter()
+                .find_map(|(regex, token_type)| regex.find(rest).map(|mat| (mat.end(), token_type.clone())));
+
+            let len = match matched {
+                Some((len, token_type)) => {
+                    // 跳过空白字符和注释
+                    if token_type != TokenType::Whitespace && token_type != TokenType::Comment {
+                        self.tokens.push(Token {
+                            token_type,
+                            value: rest[..len].to_string(),
+                            position: pos,
+                            line,
+                            column,
+                        });
                     }
+                    len
+                }
+                None if rest.starts_with('"') => {
+                    let len = rest.find('\n').unwrap_or(rest.len());
+                    self.diagnostics.push(Diagnostic::error("E0002", "字符串字面量缺少结尾的引号", span(len)));
+                    len
+                }
+                None => {
+                    let ch = rest.chars().next().unwrap();
+                    self.diagnostics.push(Diagnostic::error(
+                        "E0001",
+                        format!("无法识别的字符 '{}'", ch),
+                        span(ch.len_utf8()),
+                    ));
+                    ch.len_utf8()
+                }
+            };
+
+            for (offset, ch) in rest[..len].char_indices() {
+                if ch == '\n' {
+                    line += 1;
+                    line_start = pos + offset + 1;
                 }
             }
-            
-            if !matched {
-                pos += 1; // 跳过无法识别的字符
-            }
+            self.position += len;
         }
 
-        Ok(self.tokens.clone())
+        self.tokens.clone()
     }
 }
 
@@ -93,6 +153,7 @@ impl Lexer {
 pub struct Parser {
     tokens: Vec<Token>,
     position: usize,
+    diagnostics: Vec<Diagnostic>,
 }
 
 #[derive(Debug, Clone)]
@@ -100,6 +161,7 @@ pub struct ASTNode {
     pub node_type: ASTNodeType,
     pub value: String,
     pub children: Vec<ASTNode>,
+    pub span: SourceSpan,
 }
 
 #[derive(Debug, Clone)]
@@ -108,165 +170,1354 @@ pub enum ASTNodeType {
     Function,
     Declaration,
     Assignment,
-    Expression,
     Statement,
     Block,
+    BinaryOp,
+    UnaryOp,
+    Identifier,
+    Number,
+    If,
+    While,
+    For,
+    Return,
+    Empty,
+    Parameter,
+    Call,
+    StringLiteral,
+    CharLiteral,
+    /// 语义分析插入的隐式类型转换，值为目标类型
+    Cast,
 }
 
+impl ASTNode {
+    pub fn new(node_type: ASTNodeType, value: impl Into<String>, children: Vec<ASTNode>) -> Self {
+        Self {
+            node_type,
+            value: value.into(),
+            children,
+            span: SourceSpan::default(),
+        }
+    }
+
+    /// 设置节点对应的源代码区间
+    pub fn at(mut self, span: SourceSpan) -> Self {
+        self.span = span;
+        self
+    }
+
+    /// 以树形文本输出语法树，供编译步骤展示
+    pub fn pretty(&self) -> String {
+        let mut output = format!("{:?} {}\n", self.node_type, self.value);
+        self.pretty_children("", &mut output);
+        output
+    }
+
+    fn pretty_children(&self, prefix: &str, output: &mut String) {
+        for (i, child) in self.children.iter().enumerate() {
+            let last = i + 1 == self.children.len();
+            output.push_str(&format!(
+                "{}{}{:?} {}\n",
+                prefix,
+                if last { "└─ " } else { "├─ " },
+                child.node_type,
+                child.value
+            ));
+            child.pretty_children(&format!("{}{}", prefix, if last { "   " } else { "│  " }), output);
+        }
+    }
+}
+
+fn is_type_keyword(value: &str) -> bool {
+    matches!(value, "int" | "char" | "float" | "void")
+}
+
+/// 二元运算符的优先级，数值越大结合越紧
+fn binary_precedence(op: &str) -> Option<u8> {
+    match op {
+        "||" => Some(1),
+        "&&" => Some(2),
+        "==" | "!=" => Some(3),
+        "<" | ">" | "<=" | ">=" => Some(4),
+        "+" | "-" => Some(5),
+        "*" | "/" | "%" => Some(6),
+        _ => None,
+    }
+}
+
+type ParseResult = Result<ASTNode, Diagnostic>;
+
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
         Self {
             tokens,
             position: 0,
+            diagnostics: Vec::new(),
         }
     }
 
-    pub fn parse(&mut self) -> Result<ASTNode, String> {
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// 解析整个程序；出错的语句记录诊断后跳过，继续解析后续代码
+    pub fn parse(&mut self) -> ASTNode {
         let mut program = ASTNode {
             node_type: ASTNodeType::Program,
             value: "program".to_string(),
             children: Vec::new(),
+            span: SourceSpan::default(),
         };
 
         while self.position < self.tokens.len() {
-            if let Ok(node) = self.parse_statement() {
-                program.children.push(node);
-            } else {
-                self.position += 1; // 跳过无法解析的token
+            // 预处理指令（#include 等）不参与编译
+            if self.peek().is_some_and(|t| t.token_type == TokenType::Preprocessor) {
+                self.position += 1;
+                continue;
+            }
+
+            let start = self.position;
+            match self.parse_top_level() {
+                Ok(node) => program.children.push(node),
+                Err(diagnostic) => {
+                    self.diagnostics.push(diagnostic);
+                    self.synchronize(start);
+                }
             }
         }
 
-        Ok(program)
+        if !self.tokens.is_empty() {
+            program.span = self.span_from(0);
+        }
+        program
+    }
+
+    /// 错误恢复：至少前进一个 token，然后跳到下一个 ';' 之后或 '}' 之前
+    fn synchronize(&mut self, statement_start: usize) {
+        if self.position == statement_start {
+            self.position += 1;
+        }
+        while let Some(token) = self.peek() {
+            if token.value == ";" {
+                self.position += 1;
+                break;
+            }
+            if token.value == "}" {
+                break;
+            }
+            self.position += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.position + offset)
+    }
+
+    fn check(&self, value: &str) -> bool {
+        self.peek().is_some_and(|t| t.value == value)
+    }
+
+    /// 从第 start 个 token 到上一个已消费 token 的源代码区间
+    fn span_from(&self, start: usize) -> SourceSpan {
+        let first = match self.tokens.get(start) {
+            Some(token) => token.span(),
+            None => return self.eof_span(),
+        };
+        let end = self.position.saturating_sub(1).max(start);
+        SourceSpan {
+            end: self.tokens.get(end).map_or(first.end, |t| t.span().end),
+            ..first
+        }
+    }
+
+    fn eof_span(&self) -> SourceSpan {
+        match self.tokens.last() {
+            Some(token) => {
+                let span = token.span();
+                SourceSpan {
+                    start: span.end,
+                    column: span.column + token.value.chars().count(),
+                    ..span
+                }
+            }
+            None => SourceSpan {
+                line: 1,
+                column: 1,
+                ..SourceSpan::default()
+            },
+        }
+    }
+
+    /// 在当前位置报告错误，输入结束时报告“意外结束”
+    fn error_here(&self, message: impl Into<String>) -> Diagnostic {
+        match self.peek() {
+            Some(token) => Diagnostic::error("E0101", message, token.span()),
+            None => Diagnostic::error("E0102", "代码意外结束", self.eof_span()),
+        }
+    }
+
+    fn expect(&mut self, value: &str) -> Result<(), Diagnostic> {
+        match self.peek() {
+            Some(token) if token.value == value => {
+                self.position += 1;
+                Ok(())
+            }
+            Some(token) => Err(self.error_here(format!("期望 '{}'，实际为 '{}'", value, token.value))),
+            None => Err(Diagnostic::error(
+                "E0102",
+                format!("期望 '{}'，但代码已结束", value),
+                self.eof_span(),
+            )),
+        }
+    }
+
+    fn expect_identifier(&mut self, what: &str) -> ParseResult {
+        match self.peek() {
+            Some(token) if token.token_type == TokenType::Identifier => {
+                let node = ASTNode::new(ASTNodeType::Identifier, token.value.clone(), Vec::new()).at(token.span());
+                self.position += 1;
+                Ok(node)
+            }
+            Some(token) => Err(self.error_here(format!("期望{}，实际为 '{}'", what, token.value))),
+            None => Err(self.error_here(what)),
+        }
+    }
+
+    /// 顶层：函数定义或普通语句（全局变量声明等）
+    fn parse_top_level(&mut self) -> ParseResult {
+        let is_function = self.peek().is_some_and(|t| is_type_keyword(&t.value))
+            && self.peek_at(1).is_some_and(|t| t.token_type == TokenType::Identifier)
+            && self.peek_at(2).is_some_and(|t| t.value == "(");
+
+        if is_function {
+            self.parse_function()
+        } else {
+            self.parse_statement()
+        }
     }
 
-    fn parse_statement(&mut self) -> Result<ASTNode, String> {
-        if self.position >= self.tokens.len() {
-            return Err("Unexpected end of input".to_string());
+    /// 类型 名称(类型 参数, ...) { ... }
+    fn parse_function(&mut self) -> ParseResult {
+        let start = self.position;
+        let return_type = self.tokens[self.position].value.clone();
+        self.position += 1;
+        let name = self.expect_identifier("函数名")?;
+        self.expect("(")?;
+
+        let mut children = vec![name];
+        // 允许 f(void) 表示无参数
+        if self.check("void") && self.peek_at(1).is_some_and(|t| t.value == ")") {
+            self.position += 1;
+        }
+        while !self.check(")") {
+            if children.len() > 1 {
+                self.expect(",")?;
+            }
+            let param_start = self.position;
+            let param_type = match self.peek() {
+                Some(token) if is_type_keyword(&token.value) => token.value.clone(),
+                Some(token) => return Err(self.error_here(format!("期望参数类型，实际为 '{}'", token.value))),
+                None => return Err(self.error_here("参数列表缺少 ')'")),
+            };
+            self.position += 1;
+            let param_name = self.expect_identifier("参数名")?;
+            children.push(
+                ASTNode::new(ASTNodeType::Parameter, param_type, vec![param_name]).at(self.span_from(param_start)),
+            );
         }
+        self.expect(")")?;
+
+        children.push(self.parse_block()?);
+        Ok(ASTNode::new(ASTNodeType::Function, return_type, children).at(self.span_from(start)))
+    }
+
+    fn parse_statement(&mut self) -> ParseResult {
+        let token = match self.peek() {
+            Some(token) => token,
+            None => return Err(self.error_here("代码意外结束")),
+        };
 
-        let token = &self.tokens[self.position];
-        
         match token.token_type {
-            TokenType::Keyword if token.value == "int" => self.parse_declaration(),
-            _ => self.parse_expression(),
+            TokenType::Keyword if is_type_keyword(&token.value) => self.parse_declaration(),
+            TokenType::Keyword if token.value == "if" => self.parse_if(),
+            TokenType::Keyword if token.value == "while" => self.parse_while(),
+            TokenType::Keyword if token.value == "for" => self.parse_for(),
+            TokenType::Keyword if token.value == "return" => self.parse_return(),
+            TokenType::Delimiter if token.value == "{" => self.parse_block(),
+            TokenType::Delimiter if token.value == ";" => {
+                let span = token.span();
+                self.position += 1;
+                Ok(ASTNode::new(ASTNodeType::Empty, ";", Vec::new()).at(span))
+            }
+            _ => {
+                let start = self.position;
+                let expr = self.parse_expression()?;
+                self.expect(";")?;
+                Ok(ASTNode::new(ASTNodeType::Statement, "expression", vec![expr]).at(self.span_from(start)))
+            }
         }
     }
 
-    fn parse_declaration(&mut self) -> Result<ASTNode, String> {
-        let mut decl = ASTNode {
-            node_type: ASTNodeType::Declaration,
-            value: "declaration".to_string(),
-            children: Vec::new(),
+    /// 代码块内的语句出错时就地恢复，保证块本身能够完整解析
+    fn parse_block(&mut self) -> ParseResult {
+        let start = self.position;
+        self.expect("{")?;
+        let mut statements = Vec::new();
+        while !self.check("}") {
+            if self.peek().is_none() {
+                return Err(Diagnostic::error("E0102", "代码块缺少 '}'", self.span_from(start)));
+            }
+            let statement_start = self.position;
+            match self.parse_statement() {
+                Ok(statement) => statements.push(statement),
+                Err(diagnostic) => {
+                    self.diagnostics.push(diagnostic);
+                    self.synchronize(statement_start);
+                }
+            }
+        }
+        self.expect("}")?;
+        Ok(ASTNode::new(ASTNodeType::Block, "block", statements).at(self.span_from(start)))
+    }
+
+    /// if (条件) 语句 [else 语句]
+    fn parse_if(&mut self) -> ParseResult {
+        let start = self.position;
+        self.expect("if")?;
+        self.expect("(")?;
+        let condition = self.parse_expression()?;
+        self.expect(")")?;
+        let mut children = vec![condition, self.parse_statement()?];
+        if self.check("else") {
+            self.position += 1;
+            children.push(self.parse_statement()?);
+        }
+        Ok(ASTNode::new(ASTNodeType::If, "if", children).at(self.span_from(start)))
+    }
+
+    /// while (条件) 语句
+    fn parse_while(&mut self) -> ParseResult {
+        let start = self.position;
+        self.expect("while")?;
+        self.expect("(")?;
+        let condition = self.parse_expression()?;
+        self.expect(")")?;
+        let body = self.parse_statement()?;
+        Ok(ASTNode::new(ASTNodeType::While, "while", vec![condition, body]).at(self.span_from(start)))
+    }
+
+    /// for (初始化; 条件; 更新) 语句，省略的部分用 Empty 节点占位
+    fn parse_for(&mut self) -> ParseResult {
+        let start = self.position;
+        self.expect("for")?;
+        self.expect("(")?;
+
+        let init_start = self.position;
+        let init = if self.check(";") {
+            self.position += 1;
+            ASTNode::new(ASTNodeType::Empty, ";", Vec::new()).at(self.span_from(init_start))
+        } else if self.peek().is_some_and(|t| is_type_keyword(&t.value)) {
+            self.parse_declaration()?
+        } else {
+            let expr = self.parse_expression()?;
+            self.expect(";")?;
+            ASTNode::new(ASTNodeType::Statement, "expression", vec![expr]).at(self.span_from(init_start))
         };
 
-        // 跳过类型关键字
+        let condition = if self.check(";") {
+            ASTNode::new(ASTNodeType::Empty, ";", Vec::new())
+        } else {
+            self.parse_expression()?
+        };
+        self.expect(";")?;
+
+        let update_start = self.position;
+        let update = if self.check(")") {
+            ASTNode::new(ASTNodeType::Empty, ";", Vec::new())
+        } else {
+            let expr = self.parse_expression()?;
+            ASTNode::new(ASTNodeType::Statement, "expression", vec![expr]).at(self.span_from(update_start))
+        };
+        self.expect(")")?;
+
+        let body = self.parse_statement()?;
+        Ok(ASTNode::new(ASTNodeType::For, "for", vec![init, condition, update, body]).at(self.span_from(start)))
+    }
+
+    /// return [表达式];
+    fn parse_return(&mut self) -> ParseResult {
+        let start = self.position;
+        self.expect("return")?;
+        let mut children = Vec::new();
+        if !self.check(";") {
+            children.push(self.parse_expression()?);
+        }
+        self.expect(";")?;
+        Ok(ASTNode::new(ASTNodeType::Return, "return", children).at(self.span_from(start)))
+    }
+
+    fn parse_declaration(&mut self) -> ParseResult {
+        let start = self.position;
+        // 节点的值记录声明的类型
+        let mut decl = ASTNode::new(ASTNodeType::Declaration, self.tokens[self.position].value.clone(), Vec::new());
         self.position += 1;
-        
+
         // 获取变量名
-        if self.position < self.tokens.len() {
-            let var_name = self.tokens[self.position].value.clone();
-            decl.children.push(ASTNode {
-                node_type: ASTNodeType::Expression,
-                value: var_name,
-                children: Vec::new(),
-            });
-            self.position += 1;
-        }
+        decl.children.push(self.expect_identifier("变量名")?);
 
         // 检查是否有赋值
-        if self.position < self.tokens.len() && self.tokens[self.position].value == "=" {
+        if self.check("=") {
             self.position += 1; // 跳过 =
-            if let Ok(expr) = self.parse_expression() {
-                decl.children.push(expr);
+            decl.children.push(self.parse_expression()?);
+        }
+
+        self.expect(";")?;
+        Ok(decl.at(self.span_from(start)))
+    }
+
+    /// 表达式入口：赋值优先级最低且右结合
+    fn parse_expression(&mut self) -> ParseResult {
+        let start = self.position;
+        let lhs = self.parse_binary(1)?;
+
+        if self.check("=") {
+            if !matches!(lhs.node_type, ASTNodeType::Identifier) {
+                return Err(Diagnostic::error(
+                    "E0103",
+                    "赋值号左侧必须是变量",
+                    lhs.span,
+                ));
             }
+            self.position += 1;
+            let rhs = self.parse_expression()?;
+            return Ok(ASTNode::new(ASTNodeType::Assignment, "=", vec![lhs, rhs]).at(self.span_from(start)));
         }
 
-        Ok(decl)
+        Ok(lhs)
     }
 
-    fn parse_expression(&mut self) -> Result<ASTNode, String> {
-        if self.position >= self.tokens.len() {
-            return Err("Unexpected end of input".to_string());
+    /// 优先级爬升：只合并优先级不低于 min_precedence 的二元运算符（左结合）
+    fn parse_binary(&mut self, min_precedence: u8) -> ParseResult {
+        let start = self.position;
+        let mut lhs = self.parse_unary()?;
+
+        while let Some(token) = self.peek() {
+            if token.token_type != TokenType::Operator {
+                break;
+            }
+            let op = token.value.clone();
+            let precedence = match binary_precedence(&op) {
+                Some(p) if p >= min_precedence => p,
+                _ => break,
+            };
+            self.position += 1;
+
+            let rhs = self.parse_binary(precedence + 1)?;
+            lhs = ASTNode::new(ASTNodeType::BinaryOp, op, vec![lhs, rhs]).at(self.span_from(start));
         }
 
-        let token = &self.tokens[self.position];
-        self.position += 1;
+        Ok(lhs)
+    }
 
-        Ok(ASTNode {
-            node_type: ASTNodeType::Expression,
-            value: token.value.clone(),
-            children: Vec::new(),
-        })
+    fn parse_unary(&mut self) -> ParseResult {
+        let start = self.position;
+        if let Some(token) = self.peek() {
+            if token.token_type == TokenType::Operator && matches!(token.value.as_str(), "-" | "+" | "!") {
+                let op = token.value.clone();
+                self.position += 1;
+                let operand = self.parse_unary()?;
+                return Ok(ASTNode::new(ASTNodeType::UnaryOp, op, vec![operand]).at(self.span_from(start)));
+            }
+        }
+
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> ParseResult {
+        let start = self.position;
+        let token = match self.peek() {
+            Some(token) => token.clone(),
+            None => return Err(self.error_here("代码意外结束")),
+        };
+
+        match token.token_type {
+            TokenType::Number => {
+                self.position += 1;
+                Ok(ASTNode::new(ASTNodeType::Number, token.value.clone(), Vec::new()).at(token.span()))
+            }
+            TokenType::String => {
+                self.position += 1;
+                let content = token.value.trim_matches('"').to_string();
+                Ok(ASTNode::new(ASTNodeType::StringLiteral, content, Vec::new()).at(token.span()))
+            }
+            TokenType::Char => {
+                self.position += 1;
+                let content = &token.value[1..token.value.len() - 1];
+                let ch = match content {
+                    "\\n" => '\n',
+                    "\\t" => '\t',
+                    "\\0" => '\0',
+                    _ => content.chars().last().unwrap_or('\0'),
+                };
+                Ok(ASTNode::new(ASTNodeType::CharLiteral, ch.to_string(), Vec::new()).at(token.span()))
+            }
+            TokenType::Identifier if self.peek_at(1).is_some_and(|t| t.value == "(") => {
+                self.position += 2;
+                let mut arguments = Vec::new();
+                while !self.check(")") {
+                    if !arguments.is_empty() {
+                        self.expect(",")?;
+                    }
+                    arguments.push(self.parse_expression()?);
+                }
+                self.expect(")")?;
+                Ok(ASTNode::new(ASTNodeType::Call, token.value.clone(), arguments).at(self.span_from(start)))
+            }
+            TokenType::Identifier => {
+                self.position += 1;
+                Ok(ASTNode::new(ASTNodeType::Identifier, token.value.clone(), Vec::new()).at(token.span()))
+            }
+            TokenType::Delimiter if token.value == "(" => {
+                self.position += 1;
+                let expr = self.parse_expression()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            _ => Err(self.error_here(format!("无法解析的表达式 '{}'", token.value))),
+        }
     }
 }
 
-/// 代码生成器
+/// 编译流水线：词法分析 → 语法分析 → 语义分析 → 中间代码 → 优化 → 寄存器分配 → 代码生成 → 汇编。
+/// 各阶段的诊断信息汇总到结果中，存在错误时不再继续后续阶段；
+/// 每个已执行的阶段都会记录一条 CompilationStep，展示该阶段真实的输入与输出。
+/// target 选择代码生成的目标体系结构及其选项。
+pub fn compile(source_code: &str, passes: &[OptimizationPass], target: Target) -> CompilationResult {
+    let started = Instant::now();
+    let mut diagnostics = Vec::new();
+    let mut steps = Vec::new();
+    let mut instructions = Vec::new();
+
+    // 词法分析
+    let mut lexer = Lexer::new(source_code.to_string());
+    let tokens = lexer.tokenize();
+    diagnostics.extend_from_slice(lexer.diagnostics());
+    steps.push(CompilationStep {
+        id: "lexical_analysis".to_string(),
+        stage: CompilationStage::LexicalAnalysis,
+        input: source_code.to_string(),
+        output: format_tokens(&tokens),
+        description: "将源代码分解为词法单元(tokens)".to_string(),
+        details: token_statistics(&tokens),
+    });
+
+    // 语法分析
+    let token_text = tokens.iter().map(|t| t.value.as_str()).collect::<Vec<_>>().join(" ");
+    let mut parser = Parser::new(tokens);
+    let ast = parser.parse();
+    diagnostics.extend_from_slice(parser.diagnostics());
+    steps.push(CompilationStep {
+        id: "syntax_analysis".to_string(),
+        stage: CompilationStage::SyntaxAnalysis,
+        input: token_text,
+        output: ast.pretty(),
+        description: "根据语法规则构建抽象语法树".to_string(),
+        details: vec![
+            format!("顶层定义 {} 个", ast.children.len()),
+            format!("语法树节点 {} 个", count_nodes(&ast)),
+            format!("语法错误 {} 个", parser.diagnostics().len()),
+        ],
+    });
+
+    // 语义分析：语法正确时才进行
+    let mut ast = ast;
+    if !has_errors(&diagnostics) {
+        let input = ast.pretty();
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.analyze(&mut ast);
+        diagnostics.extend_from_slice(analyzer.diagnostics());
+        let count = |severity: DiagnosticSeverity| {
+            analyzer.diagnostics().iter().filter(|d| d.severity == severity).count()
+        };
+        steps.push(CompilationStep {
+            id: "semantic_analysis".to_string(),
+            stage: CompilationStage::SemanticAnalysis,
+            input,
+            output: format!("符号表：\n{}\n带类型转换的语法树：\n{}", analyzer.format_symbol_table(), ast.pretty()),
+            description: "检查语义正确性并添加类型信息".to_string(),
+            details: vec![
+                format!("符号 {} 个", analyzer.symbols().len() - 1),
+                format!("插入隐式类型转换 {} 处", analyzer.conversions()),
+                format!("语义错误 {} 个", count(DiagnosticSeverity::Error)),
+                format!("警告 {} 个", count(DiagnosticSeverity::Warning)),
+            ],
+        });
+    }
+
+    // 中间代码生成
+    let mut ir = None;
+    if !has_errors(&diagnostics) {
+        match IrBuilder::new().build(&ast) {
+            Ok(program) => {
+                let temps: usize = program.functions.iter().map(|f| f.temp_count).sum();
+                let labels = program
+                    .functions
+                    .iter()
+                    .flat_map(|f| &f.body)
+                    .filter(|i| matches!(i.op, IrOp::Label(_)))
+                    .count();
+                steps.push(CompilationStep {
+                    id: "intermediate_code".to_string(),
+                    stage: CompilationStage::IntermediateCode,
+                    input: ast.pretty(),
+                    output: program.dump(),
+                    description: "把语法树翻译为三地址码（四元式）".to_string(),
+                    details: vec![
+                        format!("四元式 {} 条", program.instruction_count()),
+                        format!("临时变量 {} 个", temps),
+                        format!("标签 {} 个", labels),
+                        format!("函数 {} 个（含入口 _start）", program.functions.len()),
+                    ],
+                });
+                ir = Some(program);
+            }
+            Err(diagnostic) => diagnostics.push(diagnostic),
+        }
+    }
+
+    // 代码优化
+    let mut optimizations = Vec::new();
+    if let Some(program) = ir.as_mut() {
+        let before = program.dump();
+        let before_count = program.instruction_count();
+        let mut optimizer = Optimizer::new(passes);
+        optimizer.optimize(program);
+        let mut details: Vec<String> = optimizer
+            .reports()
+            .iter()
+            .filter(|report| !report.changes.is_empty())
+            .map(|report| format!("第 {} 轮 {}：修改 {} 处", report.round, report.pass.name(), report.changes.len()))
+            .collect();
+        if optimizer.passes().is_empty() {
+            details.push("未启用任何优化遍".to_string());
+        } else if details.is_empty() {
+            details.push("没有可优化的代码".to_string());
+        }
+        details.push(format!("四元式 {} 条 → {} 条", before_count, program.instruction_count()));
+        steps.push(CompilationStep {
+            id: "optimization".to_string(),
+            stage: CompilationStage::Optimization,
+            input: before,
+            output: program.dump(),
+            description: "对中间代码进行与机器无关的优化".to_string(),
+            details,
+        });
+        optimizations = optimizer.reports().to_vec();
+    }
+
+    // 代码生成：先做寄存器分配，再翻译为汇编
+    if let Some(program) = ir.filter(|_| !has_errors(&diagnostics)) {
+        let ir_text = program.dump();
+        let generated = match target.architecture {
+            Architecture::X86 => {
+                let allocations: Vec<RegisterAllocation> = program.functions.iter().map(allocate_registers).collect();
+                let assigned: usize = allocations.iter().map(|a| a.registers.len()).sum();
+                let spilled: usize = allocations.iter().map(|a| a.spilled.len()).sum();
+                let mut used: Vec<&str> = ALLOCATABLE_REGISTERS
+                    .into_iter()
+                    .filter(|register| allocations.iter().any(|a| a.registers.values().any(|r| r == register)))
+                    .collect();
+                if used.is_empty() {
+                    used.push("无");
+                }
+                steps.push(CompilationStep {
+                    id: "register_allocation".to_string(),
+                    stage: CompilationStage::RegisterAllocation,
+                    input: ir_text.clone(),
+                    output: allocations.iter().map(|a| a.format()).collect::<Vec<_>>().join("\n"),
+                    description: "线性扫描分配寄存器，寄存器不足时溢出到栈帧".to_string(),
+                    details: vec![
+                        format!("活跃区间 {} 个", allocations.iter().map(|a| a.intervals.len()).sum::<usize>()),
+                        format!("分配到寄存器 {} 个", assigned),
+                        format!("溢出到栈帧 {} 个", spilled),
+                        format!("使用寄存器 {}", used.join(", ")),
+                    ],
+                });
+                CodeGenerator::new(program, allocations).generate()
+            }
+            Architecture::RiscV => {
+                let mut generator = RiscvCodeGenerator::new(program);
+                let generated = generator.generate();
+                steps.push(CompilationStep {
+                    id: "register_allocation".to_string(),
+                    stage: CompilationStage::RegisterAllocation,
+                    input: ir_text.clone(),
+                    output: generator.layouts().join("\n\n"),
+                    description: "为每个函数布置栈帧：变量都放在栈帧中，运算时装入 t0/t1".to_string(),
+                    details: vec![
+                        format!("栈帧 {} 个", generator.layouts().len()),
+                        "前 8 个参数通过 a0–a7 传递，其余经栈传递，返回值在 a0".to_string(),
+                    ],
+                });
+                generated
+            }
+            Architecture::Mips => {
+                let mut generator = MipsCodeGenerator::new(program, target.branch_delay_slot);
+                let generated = generator.generate();
+                steps.push(CompilationStep {
+                    id: "register_allocation".to_string(),
+                    stage: CompilationStage::RegisterAllocation,
+                    input: ir_text.clone(),
+                    output: generator.layouts().join("\n\n"),
+                    description: "为每个函数布置栈帧：变量都放在栈帧中，运算时装入 $t0/$t1".to_string(),
+                    details: vec![
+                        format!("栈帧 {} 个", generator.layouts().len()),
+                        "前 4 个参数通过 $a0–$a3 传递，其余经栈传递，返回值在 $v0".to_string(),
+                    ],
+                });
+                generated
+            }
+        };
+        // 标签确定后由目标指令集统一编码，回填相对跳转的偏移
+        let generated = generated.and_then(|mut generated| {
+            let encoded = match target.architecture {
+                Architecture::X86 => X86::default().encode_program(&mut generated),
+                Architecture::RiscV => RiscV.encode_program(&mut generated),
+                Architecture::Mips => {
+                    Mips { branch_delay_slot: target.branch_delay_slot }.encode_program(&mut generated)
+                }
+            };
+            encoded.map_err(|(index, message)| {
+                Diagnostic::error(
+                    "E0303",
+                    format!("无法编码指令 {}: {}", assembly_text(&generated[index]), message),
+                    SourceSpan::default(),
+                )
+            })?;
+            let length = generated.iter().map(|instruction| instruction.machine_code.len() as u64 / 2).sum();
+            check_code_size(length).map_err(|message| Diagnostic::error("E0305", message, SourceSpan::default()))?;
+            Ok(generated)
+        });
+        match generated {
+            Ok(generated) => instructions = generated,
+            Err(diagnostic) => diagnostics.push(diagnostic),
+        }
+
+        if !has_errors(&diagnostics) {
+            let assembly = format_assembly(&instructions);
+            steps.push(CompilationStep {
+                id: "code_generation".to_string(),
+                stage: CompilationStage::CodeGeneration,
+                input: ir_text,
+                output: assembly.clone(),
+                description: "生成目标机器的汇编代码".to_string(),
+                details: vec![
+                    format!("目标体系结构 {}", target.architecture.name()),
+                    format!("生成指令 {} 条", instructions.len()),
+                    format!("标签 {} 个", instructions.iter().filter(|i| i.label.is_some()).count()),
+                ],
+            });
+
+            // 汇编：把汇编代码翻译为机器码
+            let total_bytes: usize = instructions.iter().map(|i| i.machine_code.len() / 2).sum();
+            steps.push(CompilationStep {
+                id: "assembly".to_string(),
+                stage: CompilationStage::Assembly,
+                input: assembly,
+                output: format_machine_code(&instructions),
+                description: "将汇编代码翻译为机器码".to_string(),
+                details: vec![format!("机器码共 {} 字节", total_bytes)],
+            });
+        }
+    }
+
+    diagnostics.sort_by_key(|d| d.span.start);
+    let (errors, warnings): (Vec<Diagnostic>, Vec<Diagnostic>) = diagnostics
+        .into_iter()
+        .partition(|d| d.severity == DiagnosticSeverity::Error);
+
+    CompilationResult {
+        success: errors.is_empty(),
+        instructions,
+        errors,
+        warnings,
+        compilation_time: started.elapsed().as_millis() as u64,
+        steps,
+        optimizations,
+    }
+}
+
+/// 词法单元列表：每行一个 token，附带行列号
+fn format_tokens(tokens: &[Token]) -> String {
+    tokens
+        .iter()
+        .map(|t| format!("{}:{}\t{:?}\t{}", t.line, t.column, t.token_type, t.value))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn token_statistics(tokens: &[Token]) -> Vec<String> {
+    let count = |token_type: TokenType| tokens.iter().filter(|t| t.token_type == token_type).count();
+    vec![
+        format!("共 {} 个词法单元", tokens.len()),
+        format!("关键字 {} 个", count(TokenType::Keyword)),
+        format!("标识符 {} 个", count(TokenType::Identifier)),
+        format!("操作符 {} 个", count(TokenType::Operator)),
+        format!("字面量 {} 个", count(TokenType::Number) + count(TokenType::String) + count(TokenType::Char)),
+        format!("分隔符 {} 个", count(TokenType::Delimiter)),
+    ]
+}
+
+fn count_nodes(node: &ASTNode) -> usize {
+    1 + node.children.iter().map(count_nodes).sum::<usize>()
+}
+
+/// 单条指令的汇编文本，如 "MOV EAX, 5"
+pub fn assembly_text(instruction: &Instruction) -> String {
+    format!("{:<6} {}", instruction.mnemonic, instruction.operands.join(", "))
+        .trim_end()
+        .to_string()
+}
+
+/// 汇编代码清单，标签单独成行
+pub fn format_assembly(instructions: &[Instruction]) -> String {
+    let mut output = String::new();
+    for instruction in instructions {
+        if let Some(label) = &instruction.label {
+            output.push_str(&format!("{}:\n", label));
+        }
+        output.push_str(&format!("    {}\n", assembly_text(instruction)));
+    }
+    output
+}
+
+/// 机器码清单：地址、机器码字节与对应的汇编指令
+pub fn format_machine_code(instructions: &[Instruction]) -> String {
+    let mut output = String::new();
+    let mut address = 0;
+    for instruction in instructions {
+        let bytes = instruction
+            .machine_code
+            .as_bytes()
+            .chunks(2)
+            .map(|pair| String::from_utf8_lossy(pair).into_owned())
+            .collect::<Vec<_>>()
+            .join(" ");
+        output.push_str(&format!("{:08X}  {:<24} {}\n", address, bytes, assembly_text(instruction)));
+        address += instruction.machine_code.len() / 2;
+    }
+    output
+}
+
+fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(|d| d.severity == DiagnosticSeverity::Error)
+}
+
+/// 变量的存储位置：全局变量使用绝对地址，参数和溢出的变量相对 EBP 寻址，其余分配到寄存器
+#[derive(Debug, Clone, Copy)]
+enum VariableLocation {
+    Global(usize),
+    Frame(i32),
+    Register(&'static str),
+}
+
+impl VariableLocation {
+    fn operand(&self) -> String {
+        match self {
+            VariableLocation::Global(address) => format!("[{}]", address),
+            VariableLocation::Register(register) => register.to_string(),
+            VariableLocation::Frame(offset) if *offset < 0 => format!("[EBP-{}]", -offset),
+            VariableLocation::Frame(offset) => format!("[EBP+{}]", offset),
+        }
+    }
+}
+
+/// 代码生成器：把三地址码翻译为 x86 汇编。
+/// 每条四元式先把操作数装入 EAX/ECX，运算后写回结果；变量的位置由寄存器分配结果决定，
+/// 未分配到寄存器的局部变量和临时变量在栈帧中占一个槽位。
 pub struct CodeGenerator {
-    ast: ASTNode,
+    program: IrProgram,
+    /// 函数名 -> 寄存器分配结果
+    allocations: HashMap<String, RegisterAllocation>,
     instructions: Vec<Instruction>,
-    register_counter: usize,
     memory_offset: usize,
+    globals: HashMap<String, usize>,
+    /// 字符串常量 -> 数据区地址
+    strings: HashMap<String, usize>,
+    /// 当前函数的参数、局部变量和临时变量的位置
+    locations: HashMap<IrOperand, VariableLocation>,
+    /// 当前函数在序言中保存的寄存器，尾声中按相反顺序恢复
+    saved_registers: Vec<&'static str>,
+    /// 当前函数尾声（epilogue）的标签，入口函数为 None
+    return_label: Option<String>,
+    /// 等待附着到下一条指令上的标签
+    pending_label: Option<String>,
+    /// 落在同一位置的标签别名 -> 实际附着的标签
+    label_aliases: HashMap<String, String>,
 }
 
 impl CodeGenerator {
-    pub fn new(ast: ASTNode) -> Self {
+    pub fn new(program: IrProgram, allocations: Vec<RegisterAllocation>) -> Self {
+        let mut memory_offset = DATA_BASE as usize;
+        let mut globals = HashMap::new();
+        for name in &program.globals {
+            globals.insert(name.clone(), memory_offset);
+            memory_offset += 4;
+        }
+
         Self {
-            ast,
+            program,
+            allocations: allocations.into_iter().map(|a| (a.function.clone(), a)).collect(),
             instructions: Vec::new(),
-            register_counter: 0,
-            memory_offset: 1000,
+            memory_offset,
+            globals,
+            strings: HashMap::new(),
+            locations: HashMap::new(),
+            saved_registers: Vec::new(),
+            return_label: None,
+            pending_label: None,
+            label_aliases: HashMap::new(),
         }
     }
 
-    pub fn generate(&mut self) -> Result<Vec<Instruction>, String> {
-        self.generate_node(&self.ast.clone())?;
+    pub fn generate(&mut self) -> Result<Vec<Instruction>, Diagnostic> {
+        for function in self.program.functions.clone() {
+            self.generate_function(&function)?;
+        }
+
+        // 程序末尾的标签需要一条指令来承载
+        if self.pending_label.is_some() {
+            self.emit(InstructionType::Control, "NOP", Vec::new(), "空操作（标签占位）".to_string(), 1);
+        }
+
+        // 把跳转目标中的别名替换为实际附着的标签
+        for instruction in &mut self.instructions {
+            if matches!(instruction.instruction_type, InstructionType::Control)
+                && (instruction.mnemonic.starts_with('J') || instruction.mnemonic == "CALL")
+            {
+                if let Some(target) = instruction.operands.first_mut() {
+                    if let Some(actual) = self.label_aliases.get(target) {
+                        *target = actual.clone();
+                    }
+                }
+            }
+        }
+
         Ok(self.instructions.clone())
     }
 
-    fn generate_node(&mut self, node: &ASTNode) -> Result<(), String> {
-        match node.node_type {
-            ASTNodeType::Program => {
-                for child in &node.children {
-                    self.generate_node(child)?;
+    /// 将标签放在下一条将要生成的指令上
+    fn place_label(&mut self, label: String) {
+        match &self.pending_label {
+            Some(pending) => {
+                self.label_aliases.insert(label, pending.clone());
+            }
+            None => self.pending_label = Some(label),
+        }
+    }
+
+    fn emit_jump(&mut self, mnemonic: &str, label: &str, description: String) {
+        self.emit(InstructionType::Control, mnemonic, vec![label.to_string()], description, 1);
+    }
+
+    /// 生成一条指令；机器码在全部指令生成后由 encoder 统一编码
+    fn emit(&mut self, instruction_type: InstructionType, mnemonic: &str, operands: Vec<String>, description: String, cycles: u32) {
+        self.instructions.push(Instruction {
+            id: format!("{}_{}", mnemonic.to_lowercase(), self.instructions.len()),
+            instruction_type,
+            mnemonic: mnemonic.to_string(),
+            operands,
+            machine_code: String::new(),
+            description,
+            cycles,
+            label: self.pending_label.take(),
+            encoding: Vec::new(),
+        });
+    }
+
+    /// 生成函数：序言（保存 EBP、分配栈帧）、函数体、尾声（恢复栈帧并返回）。
+    /// 入口函数 _start 以 HLT 结束，没有尾声。
+    fn generate_function(&mut self, function: &IrFunction) -> Result<(), Diagnostic> {
+        // cdecl：参数从右向左压栈，第一个参数位于 [EBP+8]
+        self.locations.clear();
+        for (i, param) in function.params.iter().enumerate() {
+            self.locations.insert(IrOperand::Var(param.clone()), VariableLocation::Frame(8 + 4 * i as i32));
+        }
+        let allocation = self.allocations.get(&function.name);
+        let mut frame_size = 0;
+        let slots = function
+            .locals
+            .iter()
+            .map(|name| IrOperand::Var(name.clone()))
+            .chain((1..=function.temp_count).map(IrOperand::Temp));
+        for slot in slots {
+            let location = match allocation.and_then(|a| a.registers.get(&slot)) {
+                Some(register) => VariableLocation::Register(register),
+                None => {
+                    frame_size += 4;
+                    VariableLocation::Frame(-frame_size)
                 }
+            };
+            self.locations.insert(slot, location);
+        }
+        // 入口函数不返回，不必保存寄存器
+        self.saved_registers = match allocation {
+            Some(allocation) if !function.is_entry() => allocation.callee_saved(),
+            _ => Vec::new(),
+        };
+
+        let name = &function.name;
+        self.place_label(name.clone());
+        if !function.is_entry() || frame_size > 0 {
+            self.emit(InstructionType::DataTransfer, "PUSH", vec!["EBP".to_string()], format!("{} 序言：保存调用者的 EBP", name), 1);
+            self.emit(InstructionType::DataTransfer, "MOV", vec!["EBP".to_string(), "ESP".to_string()], "建立新的栈帧基址".to_string(), 1);
+        }
+        if frame_size > 0 {
+            self.emit(InstructionType::Arithmetic, "SUB", vec!["ESP".to_string(), frame_size.to_string()], format!("为溢出的变量分配 {} 字节", frame_size), 1);
+        }
+        for register in self.saved_registers.clone() {
+            self.emit(InstructionType::DataTransfer, "PUSH", vec![register.to_string()], format!("保存被调用者保存的寄存器 {}", register), 1);
+        }
+
+        self.return_label = (!function.is_entry()).then(|| format!("{}_end", name));
+        for (i, instruction) in function.body.iter().enumerate() {
+            let is_last = i + 1 == function.body.len();
+            self.generate_instruction(instruction, is_last)?;
+        }
+
+        if let Some(return_label) = self.return_label.take() {
+            self.place_label(return_label);
+            for register in self.saved_registers.clone().into_iter().rev() {
+                self.emit(InstructionType::DataTransfer, "POP", vec![register.to_string()], format!("恢复寄存器 {}", register), 1);
             }
-            ASTNodeType::Declaration => {
-                if node.children.len() >= 2 {
-                    // 简单的变量赋值
-                    let var_name = &node.children[0].value;
-                    let value = &node.children[1].value;
-                    
-                    if let Ok(num_value) = value.parse::<i64>() {
-                        self.instructions.push(Instruction {
-                            id: format!("mov_{}", self.instructions.len()),
-                            instruction_type: InstructionType::DataTransfer,
-                            mnemonic: "MOV".to_string(),
-                            operands: vec!["EAX".to_string(), num_value.to_string()],
-                            machine_code: format!("B8{:08X}", num_value),
-                            description: format!("将值 {} 加载到 EAX", num_value),
-                            cycles: 1,
-                        });
-                        
-                        self.instructions.push(Instruction {
-                            id: format!("store_{}", self.instructions.len()),
-                            instruction_type: InstructionType::Memory,
-                            mnemonic: "MOV".to_string(),
-                            operands: vec![format!("[{}]", self.memory_offset), "EAX".to_string()],
-                            machine_code: format!("8905{:08X}", self.memory_offset),
-                            description: format!("将 EAX 存储到内存地址 {}", self.memory_offset),
-                            cycles: 2,
-                        });
-                        
-                        self.memory_offset += 4;
+            self.emit(InstructionType::DataTransfer, "MOV", vec!["ESP".to_string(), "EBP".to_string()], format!("{} 尾声：释放栈帧", name), 1);
+            self.emit(InstructionType::DataTransfer, "POP", vec!["EBP".to_string()], "恢复调用者的 EBP".to_string(), 1);
+            self.emit(InstructionType::Control, "RET", Vec::new(), "返回调用者，返回值在 EAX".to_string(), 2);
+        }
+        Ok(())
+    }
+
+    fn generate_instruction(&mut self, instruction: &IrInstruction, is_last: bool) -> Result<(), Diagnostic> {
+        let arg1 = instruction.arg1.as_ref();
+        let arg2 = instruction.arg2.as_ref();
+        match &instruction.op {
+            IrOp::Label(label) => self.place_label(label.clone()),
+            IrOp::Jump(label) => self.emit_jump("JMP", label, format!("无条件跳转到 {}", label)),
+            IrOp::Assign => {
+                self.load(arg1, "EAX");
+                self.store_result(instruction);
+            }
+            IrOp::Binary(op) => {
+                self.load_pair(arg1, arg2);
+                self.generate_binary_op(op)?;
+                self.store_result(instruction);
+            }
+            IrOp::Neg => {
+                self.load(arg1, "EAX");
+                self.emit(InstructionType::Arithmetic, "NEG", vec!["EAX".to_string()], "对 EAX 取负".to_string(), 1);
+                self.store_result(instruction);
+            }
+            IrOp::Not => {
+                self.load(arg1, "EAX");
+                self.emit(InstructionType::Arithmetic, "CMP", vec!["EAX".to_string(), "0".to_string()], "比较 EAX 与 0".to_string(), 1);
+                self.emit_set_condition("E", "EAX 为 0 时结果为 1");
+                self.store_result(instruction);
+            }
+            IrOp::Cast(data_type) => {
+                self.load(arg1, "EAX");
+                if data_type == "char" {
+                    self.emit(InstructionType::DataTransfer, "MOVSX", vec!["EAX".to_string(), "AL".to_string()], "截断为 char 并符号扩展到 EAX".to_string(), 1);
+                }
+                self.store_result(instruction);
+            }
+            IrOp::CondJump(relop, label) => {
+                match arg2 {
+                    Some(IrOperand::Const(value)) => {
+                        self.load(arg1, "EAX");
+                        self.emit(InstructionType::Arithmetic, "CMP", vec!["EAX".to_string(), value.to_string()], format!("比较 EAX 与 {}", value), 1);
+                    }
+                    _ => {
+                        self.load_pair(arg1, arg2);
+                        self.emit(InstructionType::Arithmetic, "CMP", vec!["EAX".to_string(), "ECX".to_string()], "比较 EAX 与 ECX".to_string(), 1);
                     }
                 }
+                let condition = relop_condition(relop)
+                    .ok_or_else(|| Diagnostic::error("E0301", format!("不支持的运算符 '{}'", relop), SourceSpan::default()))?;
+                self.emit_jump(&format!("J{}", condition), label, format!("条件 {} 成立时跳转到 {}", relop, label));
+            }
+            IrOp::Param => {
+                let register = match arg1.map(|operand| self.location_of(operand)) {
+                    Some(Some(VariableLocation::Register(register))) => register,
+                    _ => {
+                        self.load(arg1, "EAX");
+                        "EAX"
+                    }
+                };
+                self.emit(InstructionType::DataTransfer, "PUSH", vec![register.to_string()], "参数压栈".to_string(), 1);
+            }
+            IrOp::Call(name, count) => {
+                self.emit(InstructionType::Control, "CALL", vec![name.clone()], format!("调用函数 {}", name), 3);
+                if *count > 0 {
+                    let args_size = count * 4;
+                    self.emit(InstructionType::Arithmetic, "ADD", vec!["ESP".to_string(), args_size.to_string()], format!("调用者清理 {} 字节参数", args_size), 1);
+                }
+                self.store_result(instruction);
+            }
+            IrOp::Return => {
+                self.load(arg1, "EAX");
+                // 最后一条 return 直接落入尾声
+                if let (Some(return_label), false) = (self.return_label.clone(), is_last) {
+                    self.emit_jump("JMP", &return_label, "跳转到函数尾声".to_string());
+                }
+            }
+            IrOp::Halt => self.emit(InstructionType::Control, "HLT", Vec::new(), "程序结束，停机".to_string(), 1),
+        }
+        Ok(())
+    }
+
+    fn location(&self, operand: &IrOperand) -> VariableLocation {
+        self.location_of(operand)
+            .unwrap_or_else(|| unreachable!("临时变量 {} 没有分配位置", operand))
+    }
+
+    /// 变量和临时变量的位置，常量与字符串没有位置
+    fn location_of(&self, operand: &IrOperand) -> Option<VariableLocation> {
+        match (self.locations.get(operand), operand) {
+            (Some(location), _) => Some(*location),
+            (None, IrOperand::Var(name)) => self.globals.get(name).map(|address| VariableLocation::Global(*address)),
+            _ => None,
+        }
+    }
+
+    /// 把两个操作数分别装入 EAX 和 ECX，处理它们已经位于对方寄存器中的情况
+    fn load_pair(&mut self, a: Option<&IrOperand>, b: Option<&IrOperand>) {
+        let in_register = |operand: Option<&IrOperand>, register: &str| {
+            matches!(operand.and_then(|o| self.location_of(o)), Some(VariableLocation::Register(r)) if r == register)
+        };
+        match (in_register(a, "ECX"), in_register(b, "EAX")) {
+            (true, true) => self.emit(InstructionType::DataTransfer, "XCHG", vec!["EAX".to_string(), "ECX".to_string()], "交换 EAX 与 ECX".to_string(), 2),
+            (_, true) => {
+                self.load(b, "ECX");
+                self.load(a, "EAX");
             }
             _ => {
-                for child in &node.children {
-                    self.generate_node(child)?;
+                self.load(a, "EAX");
+                self.load(b, "ECX");
+            }
+        }
+    }
+
+    /// 字符串常量放在数据区，按 4 字节对齐
+    fn string_address(&mut self, text: &str) -> usize {
+        if let Some(address) = self.strings.get(text) {
+            return *address;
+        }
+        let address = self.memory_offset;
+        self.memory_offset += (text.len() + 1).div_ceil(4) * 4;
+        self.strings.insert(text.to_string(), address);
+        address
+    }
+
+    /// 把操作数装入寄存器（EAX 或 ECX）
+    fn load(&mut self, operand: Option<&IrOperand>, register: &str) {
+        let Some(operand) = operand else { return };
+        match operand {
+            IrOperand::Const(value) => self.emit(
+                InstructionType::DataTransfer,
+                "MOV",
+                vec![register.to_string(), value.to_string()],
+                format!("将值 {} 加载到 {}", value, register),
+                1,
+            ),
+            IrOperand::Str(text) => {
+                let address = self.string_address(text);
+                self.emit(
+                    InstructionType::DataTransfer,
+                    "MOV",
+                    vec![register.to_string(), address.to_string()],
+                    format!("将字符串 \"{}\" 的地址 {} 加载到 {}", text, address, register),
+                    1,
+                );
+            }
+            IrOperand::Var(_) | IrOperand::Temp(_) => {
+                let location = self.location(operand);
+                if let VariableLocation::Register(source) = location {
+                    if source != register {
+                        self.emit(
+                            InstructionType::DataTransfer,
+                            "MOV",
+                            vec![register.to_string(), source.to_string()],
+                            format!("将 {} ({}) 复制到 {}", operand, source, register),
+                            1,
+                        );
+                    }
+                    return;
                 }
+                self.emit(
+                    InstructionType::Memory,
+                    "MOV",
+                    vec![register.to_string(), location.operand()],
+                    format!("从 {} ({}) 加载到 {}", operand, location.operand(), register),
+                    2,
+                );
+            }
+        }
+    }
+
+    /// 把 EAX 写回四元式的结果操作数
+    fn store_result(&mut self, instruction: &IrInstruction) {
+        let Some(result) = &instruction.result else { return };
+        let location = self.location(result);
+        let (instruction_type, cycles) = match location {
+            VariableLocation::Register("EAX") => return,
+            VariableLocation::Register(_) => (InstructionType::DataTransfer, 1),
+            _ => (InstructionType::Memory, 2),
+        };
+        self.emit(
+            instruction_type,
+            "MOV",
+            vec![location.operand(), "EAX".to_string()],
+            format!("将 EAX 存储到 {} ({})", result, location.operand()),
+            cycles,
+        );
+    }
+
+    /// 对 EAX（左操作数）和 ECX（右操作数）执行二元运算，结果写入 EAX
+    fn generate_binary_op(&mut self, op: &str) -> Result<(), Diagnostic> {
+        let eax_ecx = || vec!["EAX".to_string(), "ECX".to_string()];
+        match op {
+            "+" => self.emit(InstructionType::Arithmetic, "ADD", eax_ecx(), "EAX = EAX + ECX".to_string(), 1),
+            "-" => self.emit(InstructionType::Arithmetic, "SUB", eax_ecx(), "EAX = EAX - ECX".to_string(), 1),
+            "*" => self.emit(InstructionType::Arithmetic, "IMUL", eax_ecx(), "EAX = EAX * ECX".to_string(), 3),
+            "<<" => self.emit(InstructionType::Logic, "SHL", vec!["EAX".to_string(), "CL".to_string()], "EAX 左移 CL 位".to_string(), 1),
+            "/" | "%" => {
+                self.emit(InstructionType::Arithmetic, "CDQ", Vec::new(), "将 EAX 符号扩展到 EDX:EAX".to_string(), 1);
+                self.emit(InstructionType::Arithmetic, "IDIV", vec!["ECX".to_string()], "EDX:EAX 除以 ECX，商在 EAX，余数在 EDX".to_string(), 20);
+                if op == "%" {
+                    self.emit(InstructionType::DataTransfer, "MOV", vec!["EAX".to_string(), "EDX".to_string()], "取余数到 EAX".to_string(), 1);
+                }
+            }
+            _ => {
+                let condition = relop_condition(op)
+                    .ok_or_else(|| Diagnostic::error("E0301", format!("不支持的运算符 '{}'", op), SourceSpan::default()))?;
+                self.emit(InstructionType::Arithmetic, "CMP", eax_ecx(), "比较 EAX 与 ECX".to_string(), 1);
+                self.emit_set_condition(condition, &format!("{} 成立时结果为 1", op));
             }
         }
         Ok(())
     }
+
+    /// 根据标志位把条件结果 (0/1) 写入 EAX
+    fn emit_set_condition(&mut self, condition: &str, description: &str) {
+        self.emit(
+            InstructionType::Logic,
+            &format!("SET{}", condition),
+            vec!["AL".to_string()],
+            format!("{}，写入 AL", description),
+            1,
+        );
+        self.emit(
+            InstructionType::DataTransfer,
+            "MOVZX",
+            vec!["EAX".to_string(), "AL".to_string()],
+            "将 AL 零扩展到 EAX".to_string(),
+            1,
+        );
+    }
+}
+
+/// 关系运算符对应的 x86 条件后缀
+fn relop_condition(op: &str) -> Option<&'static str> {
+    match op {
+        "==" => Some("E"),
+        "!=" => Some("NE"),
+        "<" => Some("L"),
+        ">" => Some("G"),
+        "<=" => Some("LE"),
+        ">=" => Some("GE"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> (ASTNode, Vec<Diagnostic>) {
+        let mut lexer = Lexer::new(source.to_string());
+        let mut parser = Parser::new(lexer.tokenize());
+        let ast = parser.parse();
+        (ast, lexer.diagnostics().iter().chain(parser.diagnostics()).cloned().collect())
+    }
+
+    /// 语法树的括号形式：(运算符 操作数...)
+    fn sexpr(node: &ASTNode) -> String {
+        if node.children.is_empty() {
+            return node.value.clone();
+        }
+        let children: Vec<String> = node.children.iter().map(sexpr).collect();
+        format!("({} {})", node.value, children.join(" "))
+    }
+
+    /// 解析单个表达式语句
+    fn expression(source: &str) -> String {
+        let (ast, diagnostics) = parse(&format!("{};", source));
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        sexpr(&ast.children[0].children[0])
+    }
+
+    #[test]
+    fn tokenizes_with_line_and_column() {
+        let tokens = Lexer::new("int x = 10;\n  x >= 'a'; // 注释".to_string()).tokenize();
+        let values: Vec<&str> = tokens.iter().map(|token| token.value.as_str()).collect();
+        assert_eq!(values, ["int", "x", "=", "10", ";", "x", ">=", "'a'", ";"]);
+        assert_eq!(tokens[0].token_type, TokenType::Keyword);
+        assert_eq!(tokens[6].token_type, TokenType::Operator);
+        assert_eq!(tokens[7].token_type, TokenType::Char);
+        assert_eq!((tokens[6].line, tokens[6].column, tokens[6].position), (2, 5, 16));
+    }
+
+    #[test]
+    fn reports_unknown_characters_and_keeps_going() {
+        let mut lexer = Lexer::new("int a = 1 @ 2;\nchar *s = \"abc\nint b;".to_string());
+        let tokens = lexer.tokenize();
+        let codes: Vec<(&str, usize, usize)> =
+            lexer.diagnostics().iter().map(|d| (d.code.as_str(), d.span.line, d.span.column)).collect();
+        assert_eq!(codes, [("E0001", 1, 11), ("E0002", 2, 11)]);
+        assert!(tokens.iter().any(|token| token.value == "2"));
+        // 未闭合的字符串之后从下一行继续分析
+        assert_eq!(tokens[tokens.len() - 2].value, "b");
+    }
+
+    #[test]
+    fn binary_operators_follow_precedence() {
+        assert_eq!(expression("1 + 2 * 3"), "(+ 1 (* 2 3))");
+        assert_eq!(expression("(1 + 2) * 3"), "(* (+ 1 2) 3)");
+        assert_eq!(expression("a || b && c == d < e + f * g"), "(|| a (&& b (== c (< d (+ e (* f g))))))");
+        assert_eq!(expression("a % b / c - -d"), "(- (/ (% a b) c) (- d))");
+        assert_eq!(expression("!x != 0"), "(!= (! x) 0)");
+    }
+
+    #[test]
+    fn binary_operators_are_left_associative_and_assignment_right() {
+        assert_eq!(expression("a - b - c"), "(- (- a b) c)");
+        assert_eq!(expression("a / b * c"), "(* (/ a b) c)");
+        assert_eq!(expression("a = b = c + 1"), "(= a (= b (+ c 1)))");
+        assert_eq!(expression("f(a, b + 1) * 2"), "(* (f a (+ b 1)) 2)");
+    }
+
+    #[test]
+    fn recovers_after_syntax_errors() {
+        let (ast, diagnostics) = parse("int a = (1 + ;\n1 + 2 = a;\nint b = 3;");
+        let codes: Vec<(&str, usize)> = diagnostics.iter().map(|d| (d.code.as_str(), d.span.line)).collect();
+        assert_eq!(codes, [("E0101", 1), ("E0103", 2)]);
+        assert_eq!(ast.children.len(), 1);
+        assert_eq!(sexpr(&ast.children[0]), "(int b 3)");
+
+        let (_, diagnostics) = parse("int f() { return 1;");
+        assert_eq!(diagnostics[0].code, "E0102");
+    }
 }
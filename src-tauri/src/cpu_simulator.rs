@@ -1,267 +1,1281 @@
+use crate::cache::{check_config, describe, CacheHierarchy};
+use crate::disassembler::parse_hex;
+use crate::interrupt::{self, Fault, HANDLER_PREFIX, IDT_BASE, TIMER};
+use crate::isa::{
+    Branch, Context, Execution, InterruptRequest, Isa, MemoryOperation, Register, STACK_TOP, STACK_VIEW_LIMIT,
+};
+use crate::memory::{check_code_size, check_stack, CODE_BASE, STACK_SIZE};
+use crate::mips::Mips;
+use crate::mmu::{self, Mmu, PAGE_SIZE};
+use crate::pipeline::{analyze, Analysis, InFlight, Location, Pipeline};
+use crate::predictor::{BranchPredictor, Prediction, MISPREDICT_PENALTY};
+use crate::riscv::RiscV;
 use crate::types::*;
+use crate::x86::X86;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
 
-/// CPU模拟器
-pub struct CPUSimulator {
+/// 模拟器的对外接口，与具体指令集无关，便于按目标体系结构切换
+pub trait Simulator: Send {
+    /// 所执行指令的体系结构及分支延迟槽设置
+    fn target(&self) -> Target;
+    /// 载入程序；机器码超出代码段时报错，原来的程序保持不变
+    fn load_instructions(&mut self, instructions: Vec<Instruction>) -> Result<(), String>;
+    /// 载入数据段映像，复位时重新载入
+    fn load_memory_image(&mut self, image: MemoryImage);
+    fn step(&mut self) -> Result<ExecutionResult, String>;
+    fn reset(&mut self);
+    fn state(&self) -> &CPUState;
+    /// 设置栈顶地址与栈的大小（字节），随后复位
+    fn configure_stack(&mut self, stack_top: u64, stack_size: u64) -> Result<(), String>;
+    /// 按栈帧划分的栈视图，外层的帧在前
+    fn stack_frames(&self) -> Vec<StackFrame>;
+    fn pipeline_config(&self) -> PipelineConfig;
+    /// 切换逐条执行与流水线模式、开关前递，随后复位
+    fn configure_pipeline(&mut self, config: PipelineConfig);
+    /// 选择分支预测器，预测表与统计随之清空
+    fn set_predictor(&mut self, kind: PredictorKind);
+    fn branch_statistics(&self) -> BranchStatistics;
+    fn cache_config(&self) -> CacheConfig;
+    /// 设置缓存层次，随后复位
+    fn configure_cache(&mut self, config: CacheConfig) -> Result<(), String>;
+    fn cache_view(&self) -> CacheView;
+    fn mmu_config(&self) -> MmuConfig;
+    /// 设置 MMU，随后复位并重建页表
+    fn configure_mmu(&mut self, config: MmuConfig) -> Result<(), String>;
+    /// 修改页表：把虚拟页映射到物理页框，或取消映射
+    fn map_page(
+        &mut self,
+        virtual_address: u64,
+        physical_address: u64,
+        permissions: PagePermissions,
+    ) -> Result<(), String>;
+    fn unmap_page(&mut self, virtual_address: u64) -> Result<(), String>;
+    fn page_table(&self) -> PageTableView;
+    fn interrupt_config(&self) -> InterruptConfig;
+    /// 设置定时器中断，随后复位
+    fn configure_interrupts(&mut self, config: InterruptConfig);
+    /// 设置或清除中断描述符表的一项，复位后仍然有效，载入新程序时清除
+    fn set_interrupt_gate(&mut self, vector: u8, gate: Option<InterruptGate>) -> Result<(), String>;
+    fn interrupt_table(&self) -> InterruptTableView;
+}
+
+/// 按目标体系结构创建模拟器
+pub fn create_simulator(target: Target) -> Box<dyn Simulator> {
+    match target.architecture {
+        Architecture::X86 => Box::new(CPUSimulator::new(X86::default())),
+        Architecture::RiscV => Box::new(CPUSimulator::new(RiscV)),
+        Architecture::Mips => Box::new(CPUSimulator::new(Mips { branch_delay_slot: target.branch_delay_slot })),
+    }
+}
+
+/// CPU模拟器：按取指、译码、执行、访存、写回逐级推进，指令的语义由指令集 I 给出
+pub struct CPUSimulator<I: Isa> {
+    pub isa: I,
     pub state: CPUState,
     pub instructions: Vec<Instruction>,
     pub current_instruction_index: usize,
     pub execution_stage: ExecutionStage,
     pub cycle_count: u64,
+    /// 数据段的初始内容，复位时重新载入
+    pub memory_image: MemoryImage,
+    /// 栈占据 [stack_top - stack_size, stack_top)，栈指针复位时指向 stack_top
+    stack_top: u64,
+    stack_size: u64,
+    /// 寄存器文件：按编号存放的 32 位值，写回后同步到 state
+    registers: Vec<i32>,
+    special: Vec<i32>,
+    /// 标签 -> 指令下标，用于跳转
+    labels: HashMap<String, usize>,
+    /// 译码阶段的结果
+    decoded: Option<I::Decoded>,
+    /// 执行阶段的结果，等待访存与写回
+    execution: Option<Execution>,
+    /// 写回阶段确定的下一条指令
+    next_instruction_index: Option<usize>,
+    /// 延迟槽中的指令执行完后才生效的分支目标
+    delayed_branch: Option<usize>,
+    /// 当前指令位于延迟槽中时，执行完后转到的目标
+    slot_target: Option<usize>,
+    pipeline_config: PipelineConfig,
+    /// 流水线模式下各级中的指令
+    pipeline: Pipeline<I::Decoded>,
+    predictor: BranchPredictor,
+    /// 逐条执行时，取指阶段对当前指令所做的预测
+    prediction: Prediction,
+    cache: CacheHierarchy,
+    /// 本步经过缓存的访问，随执行结果返回
+    cache_accesses: Vec<CacheAccess>,
+    mmu: Mmu,
+    /// 本步的地址转换，随执行结果返回
+    translations: Vec<Translation>,
+    interrupt_config: InterruptConfig,
+    /// 通过接口设置的中断门，在按标签装入的门之后写入；None 表示清除该项
+    gates: BTreeMap<u8, Option<InterruptGate>>,
+    /// 下一次请求定时器中断的周期
+    next_timer: u64,
+    /// 本步发生的中断与异常，随执行结果返回
+    interrupts: Vec<InterruptEvent>,
 }
 
-impl CPUSimulator {
-    pub fn new() -> Self {
-        Self {
+impl<I: Isa> CPUSimulator<I> {
+    pub fn new(isa: I) -> Self {
+        let mut simulator = Self {
+            isa,
             state: CPUState::default(),
             instructions: Vec::new(),
             current_instruction_index: 0,
             execution_stage: ExecutionStage::Fetch,
             cycle_count: 0,
+            memory_image: MemoryImage::default(),
+            stack_top: STACK_TOP as u64,
+            stack_size: STACK_SIZE,
+            registers: Vec::new(),
+            special: Vec::new(),
+            labels: HashMap::new(),
+            decoded: None,
+            execution: None,
+            next_instruction_index: None,
+            delayed_branch: None,
+            slot_target: None,
+            pipeline_config: PipelineConfig::default(),
+            pipeline: Pipeline::default(),
+            predictor: BranchPredictor::new(PredictorKind::default()),
+            prediction: Prediction::default(),
+            cache: CacheHierarchy::default(),
+            cache_accesses: Vec::new(),
+            mmu: Mmu::default(),
+            translations: Vec::new(),
+            interrupt_config: InterruptConfig::default(),
+            gates: BTreeMap::new(),
+            next_timer: 0,
+            interrupts: Vec::new(),
+        };
+        simulator.reset();
+        simulator
+    }
+
+    fn init_registers(&mut self) {
+        self.registers = vec![0; self.isa.register_names().len()];
+        self.special = vec![0; self.isa.special_register_names().len()];
+        if let Some((_, status)) = self.isa.interrupt_registers() {
+            let flags = &self.state.flags;
+            self.special[status] = self.isa.interrupt_status(flags, flags.interrupt) as i32;
         }
+        self.registers[self.isa.stack_pointer()] = self.stack_top as i32;
+        if let Some(number) = self.isa.frame_pointer() {
+            self.registers[number] = self.stack_top as i32;
+        }
+        self.sync_state(0);
     }
 
-    pub fn load_instructions(&mut self, instructions: Vec<Instruction>) {
-        self.instructions = instructions;
-        self.current_instruction_index = 0;
-        self.execution_stage = ExecutionStage::Fetch;
+    /// 把寄存器文件同步到对外的状态；栈视图为栈顶到栈指针之间的双字，先压入的在前
+    fn sync_state(&mut self, next: usize) {
+        let general = &mut self.state.registers.general;
+        for (name, value) in self.isa.register_names().iter().zip(&self.registers) {
+            general.insert(name.to_string(), *value as i64);
+        }
+        let special = &mut self.state.registers.special;
+        for (name, value) in self.isa.special_register_names().iter().zip(&self.special) {
+            special.insert(name.to_string(), *value as i64);
+        }
+        let pc = self.isa.instruction_address(next);
+        self.state.program_counter = pc;
+        special.insert(self.isa.program_counter_name().to_string(), pc as i64);
+
+        let sp = self.registers[self.isa.stack_pointer()] as u32 as u64;
+        self.state.stack_pointer = sp;
+        let top = self.stack_top;
+        let depth = (top.saturating_sub(sp) / 4).min(STACK_VIEW_LIMIT);
+        let memory = &self.state.memory;
+        self.state.memory.stack =
+            (1..=depth).map(|k| self.mmu.read(memory, top - 4 * k, 4).unwrap_or(0) as i32 as i64).collect();
     }
 
-    pub fn step(&mut self) -> Result<ExecutionResult, String> {
-        if self.current_instruction_index >= self.instructions.len() {
-            return Ok(ExecutionResult {
-                stage: ExecutionStage::Complete,
-                instruction: None,
-                cpu_state: self.state.clone(),
-                message: "程序执行完成".to_string(),
-                cycle_count: self.cycle_count,
-            });
+    /// 按栈的配置新建内存，启用 MMU 时建立页表；代码与数据随后载入
+    fn new_memory(&mut self) -> MemoryState {
+        let mut memory = MemoryState::new(self.stack_top, self.stack_size);
+        self.mmu.install(&mut memory);
+        memory
+    }
+
+    /// 执行阶段看到的处理器状态
+    fn context(&self, index: usize) -> Context<'_> {
+        Context {
+            registers: &self.registers,
+            special: &self.special,
+            flags: &self.state.flags,
+            index,
+            address: self.isa.instruction_address(index),
+            labels: &self.labels,
+            memory: &self.state.memory,
+            mmu: &self.mmu,
+            reads: RefCell::default(),
         }
+    }
 
-        let instruction = &self.instructions[self.current_instruction_index].clone();
-        let result = match self.execution_stage {
-            ExecutionStage::Fetch => self.fetch(instruction),
-            ExecutionStage::Decode => self.decode(instruction),
-            ExecutionStage::Execute => self.execute(instruction),
-            ExecutionStage::MemoryAccess => self.memory_access(instruction),
-            ExecutionStage::WriteBack => self.write_back(instruction),
-            ExecutionStage::Complete => {
-                self.current_instruction_index += 1;
-                self.execution_stage = ExecutionStage::Fetch;
-                Ok(ExecutionResult {
-                    stage: ExecutionStage::Fetch,
-                    instruction: None,
-                    cpu_state: self.state.clone(),
-                    message: "准备执行下一条指令".to_string(),
-                    cycle_count: self.cycle_count,
-                })
+    /// 把各条指令的机器码按指令地址放入代码段
+    fn load_code(&mut self) {
+        for (i, instruction) in self.instructions.iter().enumerate() {
+            if let Ok(bytes) = parse_hex(&instruction.machine_code) {
+                self.state.memory.fill(self.isa.instruction_address(i), &bytes);
+            }
+        }
+    }
+
+    /// 装入中断门：标签 isr<n> 处的代码作为第 n 项的处理程序，再写入通过接口设置的门
+    fn install_gates(&mut self) {
+        let labelled: Vec<(u8, u64)> = self
+            .labels
+            .iter()
+            .filter_map(|(label, &index)| {
+                let vector = label.to_lowercase().strip_prefix(HANDLER_PREFIX)?.parse().ok()?;
+                Some((vector, self.isa.instruction_address(index)))
+            })
+            .collect();
+        for (vector, handler) in labelled {
+            let gate = InterruptGate { handler, user: true, trap: false };
+            interrupt::write_gate(&mut self.state.memory, vector, Some(gate));
+        }
+        for (&vector, &gate) in &self.gates {
+            interrupt::write_gate(&mut self.state.memory, vector, gate);
+        }
+    }
+
+    /// 处理程序地址对应的指令下标，地址须为程序中某条指令的起始地址
+    fn handler_index(&self, address: u64) -> Option<usize> {
+        let index = self.isa.instruction_index(address);
+        (index < self.instructions.len() && self.isa.instruction_address(index) == address).then_some(index)
+    }
+
+    /// 载入数据段映像
+    fn apply_memory_image(&mut self) {
+        self.state.memory.fill(self.memory_image.base, &self.memory_image.bytes);
+    }
+
+    /// 经 MMU 把 [address, address + length) 转换为物理地址的若干段，跨页时按页分开；未启用 MMU 时原样返回。
+    /// 转换过程随本步的结果返回，TLB 不命中时遍历页表的周期计入周期数
+    fn translate(&mut self, address: u64, length: u64, access: AccessKind) -> Result<Vec<(u64, u64)>, Fault> {
+        if !self.mmu.config().enabled {
+            return Ok(vec![(address, length)]);
+        }
+        let (mut start, end) = (address, address + length.max(1));
+        let mut chunks = Vec::new();
+        while start < end {
+            let size = ((start / PAGE_SIZE + 1) * PAGE_SIZE).min(end) - start;
+            let translation = self.mmu.translate(&mut self.state.memory, start, access);
+            self.cycle_count += translation.cycles;
+            let fault = || Fault::page_fault(translation.fault.clone().unwrap_or_default());
+            let physical = translation.physical_address.ok_or_else(fault);
+            self.translations.push(translation);
+            chunks.push((physical?, size));
+            start += size;
+        }
+        Ok(chunks)
+    }
+
+    /// 取指时转换指令所在的地址，要求所在的页可执行
+    fn fetch_translation(&mut self, index: usize) -> Result<String, Fault> {
+        if !self.mmu.config().enabled {
+            return Ok(String::new());
+        }
+        let (first, length) =
+            (self.translations.len(), parse_hex(&self.instructions[index].machine_code).map_or(1, |bytes| bytes.len()));
+        self.translate(self.isa.instruction_address(index), length as u64, AccessKind::Execute)?;
+        Ok(self.translated(first))
+    }
+
+    /// 从第 first 次转换起的概要
+    fn translated(&self, first: usize) -> String {
+        let described: Vec<String> = self.translations[first..].iter().map(mmu::describe).collect();
+        format!("；地址转换：{}", described.join("；"))
+    }
+
+    /// 执行一条指令；pending 为访存级中尚未写回的指令结果，执行时优先使用
+    fn run(&self, decoded: &I::Decoded, index: usize, pending: Option<&Execution>) -> Result<Execution, Fault> {
+        let (mut registers, mut special) = (self.registers.clone(), self.special.clone());
+        let mut flags = self.state.flags.clone();
+        if let Some(pending) = pending {
+            for (register, value) in &pending.writes {
+                let slot = match register {
+                    Register::General(number) if Some(*number) == self.isa.zero_register() => None,
+                    Register::General(number) => registers.get_mut(*number),
+                    Register::Special(number) => special.get_mut(*number),
+                };
+                if let Some(slot) = slot {
+                    *slot = *value;
+                }
+            }
+            if let Some(pending) = &pending.flags {
+                flags = pending.clone();
+            }
+        }
+        let context = Context { registers: &registers, special: &special, flags: &flags, ..self.context(index) };
+        let mut execution = self.isa.execute(decoded, &context)?;
+        match execution.interrupt {
+            Some(InterruptRequest::Raise(vector)) => {
+                self.enter(&mut execution, InterruptKind::Software, vector, Some(index), index + 1, &context)?
+            }
+            Some(InterruptRequest::Return) => self.leave(&mut execution, index, &context)?,
+            None => {}
+        }
+        execution.reads = context.reads.take();
+        // 栈指针越出栈段时报告栈溢出或栈下溢，指令不再访存与写回
+        let stack_pointer = Register::General(self.isa.stack_pointer());
+        if let Some((_, value)) = execution.writes.iter().rev().find(|(register, _)| *register == stack_pointer) {
+            self.state.memory.check_stack_pointer(*value as u32 as u64)?;
+        }
+        Ok(execution)
+    }
+
+    /// 进入第 vector 项的处理程序：保存中断现场（返回地址与状态字），关中断（陷阱门除外）并转到处理程序。
+    /// x86 把中断现场压入栈中，RISC-V 与 MIPS 存入特殊寄存器。
+    /// instruction 为引发中断的指令，处理程序返回后从第 resume 条指令继续执行
+    fn enter(
+        &self,
+        execution: &mut Execution,
+        kind: InterruptKind,
+        vector: u8,
+        instruction: Option<usize>,
+        resume: usize,
+        context: &Context,
+    ) -> Result<(), Fault> {
+        let gate = interrupt::read_gate(&self.state.memory, vector)
+            .ok_or_else(|| Fault::general_protection(format!("中断描述符表第 {} 项无效", vector)))?;
+        let mmu = self.mmu.config();
+        if kind == InterruptKind::Software && mmu.enabled && mmu.user_mode && !gate.user {
+            let message = format!("用户态不能通过 INT {} 进入内核的处理程序", vector);
+            return Err(Fault::general_protection(message));
+        }
+        let handler = self.handler_index(gate.handler).ok_or_else(|| {
+            let message = format!("第 {} 项的处理程序 0x{:X} 不是指令的起始地址", vector, gate.handler);
+            Fault::general_protection(message)
+        })?;
+        let enabled = context.flags.interrupt && gate.trap;
+        let (saved, return_address) =
+            (self.isa.interrupt_status(context.flags, enabled), self.isa.instruction_address(resume));
+        let stack_pointer = self.isa.stack_pointer();
+        let mut sp = context.register(stack_pointer) as u32 as u64;
+        let description = match self.isa.interrupt_registers() {
+            Some((pc, status)) => {
+                let names = self.isa.special_register_names();
+                execution.writes.push((Register::Special(pc), return_address as i32));
+                execution.writes.push((Register::Special(status), saved as i32));
+                format!(
+                    "返回地址 0x{:X} 存入 {}，{} = 0x{:X}，转到处理程序 0x{:X}",
+                    return_address, names[pc], names[status], saved, gate.handler
+                )
+            }
+            None => {
+                sp = (sp as u32).wrapping_sub(8) as u64;
+                self.state.memory.check_stack_pointer(sp)?;
+                execution.writes.push((Register::General(stack_pointer), sp as i32));
+                let bytes = interrupt::frame(return_address, saved);
+                execution.memory = Some(MemoryOperation::StoreBytes { address: sp, bytes });
+                format!(
+                    "返回地址 0x{:X} 与标志寄存器 0x{:X} 压栈，转到处理程序 0x{:X}",
+                    return_address, saved, gate.handler
+                )
             }
         };
+        execution.message = format!("{}；{}", execution.message, description);
+        execution.flags = Some(FlagsState { interrupt: enabled, ..context.flags.clone() });
+        execution.branch = Some(Branch { target: handler, delayed: false });
+        execution.event = Some(InterruptEvent {
+            kind,
+            vector: Some(vector),
+            instruction,
+            target: gate.handler,
+            flags: saved,
+            stack_pointer: sp,
+            description: execution.message.clone(),
+        });
+        Ok(())
+    }
 
-        self.cycle_count += 1;
-        result
+    /// 从处理程序返回：取回返回地址与状态字，恢复标志位并转到返回地址
+    fn leave(&self, execution: &mut Execution, index: usize, context: &Context) -> Result<(), Fault> {
+        let stack_pointer = self.isa.stack_pointer();
+        let mut sp = context.register(stack_pointer) as u32 as u64;
+        let (return_address, saved) = match self.isa.interrupt_registers() {
+            Some((pc, status)) => (context.special(pc) as u32 as u64, context.special(status) as u32),
+            None => {
+                self.state.memory.check_stack_pointer(sp + 8)?;
+                (context.read_memory(sp, 4)? as u64, context.read_memory(sp + 4, 4)?)
+            }
+        };
+        let resume = self.isa.instruction_index(return_address);
+        if resume > self.instructions.len() || self.isa.instruction_address(resume) != return_address {
+            let message = format!("返回地址 0x{:X} 不是指令的起始地址", return_address);
+            return Err(Fault::general_protection(message));
+        }
+        let (flags, restored) = self.isa.restore_status(saved, context.flags);
+        let description = match self.isa.interrupt_registers() {
+            Some((pc, status)) => {
+                let names = self.isa.special_register_names();
+                execution.writes.push((Register::Special(status), restored as i32));
+                format!(
+                    "中断返回：返回地址 0x{:X} 取自 {}，{} = 0x{:X}，{}中断",
+                    return_address,
+                    names[pc],
+                    names[status],
+                    restored,
+                    if flags.interrupt { "开" } else { "关" }
+                )
+            }
+            None => {
+                sp += 8;
+                execution.writes.push((Register::General(stack_pointer), sp as i32));
+                format!("中断返回：弹出返回地址 0x{:X} 与标志寄存器 0x{:X}，栈指针 = 0x{:X}", return_address, saved, sp)
+            }
+        };
+        execution.message = format!("{}；{}", execution.message, description);
+        execution.flags = Some(flags);
+        execution.branch = Some(Branch { target: resume, delayed: false });
+        execution.event = Some(InterruptEvent {
+            kind: InterruptKind::Return,
+            vector: None,
+            instruction: Some(index),
+            target: return_address,
+            flags: saved,
+            stack_pointer: sp,
+            description: execution.message.clone(),
+        });
+        Ok(())
     }
 
-    fn fetch(&mut self, instruction: &Instruction) -> Result<ExecutionResult, String> {
-        // 取指阶段：从内存中获取指令
-        self.execution_stage = ExecutionStage::Decode;
-        
-        Ok(ExecutionResult {
-            stage: ExecutionStage::Fetch,
-            instruction: Some(instruction.clone()),
-            cpu_state: self.state.clone(),
-            message: format!("取指：从地址 0x{:X} 获取指令 {}", 
-                           self.state.registers.special.get("EIP").unwrap_or(&0), 
-                           instruction.mnemonic),
-            cycle_count: self.cycle_count,
-        })
+    /// 在两条指令之间响应异常或中断：立即保存中断现场并转到处理程序，返回说明与处理程序的下标
+    fn deliver(
+        &mut self,
+        kind: InterruptKind,
+        vector: u8,
+        instruction: Option<usize>,
+        resume: usize,
+        cause: String,
+    ) -> Result<(String, usize), String> {
+        let mut execution = Execution::new(cause.clone());
+        let entered = self.enter(&mut execution, kind, vector, instruction, resume, &self.context(resume));
+        let delivered = entered
+            .and_then(|()| self.access(&mut execution))
+            .map_err(|error| format!("{}；无法进入处理程序：{}", cause, error.message))?;
+        let committed = self.commit(&execution, "");
+        self.interrupts.extend(execution.event.take());
+        let target = execution.branch.map_or(resume, |branch| branch.target);
+        Ok((format!("{}；{}；{}", execution.message, delivered, committed), target))
     }
 
-    fn decode(&mut self, instruction: &Instruction) -> Result<ExecutionResult, String> {
-        // 译码阶段：解析指令
-        self.execution_stage = ExecutionStage::Execute;
-        
-        Ok(ExecutionResult {
-            stage: ExecutionStage::Decode,
-            instruction: Some(instruction.clone()),
-            cpu_state: self.state.clone(),
-            message: format!("译码：解析指令 {} {}", 
-                           instruction.mnemonic, 
-                           instruction.operands.join(", ")),
-            cycle_count: self.cycle_count,
-        })
-    }
-
-    fn execute(&mut self, instruction: &Instruction) -> Result<ExecutionResult, String> {
-        // 执行阶段：根据指令类型执行操作
-        match instruction.instruction_type {
-            InstructionType::Arithmetic => self.execute_arithmetic(instruction),
-            InstructionType::Logic => self.execute_logic(instruction),
-            InstructionType::DataTransfer => self.execute_data_transfer(instruction),
-            InstructionType::Control => self.execute_control(instruction),
-            InstructionType::Memory => {
-                self.execution_stage = ExecutionStage::MemoryAccess;
-                Ok(ExecutionResult {
-                    stage: ExecutionStage::Execute,
-                    instruction: Some(instruction.clone()),
-                    cpu_state: self.state.clone(),
-                    message: format!("执行：准备内存访问操作"),
-                    cycle_count: self.cycle_count,
-                })
+    /// 指令出错时，中断描述符表中有对应的处理程序则交给处理程序，否则照原样报告错误
+    fn handled(&self, fault: Fault) -> Result<Fault, String> {
+        match interrupt::read_gate(&self.state.memory, fault.vector) {
+            Some(_) => Ok(fault),
+            None => Err(fault.message),
+        }
+    }
+
+    /// 逐条执行时指令出错：进入异常处理程序，返回后重新执行出错的指令
+    fn fault(&mut self, fault: Fault) -> Result<String, String> {
+        let fault = self.handled(fault)?;
+        let index = self.current_instruction_index;
+        (self.decoded, self.execution, self.delayed_branch, self.slot_target) = (None, None, None, None);
+        let (message, target) = self.deliver(fault.kind, fault.vector, Some(index), index, fault.message)?;
+        Ok(self.interrupted(message, target))
+    }
+
+    /// 定时器已请求中断、处理器开中断（flags 中 IF = 1）且装有定时器中断的处理程序
+    fn timer_due(&self, flags: &FlagsState) -> bool {
+        self.interrupt_config.timer_interval > 0
+            && self.cycle_count >= self.next_timer
+            && flags.interrupt
+            && interrupt::read_gate(&self.state.memory, TIMER).is_some()
+    }
+
+    /// 在第 resume 条指令之前响应定时器中断
+    fn timer(&mut self, resume: usize) -> Result<(String, usize), String> {
+        self.next_timer = self.cycle_count + self.interrupt_config.timer_interval;
+        let cause = format!("定时器中断：推迟执行 {}", self.text(resume));
+        self.deliver(InterruptKind::Timer, TIMER, None, resume, cause)
+    }
+
+    /// 逐条执行时进入处理程序之后，下一步从处理程序的第一条指令开始
+    fn interrupted(&mut self, message: String, target: usize) -> String {
+        self.execution_stage = ExecutionStage::Complete;
+        self.next_instruction_index = Some(target);
+        self.sync_state(target);
+        message
+    }
+
+    fn execute(&mut self) -> Result<String, Fault> {
+        let decoded = self.decoded.take().ok_or("指令尚未译码")?;
+        // 本条指令位于延迟槽中时，执行完就转到先前分支的目标
+        self.slot_target = self.delayed_branch.take();
+        let index = self.current_instruction_index;
+        let mut execution = self.run(&decoded, index, None)?;
+        self.interrupts.extend(execution.event.take());
+        let mut message = execution.message.clone();
+        if self.slot_target.is_some() {
+            message.push_str("（延迟槽）");
+        }
+        // 转移的结果在执行阶段确定；预测错误时，按流水线中错误路径上被清除的指令计入损失的周期。
+        // 中断返回等不带延迟槽的转移按实际情况处理
+        if let Some(kind) = self.isa.branch_kind(&decoded) {
+            let delayed = execution.branch.map_or(self.isa.branch_delay_slot(), |branch| branch.delayed);
+            let address = self.isa.instruction_address(index);
+            let actual = execution.branch.map(|branch| branch.target);
+            if !self.predictor.update(index, address, kind, self.prediction, actual, delayed) {
+                let penalty = MISPREDICT_PENALTY - delayed as u64;
+                self.cycle_count += penalty;
+                self.predictor.penalize(penalty);
+                message.push_str(&format!("；分支预测错误，损失 {} 个周期", penalty));
             }
         }
+        // 启用缓存或 MMU 时，执行阶段读取的内存操作数也要在访存阶段经过缓存与地址转换
+        let reads = (self.cache.config().enabled || self.mmu.config().enabled) && !execution.reads.is_empty();
+        self.execution_stage = match execution.memory {
+            Some(_) => ExecutionStage::MemoryAccess,
+            None if reads => ExecutionStage::MemoryAccess,
+            None => ExecutionStage::WriteBack,
+        };
+        self.execution = Some(execution);
+        Ok(message)
     }
 
-    fn execute_arithmetic(&mut self, instruction: &Instruction) -> Result<ExecutionResult, String> {
+    fn memory_access(&mut self) -> Result<String, Fault> {
         self.execution_stage = ExecutionStage::WriteBack;
-        
-        match instruction.mnemonic.as_str() {
-            "ADD" => {
-                if instruction.operands.len() >= 2 {
-                    let reg1 = &instruction.operands[0];
-                    let reg2 = &instruction.operands[1];
-                    
-                    let val1 = *self.state.registers.general.get(reg1).unwrap_or(&0);
-                    let val2 = *self.state.registers.general.get(reg2).unwrap_or(&0);
-                    let result = val1 + val2;
-                    
-                    self.state.registers.general.insert(reg1.clone(), result);
-                    
-                    // 更新标志位
-                    self.state.flags.zero = result == 0;
-                    self.state.flags.negative = result < 0;
+        let Some(mut execution) = self.execution.take() else {
+            return Ok("内存访问：无".to_string());
+        };
+        let message = self.access(&mut execution);
+        self.execution = Some(execution);
+        message
+    }
+
+    /// 访存：按指令集的对齐要求读写内存，取出的值作为寄存器写入；越界、未对齐或写入只读段时报错。
+    /// 启用 MMU 时先把各个虚拟地址转换为物理地址，出现页错误时不读写任何内存；
+    /// 启用缓存时，访问经过缓存层次，超出一个周期的延迟计入周期数
+    fn access(&mut self, execution: &mut Execution) -> Result<String, Fault> {
+        let aligned = self.isa.aligned_access();
+        let operation = execution.memory.take();
+        let mut touched: Vec<(u64, u64, bool)> =
+            execution.reads.drain(..).map(|(address, size)| (address, size as u64, false)).collect();
+        touched.extend(match &operation {
+            Some(MemoryOperation::Load { address, size, .. }) => Some((*address, *size as u64, false)),
+            Some(MemoryOperation::Store { address, size, .. }) => Some((*address, *size as u64, true)),
+            Some(MemoryOperation::StoreBytes { address, bytes }) => Some((*address, bytes.len() as u64, true)),
+            None => None,
+        });
+        let first = self.translations.len();
+        let mut physical: Vec<(u64, u64, bool)> = Vec::new();
+        let mut chunks = Vec::new();
+        for &(address, size, write) in &touched {
+            chunks = self.translate(address, size, if write { AccessKind::Write } else { AccessKind::Read })?;
+            physical.extend(chunks.iter().map(|&(address, size)| (address, size, write)));
+        }
+
+        // 访存指令的地址排在最后，chunks 为其转换结果；跨页时逐段读写
+        let memory = &mut self.state.memory;
+        let mut message = match operation {
+            Some(MemoryOperation::Load { address, size, signed, destination }) => {
+                let raw = match chunks[..] {
+                    [(physical, _)] => memory.read(physical, size, aligned)?,
+                    _ => read_chunks(memory, &chunks)?,
+                } as u32;
+                let value = match (size, signed) {
+                    (1, true) => raw as u8 as i8 as i32,
+                    (2, true) => raw as u16 as i16 as i32,
+                    _ => raw as i32,
+                };
+                execution.writes.push((destination, value));
+                format!("内存访问：从地址 {} 读取 {} 字节，值为 {}", address, size, value)
+            }
+            Some(MemoryOperation::Store { address, size, value }) => {
+                match chunks[..] {
+                    [(physical, _)] => memory.write(physical, size, value as u32 as u64, aligned)?,
+                    _ => write_chunks(memory, &chunks, &(value as u32 as u64).to_le_bytes()[..size as usize])?,
                 }
+                format!("内存访问：把 {} 的低 {} 字节写入地址 {}", value, size, address)
+            }
+            Some(MemoryOperation::StoreBytes { address, bytes }) => {
+                write_chunks(memory, &chunks, &bytes)?;
+                format!("内存访问：向地址 {} 开始写入 {} 字节", address, bytes.len())
+            }
+            None if !touched.is_empty() => "内存访问：执行阶段已读取内存操作数".to_string(),
+            None => "内存访问：无".to_string(),
+        };
+        if self.translations.len() > first {
+            message.push_str(&self.translated(first));
+        }
+        if self.cache.config().enabled && !physical.is_empty() {
+            let accesses: Vec<CacheAccess> = physical
+                .into_iter()
+                .flat_map(|(address, size, write)| self.cache.access(address, size, write))
+                .collect();
+            let latency: u64 = accesses.iter().map(|access| access.latency).sum();
+            let stall = latency.saturating_sub(1);
+            self.cycle_count += stall;
+            let described: Vec<String> = accesses.iter().map(describe).collect();
+            message.push_str(&format!("；缓存：{}，共 {} 个周期", described.join("；"), latency));
+            if stall > 0 {
+                message.push_str(&format!("，停顿 {} 个周期", stall));
             }
-            "SUB" => {
-                if instruction.operands.len() >= 2 {
-                    let reg1 = &instruction.operands[0];
-                    let reg2 = &instruction.operands[1];
-                    
-                    let val1 = *self.state.registers.general.get(reg1).unwrap_or(&0);
-                    let val2 = *self.state.registers.general.get(reg2).unwrap_or(&0);
-                    let result = val1 - val2;
-                    
-                    self.state.registers.general.insert(reg1.clone(), result);
-                    
-                    // 更新标志位
-                    self.state.flags.zero = result == 0;
-                    self.state.flags.negative = result < 0;
+            self.cache_accesses.extend(accesses);
+        }
+        Ok(message)
+    }
+
+    /// 提交寄存器与标志位的写入
+    fn commit(&mut self, execution: &Execution, mnemonic: &str) -> String {
+        let mut written = Vec::new();
+        for &(register, value) in &execution.writes {
+            let (slot, name) = match register {
+                // 0 号寄存器恒为 0，写入被忽略
+                Register::General(number) if Some(number) == self.isa.zero_register() => continue,
+                Register::General(number) => (self.registers.get_mut(number), self.isa.register_names().get(number)),
+                Register::Special(number) => {
+                    (self.special.get_mut(number), self.isa.special_register_names().get(number))
                 }
+            };
+            if let (Some(slot), Some(name)) = (slot, name) {
+                *slot = value;
+                written.push(format!("{} = {}", name, value));
             }
-            _ => {}
         }
+        if let Some(flags) = &execution.flags {
+            self.state.flags = flags.clone();
+        }
+        if written.is_empty() {
+            format!("写回：{} 没有寄存器结果", mnemonic)
+        } else {
+            format!("写回：{}", written.join("，"))
+        }
+    }
 
-        Ok(ExecutionResult {
-            stage: ExecutionStage::Execute,
-            instruction: Some(instruction.clone()),
-            cpu_state: self.state.clone(),
-            message: format!("执行：算术运算 {}", instruction.mnemonic),
-            cycle_count: self.cycle_count,
-        })
+    fn write_back(&mut self, instruction: &Instruction) -> String {
+        self.execution_stage = ExecutionStage::Complete;
+        let execution = self.execution.take().unwrap_or_default();
+        let message = self.commit(&execution, &instruction.mnemonic);
+
+        let mut next = self.slot_target.take();
+        if let Some(branch) = execution.branch {
+            if branch.delayed {
+                self.delayed_branch = Some(branch.target);
+            } else {
+                next = Some(branch.target);
+            }
+        }
+        if execution.halt {
+            next = Some(self.instructions.len());
+        }
+        let next = next.unwrap_or(self.current_instruction_index + 1);
+        self.next_instruction_index = Some(next);
+        self.sync_state(next);
+        message
     }
 
-    fn execute_logic(&mut self, instruction: &Instruction) -> Result<ExecutionResult, String> {
-        self.execution_stage = ExecutionStage::WriteBack;
-        
-        Ok(ExecutionResult {
-            stage: ExecutionStage::Execute,
-            instruction: Some(instruction.clone()),
-            cpu_state: self.state.clone(),
-            message: format!("执行：逻辑运算 {}", instruction.mnemonic),
-            cycle_count: self.cycle_count,
-        })
+    /// 取指时预测转移的说明
+    fn predicted(&self, prediction: Prediction) -> String {
+        match prediction.next() {
+            Some(target) => format!("，预测转移到 0x{:X}", self.isa.instruction_address(target)),
+            None => String::new(),
+        }
     }
 
-    fn execute_data_transfer(&mut self, instruction: &Instruction) -> Result<ExecutionResult, String> {
-        self.execution_stage = ExecutionStage::WriteBack;
-        
-        match instruction.mnemonic.as_str() {
-            "MOV" => {
-                if instruction.operands.len() >= 2 {
-                    let dest = &instruction.operands[0];
-                    let src = &instruction.operands[1];
-                    
-                    // 简单的立即数到寄存器的传送
-                    if let Ok(value) = src.parse::<i64>() {
-                        self.state.registers.general.insert(dest.clone(), value);
+    /// 指令的写法，用于流水线的占用表与冒险说明
+    fn text(&self, index: usize) -> String {
+        let instruction = &self.instructions[index];
+        format!("{} {}", instruction.mnemonic, instruction.operands.join(", ")).trim_end().to_string()
+    }
+
+    fn location_name(&self, location: Location) -> String {
+        let name = match location {
+            Location::Register(Register::General(number)) => self.isa.register_names().get(number),
+            Location::Register(Register::Special(number)) => self.isa.special_register_names().get(number),
+            Location::Flags => return "标志位".to_string(),
+        };
+        name.map_or_else(|| "?".to_string(), |name| name.to_string())
+    }
+
+    /// 流水线模式下推进一个时钟周期。各级从写回级向前依次完成本周期的工作，执行级因此能直接使用
+    /// 访存级中指令的结果；何时停顿、经哪条通路前递由冒险检测决定。取指级按分支预测取指，
+    /// 转移在执行级确定，预测错误时清除其后已进入流水线的指令（延迟槽中的指令除外）
+    fn cycle(&mut self) -> Result<ExecutionResult, String> {
+        let mut messages = Vec::new();
+        let analysis = match &self.pipeline.decode {
+            Some(consumer) => {
+                let older: Vec<_> = [
+                    (&self.pipeline.execute, ExecutionStage::Execute),
+                    (&self.pipeline.memory, ExecutionStage::MemoryAccess),
+                ]
+                .into_iter()
+                .filter_map(|(instruction, stage)| instruction.as_ref().map(|instruction| (instruction, stage)))
+                .collect();
+                analyze(
+                    consumer,
+                    &older,
+                    self.pipeline_config.forwarding,
+                    self.isa.zero_register(),
+                    |index| self.text(index),
+                    |location| self.location_name(location),
+                )
+            }
+            None => Analysis::default(),
+        };
+        let Analysis { stall, mut hazards, forwardings } = analysis;
+
+        // 写回级：提交上一周期访存级中的指令
+        self.pipeline.write_back = None;
+        if let Some(mut instruction) = self.pipeline.memory.take() {
+            let execution = instruction.execution.take().unwrap_or_default();
+            let mnemonic = self.instructions[instruction.index].mnemonic.clone();
+            messages.push(format!("[WB] {}", self.commit(&execution, &mnemonic)));
+            self.pipeline.retired += 1;
+            self.pipeline.write_back = Some(instruction);
+        }
+
+        // 访存级；出错的指令留在访存级，周期末与其后的指令一起清除
+        let mut fault = None;
+        if let Some(mut instruction) = self.pipeline.execute.take() {
+            let mut execution = instruction.execution.take().unwrap_or_default();
+            match self.access(&mut execution) {
+                Ok(message) => {
+                    messages.push(format!("[MEM] {}", message));
+                    instruction.execution = Some(execution);
+                }
+                Err(error) => {
+                    let error = self.handled(error)?;
+                    messages.push(format!("[MEM] {}", error.message));
+                    fault = Some((ExecutionStage::MemoryAccess, instruction.index, error));
+                }
+            }
+            self.pipeline.memory = Some(instruction);
+        }
+
+        // 定时器中断在译码级的指令进入执行级之前响应；延迟槽中的指令不被打断
+        let older = self.pipeline.memory.as_ref().and_then(|instruction| instruction.execution.as_ref());
+        let flags = older.and_then(|execution| execution.flags.as_ref()).unwrap_or(&self.state.flags);
+        let in_slot = older.and_then(|execution| execution.branch).is_some_and(|branch| branch.delayed);
+        let timer = fault.is_none() && !stall && !in_slot && self.pipeline.decode.is_some() && self.timer_due(flags);
+
+        // 执行级：停顿时插入气泡；转移预测错误或停止时记下新的取指位置
+        let (mut redirect, mut halted, mut actual) = (None, false, None);
+        if stall {
+            self.pipeline.stalls += 1;
+            messages.push("[EX] 气泡（停顿）".to_string());
+        } else if fault.is_some() || timer {
+            // 本周期转去处理异常或中断，不再执行新的指令
+        } else if let Some(mut instruction) = self.pipeline.decode.take() {
+            let pending = self.pipeline.memory.as_ref().and_then(|instruction| instruction.execution.as_ref());
+            let index = instruction.index;
+            let decoded = instruction.decoded.take().unwrap_or_else(|| Err("指令尚未译码".into()));
+            match decoded.and_then(|decoded| Ok((self.run(&decoded, index, pending)?, decoded))) {
+                Ok((mut execution, decoded)) => {
+                    messages.push(format!("[EX] {}", execution.message));
+                    self.interrupts.extend(execution.event.take());
+                    actual = execution.branch.map(|branch| branch.target);
+                    if execution.halt {
+                        (redirect, halted) = (Some((self.instructions.len(), false)), true);
+                    } else if let Some(kind) = self.isa.branch_kind(&decoded) {
+                        let delayed = execution.branch.map_or(self.isa.branch_delay_slot(), |branch| branch.delayed);
+                        let address = self.isa.instruction_address(index);
+                        if !self.predictor.update(index, address, kind, instruction.prediction, actual, delayed) {
+                            // 不转移时从延迟槽之后顺序取指
+                            redirect = Some((actual.unwrap_or(index + 1 + delayed as usize), delayed));
+                        }
                     }
+                    instruction.execution = Some(execution);
+                }
+                Err(error) => {
+                    let error = self.handled(error)?;
+                    messages.push(format!("[EX] {}", error.message));
+                    fault = Some((ExecutionStage::Execute, index, error));
                 }
             }
-            _ => {}
+            self.pipeline.execute = Some(instruction);
         }
 
-        Ok(ExecutionResult {
-            stage: ExecutionStage::Execute,
-            instruction: Some(instruction.clone()),
-            cpu_state: self.state.clone(),
-            message: format!("执行：数据传送 {}", instruction.mnemonic),
-            cycle_count: self.cycle_count,
-        })
-    }
+        // 处理程序返回后继续执行的指令；指令出错时还有出错的级与错误
+        let interruption = match fault {
+            Some((stage, index, error)) => Some((index, Some((stage, error)))),
+            None if timer => self.pipeline.decode.as_ref().map(|instruction| (instruction.index, None)),
+            None => None,
+        };
+        let (execute_faulted, memory_faulted) = match &interruption {
+            Some((_, Some((ExecutionStage::Execute, _)))) => (true, false),
+            Some((_, Some((ExecutionStage::MemoryAccess, _)))) => (false, true),
+            _ => (false, false),
+        };
 
-    fn execute_control(&mut self, instruction: &Instruction) -> Result<ExecutionResult, String> {
-        self.execution_stage = ExecutionStage::WriteBack;
-        
-        Ok(ExecutionResult {
-            stage: ExecutionStage::Execute,
-            instruction: Some(instruction.clone()),
-            cpu_state: self.state.clone(),
-            message: format!("执行：控制流 {}", instruction.mnemonic),
-            cycle_count: self.cycle_count,
-        })
-    }
+        // 译码级与取指级：停顿时保持不动，转去处理中断时在周期末清除
+        if interruption.is_some() {
+        } else if stall {
+            if let Some(instruction) = &self.pipeline.decode {
+                messages.push(format!("[ID] {} 停顿", self.text(instruction.index)));
+            }
+        } else {
+            if let Some(mut instruction) = self.pipeline.fetch.take() {
+                // 取指时的页错误保留到进入执行级时报告
+                let decoded = match instruction.decoded.take() {
+                    Some(fault) => fault,
+                    None => self.isa.decode(&self.instructions[instruction.index]).map_err(Fault::from),
+                };
+                if let Ok(decoded) = &decoded {
+                    instruction.usage = self.isa.register_usage(decoded);
+                }
+                instruction.decoded = Some(decoded);
+                messages.push(format!("[ID] 译码：{}", self.text(instruction.index)));
+                self.pipeline.decode = Some(instruction);
+            }
+            // 取指时按预测决定下一条取哪里；带延迟槽的转移先取延迟槽，再转到预测的目标
+            let index = self.pipeline.next_fetch;
+            if index < self.instructions.len() {
+                let address = self.isa.instruction_address(index);
+                let prediction = self.predictor.predict(address);
+                let (translated, fault) = match self.fetch_translation(index) {
+                    Ok(translated) => (translated, None),
+                    Err(fault) => (format!("；{}", fault.message), Some(Err(fault))),
+                };
+                let mnemonic = &self.instructions[index].mnemonic;
+                let predicted = self.predicted(prediction);
+                messages.push(format!(
+                    "[IF] 取指：从地址 0x{:X} 获取指令 {}{}{}",
+                    address, mnemonic, predicted, translated
+                ));
+                let instruction =
+                    InFlight { prediction, decoded: fault, ..InFlight::new(self.pipeline.sequence, index) };
+                self.pipeline.fetch = Some(instruction);
+                self.pipeline.sequence += 1;
+                self.pipeline.next_fetch = self.pipeline.slot_target.take().unwrap_or(index + 1);
+                match (prediction.next(), prediction.delayed) {
+                    (Some(target), true) => self.pipeline.slot_target = Some(target),
+                    (Some(target), false) => self.pipeline.next_fetch = target,
+                    (None, _) => {}
+                }
+            }
+        }
 
-    fn memory_access(&mut self, instruction: &Instruction) -> Result<ExecutionResult, String> {
-        self.execution_stage = ExecutionStage::WriteBack;
-        
-        // 模拟内存访问
-        if instruction.mnemonic == "MOV" && instruction.operands.len() >= 2 {
-            let dest = &instruction.operands[0];
-            let src = &instruction.operands[1];
-            
-            // 检查是否是内存操作
-            if dest.starts_with('[') && dest.ends_with(']') {
-                // 存储到内存
-                let addr_str = &dest[1..dest.len()-1];
-                if let Ok(addr) = addr_str.parse::<u32>() {
-                    if let Some(value) = self.state.registers.general.get(src) {
-                        self.state.memory.data.insert(addr.into(), *value);
-                    }
+        // 本周期的占用表；预测错误或转去处理中断时，错误路径上的指令在周期末被清除
+        let (flush_decode, flush_fetch) = match redirect {
+            _ if interruption.is_some() => (true, true),
+            Some((_, delayed)) => (!delayed, true),
+            None => (false, false),
+        };
+        let slot = |instruction: &Option<InFlight<I::Decoded>>, stage: ExecutionStage, stalled: bool, flushed: bool| {
+            PipelineSlot {
+                stage,
+                instruction: instruction.as_ref().map(|instruction| instruction.index),
+                sequence: instruction.as_ref().map(|instruction| instruction.sequence),
+                text: match instruction {
+                    Some(instruction) => self.text(instruction.index),
+                    None => "气泡".to_string(),
+                },
+                stalled: stalled && instruction.is_some(),
+                flushed: flushed && instruction.is_some(),
+            }
+        };
+        let slots = vec![
+            slot(&self.pipeline.fetch, ExecutionStage::Fetch, stall, flush_fetch),
+            slot(&self.pipeline.decode, ExecutionStage::Decode, stall, flush_decode),
+            slot(&self.pipeline.execute, ExecutionStage::Execute, false, execute_faulted),
+            slot(&self.pipeline.memory, ExecutionStage::MemoryAccess, false, memory_faulted),
+            slot(&self.pipeline.write_back, ExecutionStage::WriteBack, false, false),
+        ];
+
+        if let Some((target, delayed)) = redirect {
+            let mut flushed = Vec::new();
+            if !delayed {
+                flushed.extend(self.pipeline.decode.take());
+            }
+            flushed.extend(self.pipeline.fetch.take());
+            self.pipeline.next_fetch = target;
+            self.pipeline.slot_target = None;
+            self.pipeline.flushes += flushed.len() as u64;
+            let branch = self.pipeline.execute.as_ref().map_or(0, |instruction| instruction.index);
+            let effect = if halted {
+                "使程序结束".to_string()
+            } else {
+                self.predictor.penalize(flushed.len() as u64);
+                match actual {
+                    Some(target) => format!("转移到 0x{:X}，预测错误", self.isa.instruction_address(target)),
+                    None => "不转移，预测错误".to_string(),
+                }
+            };
+            for instruction in flushed {
+                hazards.push(Hazard {
+                    kind: HazardKind::Control,
+                    register: None,
+                    producer: branch,
+                    consumer: instruction.index,
+                    stall: false,
+                    description: format!(
+                        "控制冒险：{} {}，清除错误路径上的 {}",
+                        self.text(branch),
+                        effect,
+                        self.text(instruction.index)
+                    ),
+                });
+            }
+        }
+        // 异常与中断：出错的指令作废，访存级中较早的指令已完成访存，先行写回；其后的指令全部清除
+        if let Some((resume, fault)) = interruption {
+            if !memory_faulted {
+                if let Some(mut older) = self.pipeline.memory.take() {
+                    let execution = older.execution.take().unwrap_or_default();
+                    let mnemonic = self.instructions[older.index].mnemonic.clone();
+                    messages.push(format!("[WB] 提前{}", self.commit(&execution, &mnemonic)));
+                    self.pipeline.retired += 1;
                 }
             }
+            (self.pipeline.memory, self.pipeline.execute) = (None, None);
+            let flushed: Vec<_> = self.pipeline.decode.take().into_iter().chain(self.pipeline.fetch.take()).collect();
+            self.pipeline.flushes += flushed.len() as u64;
+            let (kind, vector) =
+                fault.as_ref().map_or((InterruptKind::Timer, TIMER), |(_, error)| (error.kind, error.vector));
+            let (message, target) = match fault {
+                Some((_, error)) => self.deliver(kind, vector, Some(resume), resume, error.message)?,
+                None => self.timer(resume)?,
+            };
+            messages.push(format!("[INT] {}", message));
+            self.pipeline.next_fetch = target;
+            self.pipeline.slot_target = None;
+            for flushed in flushed {
+                hazards.push(Hazard {
+                    kind: HazardKind::Control,
+                    register: None,
+                    producer: resume,
+                    consumer: flushed.index,
+                    stall: false,
+                    description: format!(
+                        "控制冒险：{}，清除 {}",
+                        interrupt::name(kind, vector),
+                        self.text(flushed.index)
+                    ),
+                });
+            }
         }
+        messages.extend(hazards.iter().map(|hazard| hazard.description.clone()));
+
+        // 程序计数器指向下一条要取的指令；各级都已排空且没有指令可取时程序结束
+        self.sync_state(self.pipeline.next_fetch);
+        let stages = [&self.pipeline.memory, &self.pipeline.execute, &self.pipeline.decode, &self.pipeline.fetch];
+        self.current_instruction_index = stages
+            .into_iter()
+            .find_map(|instruction| instruction.as_ref().map(|instruction| instruction.index))
+            .unwrap_or(self.pipeline.next_fetch);
 
-        Ok(ExecutionResult {
-            stage: ExecutionStage::MemoryAccess,
-            instruction: Some(instruction.clone()),
+        let oldest = slots.iter().rev().find(|slot| slot.instruction.is_some());
+        let result = ExecutionResult {
+            stage: oldest.map_or(ExecutionStage::Fetch, |slot| slot.stage.clone()),
+            instruction: oldest.and_then(|slot| slot.instruction).map(|index| self.instructions[index].clone()),
             cpu_state: self.state.clone(),
-            message: format!("内存访问：{}", instruction.description),
+            message: messages.join("\n"),
             cycle_count: self.cycle_count,
-        })
+            cache: std::mem::take(&mut self.cache_accesses),
+            translations: std::mem::take(&mut self.translations),
+            interrupts: std::mem::take(&mut self.interrupts),
+            pipeline: Some(PipelineCycle {
+                cycle: self.cycle_count,
+                slots,
+                hazards,
+                forwardings,
+                retired: self.pipeline.retired,
+                stalls: self.pipeline.stalls,
+                flushes: self.pipeline.flushes,
+            }),
+        };
+        self.cycle_count += 1;
+        Ok(result)
     }
+}
 
-    fn write_back(&mut self, instruction: &Instruction) -> Result<ExecutionResult, String> {
-        self.execution_stage = ExecutionStage::Complete;
-        
-        // 更新指令指针
-        if let Some(eip) = self.state.registers.special.get_mut("EIP") {
-            *eip += 1;
+impl<I: Isa> Simulator for CPUSimulator<I> {
+    fn target(&self) -> Target {
+        Target { architecture: self.isa.architecture(), branch_delay_slot: self.isa.branch_delay_slot() }
+    }
+
+    fn load_instructions(&mut self, instructions: Vec<Instruction>) -> Result<(), String> {
+        self.isa.load_program(&instructions);
+        let length = self.isa.instruction_address(instructions.len()) - CODE_BASE as u64;
+        if let Err(message) = check_code_size(length) {
+            self.isa.load_program(&self.instructions);
+            return Err(message);
+        }
+        self.labels = instructions
+            .iter()
+            .enumerate()
+            .filter_map(|(i, instruction)| instruction.label.clone().map(|label| (label, i)))
+            .collect();
+        self.instructions = instructions;
+        // 新程序使用新的内存，数据段映像随后由 load_memory_image 载入
+        self.state.memory = self.new_memory();
+        self.memory_image = MemoryImage::default();
+        self.load_code();
+        self.current_instruction_index = 0;
+        self.execution_stage = ExecutionStage::Fetch;
+        self.decoded = None;
+        self.execution = None;
+        self.next_instruction_index = None;
+        self.delayed_branch = None;
+        self.slot_target = None;
+        self.pipeline = Pipeline::default();
+        self.predictor = BranchPredictor::new(self.predictor.kind());
+        self.prediction = Prediction::default();
+        self.cache = CacheHierarchy::new(self.cache.config());
+        self.cache_accesses.clear();
+        self.translations.clear();
+        // 通过接口设置的中断门随旧程序作废
+        self.gates.clear();
+        self.install_gates();
+        self.next_timer = self.interrupt_config.timer_interval;
+        self.interrupts.clear();
+        // 指令地址随程序改变，程序计数器指向第一条指令
+        self.sync_state(0);
+        Ok(())
+    }
+
+    fn load_memory_image(&mut self, image: MemoryImage) {
+        self.memory_image = image;
+        self.apply_memory_image();
+    }
+
+    fn step(&mut self) -> Result<ExecutionResult, String> {
+        if self.current_instruction_index >= self.instructions.len() {
+            return Ok(ExecutionResult {
+                stage: ExecutionStage::Complete,
+                instruction: None,
+                cpu_state: self.state.clone(),
+                message: "程序执行完成".to_string(),
+                cycle_count: self.cycle_count,
+                pipeline: None,
+                cache: Vec::new(),
+                translations: Vec::new(),
+                interrupts: Vec::new(),
+            });
+        }
+        if self.pipeline_config.enabled {
+            return self.cycle();
         }
 
-        Ok(ExecutionResult {
-            stage: ExecutionStage::WriteBack,
-            instruction: Some(instruction.clone()),
+        let index = self.current_instruction_index;
+        let instruction = self.instructions[index].clone();
+        let stage = self.execution_stage.clone();
+        let outcome = match stage {
+            // 定时器中断在取指之前响应，延迟槽中的指令不被打断
+            ExecutionStage::Fetch if self.delayed_branch.is_none() && self.timer_due(&self.state.flags) => {
+                let (message, target) = self.timer(index)?;
+                Ok(self.interrupted(message, target))
+            }
+            ExecutionStage::Fetch => {
+                // 取指阶段：从内存中获取指令，同时预测下一条指令的位置
+                self.fetch_translation(index).map(|translated| {
+                    self.execution_stage = ExecutionStage::Decode;
+                    let address = self.isa.instruction_address(index);
+                    self.prediction = self.predictor.predict(address);
+                    let predicted = self.predicted(self.prediction);
+                    format!("取指：从地址 0x{:X} 获取指令 {}{}{}", address, instruction.mnemonic, predicted, translated)
+                })
+            }
+            ExecutionStage::Decode => {
+                // 译码阶段：由指令集解析操作数
+                self.isa.decode(&instruction).map_err(Fault::from).map(|decoded| {
+                    self.decoded = Some(decoded);
+                    self.execution_stage = ExecutionStage::Execute;
+                    format!("译码：解析指令 {} {}", instruction.mnemonic, instruction.operands.join(", "))
+                })
+            }
+            ExecutionStage::Execute => self.execute(),
+            ExecutionStage::MemoryAccess => self.memory_access(),
+            ExecutionStage::WriteBack => Ok(self.write_back(&instruction)),
+            ExecutionStage::Complete => {
+                self.current_instruction_index =
+                    self.next_instruction_index.take().unwrap_or(self.current_instruction_index + 1);
+                self.execution_stage = ExecutionStage::Fetch;
+                let result = ExecutionResult {
+                    stage: ExecutionStage::Fetch,
+                    instruction: None,
+                    cpu_state: self.state.clone(),
+                    message: "准备执行下一条指令".to_string(),
+                    cycle_count: self.cycle_count,
+                    pipeline: None,
+                    cache: Vec::new(),
+                    translations: Vec::new(),
+                    interrupts: Vec::new(),
+                };
+                self.cycle_count += 1;
+                return Ok(result);
+            }
+        };
+        // 指令出错时进入异常处理程序，没有处理程序时照原样报告错误
+        let message = match outcome {
+            Ok(message) => message,
+            Err(fault) => self.fault(fault)?,
+        };
+
+        let result = ExecutionResult {
+            stage,
+            instruction: Some(instruction),
             cpu_state: self.state.clone(),
-            message: format!("写回：完成指令 {}", instruction.mnemonic),
+            message,
             cycle_count: self.cycle_count,
-        })
+            pipeline: None,
+            cache: std::mem::take(&mut self.cache_accesses),
+            translations: std::mem::take(&mut self.translations),
+            interrupts: std::mem::take(&mut self.interrupts),
+        };
+        self.cycle_count += 1;
+        Ok(result)
     }
 
-    pub fn reset(&mut self) {
-        self.state = CPUState::default();
+    fn reset(&mut self) {
+        self.state = CPUState { memory: self.new_memory(), ..CPUState::default() };
         self.current_instruction_index = 0;
         self.execution_stage = ExecutionStage::Fetch;
         self.cycle_count = 0;
+        self.decoded = None;
+        self.execution = None;
+        self.next_instruction_index = None;
+        self.delayed_branch = None;
+        self.slot_target = None;
+        self.pipeline = Pipeline::default();
+        self.predictor = BranchPredictor::new(self.predictor.kind());
+        self.prediction = Prediction::default();
+        self.cache = CacheHierarchy::new(self.cache.config());
+        self.cache_accesses.clear();
+        self.translations.clear();
+        self.next_timer = self.interrupt_config.timer_interval;
+        self.interrupts.clear();
+        self.load_code();
+        self.install_gates();
+        self.apply_memory_image();
+        self.init_registers();
+    }
+
+    fn state(&self) -> &CPUState {
+        &self.state
+    }
+
+    fn configure_stack(&mut self, stack_top: u64, stack_size: u64) -> Result<(), String> {
+        check_stack(stack_top, stack_size)?;
+        mmu::check_config(&self.mmu.config(), stack_top, stack_size)?;
+        self.stack_top = stack_top;
+        self.stack_size = stack_size;
+        self.reset();
+        Ok(())
+    }
+
+    fn stack_frames(&self) -> Vec<StackFrame> {
+        self.isa.stack_frames(&self.context(self.current_instruction_index))
+    }
+
+    fn pipeline_config(&self) -> PipelineConfig {
+        self.pipeline_config
+    }
+
+    fn configure_pipeline(&mut self, config: PipelineConfig) {
+        self.pipeline_config = config;
+        self.reset();
+    }
+
+    fn set_predictor(&mut self, kind: PredictorKind) {
+        self.predictor = BranchPredictor::new(kind);
+    }
+
+    fn branch_statistics(&self) -> BranchStatistics {
+        self.predictor.statistics(|index| (self.isa.instruction_address(index), self.text(index)))
+    }
+
+    fn cache_config(&self) -> CacheConfig {
+        self.cache.config()
+    }
+
+    fn configure_cache(&mut self, config: CacheConfig) -> Result<(), String> {
+        check_config(&config)?;
+        self.cache = CacheHierarchy::new(config);
+        self.reset();
+        Ok(())
+    }
+
+    fn cache_view(&self) -> CacheView {
+        self.cache.view(&self.state.memory)
+    }
+
+    fn mmu_config(&self) -> MmuConfig {
+        self.mmu.config()
+    }
+
+    fn configure_mmu(&mut self, config: MmuConfig) -> Result<(), String> {
+        mmu::check_config(&config, self.stack_top, self.stack_size)?;
+        self.mmu = Mmu::new(config);
+        self.reset();
+        Ok(())
+    }
+
+    fn map_page(
+        &mut self,
+        virtual_address: u64,
+        physical_address: u64,
+        permissions: PagePermissions,
+    ) -> Result<(), String> {
+        self.mmu.map(&mut self.state.memory, virtual_address, physical_address, permissions)
+    }
+
+    fn unmap_page(&mut self, virtual_address: u64) -> Result<(), String> {
+        self.mmu.unmap(&mut self.state.memory, virtual_address)
+    }
+
+    fn page_table(&self) -> PageTableView {
+        self.mmu.view(&self.state.memory)
+    }
+
+    fn interrupt_config(&self) -> InterruptConfig {
+        self.interrupt_config
+    }
+
+    fn configure_interrupts(&mut self, config: InterruptConfig) {
+        self.interrupt_config = config;
+        self.reset();
+    }
+
+    fn set_interrupt_gate(&mut self, vector: u8, gate: Option<InterruptGate>) -> Result<(), String> {
+        if let Some(gate) = gate {
+            self.handler_index(gate.handler)
+                .ok_or_else(|| format!("处理程序地址 0x{:X} 不是指令的起始地址", gate.handler))?;
+        }
+        self.gates.insert(vector, gate);
+        interrupt::write_gate(&mut self.state.memory, vector, gate);
+        Ok(())
+    }
+
+    fn interrupt_table(&self) -> InterruptTableView {
+        let entries = (0..=u8::MAX)
+            .filter_map(|vector| {
+                let gate = interrupt::read_gate(&self.state.memory, vector)?;
+                let label = self.handler_index(gate.handler).and_then(|index| self.instructions[index].label.clone());
+                Some(InterruptTableEntry { vector, gate, label })
+            })
+            .collect();
+        InterruptTableView {
+            base: IDT_BASE,
+            config: self.interrupt_config,
+            entries,
+            timer_pending: self.interrupt_config.timer_interval > 0 && self.cycle_count >= self.next_timer,
+        }
+    }
+}
+
+/// 依次读取转换后的各段物理内存，按小端序拼成一个值
+fn read_chunks(memory: &MemoryState, chunks: &[(u64, u64)]) -> Result<u64, Fault> {
+    let addresses = chunks.iter().flat_map(|&(address, size)| address..address + size);
+    addresses.enumerate().try_fold(0, |value, (i, address)| Ok(value | memory.read(address, 1, false)? << (8 * i)))
+}
+
+/// 把 bytes 依次写入转换后的各段物理内存
+fn write_chunks(memory: &mut MemoryState, chunks: &[(u64, u64)], bytes: &[u8]) -> Result<(), Fault> {
+    let mut offset = 0;
+    for &(address, size) in chunks {
+        let size = (size as usize).min(bytes.len() - offset);
+        memory.write_bytes(address, &bytes[offset..offset + size])?;
+        offset += size;
     }
+    Ok(())
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -271,4 +1285,12 @@ pub struct ExecutionResult {
     pub cpu_state: CPUState,
     pub message: String,
     pub cycle_count: u64,
+    /// 流水线模式下本周期的占用表，逐条执行时为 None
+    pub pipeline: Option<PipelineCycle>,
+    /// 本步经过缓存的访存，未启用缓存时为空
+    pub cache: Vec<CacheAccess>,
+    /// 本步的地址转换，未启用 MMU 时为空
+    pub translations: Vec<Translation>,
+    /// 本步发生的异常、中断与中断返回
+    pub interrupts: Vec<InterruptEvent>,
 }
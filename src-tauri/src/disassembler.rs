@@ -0,0 +1,708 @@
+use crate::encoder::{
+    classify, hex, word_form, Operand, ALU_OPERATIONS, CONDITION_NAMES, GROUP3_OPERATIONS, REGISTERS_16, REGISTERS_32,
+    REGISTERS_8, SHIFT_OPERATIONS,
+};
+use crate::memory::CODE_BASE;
+use crate::types::{EncodingField, Instruction};
+use std::collections::HashMap;
+
+/// 解析十六进制字节串，允许空白、逗号与 0x 前缀，如 "B8 05 00 00 00" 或 "0xB8,0x05"
+pub fn parse_hex(text: &str) -> Result<Vec<u8>, String> {
+    let digits: String = text
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .map(|token| token.trim_start_matches("0x").trim_start_matches("0X"))
+        .collect();
+    if let Some(c) = digits.chars().find(|c| !c.is_ascii_hexdigit()) {
+        return Err(format!("非法的十六进制字符 '{}'", c));
+    }
+    if !digits.len().is_multiple_of(2) {
+        return Err("十六进制数字个数必须为偶数".to_string());
+    }
+    Ok((0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).unwrap())
+        .collect())
+}
+
+/// 一条解码后的指令，跳转目标在全部解码后才换成标签
+struct Decoded {
+    address: i64,
+    mnemonic: String,
+    operands: Vec<String>,
+    target: Option<i64>,
+    memory: bool,
+    form: String,
+    fields: Vec<EncodingField>,
+    bytes: Vec<u8>,
+}
+
+/// 逐字节读取一条指令，同时记录各字段
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    fields: Vec<EncodingField>,
+    memory: bool,
+    /// 带操作数大小前缀 66：32 位的立即数变为 16 位
+    word: bool,
+}
+
+impl<'a> Decoder<'a> {
+    fn byte(&mut self) -> Result<u8, String> {
+        let byte = *self.bytes.get(self.pos).ok_or("指令在字节流末尾被截断")?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn read(&mut self, size: usize) -> Result<&'a [u8], String> {
+        let slice = self.bytes.get(self.pos..self.pos + size).ok_or("指令在字节流末尾被截断")?;
+        self.pos += size;
+        Ok(slice)
+    }
+
+    fn field(&mut self, field: &str, from: usize, description: String) {
+        self.fields.push(EncodingField {
+            field: field.to_string(),
+            hex: hex(&self.bytes[from..self.pos]),
+            description,
+        });
+    }
+
+    /// 有符号立即数（或位移），按小端序读取 size 个字节
+    fn signed(&mut self, size: usize) -> Result<i64, String> {
+        let bytes = self.read(size)?;
+        Ok(match size {
+            1 => bytes[0] as i8 as i64,
+            2 => i16::from_le_bytes([bytes[0], bytes[1]]) as i64,
+            _ => i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as i64,
+        })
+    }
+
+    fn immediate(&mut self, size: usize, signed: bool) -> Result<i64, String> {
+        let size = if self.word && size == 4 { 2 } else { size };
+        let from = self.pos;
+        let mut value = self.signed(size)?;
+        if !signed && value < 0 {
+            value += 1 << (size * 8);
+        }
+        self.field("立即数", from, format!("imm{} = {}（小端序）", size * 8, value));
+        Ok(value)
+    }
+
+    /// 读取 ModR/M（以及 SIB 与位移），返回 reg 字段与 r/m 操作数
+    fn modrm(&mut self, byte_sized: bool) -> Result<(u8, Operand), String> {
+        let from = self.pos;
+        let modrm = self.byte()?;
+        let (mode, reg, rm) = (modrm >> 6, (modrm >> 3) & 7, modrm & 7);
+        self.field("ModR/M", from, format!("mod={:02b} reg={:03b} r/m={:03b}", mode, reg, rm));
+        if mode == 0b11 {
+            let operand = if byte_sized { Operand::Reg8(rm) } else { Operand::Reg32(rm) };
+            return Ok((reg, operand));
+        }
+
+        self.memory = true;
+        let (mut base, mut index) = (Some(rm), None);
+        if rm == 0b100 {
+            let from = self.pos;
+            let sib = self.byte()?;
+            let (scale, index_code, base_code) = (1u8 << (sib >> 6), (sib >> 3) & 7, sib & 7);
+            index = (index_code != 0b100).then_some((index_code, scale));
+            base = (base_code != 0b101 || mode != 0b00).then_some(base_code);
+            self.field(
+                "SIB",
+                from,
+                format!("scale={:02b} index={:03b} base={:03b}", sib >> 6, index_code, base_code),
+            );
+        } else if rm == 0b101 && mode == 0b00 {
+            base = None;
+        }
+        let disp_size = match mode {
+            0b01 => 1,
+            0b10 => 4,
+            _ if base.is_none() => 4,
+            _ => 0,
+        };
+        let mut disp = 0;
+        if disp_size > 0 {
+            let from = self.pos;
+            disp = self.signed(disp_size)?;
+            self.field("位移", from, format!("disp{} = {}", disp_size * 8, disp));
+        }
+        Ok((reg, Operand::Mem { base, index, disp }))
+    }
+
+    /// 相对偏移，返回跳转目标地址
+    fn relative(&mut self, size: usize, base: i64) -> Result<i64, String> {
+        let from = self.pos;
+        let offset = self.signed(size)?;
+        let target = base + self.pos as i64 + offset;
+        self.field("相对偏移", from, format!("rel{} = {}，目标地址 {:08X}", size * 8, offset, target));
+        Ok(target)
+    }
+}
+
+/// 操作数的汇编文本；size 为内存操作数需要的长度说明（如 "DWORD PTR"）
+pub fn format_operand(operand: &Operand, size: Option<&str>) -> String {
+    match operand {
+        Operand::Reg32(code) => REGISTERS_32[*code as usize].to_string(),
+        Operand::Reg16(code) => REGISTERS_16[*code as usize].to_string(),
+        Operand::Reg8(code) => REGISTERS_8[*code as usize].to_string(),
+        Operand::Imm(value) => value.to_string(),
+        Operand::Label(label) => label.clone(),
+        Operand::Mem { base, index, disp } => {
+            let mut terms = Vec::new();
+            if let Some(base) = base {
+                terms.push(REGISTERS_32[*base as usize].to_string());
+            }
+            if let Some((index, scale)) = index {
+                terms.push(match scale {
+                    1 => REGISTERS_32[*index as usize].to_string(),
+                    _ => format!("{}*{}", REGISTERS_32[*index as usize], scale),
+                });
+            }
+            let mut text = terms.join("+");
+            if terms.is_empty() {
+                text = disp.to_string();
+            } else if *disp > 0 {
+                text.push_str(&format!("+{}", disp));
+            } else if *disp < 0 {
+                text.push_str(&format!("-{}", -disp));
+            }
+            match size {
+                Some(size) => format!("{} [{}]", size, text),
+                None => format!("[{}]", text),
+            }
+        }
+    }
+}
+
+fn reg32(code: u8) -> String {
+    REGISTERS_32[code as usize].to_string()
+}
+
+fn reg8(code: u8) -> String {
+    REGISTERS_8[code as usize].to_string()
+}
+
+/// 在 address 处解码一条指令
+fn decode_one(bytes: &[u8], offset: usize, base: i64) -> Result<Decoded, String> {
+    let mut d = Decoder {
+        bytes,
+        pos: offset,
+        fields: Vec::new(),
+        memory: false,
+        word: false,
+    };
+    let address = base + offset as i64;
+    let start = offset;
+    let mut opcode = d.byte()?;
+    if opcode == 0x66 {
+        d.word = true;
+        d.field("前缀", start, "操作数大小前缀：16 位操作数".to_string());
+        opcode = d.byte()?;
+    }
+    // 操作码字段从前缀之后开始
+    let offset = d.pos - 1;
+    let opcode_field = d.fields.len();
+    let dword = Some("DWORD PTR");
+    let byte = Some("BYTE PTR");
+
+    // 先确定助记符与形式，再读取其余字段（操作码字段必须排在最前面）
+    let mut target = None;
+    let (mnemonic, form, operands): (String, String, Vec<String>) = match opcode {
+        0x0F => {
+            let second = d.byte()?;
+            match second {
+                0x80..=0x8F => {
+                    let condition = CONDITION_NAMES[(second - 0x80) as usize];
+                    d.field("操作码", offset, format!("J{} rel32", condition));
+                    target = Some(d.relative(4, base)?);
+                    (format!("J{}", condition), format!("J{} rel32", condition), Vec::new())
+                }
+                0x90..=0x9F => {
+                    let condition = CONDITION_NAMES[(second - 0x90) as usize];
+                    d.field("操作码", offset, format!("SET{} r/m8", condition));
+                    let (_, rm) = d.modrm(true)?;
+                    (format!("SET{}", condition), format!("SET{} r/m8", condition), vec![format_operand(&rm, byte)])
+                }
+                0xAF => {
+                    d.field("操作码", offset, "IMUL r32, r/m32".to_string());
+                    let (reg, rm) = d.modrm(false)?;
+                    ("IMUL".into(), "IMUL r32, r/m32".into(), vec![reg32(reg), format_operand(&rm, None)])
+                }
+                0xB6 | 0xBE => {
+                    let name = if second == 0xB6 { "MOVZX" } else { "MOVSX" };
+                    d.field("操作码", offset, format!("{} r32, r/m8", name));
+                    let (reg, rm) = d.modrm(true)?;
+                    (name.into(), format!("{} r32, r/m8", name), vec![reg32(reg), format_operand(&rm, byte)])
+                }
+                _ => return Err(format!("无法识别的操作码 0F {:02X}", second)),
+            }
+        }
+        // ALU 的 r/m,r、r,r/m 与 AL/EAX,imm 形式；操作码最低位为 0 时是 8 位形式
+        0x00..=0x3F if opcode & 7 <= 5 => {
+            let name = ALU_OPERATIONS[(opcode >> 3) as usize].0;
+            match opcode & 7 {
+                0 | 2 => {
+                    let form = match opcode & 7 {
+                        0 => format!("{} r/m8, r8", name),
+                        _ => format!("{} r8, r/m8", name),
+                    };
+                    d.field("操作码", offset, form.clone());
+                    let (reg, rm) = d.modrm(true)?;
+                    let operands = match opcode & 7 {
+                        0 => vec![format_operand(&rm, None), reg8(reg)],
+                        _ => vec![reg8(reg), format_operand(&rm, None)],
+                    };
+                    (name.into(), form, operands)
+                }
+                4 => {
+                    let form = format!("{} AL, imm8", name);
+                    d.field("操作码", offset, form.clone());
+                    let value = d.immediate(1, false)?;
+                    (name.into(), form, vec!["AL".into(), value.to_string()])
+                }
+                1 => {
+                    let form = format!("{} r/m32, r32", name);
+                    d.field("操作码", offset, form.clone());
+                    let (reg, rm) = d.modrm(false)?;
+                    (name.into(), form, vec![format_operand(&rm, None), reg32(reg)])
+                }
+                3 => {
+                    let form = format!("{} r32, r/m32", name);
+                    d.field("操作码", offset, form.clone());
+                    let (reg, rm) = d.modrm(false)?;
+                    (name.into(), form, vec![reg32(reg), format_operand(&rm, None)])
+                }
+                _ => {
+                    let form = format!("{} EAX, imm32", name);
+                    d.field("操作码", offset, form.clone());
+                    let value = d.immediate(4, true)?;
+                    (name.into(), form, vec!["EAX".into(), value.to_string()])
+                }
+            }
+        }
+        0x40..=0x4F => {
+            let name = if opcode < 0x48 { "INC" } else { "DEC" };
+            let form = format!("{} r32（{:02X}+rd）", name, opcode & 0xF8);
+            d.field("操作码", offset, form.clone());
+            (name.into(), form, vec![reg32(opcode & 7)])
+        }
+        0x50..=0x5F => {
+            let name = if opcode < 0x58 { "PUSH" } else { "POP" };
+            let form = format!("{} r32（{:02X}+rd）", name, opcode & 0xF8);
+            d.field("操作码", offset, form.clone());
+            (name.into(), form, vec![reg32(opcode & 7)])
+        }
+        0x68 | 0x6A => {
+            let size = if opcode == 0x68 { 4 } else { 1 };
+            let form = if size == 4 { "PUSH imm32" } else { "PUSH imm8（符号扩展）" };
+            d.field("操作码", offset, form.to_string());
+            let value = d.immediate(size, true)?;
+            ("PUSH".into(), form.into(), vec![value.to_string()])
+        }
+        0x69 | 0x6B => {
+            let size = if opcode == 0x69 { 4 } else { 1 };
+            let form = format!("IMUL r32, r/m32, imm{}", size * 8);
+            d.field("操作码", offset, form.clone());
+            let (reg, rm) = d.modrm(false)?;
+            let value = d.immediate(size, true)?;
+            ("IMUL".into(), form, vec![reg32(reg), format_operand(&rm, None), value.to_string()])
+        }
+        0x70..=0x7F | 0xEB | 0xE0..=0xE3 => {
+            let name = match opcode {
+                0xEB => "JMP".to_string(),
+                0xE0 => "LOOPNE".to_string(),
+                0xE1 => "LOOPE".to_string(),
+                0xE2 => "LOOP".to_string(),
+                0xE3 => "JECXZ".to_string(),
+                _ => format!("J{}", CONDITION_NAMES[(opcode - 0x70) as usize]),
+            };
+            let form = format!("{} rel8", name);
+            d.field("操作码", offset, form.clone());
+            target = Some(d.relative(1, base)?);
+            (name, form, Vec::new())
+        }
+        0x80 => {
+            d.field("操作码", offset, String::new());
+            let (digit, rm) = d.modrm(true)?;
+            let name = ALU_OPERATIONS[digit as usize].0;
+            let form = format!("{} r/m8, imm8", name);
+            d.fields[opcode_field].description = form.clone();
+            let value = d.immediate(1, false)?;
+            (name.into(), form, vec![format_operand(&rm, byte), value.to_string()])
+        }
+        0x84 => {
+            d.field("操作码", offset, "TEST r/m8, r8".to_string());
+            let (reg, rm) = d.modrm(true)?;
+            ("TEST".into(), "TEST r/m8, r8".into(), vec![format_operand(&rm, None), reg8(reg)])
+        }
+        0xA8 => {
+            d.field("操作码", offset, "TEST AL, imm8".to_string());
+            let value = d.immediate(1, false)?;
+            ("TEST".into(), "TEST AL, imm8".into(), vec!["AL".into(), value.to_string()])
+        }
+        0x81 | 0x83 => {
+            let size = if opcode == 0x81 { 4 } else { 1 };
+            d.field("操作码", offset, String::new());
+            let (digit, rm) = d.modrm(false)?;
+            let name = ALU_OPERATIONS[digit as usize].0;
+            let form = match size {
+                4 => format!("{} r/m32, imm32", name),
+                _ => format!("{} r/m32, imm8（符号扩展）", name),
+            };
+            d.fields[opcode_field].description = form.clone();
+            let value = d.immediate(size, true)?;
+            (name.into(), form, vec![format_operand(&rm, dword), value.to_string()])
+        }
+        0x85 | 0x87 | 0x89 | 0x8B | 0x8D => {
+            let (name, form) = match opcode {
+                0x85 => ("TEST", "TEST r/m32, r32"),
+                0x87 => ("XCHG", "XCHG r/m32, r32"),
+                0x89 => ("MOV", "MOV r/m32, r32"),
+                0x8B => ("MOV", "MOV r32, r/m32"),
+                _ => ("LEA", "LEA r32, m"),
+            };
+            d.field("操作码", offset, form.to_string());
+            let (reg, rm) = d.modrm(false)?;
+            if opcode == 0x8D && !matches!(rm, Operand::Mem { .. }) {
+                return Err("LEA 的源操作数必须是内存".to_string());
+            }
+            let operands = match opcode {
+                0x8B | 0x8D => vec![reg32(reg), format_operand(&rm, None)],
+                _ => vec![format_operand(&rm, None), reg32(reg)],
+            };
+            (name.into(), form.into(), operands)
+        }
+        0x88 | 0x8A => {
+            let form = if opcode == 0x88 { "MOV r/m8, r8" } else { "MOV r8, r/m8" };
+            d.field("操作码", offset, form.to_string());
+            let (reg, rm) = d.modrm(true)?;
+            let reg = REGISTERS_8[reg as usize].to_string();
+            let operands = match opcode {
+                0x88 => vec![format_operand(&rm, None), reg],
+                _ => vec![reg, format_operand(&rm, None)],
+            };
+            ("MOV".into(), form.into(), operands)
+        }
+        0x8F => {
+            d.field("操作码", offset, "POP r/m32".to_string());
+            let (digit, rm) = d.modrm(false)?;
+            if digit != 0 {
+                return Err(format!("8F /{} 不是合法的指令", digit));
+            }
+            ("POP".into(), "POP r/m32".into(), vec![format_operand(&rm, dword)])
+        }
+        0x91..=0x97 => {
+            let form = format!("XCHG EAX, r32（90+rd，rd={}）", REGISTERS_32[(opcode & 7) as usize]);
+            d.field("操作码", offset, form.clone());
+            ("XCHG".into(), form, vec!["EAX".into(), reg32(opcode & 7)])
+        }
+        0xA1 | 0xA3 => {
+            let form = if opcode == 0xA1 { "MOV EAX, moffs32" } else { "MOV moffs32, EAX" };
+            d.field("操作码", offset, form.to_string());
+            let from = d.pos;
+            let disp = d.signed(4)?;
+            d.field("位移", from, format!("moffs32 = {}（小端序）", disp));
+            d.memory = true;
+            let memory = format_operand(&Operand::Mem { base: None, index: None, disp }, None);
+            let operands = match opcode {
+                0xA1 => vec!["EAX".into(), memory],
+                _ => vec![memory, "EAX".into()],
+            };
+            ("MOV".into(), form.into(), operands)
+        }
+        0xA9 => {
+            d.field("操作码", offset, "TEST EAX, imm32".to_string());
+            let value = d.immediate(4, true)?;
+            ("TEST".into(), "TEST EAX, imm32".into(), vec!["EAX".into(), value.to_string()])
+        }
+        0xB0..=0xB7 => {
+            let form = format!("MOV r8, imm8（B0+rb，rb={}）", REGISTERS_8[(opcode & 7) as usize]);
+            d.field("操作码", offset, form.clone());
+            let value = d.immediate(1, false)?;
+            ("MOV".into(), form, vec![REGISTERS_8[(opcode & 7) as usize].to_string(), value.to_string()])
+        }
+        0xB8..=0xBF => {
+            let form = format!("MOV r32, imm32（B8+rd，rd={}）", REGISTERS_32[(opcode & 7) as usize]);
+            d.field("操作码", offset, form.clone());
+            let value = d.immediate(4, true)?;
+            ("MOV".into(), form, vec![reg32(opcode & 7), value.to_string()])
+        }
+        0xC0 | 0xC1 | 0xD0..=0xD3 => {
+            let byte_sized = opcode & 1 == 0;
+            let bits = if byte_sized { 8 } else { 32 };
+            d.field("操作码", offset, String::new());
+            let (digit, rm) = d.modrm(byte_sized)?;
+            let name = SHIFT_OPERATIONS
+                .iter()
+                .find(|(_, d)| *d == digit)
+                .map(|(name, _)| *name)
+                .ok_or_else(|| format!("{:02X} /{} 不受支持", opcode, digit))?;
+            let (form, count) = match opcode {
+                0xC0 | 0xC1 => (format!("{} r/m{}, imm8", name, bits), None),
+                0xD0 | 0xD1 => (format!("{} r/m{}, 1", name, bits), Some("1".to_string())),
+                _ => (format!("{} r/m{}, CL", name, bits), Some("CL".to_string())),
+            };
+            d.fields[opcode_field].description = form.clone();
+            let count = match count {
+                Some(count) => count,
+                None => d.immediate(1, false)?.to_string(),
+            };
+            (name.into(), form, vec![format_operand(&rm, if byte_sized { byte } else { dword }), count])
+        }
+        0xC2 => {
+            d.field("操作码", offset, "RET imm16".to_string());
+            let size = d.immediate(2, false)?;
+            ("RET".into(), "RET imm16".into(), vec![size.to_string()])
+        }
+        0xC6 | 0xC7 => {
+            let (size, form, prefix) = match opcode {
+                0xC6 => (1, "MOV r/m8, imm8", byte),
+                _ => (4, "MOV r/m32, imm32", dword),
+            };
+            d.field("操作码", offset, form.to_string());
+            let (digit, rm) = d.modrm(size == 1)?;
+            if digit != 0 {
+                return Err(format!("{:02X} /{} 不是合法的指令", opcode, digit));
+            }
+            let value = d.immediate(size, size == 4)?;
+            ("MOV".into(), form.into(), vec![format_operand(&rm, prefix), value.to_string()])
+        }
+        0xC8 => {
+            d.field("操作码", offset, "ENTER imm16, imm8".to_string());
+            let size = d.immediate(2, false)?;
+            let level = d.immediate(1, false)?;
+            ("ENTER".into(), "ENTER imm16, imm8".into(), vec![size.to_string(), level.to_string()])
+        }
+        0xCD => {
+            d.field("操作码", offset, "INT imm8".to_string());
+            let vector = d.immediate(1, false)?;
+            ("INT".into(), "INT imm8".into(), vec![vector.to_string()])
+        }
+        0xE8 | 0xE9 => {
+            let name = if opcode == 0xE8 { "CALL" } else { "JMP" };
+            let form = format!("{} rel32", name);
+            d.field("操作码", offset, form.clone());
+            target = Some(d.relative(4, base)?);
+            (name.into(), form, Vec::new())
+        }
+        0xF6 | 0xF7 => {
+            let (size, bits, prefix) = if opcode == 0xF6 { (1, 8, byte) } else { (4, 32, dword) };
+            d.field("操作码", offset, String::new());
+            let (digit, rm) = d.modrm(size == 1)?;
+            let (name, form, operands) = if digit == 0 {
+                let form = format!("TEST r/m{}, imm{}", bits, bits);
+                let value = d.immediate(size, size == 4)?;
+                ("TEST", form, vec![format_operand(&rm, prefix), value.to_string()])
+            } else {
+                let name = GROUP3_OPERATIONS
+                    .iter()
+                    .find(|(_, d)| *d == digit)
+                    .map(|(name, _)| *name)
+                    .ok_or_else(|| format!("{:02X} /{} 不是合法的指令", opcode, digit))?;
+                (name, format!("{} r/m{}", name, bits), vec![format_operand(&rm, prefix)])
+            };
+            d.fields[opcode_field].description = form.clone();
+            (name.into(), form, operands)
+        }
+        0xFE => {
+            d.field("操作码", offset, String::new());
+            let (digit, rm) = d.modrm(true)?;
+            let name = match digit {
+                0 => "INC",
+                1 => "DEC",
+                _ => return Err(format!("FE /{} 不是合法的指令", digit)),
+            };
+            let form = format!("{} r/m8", name);
+            d.fields[opcode_field].description = form.clone();
+            (name.into(), form, vec![format_operand(&rm, byte)])
+        }
+        0xFF => {
+            d.field("操作码", offset, String::new());
+            let (digit, rm) = d.modrm(false)?;
+            let name = match digit {
+                0 => "INC",
+                1 => "DEC",
+                2 => "CALL",
+                4 => "JMP",
+                6 => "PUSH",
+                _ => return Err(format!("FF /{} 不受支持", digit)),
+            };
+            let form = format!("{} r/m32", name);
+            d.fields[opcode_field].description = form.clone();
+            (name.into(), form, vec![format_operand(&rm, dword)])
+        }
+        _ => {
+            let (name, form) = match opcode {
+                0x90 => ("NOP", "NOP"),
+                0x99 => ("CDQ", "CDQ"),
+                0x60 => ("PUSHAD", "PUSHAD"),
+                0x61 => ("POPAD", "POPAD"),
+                0x9C => ("PUSHFD", "PUSHFD"),
+                0x9D => ("POPFD", "POPFD"),
+                0xC3 => ("RET", "RET"),
+                0xC9 => ("LEAVE", "LEAVE"),
+                0xCC => ("INT", "INT 3（断点）"),
+                0xCF => ("IRETD", "IRETD"),
+                0xF4 => ("HLT", "HLT"),
+                0xFA => ("CLI", "CLI"),
+                0xFB => ("STI", "STI"),
+                _ => return Err(format!("无法识别的操作码 {:02X}", opcode)),
+            };
+            d.field("操作码", offset, form.to_string());
+            let operands = if opcode == 0xCC { vec!["3".to_string()] } else { Vec::new() };
+            (name.into(), form.into(), operands)
+        }
+    };
+
+    // 带前缀 66 时寄存器操作数与长度说明按 16 位书写（内存地址仍用 32 位寄存器）
+    let (form, operands) = match d.word {
+        true => {
+            d.fields[opcode_field].description = word_form(&d.fields[opcode_field].description);
+            let operands = operands
+                .into_iter()
+                .map(|operand| match REGISTERS_32.iter().position(|r| *r == operand) {
+                    Some(code) => REGISTERS_16[code].to_string(),
+                    None => operand.replacen("DWORD PTR", "WORD PTR", 1),
+                })
+                .collect();
+            (word_form(&form), operands)
+        }
+        false => (form, operands),
+    };
+
+    Ok(Decoded {
+        address,
+        mnemonic,
+        operands,
+        target,
+        memory: d.memory,
+        form,
+        fields: d.fields,
+        bytes: bytes[start..d.pos].to_vec(),
+    })
+}
+
+/// 把机器码反汇编为指令序列。
+/// 跳转与调用的目标若落在某条指令的起始处，就为该指令生成标签 loc_XXXXXXXX，否则保留绝对地址。
+pub fn disassemble(bytes: &[u8]) -> Result<Vec<Instruction>, String> {
+    let base = CODE_BASE as i64;
+    let mut decoded = Vec::new();
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let instruction =
+            decode_one(bytes, offset, base).map_err(|message| format!("地址 {:08X}: {}", base + offset as i64, message))?;
+        offset += instruction.bytes.len();
+        decoded.push(instruction);
+    }
+
+    let starts: HashMap<i64, usize> = decoded.iter().enumerate().map(|(i, d)| (d.address, i)).collect();
+    let mut labels: HashMap<usize, String> = HashMap::new();
+    for instruction in &mut decoded {
+        let Some(target) = instruction.target else { continue };
+        let operand = match starts.get(&target) {
+            Some(&index) => labels.entry(index).or_insert_with(|| format!("loc_{:08X}", target)).clone(),
+            None => format!("0x{:08X}", target),
+        };
+        instruction.operands.push(operand);
+    }
+
+    Ok(decoded
+        .into_iter()
+        .enumerate()
+        .map(|(i, d)| {
+            let (instruction_type, cycles) = classify(&d.mnemonic, d.memory);
+            Instruction {
+                id: format!("{}_{}", d.mnemonic.to_lowercase(), i),
+                instruction_type,
+                description: format!("{:08X}: {}", d.address, d.form),
+                mnemonic: d.mnemonic,
+                operands: d.operands,
+                machine_code: hex(&d.bytes),
+                cycles,
+                label: labels.remove(&i),
+                encoding: d.fields,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble;
+    use crate::compiler::compile;
+    use crate::encoder::parse_operand;
+    use crate::types::{OptimizationPass, Target};
+
+    /// 机器码反汇编后应得到原来的指令：助记符、操作数（不计长度前缀）与机器码都相同，
+    /// 转移目标比较目标指令的位置；程序外的符号（如 printf）不比较
+    fn assert_round_trip(instructions: &[Instruction]) {
+        let bytes: Vec<u8> =
+            instructions.iter().flat_map(|instruction| parse_hex(&instruction.machine_code).unwrap()).collect();
+        let decoded = disassemble(&bytes).unwrap();
+        assert_eq!(decoded.len(), instructions.len());
+
+        let position = |program: &[Instruction], label: &str| {
+            program.iter().position(|instruction| instruction.label.as_deref() == Some(label))
+        };
+        for (original, copy) in instructions.iter().zip(&decoded) {
+            let text = format!("{} {:?}", original.mnemonic, original.operands);
+            assert!(original.mnemonic.eq_ignore_ascii_case(&copy.mnemonic), "{} 反汇编为 {}", text, copy.mnemonic);
+            assert_eq!(original.operands.len(), copy.operands.len(), "{}", text);
+            for (a, b) in original.operands.iter().zip(&copy.operands) {
+                match (parse_operand(a).unwrap(), parse_operand(b).unwrap()) {
+                    (Operand::Label(a), Operand::Label(b)) => {
+                        if let Some(index) = position(instructions, &a) {
+                            assert_eq!(position(&decoded, &b), Some(index), "{}", text);
+                        }
+                    }
+                    (a, b) => assert_eq!(a, b, "{}", text),
+                }
+            }
+            assert_eq!(original.machine_code, copy.machine_code, "{}", text);
+        }
+    }
+
+    #[test]
+    fn compiled_programs_round_trip() {
+        let sources = [
+            "int g = 5;\nchar c = 'a';\n\
+             int f(int n) { int s = 0; while (n > 0) { if (n % 2 == 0) s = s + n * g; else s = s - n * 4; n = n - 1; } \
+             return s; }\n\
+             int main() { char d = c + 1; int k = -300; printf(\"%d\", f(10)); return f(10) + d / 3 + k; }",
+            "int fib(int n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }\n\
+             int main() { int i; int s = 0; for (i = 0; i < 10; i = i + 1) s = s + fib(i); return s; }",
+        ];
+        for source in sources {
+            for level in 0..=2 {
+                let result = compile(source, &OptimizationPass::for_level(level), Target::default());
+                assert!(result.success, "{:?}", result.errors);
+                assert_round_trip(&result.instructions);
+            }
+        }
+    }
+
+    #[test]
+    fn assembled_forms_round_trip() {
+        let source = ".data\nx dd 0\nb db 1\n.text\n\
+            start: mov eax, [ebx+ecx*4+8]\nmov byte ptr [x], 5\nmov word ptr [x+2], ax\nadd eax, 100\nadd eax, 1000\n\
+            sub dword ptr [ebp-8], 3\nshl eax, 3\nsar ecx, cl\nlea esi, [eax+eax*2]\nmovzx eax, byte ptr [b]\n\
+            movsx edx, cl\nxchg eax, ebx\ntest al, 1\njz start\ninc dword ptr [x]\npush 5\npush dword ptr [x]\n\
+            pop ecx\npushfd\npopfd\nint 3\ncli\nsti\nmul ecx\ndiv dword ptr [x]\nimul eax, ebx, 12\nret 4\n";
+        let result = assemble(source);
+        assert!(result.success, "{:?}", result.errors);
+        assert_round_trip(&result.instructions);
+    }
+
+    #[test]
+    fn decodes_hex_text() {
+        let instructions = disassemble(&parse_hex("B8 FF FF FF FF 89 45 FC EB FE").unwrap()).unwrap();
+        let text: Vec<String> = instructions
+            .iter()
+            .map(|instruction| format!("{} {}", instruction.mnemonic, instruction.operands.join(", ")))
+            .collect();
+        assert_eq!(text, ["MOV EAX, -1", "MOV [EBP-4], EAX", "JMP loc_00000008"]);
+        assert_eq!(instructions[2].label.as_deref(), Some("loc_00000008"));
+    }
+}
@@ -0,0 +1,856 @@
+use crate::memory::CODE_BASE;
+use crate::types::{EncodingField, Instruction, InstructionType};
+use std::collections::HashMap;
+
+/// 32 位通用寄存器，下标即 ModR/M 中的编号
+pub const REGISTERS_32: [&str; 8] = ["EAX", "ECX", "EDX", "EBX", "ESP", "EBP", "ESI", "EDI"];
+/// 16 位通用寄存器（32 位寄存器的低半部分），需要操作数大小前缀 66
+pub const REGISTERS_16: [&str; 8] = ["AX", "CX", "DX", "BX", "SP", "BP", "SI", "DI"];
+/// 8 位寄存器，下标即 ModR/M 中的编号
+pub const REGISTERS_8: [&str; 8] = ["AL", "CL", "DL", "BL", "AH", "CH", "DH", "BH"];
+
+/// 汇编语言中的一个操作数
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Reg32(u8),
+    Reg16(u8),
+    Reg8(u8),
+    Imm(i64),
+    /// [base + index*scale + disp]
+    Mem {
+        base: Option<u8>,
+        index: Option<(u8, u8)>,
+        disp: i64,
+    },
+    Label(String),
+}
+
+/// 相对跳转的目标：标签或绝对地址
+#[derive(Debug, Clone, PartialEq)]
+pub enum RelocationTarget {
+    Label(String),
+    Address(i64),
+}
+
+/// 需要在地址确定后回填的相对偏移字段
+#[derive(Debug, Clone)]
+pub struct Relocation {
+    /// 偏移字段在指令字节中的位置
+    pub offset: usize,
+    /// 字段宽度（字节）
+    pub size: usize,
+    pub target: RelocationTarget,
+}
+
+/// 一条指令的编码结果
+#[derive(Debug, Clone, Default)]
+pub struct Encoding {
+    pub bytes: Vec<u8>,
+    pub fields: Vec<EncodingField>,
+    pub relocation: Option<Relocation>,
+}
+
+/// 解析数字：十进制、0x 前缀或 h 后缀的十六进制，可带负号
+pub fn parse_number(text: &str) -> Option<i64> {
+    let text = text.trim();
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest.trim()),
+        None => (false, text.strip_prefix('+').unwrap_or(text).trim()),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(hex) = digits.strip_suffix('h').or_else(|| digits.strip_suffix('H')) {
+        if !hex.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+        i64::from_str_radix(hex, 16).ok()?
+    } else {
+        digits.parse::<i64>().ok()?
+    };
+    Some(if negative { -value } else { value })
+}
+
+/// 解析操作数文本，如 "EAX"、"-5"、"[EBP-4]"、"[EBX+ESI*4+8]"、"L1"
+pub fn parse_operand(text: &str) -> Result<Operand, String> {
+    let text = text.trim();
+    let upper = text.to_uppercase();
+    if let Some(code) = REGISTERS_32.iter().position(|r| *r == upper) {
+        return Ok(Operand::Reg32(code as u8));
+    }
+    if let Some(code) = REGISTERS_16.iter().position(|r| *r == upper) {
+        return Ok(Operand::Reg16(code as u8));
+    }
+    if let Some(code) = REGISTERS_8.iter().position(|r| *r == upper) {
+        return Ok(Operand::Reg8(code as u8));
+    }
+    if let Some(value) = parse_number(text) {
+        return Ok(Operand::Imm(value));
+    }
+
+    // 去掉 DWORD PTR / WORD PTR / BYTE PTR 等长度说明
+    let memory = upper.trim_start_matches("DWORD").trim_start_matches("WORD").trim_start_matches("BYTE").trim_start();
+    let memory = memory.strip_prefix("PTR").unwrap_or(memory).trim_start();
+    if let Some(inner) = memory.strip_prefix('[').and_then(|m| m.strip_suffix(']')) {
+        return parse_memory(inner).ok_or_else(|| format!("无法解析内存操作数 '{}'", text));
+    }
+
+    if text.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_' || c == '.')
+        && text.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+    {
+        return Ok(Operand::Label(text.to_string()));
+    }
+    Err(format!("无法解析操作数 '{}'", text))
+}
+
+fn parse_memory(inner: &str) -> Option<Operand> {
+    let mut base = None;
+    let mut index = None;
+    let mut disp = 0;
+
+    // 按 + / - 切分，保留符号
+    let mut terms = Vec::new();
+    let mut current = String::new();
+    for c in inner.chars().filter(|c| !c.is_whitespace()) {
+        if (c == '+' || c == '-') && !current.is_empty() {
+            terms.push(std::mem::take(&mut current));
+        }
+        current.push(c);
+    }
+    terms.push(current);
+
+    for term in terms {
+        let (negative, body) = match term.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, term.strip_prefix('+').unwrap_or(&term)),
+        };
+        if let Some(value) = parse_number(body) {
+            disp += if negative { -value } else { value };
+            continue;
+        }
+        if negative {
+            return None;
+        }
+        let (register, scale) = match body.split_once('*') {
+            Some((register, scale)) => (register, parse_number(scale)? as u8),
+            None => (body, 1),
+        };
+        let code = REGISTERS_32.iter().position(|r| *r == register)? as u8;
+        if scale == 1 && base.is_none() {
+            base = Some(code);
+        } else if index.is_none() && matches!(scale, 1 | 2 | 4 | 8) && code != 4 {
+            index = Some((code, scale));
+        } else {
+            return None;
+        }
+    }
+    Some(Operand::Mem { base, index, disp })
+}
+
+/// 条件码（Jcc/SETcc 操作码的低 4 位），支持常见的别名
+pub fn condition_code(condition: &str) -> Option<u8> {
+    let code = match condition {
+        "O" => 0x0,
+        "NO" => 0x1,
+        "B" | "C" | "NAE" => 0x2,
+        "AE" | "NB" | "NC" => 0x3,
+        "E" | "Z" => 0x4,
+        "NE" | "NZ" => 0x5,
+        "BE" | "NA" => 0x6,
+        "A" | "NBE" => 0x7,
+        "S" => 0x8,
+        "NS" => 0x9,
+        "P" | "PE" => 0xA,
+        "NP" | "PO" => 0xB,
+        "L" | "NGE" => 0xC,
+        "GE" | "NL" => 0xD,
+        "LE" | "NG" => 0xE,
+        "G" | "NLE" => 0xF,
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// 条件码的规范后缀，反汇编时使用
+pub const CONDITION_NAMES: [&str; 16] = ["O", "NO", "B", "AE", "E", "NE", "BE", "A", "S", "NS", "P", "NP", "L", "GE", "LE", "G"];
+
+/// 算术/逻辑运算：(助记符, ModR/M 中的 /digit, r/m,r 形式的操作码, r,r/m 形式的操作码)
+pub const ALU_OPERATIONS: [(&str, u8, u8, u8); 8] = [
+    ("ADD", 0, 0x01, 0x03),
+    ("OR", 1, 0x09, 0x0B),
+    ("ADC", 2, 0x11, 0x13),
+    ("SBB", 3, 0x19, 0x1B),
+    ("AND", 4, 0x21, 0x23),
+    ("SUB", 5, 0x29, 0x2B),
+    ("XOR", 6, 0x31, 0x33),
+    ("CMP", 7, 0x39, 0x3B),
+];
+
+/// 单操作数的 F7 组：(助记符, /digit)
+pub const GROUP3_OPERATIONS: [(&str, u8); 6] = [("NOT", 2), ("NEG", 3), ("MUL", 4), ("IMUL", 5), ("DIV", 6), ("IDIV", 7)];
+
+/// 移位组：(助记符, /digit)
+pub const SHIFT_OPERATIONS: [(&str, u8); 6] = [("ROL", 0), ("ROR", 1), ("SHL", 4), ("SAL", 4), ("SHR", 5), ("SAR", 7)];
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+fn fits_i8(value: i64) -> bool {
+    (-128..=127).contains(&value)
+}
+
+/// 逐字段构造编码
+struct Builder {
+    encoding: Encoding,
+    /// 16 位操作数：立即数为 16 位，寄存器按 16 位命名
+    word: bool,
+}
+
+impl Builder {
+    fn new() -> Self {
+        Self { encoding: Encoding::default(), word: false }
+    }
+
+    /// 加上操作数大小前缀，其余字段与 32 位形式相同
+    fn operand_size_prefix(&mut self) {
+        self.word = true;
+        self.field("前缀", &[0x66], "操作数大小前缀：16 位操作数".to_string());
+    }
+
+    fn field(&mut self, field: &str, bytes: &[u8], description: String) {
+        self.encoding.bytes.extend_from_slice(bytes);
+        self.encoding.fields.push(EncodingField {
+            field: field.to_string(),
+            hex: hex(bytes),
+            description,
+        });
+    }
+
+    fn opcode(&mut self, bytes: &[u8], form: &str) -> &mut Self {
+        self.field("操作码", bytes, form.to_string());
+        self
+    }
+
+    /// ModR/M（以及需要时的 SIB 与位移）。reg 为寄存器编号或 /digit 扩展操作码
+    fn modrm(&mut self, reg: u8, reg_meaning: String, rm: &Operand) -> Result<&mut Self, String> {
+        let (mode, rm_code, rm_meaning) = match rm {
+            Operand::Reg32(code) if self.word => (0b11, *code, REGISTERS_16[*code as usize].to_string()),
+            Operand::Reg32(code) | Operand::Reg16(code) => (0b11, *code, REGISTERS_32[*code as usize].to_string()),
+            Operand::Reg8(code) => (0b11, *code, REGISTERS_8[*code as usize].to_string()),
+            Operand::Mem { base, index, disp } => {
+                let disp = *disp;
+                let (mode, disp_size) = match base {
+                    None => (0b00, 4),
+                    Some(5) if disp == 0 => (0b01, 1),
+                    Some(_) if disp == 0 => (0b00, 0),
+                    Some(_) if fits_i8(disp) => (0b01, 1),
+                    Some(_) => (0b10, 4),
+                };
+                let needs_sib = index.is_some() || *base == Some(4);
+                let rm_code = match (needs_sib, base) {
+                    (true, _) => 0b100,
+                    (false, None) => 0b101,
+                    (false, Some(base)) => *base,
+                };
+                let meaning = match (needs_sib, base) {
+                    (true, _) => "100 (使用 SIB)".to_string(),
+                    (false, None) => "101 (disp32 绝对地址)".to_string(),
+                    (false, Some(base)) => format!("[{}]", REGISTERS_32[*base as usize]),
+                };
+                self.modrm_byte(mode, reg, &reg_meaning, rm_code, &meaning);
+
+                if needs_sib {
+                    let (scale, index_code) = match index {
+                        Some((code, scale)) => (*scale, *code),
+                        None => (1, 0b100),
+                    };
+                    let base_code = base.unwrap_or(0b101);
+                    let scale_bits = scale.trailing_zeros() as u8;
+                    let sib = (scale_bits << 6) | (index_code << 3) | base_code;
+                    let index_meaning = match index {
+                        Some((code, scale)) => format!("{}*{}", REGISTERS_32[*code as usize], scale),
+                        None => "无".to_string(),
+                    };
+                    let base_meaning = match base {
+                        Some(code) => REGISTERS_32[*code as usize].to_string(),
+                        None => "无 (disp32)".to_string(),
+                    };
+                    self.field(
+                        "SIB",
+                        &[sib],
+                        format!(
+                            "scale={:02b} index={:03b} base={:03b}：变址 {}，基址 {}",
+                            scale_bits, index_code, base_code, index_meaning, base_meaning
+                        ),
+                    );
+                }
+                match disp_size {
+                    1 => self.field("位移", &[disp as i8 as u8], format!("disp8 = {}", disp)),
+                    4 => self.field(
+                        "位移",
+                        &(disp as i32).to_le_bytes(),
+                        format!("disp32 = {}（小端序）", disp),
+                    ),
+                    _ => {}
+                }
+                return Ok(self);
+            }
+            other => return Err(format!("操作数 {:?} 不能用作 r/m", other)),
+        };
+        self.modrm_byte(mode, reg, &reg_meaning, rm_code, &rm_meaning);
+        Ok(self)
+    }
+
+    fn modrm_byte(&mut self, mode: u8, reg: u8, reg_meaning: &str, rm: u8, rm_meaning: &str) {
+        self.field(
+            "ModR/M",
+            &[(mode << 6) | (reg << 3) | rm],
+            format!("mod={:02b} reg={:03b} ({}) r/m={:03b} {}", mode, reg, reg_meaning, rm, rm_meaning),
+        );
+    }
+
+    fn immediate(&mut self, value: i64, size: usize) -> Result<&mut Self, String> {
+        let size = if self.word && size == 4 { 2 } else { size };
+        let in_range = match size {
+            1 => (-128..=255).contains(&value),
+            2 => (-32768..=65535).contains(&value),
+            _ => (i32::MIN as i64..=u32::MAX as i64).contains(&value),
+        };
+        if !in_range {
+            return Err(format!("立即数 {} 超出 {} 位范围", value, size * 8));
+        }
+        let bytes = (value as u32).to_le_bytes();
+        self.field("立即数", &bytes[..size], format!("imm{} = {}（小端序）", size * 8, value));
+        Ok(self)
+    }
+
+    /// 相对偏移，地址确定后由 encode_program 回填
+    fn relative(&mut self, target: &Operand, size: usize) -> Result<&mut Self, String> {
+        let target = match target {
+            Operand::Label(label) => RelocationTarget::Label(label.clone()),
+            Operand::Imm(address) => RelocationTarget::Address(*address),
+            other => return Err(format!("跳转目标 {:?} 无效", other)),
+        };
+        let description = match &target {
+            RelocationTarget::Label(label) => format!("rel{} → {}", size * 8, label),
+            RelocationTarget::Address(address) => format!("rel{} → {:08X}", size * 8, address),
+        };
+        self.encoding.relocation = Some(Relocation {
+            offset: self.encoding.bytes.len(),
+            size,
+            target,
+        });
+        self.field("相对偏移", &vec![0; size], description);
+        Ok(self)
+    }
+
+    fn finish(&mut self) -> Result<Encoding, String> {
+        if self.word {
+            // 操作码字段按 16 位形式描述
+            if let Some(field) = self.encoding.fields.iter_mut().find(|field| field.field == "操作码") {
+                field.description = word_form(&field.description);
+            }
+        }
+        Ok(std::mem::take(&mut self.encoding))
+    }
+}
+
+fn register_field(code: u8) -> String {
+    REGISTERS_32[code as usize].to_string()
+}
+
+fn digit_field(digit: u8) -> String {
+    format!("/{} 扩展操作码", digit)
+}
+
+/// 把 32 位形式的描述（如 "ADD r/m32, imm32"、"MOV EAX, moffs32"）改写为 16 位形式
+pub fn word_form(form: &str) -> String {
+    let mut form = form.replace("r/m32", "r/m16").replace("r32", "r16").replace("imm32", "imm16");
+    for (long, short) in REGISTERS_32.iter().zip(REGISTERS_16) {
+        form = form.replace(long, short);
+    }
+    form
+}
+
+/// 操作数长度（字节）：由目的寄存器决定；目的操作数是内存时看长度说明或源寄存器，默认 32 位
+pub fn operand_size(mnemonic: &str, operands: &[String]) -> usize {
+    if matches!(mnemonic, "MOVZX" | "MOVSX") || mnemonic.starts_with("SET") {
+        return 4;
+    }
+    // 移位次数可以是 CL，不影响操作数长度
+    let considered = if SHIFT_OPERATIONS.iter().any(|(name, _)| *name == mnemonic) { 1 } else { operands.len() };
+    for text in &operands[..considered.min(operands.len())] {
+        let upper = text.trim().to_uppercase();
+        match parse_operand(text) {
+            Ok(Operand::Reg8(_)) => return 1,
+            Ok(Operand::Reg16(_)) => return 2,
+            Ok(Operand::Reg32(_)) => return 4,
+            _ if upper.starts_with("BYTE") => return 1,
+            _ if upper.starts_with("WORD") => return 2,
+            _ if upper.starts_with("DWORD") => return 4,
+            _ => {}
+        }
+    }
+    4
+}
+
+/// 把一条汇编指令编码为 IA-32 机器码
+pub fn encode(mnemonic: &str, operands: &[String]) -> Result<Encoding, String> {
+    let mnemonic = mnemonic.to_uppercase();
+    let ops = operands.iter().map(|o| parse_operand(o)).collect::<Result<Vec<_>, _>>()?;
+    let mut b = Builder::new();
+    use Operand::*;
+
+    let is_rm32 = |op: &Operand| matches!(op, Reg32(_) | Mem { .. });
+    let is_rm8 = |op: &Operand| matches!(op, Reg8(_) | Mem { .. });
+    // 内存操作数带 BYTE PTR 时按字节操作
+    let byte_sized = operands.iter().any(|o| o.trim().to_uppercase().starts_with("BYTE"));
+    let size = operand_size(&mnemonic, operands);
+    // 16 位操作数：加前缀 66 后按 32 位形式编码
+    let ops: Vec<Operand> = if size == 2 {
+        b.operand_size_prefix();
+        ops.into_iter().map(|op| if let Reg16(code) = op { Reg32(code) } else { op }).collect()
+    } else {
+        ops
+    };
+    let register_field = |code: u8| if size == 2 { REGISTERS_16 } else { REGISTERS_32 }[code as usize].to_string();
+
+    // 无操作数指令
+    let simple: Option<(u8, &str)> = match mnemonic.as_str() {
+        "NOP" => Some((0x90, "NOP")),
+        "HLT" => Some((0xF4, "HLT")),
+        "CDQ" => Some((0x99, "CDQ")),
+        "LEAVE" => Some((0xC9, "LEAVE")),
+        "PUSHAD" | "PUSHA" => Some((0x60, "PUSHAD")),
+        "POPAD" | "POPA" => Some((0x61, "POPAD")),
+        "IRET" | "IRETD" => Some((0xCF, "IRETD")),
+        "CLI" => Some((0xFA, "CLI")),
+        "STI" => Some((0xFB, "STI")),
+        "PUSHFD" | "PUSHF" => Some((0x9C, "PUSHFD")),
+        "POPFD" | "POPF" => Some((0x9D, "POPFD")),
+        _ => None,
+    };
+    if let (Some((opcode, form)), true) = (simple, ops.is_empty()) {
+        return b.opcode(&[opcode], form).finish();
+    }
+
+    if let Some(&(_, digit, mr, rm)) = ALU_OPERATIONS.iter().find(|(name, ..)| *name == mnemonic) {
+        // 8 位形式的操作码比 32 位形式小 1
+        if size == 1 {
+            return match ops.as_slice() {
+                [dst, Reg8(src)] if is_rm8(dst) => b
+                    .opcode(&[mr - 1], &format!("{} r/m8, r8", mnemonic))
+                    .modrm(*src, REGISTERS_8[*src as usize].to_string(), dst)?
+                    .finish(),
+                [Reg8(dst), src @ Mem { .. }] => b
+                    .opcode(&[rm - 1], &format!("{} r8, r/m8", mnemonic))
+                    .modrm(*dst, REGISTERS_8[*dst as usize].to_string(), src)?
+                    .finish(),
+                [Reg8(0), Imm(value)] => {
+                    b.opcode(&[(digit << 3) | 0x04], &format!("{} AL, imm8", mnemonic)).immediate(*value, 1)?.finish()
+                }
+                [dst, Imm(value)] if is_rm8(dst) => b
+                    .opcode(&[0x80], &format!("{} r/m8, imm8", mnemonic))
+                    .modrm(digit, digit_field(digit), dst)?
+                    .immediate(*value, 1)?
+                    .finish(),
+                _ => Err(unsupported(&mnemonic, operands)),
+            };
+        }
+        return match ops.as_slice() {
+            [dst, Reg32(src)] if is_rm32(dst) => {
+                b.opcode(&[mr], &format!("{} r/m32, r32", mnemonic)).modrm(*src, register_field(*src), dst)?.finish()
+            }
+            [Reg32(dst), src @ Mem { .. }] => {
+                b.opcode(&[rm], &format!("{} r32, r/m32", mnemonic)).modrm(*dst, register_field(*dst), src)?.finish()
+            }
+            [dst, Imm(value)] if is_rm32(dst) && fits_i8(*value) => b
+                .opcode(&[0x83], &format!("{} r/m32, imm8（符号扩展）", mnemonic))
+                .modrm(digit, digit_field(digit), dst)?
+                .immediate(*value, 1)?
+                .finish(),
+            [Reg32(0), Imm(value)] => b
+                .opcode(&[(digit << 3) | 0x05], &format!("{} EAX, imm32", mnemonic))
+                .immediate(*value, 4)?
+                .finish(),
+            [dst, Imm(value)] if is_rm32(dst) => b
+                .opcode(&[0x81], &format!("{} r/m32, imm32", mnemonic))
+                .modrm(digit, digit_field(digit), dst)?
+                .immediate(*value, 4)?
+                .finish(),
+            _ => Err(unsupported(&mnemonic, operands)),
+        };
+    }
+
+    if let Some(&(_, digit)) = GROUP3_OPERATIONS.iter().find(|(name, _)| *name == mnemonic) {
+        match ops.as_slice() {
+            [rm] if size == 1 && is_rm8(rm) => {
+                return b.opcode(&[0xF6], &format!("{} r/m8", mnemonic)).modrm(digit, digit_field(digit), rm)?.finish();
+            }
+            [rm] if is_rm32(rm) => {
+                return b.opcode(&[0xF7], &format!("{} r/m32", mnemonic)).modrm(digit, digit_field(digit), rm)?.finish();
+            }
+            _ if mnemonic != "IMUL" => return Err(unsupported(&mnemonic, operands)),
+            _ => {}
+        }
+    }
+
+    if let Some(&(_, digit)) = SHIFT_OPERATIONS.iter().find(|(name, _)| *name == mnemonic) {
+        if size == 1 {
+            return match ops.as_slice() {
+                [rm, Imm(1)] if is_rm8(rm) => {
+                    b.opcode(&[0xD0], &format!("{} r/m8, 1", mnemonic)).modrm(digit, digit_field(digit), rm)?.finish()
+                }
+                [rm, Reg8(1)] if is_rm8(rm) => {
+                    b.opcode(&[0xD2], &format!("{} r/m8, CL", mnemonic)).modrm(digit, digit_field(digit), rm)?.finish()
+                }
+                [rm, Imm(count)] if is_rm8(rm) => b
+                    .opcode(&[0xC0], &format!("{} r/m8, imm8", mnemonic))
+                    .modrm(digit, digit_field(digit), rm)?
+                    .immediate(*count, 1)?
+                    .finish(),
+                _ => Err(unsupported(&mnemonic, operands)),
+            };
+        }
+        return match ops.as_slice() {
+            [rm, Imm(1)] if is_rm32(rm) => {
+                b.opcode(&[0xD1], &format!("{} r/m32, 1", mnemonic)).modrm(digit, digit_field(digit), rm)?.finish()
+            }
+            [rm, Reg8(1)] if is_rm32(rm) => {
+                b.opcode(&[0xD3], &format!("{} r/m32, CL", mnemonic)).modrm(digit, digit_field(digit), rm)?.finish()
+            }
+            [rm, Imm(count)] if is_rm32(rm) => b
+                .opcode(&[0xC1], &format!("{} r/m32, imm8", mnemonic))
+                .modrm(digit, digit_field(digit), rm)?
+                .immediate(*count, 1)?
+                .finish(),
+            _ => Err(unsupported(&mnemonic, operands)),
+        };
+    }
+
+    if let Some(condition) = mnemonic.strip_prefix("SET") {
+        if let (Some(cc), [rm @ (Reg8(_) | Mem { .. })]) = (condition_code(condition), ops.as_slice()) {
+            return b
+                .opcode(&[0x0F, 0x90 + cc], &format!("SET{} r/m8", condition))
+                .modrm(0, "/0".to_string(), rm)?
+                .finish();
+        }
+        return Err(unsupported(&mnemonic, operands));
+    }
+
+    if mnemonic.starts_with('J') && mnemonic != "JMP" {
+        let condition = &mnemonic[1..];
+        if condition == "ECXZ" {
+            if let [target] = ops.as_slice() {
+                return b.opcode(&[0xE3], "JECXZ rel8").relative(target, 1)?.finish();
+            }
+        }
+        if let (Some(cc), [target]) = (condition_code(condition), ops.as_slice()) {
+            return b
+                .opcode(&[0x0F, 0x80 + cc], &format!("J{} rel32", condition))
+                .relative(target, 4)?
+                .finish();
+        }
+        return Err(unsupported(&mnemonic, operands));
+    }
+
+    match (mnemonic.as_str(), ops.as_slice()) {
+        ("MOV", [Reg32(dst), Imm(value)]) => b
+            .opcode(&[0xB8 + dst], &format!("MOV r32, imm32（B8+rd，rd={}）", REGISTERS_32[*dst as usize]))
+            .immediate(*value, 4)?
+            .finish(),
+        ("MOV", [Reg8(dst), Imm(value)]) => b
+            .opcode(&[0xB0 + dst], &format!("MOV r8, imm8（B0+rb，rb={}）", REGISTERS_8[*dst as usize]))
+            .immediate(*value, 1)?
+            .finish(),
+        ("MOV", [dst @ Mem { .. }, Imm(value)]) if byte_sized => b
+            .opcode(&[0xC6], "MOV r/m8, imm8")
+            .modrm(0, "/0".to_string(), dst)?
+            .immediate(*value, 1)?
+            .finish(),
+        ("MOV", [dst @ Mem { .. }, Imm(value)]) => b
+            .opcode(&[0xC7], "MOV r/m32, imm32")
+            .modrm(0, "/0".to_string(), dst)?
+            .immediate(*value, 4)?
+            .finish(),
+        ("MOV", [Reg32(0), Mem { base: None, index: None, disp }]) => {
+            b.opcode(&[0xA1], "MOV EAX, moffs32");
+            b.field("位移", &(*disp as i32).to_le_bytes(), format!("moffs32 = {}（小端序）", disp));
+            b.finish()
+        }
+        ("MOV", [Mem { base: None, index: None, disp }, Reg32(0)]) => {
+            b.opcode(&[0xA3], "MOV moffs32, EAX");
+            b.field("位移", &(*disp as i32).to_le_bytes(), format!("moffs32 = {}（小端序）", disp));
+            b.finish()
+        }
+        ("MOV", [dst, Reg32(src)]) if is_rm32(dst) => {
+            b.opcode(&[0x89], "MOV r/m32, r32").modrm(*src, register_field(*src), dst)?.finish()
+        }
+        ("MOV", [Reg32(dst), src @ Mem { .. }]) => {
+            b.opcode(&[0x8B], "MOV r32, r/m32").modrm(*dst, register_field(*dst), src)?.finish()
+        }
+        ("MOV", [dst @ (Reg8(_) | Mem { .. }), Reg8(src)]) => {
+            b.opcode(&[0x88], "MOV r/m8, r8").modrm(*src, REGISTERS_8[*src as usize].to_string(), dst)?.finish()
+        }
+        ("MOV", [Reg8(dst), src @ Mem { .. }]) => {
+            b.opcode(&[0x8A], "MOV r8, r/m8").modrm(*dst, REGISTERS_8[*dst as usize].to_string(), src)?.finish()
+        }
+        ("MOVZX" | "MOVSX", [Reg32(dst), src @ (Reg8(_) | Mem { .. })]) => {
+            let opcode = if mnemonic == "MOVZX" { 0xB6 } else { 0xBE };
+            b.opcode(&[0x0F, opcode], &format!("{} r32, r/m8", mnemonic))
+                .modrm(*dst, register_field(*dst), src)?
+                .finish()
+        }
+        ("LEA", [Reg32(dst), src @ Mem { .. }]) => {
+            b.opcode(&[0x8D], "LEA r32, m").modrm(*dst, register_field(*dst), src)?.finish()
+        }
+        ("XCHG", [Reg32(0), Reg32(other)]) | ("XCHG", [Reg32(other), Reg32(0)]) => {
+            b.opcode(&[0x90 + other], &format!("XCHG EAX, r32（90+rd，rd={}）", REGISTERS_32[*other as usize])).finish()
+        }
+        ("XCHG", [rm, Reg32(reg)]) if is_rm32(rm) => {
+            b.opcode(&[0x87], "XCHG r/m32, r32").modrm(*reg, register_field(*reg), rm)?.finish()
+        }
+        ("TEST", [Reg8(0), Imm(value)]) => b.opcode(&[0xA8], "TEST AL, imm8").immediate(*value, 1)?.finish(),
+        ("TEST", [rm, Imm(value)]) if size == 1 && is_rm8(rm) => b
+            .opcode(&[0xF6], "TEST r/m8, imm8")
+            .modrm(0, "/0".to_string(), rm)?
+            .immediate(*value, 1)?
+            .finish(),
+        ("TEST", [rm, Reg8(reg)]) if is_rm8(rm) => {
+            b.opcode(&[0x84], "TEST r/m8, r8").modrm(*reg, REGISTERS_8[*reg as usize].to_string(), rm)?.finish()
+        }
+        ("TEST", [Reg32(0), Imm(value)]) => b.opcode(&[0xA9], "TEST EAX, imm32").immediate(*value, 4)?.finish(),
+        ("TEST", [rm, Imm(value)]) if is_rm32(rm) => b
+            .opcode(&[0xF7], "TEST r/m32, imm32")
+            .modrm(0, "/0".to_string(), rm)?
+            .immediate(*value, 4)?
+            .finish(),
+        ("TEST", [rm, Reg32(reg)]) if is_rm32(rm) => {
+            b.opcode(&[0x85], "TEST r/m32, r32").modrm(*reg, register_field(*reg), rm)?.finish()
+        }
+        ("IMUL", [Reg32(dst), src]) if is_rm32(src) => {
+            b.opcode(&[0x0F, 0xAF], "IMUL r32, r/m32").modrm(*dst, register_field(*dst), src)?.finish()
+        }
+        ("IMUL", [Reg32(dst), Imm(value)]) => encode_imul_immediate(&mut b, *dst, &Reg32(*dst), *value),
+        ("IMUL", [Reg32(dst), src, Imm(value)]) if is_rm32(src) => encode_imul_immediate(&mut b, *dst, src, *value),
+        ("INC" | "DEC", [rm]) if size == 1 && is_rm8(rm) => {
+            let digit = if mnemonic == "INC" { 0 } else { 1 };
+            b.opcode(&[0xFE], &format!("{} r/m8", mnemonic)).modrm(digit, digit_field(digit), rm)?.finish()
+        }
+        ("INC" | "DEC", [Reg32(reg)]) => {
+            let opcode = if mnemonic == "INC" { 0x40 } else { 0x48 };
+            b.opcode(&[opcode + reg], &format!("{} r32（{:02X}+rd，rd={}）", mnemonic, opcode, REGISTERS_32[*reg as usize])).finish()
+        }
+        ("INC" | "DEC", [rm @ Mem { .. }]) => {
+            let digit = if mnemonic == "INC" { 0 } else { 1 };
+            b.opcode(&[0xFF], &format!("{} r/m32", mnemonic)).modrm(digit, digit_field(digit), rm)?.finish()
+        }
+        ("PUSH", [Reg32(reg)]) => {
+            b.opcode(&[0x50 + reg], &format!("PUSH r32（50+rd，rd={}）", REGISTERS_32[*reg as usize])).finish()
+        }
+        ("PUSH", [Imm(value)]) if fits_i8(*value) => {
+            b.opcode(&[0x6A], "PUSH imm8（符号扩展）").immediate(*value, 1)?.finish()
+        }
+        ("PUSH", [Imm(value)]) => b.opcode(&[0x68], "PUSH imm32").immediate(*value, 4)?.finish(),
+        ("PUSH", [rm @ Mem { .. }]) => b.opcode(&[0xFF], "PUSH r/m32").modrm(6, digit_field(6), rm)?.finish(),
+        ("POP", [Reg32(reg)]) => {
+            b.opcode(&[0x58 + reg], &format!("POP r32（58+rd，rd={}）", REGISTERS_32[*reg as usize])).finish()
+        }
+        ("POP", [rm @ Mem { .. }]) => b.opcode(&[0x8F], "POP r/m32").modrm(0, "/0".to_string(), rm)?.finish(),
+        ("JMP", [target @ (Label(_) | Imm(_))]) => b.opcode(&[0xE9], "JMP rel32").relative(target, 4)?.finish(),
+        ("JMP", [rm]) if is_rm32(rm) => b.opcode(&[0xFF], "JMP r/m32").modrm(4, digit_field(4), rm)?.finish(),
+        ("CALL", [target @ (Label(_) | Imm(_))]) => b.opcode(&[0xE8], "CALL rel32").relative(target, 4)?.finish(),
+        ("CALL", [rm]) if is_rm32(rm) => b.opcode(&[0xFF], "CALL r/m32").modrm(2, digit_field(2), rm)?.finish(),
+        ("LOOP", [target]) => b.opcode(&[0xE2], "LOOP rel8").relative(target, 1)?.finish(),
+        ("LOOPE" | "LOOPZ", [target]) => b.opcode(&[0xE1], "LOOPE rel8").relative(target, 1)?.finish(),
+        ("LOOPNE" | "LOOPNZ", [target]) => b.opcode(&[0xE0], "LOOPNE rel8").relative(target, 1)?.finish(),
+        ("RET", []) => b.opcode(&[0xC3], "RET").finish(),
+        ("RET", [Imm(size)]) => b.opcode(&[0xC2], "RET imm16").immediate(*size, 2)?.finish(),
+        ("INT", [Imm(3)]) => b.opcode(&[0xCC], "INT 3（断点）").finish(),
+        ("INT", [Imm(vector)]) => b.opcode(&[0xCD], "INT imm8").immediate(*vector, 1)?.finish(),
+        ("ENTER", [Imm(size), Imm(level)]) => {
+            b.opcode(&[0xC8], "ENTER imm16, imm8").immediate(*size, 2)?.immediate(*level, 1)?.finish()
+        }
+        _ => Err(unsupported(&mnemonic, operands)),
+    }
+}
+
+fn encode_imul_immediate(b: &mut Builder, dst: u8, src: &Operand, value: i64) -> Result<Encoding, String> {
+    if fits_i8(value) {
+        b.opcode(&[0x6B], "IMUL r32, r/m32, imm8").modrm(dst, register_field(dst), src)?.immediate(value, 1)?;
+    } else {
+        b.opcode(&[0x69], "IMUL r32, r/m32, imm32").modrm(dst, register_field(dst), src)?.immediate(value, 4)?;
+    }
+    b.finish()
+}
+
+/// 指令类别与周期估计，与代码生成器的取值一致：访存指令额外加 1 个周期
+pub fn classify(mnemonic: &str, memory: bool) -> (InstructionType, u32) {
+    let (instruction_type, cycles) = match mnemonic {
+        "ADD" | "SUB" | "ADC" | "SBB" | "CMP" | "NEG" | "INC" | "DEC" | "CDQ" => (InstructionType::Arithmetic, 1),
+        "IMUL" | "MUL" => (InstructionType::Arithmetic, 3),
+        "IDIV" | "DIV" => (InstructionType::Arithmetic, 20),
+        "AND" | "OR" | "XOR" | "NOT" | "TEST" | "SHL" | "SHR" | "SAR" | "ROL" | "ROR" => (InstructionType::Logic, 1),
+        m if m.starts_with("SET") => (InstructionType::Logic, 1),
+        "MOV" | "MOVZX" | "MOVSX" | "LEA" | "PUSH" | "POP" | "PUSHFD" | "POPFD" => (InstructionType::DataTransfer, 1),
+        "XCHG" => (InstructionType::DataTransfer, 2),
+        "PUSHAD" | "POPAD" => (InstructionType::DataTransfer, 5),
+        "CALL" => (InstructionType::Control, 3),
+        "RET" | "LEAVE" => (InstructionType::Control, 2),
+        "INT" | "IRETD" => (InstructionType::Control, 10),
+        "ENTER" => (InstructionType::Control, 4),
+        _ => (InstructionType::Control, 1),
+    };
+    match (instruction_type, memory) {
+        (InstructionType::DataTransfer, true) if mnemonic == "MOV" => (InstructionType::Memory, 2),
+        (instruction_type, true) if mnemonic != "LEA" => (instruction_type, cycles + 1),
+        (instruction_type, _) => (instruction_type, cycles),
+    }
+}
+
+fn unsupported(mnemonic: &str, operands: &[String]) -> String {
+    format!("不支持的指令形式 '{} {}'", mnemonic, operands.join(", "))
+}
+
+/// 为整段程序编码：先逐条编码并确定地址，再回填跳转与调用的相对偏移。
+/// 程序中不存在的标签（如 printf）视为外部符号，偏移保持为 0，由链接时重定位。
+pub fn encode_program(instructions: &mut [Instruction]) -> Result<(), (usize, String)> {
+    let mut encodings = Vec::with_capacity(instructions.len());
+    for (i, instruction) in instructions.iter().enumerate() {
+        encodings.push(encode(&instruction.mnemonic, &instruction.operands).map_err(|message| (i, message))?);
+    }
+
+    let mut addresses = Vec::with_capacity(instructions.len());
+    let mut address = CODE_BASE as i64;
+    for encoding in &encodings {
+        addresses.push(address);
+        address += encoding.bytes.len() as i64;
+    }
+    let labels: HashMap<&str, i64> = instructions
+        .iter()
+        .zip(&addresses)
+        .filter_map(|(instruction, address)| instruction.label.as_deref().map(|label| (label, *address)))
+        .collect();
+
+    for (i, encoding) in encodings.iter_mut().enumerate() {
+        let Some(relocation) = encoding.relocation.clone() else { continue };
+        let target = match &relocation.target {
+            RelocationTarget::Label(label) => labels.get(label.as_str()).copied(),
+            RelocationTarget::Address(address) => Some(*address),
+        };
+        let Some(target) = target else {
+            if let Some(field) = encoding.fields.last_mut() {
+                field.description.push_str("（外部符号，链接时重定位）");
+            }
+            continue;
+        };
+        let next = addresses[i] + encoding.bytes.len() as i64;
+        let offset = target - next;
+        let bytes = match relocation.size {
+            1 if fits_i8(offset) => vec![offset as i8 as u8],
+            1 => return Err((i, format!("跳转目标超出 rel8 范围（偏移 {}）", offset))),
+            _ => (offset as i32).to_le_bytes().to_vec(),
+        };
+        encoding.bytes[relocation.offset..relocation.offset + relocation.size].copy_from_slice(&bytes);
+        if let Some(field) = encoding.fields.last_mut() {
+            field.hex = hex(&bytes);
+            field.description.push_str(&format!("，目标地址 {:08X} - 下一条指令地址 {:08X} = {}", target, next, offset));
+        }
+    }
+
+    for (instruction, encoding) in instructions.iter_mut().zip(encodings) {
+        instruction.machine_code = hex(&encoding.bytes);
+        instruction.encoding = encoding.fields;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 按汇编文本编码一条指令，返回机器码的十六进制形式
+    fn bytes(text: &str) -> String {
+        let (mnemonic, operands) = text.split_once(' ').unwrap_or((text, ""));
+        let operands: Vec<String> =
+            operands.split(',').map(str::trim).filter(|operand| !operand.is_empty()).map(String::from).collect();
+        hex(&encode(mnemonic, &operands).unwrap().bytes)
+    }
+
+    fn instruction(label: Option<&str>, mnemonic: &str, operands: &[&str]) -> Instruction {
+        let (instruction_type, cycles) = classify(mnemonic, false);
+        Instruction {
+            id: mnemonic.to_lowercase(),
+            instruction_type,
+            mnemonic: mnemonic.to_string(),
+            operands: operands.iter().map(|operand| operand.to_string()).collect(),
+            machine_code: String::new(),
+            description: String::new(),
+            cycles,
+            label: label.map(String::from),
+            encoding: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn register_and_immediate_forms() {
+        assert_eq!(bytes("MOV EAX, 1"), "B801000000");
+        assert_eq!(bytes("MOV EAX, -1"), "B8FFFFFFFF");
+        assert_eq!(bytes("MOV ECX, EAX"), "89C1");
+        assert_eq!(bytes("MOV AX, 5"), "66B80500");
+        // 立即数能用 8 位表示时用 83 /0 ib，否则 EAX 用短格式 05 id
+        assert_eq!(bytes("ADD EAX, 100"), "83C064");
+        assert_eq!(bytes("ADD EAX, 1000"), "05E8030000");
+        assert_eq!(bytes("ADD ECX, 1000"), "81C1E8030000");
+        assert_eq!(bytes("PUSH 5"), "6A05");
+        assert_eq!(bytes("PUSH 1000"), "68E8030000");
+        assert_eq!(bytes("PUSH EBP"), "55");
+        assert_eq!(bytes("POP EBP"), "5D");
+        assert_eq!(bytes("IDIV ECX"), "F7F9");
+        assert_eq!(bytes("IMUL EAX, ECX"), "0FAFC1");
+        assert_eq!(bytes("MOVSX EAX, AL"), "0FBEC0");
+        assert_eq!(bytes("SHL EAX, 3"), "C1E003");
+        assert_eq!(bytes("INT 3"), "CC");
+        assert_eq!(bytes("INT 32"), "CD20");
+    }
+
+    #[test]
+    fn memory_operand_forms() {
+        assert_eq!(bytes("MOV [EBP-4], EAX"), "8945FC");
+        assert_eq!(bytes("MOV EAX, [EBP+8]"), "8B4508");
+        // [EBP] 没有 mod=00 的形式，须带 8 位位移 0；[ESP] 须用 SIB
+        assert_eq!(bytes("MOV EAX, [EBP]"), "8B4500");
+        assert_eq!(bytes("MOV EAX, [ESP]"), "8B0424");
+        assert_eq!(bytes("MOV EAX, [EBX+ECX*4+8]"), "8B448B08");
+        assert_eq!(bytes("LEA EAX, [EAX+EAX*2]"), "8D0440");
+        // 绝对地址：EAX 用 A1 moffs32，其他寄存器用 mod=00 r/m=101
+        assert_eq!(bytes("MOV EAX, [1000]"), "A1E8030000");
+        assert_eq!(bytes("MOV ECX, [1000]"), "8B0DE8030000");
+        assert_eq!(bytes("MOV BYTE PTR [1000], 5"), "C605E803000005");
+    }
+
+    #[test]
+    fn fields_describe_each_part() {
+        let encoding = encode("MOV", &["EAX".to_string(), "[EBX+ECX*4+8]".to_string()]).unwrap();
+        let fields: Vec<(&str, &str)> =
+            encoding.fields.iter().map(|field| (field.field.as_str(), field.hex.as_str())).collect();
+        assert_eq!(fields, [("操作码", "8B"), ("ModR/M", "44"), ("SIB", "8B"), ("位移", "08")]);
+    }
+
+    #[test]
+    fn program_resolves_relative_jumps() {
+        let mut program = vec![
+            instruction(Some("start"), "MOV", &["EAX", "0"]),
+            instruction(Some("loop"), "ADD", &["EAX", "1"]),
+            instruction(None, "CMP", &["EAX", "10"]),
+            instruction(None, "JL", &["loop"]),
+            instruction(None, "CALL", &["printf"]),
+            instruction(None, "JMP", &["start"]),
+        ];
+        encode_program(&mut program).unwrap();
+        let code: Vec<&str> = program.iter().map(|instruction| instruction.machine_code.as_str()).collect();
+        // 标签用 rel32，偏移相对下一条指令的地址；外部函数的偏移留给链接时重定位
+        assert_eq!(code, ["B800000000", "83C001", "83F80A", "0F8CF4FFFFFF", "E800000000", "E9E5FFFFFF"]);
+        assert!(program[4].encoding.last().unwrap().description.contains("外部符号"));
+    }
+}
@@ -0,0 +1,256 @@
+use crate::types::{InterruptGate, InterruptKind, MemoryState};
+
+/// 中断描述符表的物理地址：256 项，每项 8 字节
+pub const IDT_BASE: u64 = 0x11_0000;
+pub const IDT_ENTRIES: u64 = 256;
+pub const IDT_SIZE: u64 = IDT_ENTRIES * 8;
+
+/// 异常与中断的向量号
+pub const DIVIDE_ERROR: u8 = 0;
+pub const BREAKPOINT: u8 = 3;
+pub const INVALID_OPCODE: u8 = 6;
+pub const GENERAL_PROTECTION_FAULT: u8 = 13;
+pub const PAGE_FAULT: u8 = 14;
+/// 定时器接在 8259A 的 IRQ0 上，按惯例映射到 32 号向量
+pub const TIMER: u8 = 32;
+
+/// 一般保护异常说明的开头，内存访问越界、写入只读段与栈越界都属于这一类
+const GENERAL_PROTECTION: &str = "一般保护异常（#GP）：";
+
+/// 门描述符的后 4 字节：有效、允许用户态调用、陷阱门
+const PRESENT: u32 = 1;
+const USER: u32 = 1 << 1;
+const TRAP: u32 = 1 << 2;
+
+/// 处理程序标签的前缀：标签 isr<n> 处的代码在载入时装入第 n 项
+pub const HANDLER_PREFIX: &str = "isr";
+
+/// 读取第 vector 项，无效时为 None；中断描述符表由硬件按物理地址访问，不经 MMU
+pub fn read_gate(memory: &MemoryState, vector: u8) -> Option<InterruptGate> {
+    let address = IDT_BASE + vector as u64 * 8;
+    let word =
+        |offset: u64| (0..4).fold(0u32, |value, i| value | (memory.read_byte(address + offset + i) as u32) << (8 * i));
+    let flags = word(4);
+    (flags & PRESENT != 0).then(|| InterruptGate {
+        handler: word(0) as u64,
+        user: flags & USER != 0,
+        trap: flags & TRAP != 0,
+    })
+}
+
+/// 写入第 vector 项，gate 为 None 时清除该项
+pub fn write_gate(memory: &mut MemoryState, vector: u8, gate: Option<InterruptGate>) {
+    let (handler, flags) = match gate {
+        Some(gate) => {
+            (gate.handler as u32, PRESENT | if gate.user { USER } else { 0 } | if gate.trap { TRAP } else { 0 })
+        }
+        None => (0, 0),
+    };
+    let address = IDT_BASE + vector as u64 * 8;
+    for (i, byte) in handler.to_le_bytes().into_iter().chain(flags.to_le_bytes()).enumerate() {
+        memory.write_byte(address + i as u64, byte);
+    }
+}
+
+/// 译码、执行或访存时出现的异常：种类与向量号由出错的地方给出，message 为报告给用户的说明
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fault {
+    pub kind: InterruptKind,
+    pub vector: u8,
+    pub message: String,
+}
+
+impl Fault {
+    pub fn divide_error(message: String) -> Self {
+        Self { kind: InterruptKind::DivideError, vector: DIVIDE_ERROR, message }
+    }
+
+    pub fn invalid_opcode(message: String) -> Self {
+        Self { kind: InterruptKind::InvalidOpcode, vector: INVALID_OPCODE, message }
+    }
+
+    /// 说明前加上 “一般保护异常（#GP）：”
+    pub fn general_protection(message: String) -> Self {
+        let message = format!("{}{}", GENERAL_PROTECTION, message);
+        Self { kind: InterruptKind::GeneralProtection, vector: GENERAL_PROTECTION_FAULT, message }
+    }
+
+    pub fn page_fault(message: String) -> Self {
+        Self { kind: InterruptKind::PageFault, vector: PAGE_FAULT, message }
+    }
+}
+
+/// 其余译码与执行错误都视为无效指令
+impl From<String> for Fault {
+    fn from(message: String) -> Self {
+        Self::invalid_opcode(message)
+    }
+}
+
+impl From<&str> for Fault {
+    fn from(message: &str) -> Self {
+        Self::invalid_opcode(message.to_string())
+    }
+}
+
+/// x86 进入处理程序时压入的中断帧：低地址为返回地址，其上为标志寄存器（不保存段寄存器）
+pub fn frame(return_address: u64, flags: u32) -> Vec<u8> {
+    (return_address as u32).to_le_bytes().into_iter().chain(flags.to_le_bytes()).collect()
+}
+
+/// 中断种类的名称
+pub fn name(kind: InterruptKind, vector: u8) -> String {
+    match kind {
+        InterruptKind::DivideError => "除法错误（#DE）".to_string(),
+        InterruptKind::InvalidOpcode => "无效指令（#UD）".to_string(),
+        InterruptKind::GeneralProtection => "一般保护异常（#GP）".to_string(),
+        InterruptKind::PageFault => "页错误（#PF）".to_string(),
+        InterruptKind::Software if vector == BREAKPOINT => "断点（INT 3）".to_string(),
+        InterruptKind::Software => format!("软件中断 INT {}", vector),
+        InterruptKind::Timer => "定时器中断".to_string(),
+        InterruptKind::Return => "中断返回".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble;
+    use crate::cpu_simulator::{create_simulator, Simulator};
+    use crate::types::{Architecture, ExecutionStage, Instruction, InterruptEvent, PipelineConfig, Target};
+    use crate::{mips, riscv};
+
+    /// 每行一条指令，"标签: 助记符 操作数, ..." 形式
+    fn program(architecture: Architecture, source: &str) -> Vec<Instruction> {
+        source
+            .lines()
+            .map(|line| {
+                let (label, text) = match line.split_once(':') {
+                    Some((label, text)) => (Some(label.trim().to_string()), text.trim()),
+                    None => (None, line.trim()),
+                };
+                let (mnemonic, operands) = text.split_once(' ').unwrap_or((text, ""));
+                let operands = operands.split(',').map(|operand| operand.trim().to_string());
+                let (instruction_type, cycles) = match architecture {
+                    Architecture::Mips => mips::classify(mnemonic),
+                    _ => riscv::classify(mnemonic),
+                };
+                Instruction {
+                    id: mnemonic.to_string(),
+                    instruction_type,
+                    mnemonic: mnemonic.to_string(),
+                    operands: operands.filter(|operand| !operand.is_empty()).collect(),
+                    machine_code: String::new(),
+                    description: String::new(),
+                    cycles,
+                    label,
+                    encoding: Vec::new(),
+                }
+            })
+            .collect()
+    }
+
+    /// 运行到程序结束，返回模拟器与全部中断事件
+    fn run(
+        target: Target,
+        instructions: Vec<Instruction>,
+        pipelined: bool,
+    ) -> (Box<dyn Simulator>, Vec<InterruptEvent>) {
+        let mut simulator = create_simulator(target);
+        simulator.configure_pipeline(PipelineConfig { enabled: pipelined, forwarding: true });
+        simulator.load_instructions(instructions).unwrap();
+        let mut events = Vec::new();
+        for _ in 0..1000 {
+            let result = simulator.step().unwrap();
+            events.extend(result.interrupts);
+            if matches!(result.stage, ExecutionStage::Complete) && result.instruction.is_none() {
+                return (simulator, events);
+            }
+        }
+        panic!("程序没有结束");
+    }
+
+    #[test]
+    fn gates_round_trip_through_memory() {
+        let mut memory = MemoryState::new(0x8000, 0x1000);
+        assert_eq!(read_gate(&memory, TIMER), None);
+        let gate = InterruptGate { handler: 0x40, user: true, trap: false };
+        write_gate(&mut memory, TIMER, Some(gate));
+        assert_eq!(read_gate(&memory, TIMER), Some(gate));
+        write_gate(&mut memory, TIMER, None);
+        assert_eq!(read_gate(&memory, TIMER), None);
+    }
+
+    #[test]
+    fn x86_pushes_the_frame_on_the_stack() {
+        let source = "mov eax, 1\nint 128\nadd eax, 100\nhlt\nisr128: add eax, 10\npushfd\npop edx\niretd\n";
+        for pipelined in [false, true] {
+            let (simulator, events) = run(Target::default(), assemble(source).instructions, pipelined);
+            let state = simulator.state();
+            assert_eq!(state.registers.general["EAX"], 111);
+            // 处理程序中 IF = 0，中断帧里保存的是进入前的 EFLAGS
+            assert_eq!(state.registers.general["EDX"] & 0x200, 0);
+            assert_eq!(
+                (events[0].vector, events[0].stack_pointer, events[0].flags & 0x200),
+                (Some(128), 0x7FF8, 0x200)
+            );
+            assert_eq!((events[1].kind, events[1].stack_pointer), (InterruptKind::Return, 0x8000));
+            assert!(state.flags.interrupt);
+        }
+    }
+
+    #[test]
+    fn riscv_saves_the_context_in_mepc_and_mstatus() {
+        // 非对齐的 lw 引发一般保护异常，处理程序把 mepc 加 4 跳过它
+        let source = "addi a0, zero, 5\nlw a1, 1(zero)\naddi a0, a0, 1\nebreak\n\
+                      isr13: csrrs t1, mstatus, zero\ncsrrs t0, mepc, zero\naddi t0, t0, 4\ncsrrw zero, mepc, t0\nmret";
+        let target = Target { architecture: Architecture::RiscV, branch_delay_slot: false };
+        for pipelined in [false, true] {
+            let (simulator, events) = run(target, program(Architecture::RiscV, source), pipelined);
+            let state = simulator.state();
+            assert_eq!(state.registers.general["x10"], 6);
+            // 处理程序中 MIE = 0，MPIE 记下原来的 MIE
+            assert_eq!(state.registers.general["x6"], 0x80);
+            assert_eq!(
+                (events[0].kind, events[0].flags, events[0].stack_pointer),
+                (InterruptKind::GeneralProtection, 0x80, 0x8000)
+            );
+            assert_eq!((events[1].kind, events[1].target), (InterruptKind::Return, 8));
+            assert_eq!((state.registers.special["mepc"], state.registers.special["mstatus"]), (8, 0x88));
+            assert!(state.flags.interrupt);
+        }
+    }
+
+    #[test]
+    fn mips_saves_the_context_in_epc_and_status() {
+        let source = "addiu $a0, $zero, 5\nlw $a1, 1($zero)\naddiu $a0, $a0, 1\nbreak\n\
+                      isr13: mfc0 $t1, $12\nmfc0 $t0, $14\naddiu $t0, $t0, 4\nmtc0 $t0, $14\neret\nnop";
+        for branch_delay_slot in [false, true] {
+            for pipelined in [false, true] {
+                let target = Target { architecture: Architecture::Mips, branch_delay_slot };
+                let (simulator, events) = run(target, program(Architecture::Mips, source), pipelined);
+                let state = simulator.state();
+                assert_eq!(state.registers.general["$4"], 6);
+                // 处理程序中 EXL = 1，IE 保持进入前的值
+                assert_eq!(state.registers.general["$9"], 3);
+                assert_eq!((events[0].flags, events[0].stack_pointer), (3, 0x8000));
+                assert_eq!((events[1].kind, events[1].target), (InterruptKind::Return, 8));
+                assert_eq!((state.registers.special["EPC"], state.registers.special["Status"]), (8, 1));
+                assert!(state.flags.interrupt);
+            }
+        }
+    }
+
+    #[test]
+    fn writing_the_status_register_masks_interrupts() {
+        let source = "csrrw zero, mstatus, zero\nebreak";
+        let target = Target { architecture: Architecture::RiscV, branch_delay_slot: false };
+        let (simulator, _) = run(target, program(Architecture::RiscV, source), false);
+        assert!(!simulator.state().flags.interrupt);
+
+        let source = "addiu $t0, $zero, 3\nmtc0 $t0, $12\nbreak";
+        let target = Target { architecture: Architecture::Mips, branch_delay_slot: false };
+        let (simulator, _) = run(target, program(Architecture::Mips, source), false);
+        assert!(!simulator.state().flags.interrupt);
+    }
+}
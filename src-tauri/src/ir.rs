@@ -0,0 +1,567 @@
+use crate::compiler::{ASTNode, ASTNodeType};
+use crate::types::*;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// 三地址码的操作数
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum IrOperand {
+    /// 编译器生成的临时变量 t1, t2, ...
+    Temp(usize),
+    /// 源程序中的变量（已消除同名遮蔽）
+    Var(String),
+    Const(i64),
+    /// 字符串常量，值为它在数据区的地址
+    Str(String),
+}
+
+impl fmt::Display for IrOperand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IrOperand::Temp(n) => write!(f, "t{}", n),
+            IrOperand::Var(name) => write!(f, "{}", name),
+            IrOperand::Const(value) => write!(f, "{}", value),
+            IrOperand::Str(text) => write!(f, "&\"{}\"", text),
+        }
+    }
+}
+
+/// 三地址码操作
+#[derive(Debug, Clone, PartialEq)]
+pub enum IrOp {
+    /// result = arg1
+    Assign,
+    /// result = arg1 op arg2，op 为 + - * / % == != < > <= >=
+    Binary(String),
+    /// result = -arg1
+    Neg,
+    /// result = !arg1
+    Not,
+    /// result = (类型) arg1
+    Cast(String),
+    /// 标签定义
+    Label(String),
+    /// goto 标签
+    Jump(String),
+    /// if arg1 relop arg2 goto 标签
+    CondJump(String, String),
+    /// 传递参数 arg1（按 cdecl 从右向左给出）
+    Param,
+    /// result = call 函数名, 参数个数
+    Call(String, usize),
+    /// return [arg1]
+    Return,
+    /// 停机（仅出现在程序入口）
+    Halt,
+}
+
+/// 一条四元式 (op, arg1, arg2, result)
+#[derive(Debug, Clone, PartialEq)]
+pub struct IrInstruction {
+    pub op: IrOp,
+    pub arg1: Option<IrOperand>,
+    pub arg2: Option<IrOperand>,
+    pub result: Option<IrOperand>,
+}
+
+impl IrInstruction {
+    fn new(op: IrOp, arg1: Option<IrOperand>, arg2: Option<IrOperand>, result: Option<IrOperand>) -> Self {
+        Self { op, arg1, arg2, result }
+    }
+
+    /// 本条指令读取的操作数
+    pub fn uses(&self) -> Vec<&IrOperand> {
+        self.arg1.iter().chain(self.arg2.iter()).collect()
+    }
+}
+
+impl fmt::Display for IrInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let arg = |operand: &Option<IrOperand>| operand.as_ref().map_or(String::new(), |o| o.to_string());
+        let (a, b, r) = (arg(&self.arg1), arg(&self.arg2), arg(&self.result));
+        match &self.op {
+            IrOp::Assign => write!(f, "    {} = {}", r, a),
+            IrOp::Binary(op) => write!(f, "    {} = {} {} {}", r, a, op, b),
+            IrOp::Neg => write!(f, "    {} = -{}", r, a),
+            IrOp::Not => write!(f, "    {} = !{}", r, a),
+            IrOp::Cast(data_type) => write!(f, "    {} = ({}) {}", r, data_type, a),
+            IrOp::Label(label) => write!(f, "{}:", label),
+            IrOp::Jump(label) => write!(f, "    goto {}", label),
+            IrOp::CondJump(relop, label) => write!(f, "    if {} {} {} goto {}", a, relop, b, label),
+            IrOp::Param => write!(f, "    param {}", a),
+            IrOp::Call(name, count) if self.result.is_some() => write!(f, "    {} = call {}, {}", r, name, count),
+            IrOp::Call(name, count) => write!(f, "    call {}, {}", name, count),
+            IrOp::Return if self.arg1.is_some() => write!(f, "    return {}", a),
+            IrOp::Return => write!(f, "    return"),
+            IrOp::Halt => write!(f, "    halt"),
+        }
+    }
+}
+
+/// 一个函数的中间代码
+#[derive(Debug, Clone)]
+pub struct IrFunction {
+    pub name: String,
+    pub params: Vec<String>,
+    /// 局部变量（不含参数），已按遮蔽关系重命名
+    pub locals: Vec<String>,
+    pub body: Vec<IrInstruction>,
+    /// 使用到的临时变量个数
+    pub temp_count: usize,
+}
+
+impl IrFunction {
+    /// 程序入口：执行全局初始化并调用 main
+    pub fn is_entry(&self) -> bool {
+        self.name == ENTRY_FUNCTION
+    }
+}
+
+/// 整个程序的中间代码
+#[derive(Debug, Clone)]
+pub struct IrProgram {
+    pub globals: Vec<String>,
+    /// 第一个函数是程序入口 _start
+    pub functions: Vec<IrFunction>,
+}
+
+impl IrProgram {
+    pub fn instruction_count(&self) -> usize {
+        self.functions.iter().map(|f| f.body.len()).sum()
+    }
+
+    /// 中间代码的文本形式
+    pub fn dump(&self) -> String {
+        let mut output = String::new();
+        if !self.globals.is_empty() {
+            output.push_str(&format!("global {}\n\n", self.globals.join(", ")));
+        }
+        for function in &self.functions {
+            output.push_str(&format!("function {}({}):\n", function.name, function.params.join(", ")));
+            for instruction in &function.body {
+                output.push_str(&format!("{}\n", instruction));
+            }
+            output.push_str("end\n\n");
+        }
+        output
+    }
+}
+
+pub const ENTRY_FUNCTION: &str = "_start";
+
+/// 把（经过语义分析的）语法树翻译为三地址码
+pub struct IrBuilder {
+    globals: Vec<String>,
+    /// 源程序名字 -> 中间代码中的名字，按作用域嵌套
+    scopes: Vec<HashMap<String, String>>,
+    /// 当前函数中已经使用过的名字，用于消除遮蔽
+    used_names: HashSet<String>,
+    locals: Vec<String>,
+    body: Vec<IrInstruction>,
+    temp_counter: usize,
+    label_counter: usize,
+    /// 正在翻译入口 _start 中的顶层语句
+    in_entry: bool,
+}
+
+impl IrBuilder {
+    pub fn new() -> Self {
+        Self {
+            globals: Vec::new(),
+            scopes: vec![HashMap::new()],
+            used_names: HashSet::new(),
+            locals: Vec::new(),
+            body: Vec::new(),
+            temp_counter: 0,
+            label_counter: 0,
+            in_entry: false,
+        }
+    }
+
+    pub fn build(mut self, program: &ASTNode) -> Result<IrProgram, Diagnostic> {
+        let (functions, top_level): (Vec<&ASTNode>, Vec<&ASTNode>) = program
+            .children
+            .iter()
+            .partition(|child| matches!(child.node_type, ASTNodeType::Function));
+
+        // 入口：全局声明的初始化、其他顶层语句，最后调用 main 并停机
+        self.in_entry = true;
+        for statement in top_level {
+            self.lower_statement(statement)?;
+        }
+        self.in_entry = false;
+        if functions.iter().any(|f| f.children[0].value == "main") {
+            self.emit(IrOp::Call("main".to_string(), 0), None, None, None);
+        }
+        self.emit(IrOp::Halt, None, None, None);
+        let mut ir_functions = vec![self.finish_function(ENTRY_FUNCTION.to_string(), Vec::new())];
+
+        for function in functions {
+            ir_functions.push(self.lower_function(function)?);
+        }
+
+        Ok(IrProgram {
+            globals: self.globals,
+            functions: ir_functions,
+        })
+    }
+
+    fn finish_function(&mut self, name: String, params: Vec<String>) -> IrFunction {
+        let function = IrFunction {
+            name,
+            params,
+            locals: std::mem::take(&mut self.locals),
+            body: std::mem::take(&mut self.body),
+            temp_count: self.temp_counter,
+        };
+        self.temp_counter = 0;
+        self.used_names.clear();
+        function
+    }
+
+    fn lower_function(&mut self, node: &ASTNode) -> Result<IrFunction, Diagnostic> {
+        let name = node.children[0].value.clone();
+        let last = node.children.len() - 1;
+
+        self.scopes.push(HashMap::new());
+        let params: Vec<String> = node.children[1..last]
+            .iter()
+            .map(|param| self.declare(&param.children[0].value, false))
+            .collect();
+        for statement in &node.children[last].children {
+            self.lower_statement(statement)?;
+        }
+        self.scopes.pop();
+
+        // 保证函数末尾有 return
+        if !matches!(self.body.last(), Some(IrInstruction { op: IrOp::Return, .. })) {
+            self.emit(IrOp::Return, None, None, None);
+        }
+        Ok(self.finish_function(name, params))
+    }
+
+    fn emit(&mut self, op: IrOp, arg1: Option<IrOperand>, arg2: Option<IrOperand>, result: Option<IrOperand>) {
+        self.body.push(IrInstruction::new(op, arg1, arg2, result));
+    }
+
+    fn new_temp(&mut self) -> IrOperand {
+        self.temp_counter += 1;
+        IrOperand::Temp(self.temp_counter)
+    }
+
+    fn new_label(&mut self) -> String {
+        self.label_counter += 1;
+        format!("L{}", self.label_counter)
+    }
+
+    /// 在当前作用域声明变量，与已有名字冲突时重命名为 name.N
+    fn declare(&mut self, name: &str, is_local: bool) -> String {
+        let in_function = self.scopes.len() > 1;
+        let mut ir_name = name.to_string();
+        let mut suffix = 1;
+        while in_function && (self.used_names.contains(&ir_name) || self.globals.contains(&ir_name)) {
+            suffix += 1;
+            ir_name = format!("{}.{}", name, suffix);
+        }
+
+        if in_function {
+            self.used_names.insert(ir_name.clone());
+            if is_local {
+                self.locals.push(ir_name.clone());
+            }
+        } else {
+            self.globals.push(ir_name.clone());
+        }
+        self.scopes.last_mut().unwrap().insert(name.to_string(), ir_name.clone());
+        ir_name
+    }
+
+    /// 语义分析已对未声明的标识符报告 E0403，出错的程序不会生成中间代码
+    fn resolve(&self, identifier: &ASTNode) -> IrOperand {
+        match self.scopes.iter().rev().find_map(|scope| scope.get(&identifier.value)) {
+            Some(name) => IrOperand::Var(name.clone()),
+            None => unreachable!("变量 '{}' 未经语义分析就生成中间代码", identifier.value),
+        }
+    }
+
+    fn lower_statement(&mut self, node: &ASTNode) -> Result<(), Diagnostic> {
+        match node.node_type {
+            ASTNodeType::Declaration => {
+                // 先计算初始化表达式，再让新名字生效
+                let init = match node.children.get(1) {
+                    Some(init) => Some(self.lower_expression(init)?),
+                    None => None,
+                };
+                let in_function = self.scopes.len() > 1;
+                let name = self.declare(&node.children[0].value, in_function);
+                if let Some(value) = init {
+                    self.emit(IrOp::Assign, Some(value), None, Some(IrOperand::Var(name)));
+                }
+            }
+            ASTNodeType::Block => {
+                self.scopes.push(HashMap::new());
+                for child in &node.children {
+                    self.lower_statement(child)?;
+                }
+                self.scopes.pop();
+            }
+            ASTNodeType::Statement => {
+                for child in &node.children {
+                    self.lower_expression(child)?;
+                }
+            }
+            ASTNodeType::If => {
+                let else_label = self.new_label();
+                self.lower_condition(&node.children[0], &else_label)?;
+                self.lower_statement(&node.children[1])?;
+                if let Some(else_branch) = node.children.get(2) {
+                    let end_label = self.new_label();
+                    self.emit(IrOp::Jump(end_label.clone()), None, None, None);
+                    self.emit(IrOp::Label(else_label), None, None, None);
+                    self.lower_statement(else_branch)?;
+                    self.emit(IrOp::Label(end_label), None, None, None);
+                } else {
+                    self.emit(IrOp::Label(else_label), None, None, None);
+                }
+            }
+            ASTNodeType::While => {
+                let start_label = self.new_label();
+                let end_label = self.new_label();
+                self.emit(IrOp::Label(start_label.clone()), None, None, None);
+                self.lower_condition(&node.children[0], &end_label)?;
+                self.lower_statement(&node.children[1])?;
+                self.emit(IrOp::Jump(start_label), None, None, None);
+                self.emit(IrOp::Label(end_label), None, None, None);
+            }
+            ASTNodeType::For => {
+                self.scopes.push(HashMap::new());
+                let start_label = self.new_label();
+                let end_label = self.new_label();
+                self.lower_statement(&node.children[0])?;
+                self.emit(IrOp::Label(start_label.clone()), None, None, None);
+                if !matches!(node.children[1].node_type, ASTNodeType::Empty) {
+                    self.lower_condition(&node.children[1], &end_label)?;
+                }
+                self.lower_statement(&node.children[3])?;
+                self.lower_statement(&node.children[2])?;
+                self.emit(IrOp::Jump(start_label), None, None, None);
+                self.emit(IrOp::Label(end_label), None, None, None);
+                self.scopes.pop();
+            }
+            ASTNodeType::Return => {
+                let value = match node.children.first() {
+                    Some(value) => Some(self.lower_expression(value)?),
+                    None => None,
+                };
+                // _start 没有调用者可以返回，顶层的 return 直接停机（语义分析已报告错误）
+                if self.in_entry {
+                    self.emit(IrOp::Halt, None, None, None);
+                } else {
+                    self.emit(IrOp::Return, value, None, None);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// 条件为假时跳转到 false_label
+    fn lower_condition(&mut self, condition: &ASTNode, false_label: &str) -> Result<(), Diagnostic> {
+        if matches!(condition.node_type, ASTNodeType::BinaryOp) {
+            if let Some(inverse) = inverse_relop(&condition.value) {
+                let a = self.lower_expression(&condition.children[0])?;
+                let b = self.lower_expression(&condition.children[1])?;
+                self.emit(IrOp::CondJump(inverse.to_string(), false_label.to_string()), Some(a), Some(b), None);
+                return Ok(());
+            }
+            if condition.value == "&&" {
+                self.lower_condition(&condition.children[0], false_label)?;
+                return self.lower_condition(&condition.children[1], false_label);
+            }
+        }
+
+        let value = self.lower_expression(condition)?;
+        self.emit(
+            IrOp::CondJump("==".to_string(), false_label.to_string()),
+            Some(value),
+            Some(IrOperand::Const(0)),
+            None,
+        );
+        Ok(())
+    }
+
+    /// 翻译表达式，返回保存结果的操作数
+    fn lower_expression(&mut self, node: &ASTNode) -> Result<IrOperand, Diagnostic> {
+        match node.node_type {
+            ASTNodeType::Number => {
+                // 模拟器没有浮点单元，浮点常量按整数截断（语义分析已给出 W0404 警告）
+                let value = if node.value.contains('.') {
+                    node.value.parse::<f64>().map(|v| v as i64).ok()
+                } else {
+                    node.value.parse::<i64>().ok()
+                };
+                value
+                    .map(IrOperand::Const)
+                    .ok_or_else(|| Diagnostic::error("E0202", format!("无效的数字 '{}'", node.value), node.span))
+            }
+            ASTNodeType::CharLiteral => Ok(IrOperand::Const(node.value.chars().next().map_or(0, |c| c as i64))),
+            ASTNodeType::StringLiteral => Ok(IrOperand::Str(node.value.clone())),
+            ASTNodeType::Identifier => Ok(self.resolve(node)),
+            ASTNodeType::Assignment => {
+                let target = self.resolve(&node.children[0]);
+                let value = self.lower_expression(&node.children[1])?;
+                self.emit(IrOp::Assign, Some(value), None, Some(target.clone()));
+                Ok(target)
+            }
+            ASTNodeType::UnaryOp => {
+                let operand = self.lower_expression(&node.children[0])?;
+                let op = match node.value.as_str() {
+                    "-" => IrOp::Neg,
+                    "!" => IrOp::Not,
+                    _ => return Ok(operand),
+                };
+                let result = self.new_temp();
+                self.emit(op, Some(operand), None, Some(result.clone()));
+                Ok(result)
+            }
+            ASTNodeType::BinaryOp if matches!(node.value.as_str(), "&&" | "||") => {
+                // 短路求值：&& 遇假、|| 遇真时跳过右操作数
+                let is_and = node.value == "&&";
+                let result = self.new_temp();
+                let done = self.new_label();
+                let (short, other) = if is_and { (0, 1) } else { (1, 0) };
+                let relop = if is_and { "==" } else { "!=" };
+                self.emit(IrOp::Assign, Some(IrOperand::Const(short)), None, Some(result.clone()));
+                for child in &node.children {
+                    let value = self.lower_expression(child)?;
+                    self.emit(
+                        IrOp::CondJump(relop.to_string(), done.clone()),
+                        Some(value),
+                        Some(IrOperand::Const(0)),
+                        None,
+                    );
+                }
+                self.emit(IrOp::Assign, Some(IrOperand::Const(other)), None, Some(result.clone()));
+                self.emit(IrOp::Label(done), None, None, None);
+                Ok(result)
+            }
+            ASTNodeType::BinaryOp => {
+                let a = self.lower_expression(&node.children[0])?;
+                let b = self.lower_expression(&node.children[1])?;
+                let result = self.new_temp();
+                self.emit(IrOp::Binary(node.value.clone()), Some(a), Some(b), Some(result.clone()));
+                Ok(result)
+            }
+            ASTNodeType::Cast => {
+                let value = self.lower_expression(&node.children[0])?;
+                let result = self.new_temp();
+                self.emit(IrOp::Cast(node.value.clone()), Some(value), None, Some(result.clone()));
+                Ok(result)
+            }
+            ASTNodeType::Call => {
+                let mut arguments = Vec::new();
+                for argument in &node.children {
+                    arguments.push(self.lower_expression(argument)?);
+                }
+                for argument in arguments.into_iter().rev() {
+                    self.emit(IrOp::Param, Some(argument), None, None);
+                }
+                let result = self.new_temp();
+                self.emit(
+                    IrOp::Call(node.value.clone(), node.children.len()),
+                    None,
+                    None,
+                    Some(result.clone()),
+                );
+                Ok(result)
+            }
+            _ => Err(Diagnostic::error(
+                "E0302",
+                format!("无法为表达式 '{}' 生成中间代码", node.value),
+                node.span,
+            )),
+        }
+    }
+}
+
+impl Default for IrBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 关系运算符取反，用于“条件不成立时跳转”
+fn inverse_relop(op: &str) -> Option<&'static str> {
+    match op {
+        "==" => Some("!="),
+        "!=" => Some("=="),
+        "<" => Some(">="),
+        ">" => Some("<="),
+        "<=" => Some(">"),
+        ">=" => Some("<"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{Lexer, Parser};
+    use crate::semantic::SemanticAnalyzer;
+
+    /// 经过语义分析后生成中间代码，返回文本形式与语义分析的诊断代码
+    fn lower(source: &str) -> (String, Vec<String>) {
+        let mut ast = Parser::new(Lexer::new(source.to_string()).tokenize()).parse();
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.analyze(&mut ast);
+        let codes = analyzer.diagnostics().iter().map(|diagnostic| diagnostic.code.clone()).collect();
+        (IrBuilder::new().build(&ast).unwrap().dump(), codes)
+    }
+
+    #[test]
+    fn lowers_expressions_and_renames_shadowed_variables() {
+        let (ir, codes) = lower("int g = 2;\nint main() { int x = g + 3 * 4; { int x = 1; x = x - 1; } return x; }");
+        assert!(codes.is_empty(), "{:?}", codes);
+        assert_eq!(
+            ir,
+            "global g\n\n\
+             function _start():\n    g = 2\n    call main, 0\n    halt\nend\n\n\
+             function main():\n    t1 = 3 * 4\n    t2 = g + t1\n    x = t2\n    x.2 = 1\n    t3 = x.2 - 1\n    \
+             x.2 = t3\n    return x\nend\n\n"
+        );
+    }
+
+    #[test]
+    fn lowers_control_flow_and_calls() {
+        let (ir, _) = lower(
+            "int f(int a, int b) { while (a < b && b != 0) { a = a + 1; } return a; }\nint main() { return f(1, 2); }",
+        );
+        // 条件取反后跳出循环，&& 的两个条件各自跳转；参数从右向左给出
+        let expected = "function f(a, b):\nL1:\n    if a >= b goto L2\n    if b == 0 goto L2\n    t1 = a + 1\n    \
+                        a = t1\n    goto L1\nL2:\n    return a\nend\n\n\
+                        function main():\n    param 2\n    param 1\n    t1 = call f, 2\n    return t1\nend\n\n";
+        assert!(ir.ends_with(expected), "{}", ir);
+    }
+
+    #[test]
+    fn halts_on_top_level_return() {
+        let (ir, codes) = lower("int i = 0; while (1) { i = i + 1; if (i > 3) return 0; }");
+        assert_eq!(codes, ["E0411"]);
+        assert!(!ir.contains("return"), "{}", ir);
+        assert_eq!(ir.matches("halt").count(), 2);
+    }
+
+    #[test]
+    fn truncates_float_literals_with_a_warning() {
+        let (ir, codes) = lower("int main() { int x = 2.7; return x; }");
+        // W0404 在字面量处提示截断，W0403 提示 float 到 int 的窄化
+        assert_eq!(codes, ["W0404", "W0403"]);
+        assert!(ir.contains("t1 = (int) 2\n"), "{}", ir);
+    }
+
+    #[test]
+    fn adds_missing_returns() {
+        let (ir, _) = lower("void f() { }\nint main() { f(); return 0; }");
+        assert!(ir.contains("function f():\n    return\nend"), "{}", ir);
+    }
+}
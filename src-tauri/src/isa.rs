@@ -0,0 +1,327 @@
+use crate::interrupt::Fault;
+use crate::memory::CODE_BASE;
+use crate::mmu::Mmu;
+use crate::types::{
+    Architecture, BranchKind, FlagsState, Instruction, InstructionType, InterruptEvent, MemoryState, StackFrame,
+    StackSlot, StackSlotKind,
+};
+use crate::x86::{eflags, flags_from};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+/// 栈顶初始地址，栈向低地址增长
+pub const STACK_TOP: i64 = 0x8000;
+/// 栈视图最多显示的双字个数
+pub const STACK_VIEW_LIMIT: u64 = 1024;
+
+/// 寄存器的引用：通用寄存器按编号，特殊寄存器（如 MIPS 的 HI/LO）按其在描述中的序号
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    General(usize),
+    Special(usize),
+}
+
+/// 访存阶段要完成的操作，size 为字节数（1、2、4）
+#[derive(Debug, Clone, PartialEq)]
+pub enum MemoryOperation {
+    /// 读取内存并写回寄存器，signed 表示符号扩展
+    Load {
+        address: u64,
+        size: u8,
+        signed: bool,
+        destination: Register,
+    },
+    Store {
+        address: u64,
+        size: u8,
+        value: i32,
+    },
+    /// 连续写入多个字节，用于一次压入多个双字（PUSHAD、ENTER）
+    StoreBytes { address: u64, bytes: Vec<u8> },
+}
+
+/// 控制转移：目标指令下标，delayed 表示先执行延迟槽中的下一条指令
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Branch {
+    pub target: usize,
+    pub delayed: bool,
+}
+
+/// 指令请求的中断操作，由模拟器按中断描述符表完成
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterruptRequest {
+    /// 软件中断 INT n
+    Raise(u8),
+    /// 从处理程序返回（IRETD、mret、eret）：恢复返回地址与标志位，中断现场的位置见 [`Isa::interrupt_registers`]
+    Return,
+}
+
+/// 指令读写的寄存器与标志位，用于流水线的冒险检测
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RegisterUsage {
+    pub reads: Vec<Register>,
+    pub writes: Vec<Register>,
+    pub reads_flags: bool,
+    pub writes_flags: bool,
+}
+
+impl RegisterUsage {
+    /// 只涉及通用寄存器的读写
+    pub fn general(reads: &[usize], writes: &[usize]) -> Self {
+        Self {
+            reads: reads.iter().map(|number| Register::General(*number)).collect(),
+            writes: writes.iter().map(|number| Register::General(*number)).collect(),
+            ..Default::default()
+        }
+    }
+}
+
+/// 执行阶段的结果：访存阶段与写回阶段按此完成指令
+#[derive(Debug, Clone, Default)]
+pub struct Execution {
+    pub message: String,
+    /// 写回阶段提交的寄存器值
+    pub writes: Vec<(Register, i32)>,
+    /// 写回阶段提交的标志位
+    pub flags: Option<FlagsState>,
+    pub memory: Option<MemoryOperation>,
+    pub branch: Option<Branch>,
+    /// 程序停止
+    pub halt: bool,
+    /// 执行阶段读取的内存（地址、字节数），访存阶段据此访问缓存
+    pub reads: Vec<(u64, u8)>,
+    /// 进入或返回中断处理程序，模拟器在执行阶段把它展开为保存、恢复中断现场，转移与标志位的修改
+    pub interrupt: Option<InterruptRequest>,
+    /// 本条指令引发的中断事件，随执行结果返回
+    pub event: Option<InterruptEvent>,
+}
+
+impl Execution {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self { message: message.into(), ..Default::default() }
+    }
+
+    pub fn write(mut self, register: Register, value: i32) -> Self {
+        self.writes.push((register, value));
+        self
+    }
+}
+
+/// 执行阶段可以看到的处理器状态（只读）
+pub struct Context<'a> {
+    pub registers: &'a [i32],
+    pub special: &'a [i32],
+    pub flags: &'a FlagsState,
+    /// 当前指令的下标与地址
+    pub index: usize,
+    pub address: u64,
+    pub labels: &'a HashMap<String, usize>,
+    pub memory: &'a MemoryState,
+    /// 内存操作数的虚拟地址经 MMU 转换后再读取
+    pub mmu: &'a Mmu,
+    /// read_memory 读取过的内存
+    pub reads: RefCell<Vec<(u64, u8)>>,
+}
+
+impl Context<'_> {
+    pub fn register(&self, number: usize) -> i32 {
+        self.registers.get(number).copied().unwrap_or(0)
+    }
+
+    pub fn special(&self, number: usize) -> i32 {
+        self.special.get(number).copied().unwrap_or(0)
+    }
+
+    /// 标签所在的指令下标
+    pub fn label(&self, name: &str) -> Option<usize> {
+        self.labels.get(name.trim()).copied()
+    }
+
+    /// 定长 4 字节指令的跳转目标：标签，或相对 base 的字节偏移
+    pub fn word_target(&self, operand: &str, base: i64) -> Option<usize> {
+        match crate::encoder::parse_number(operand) {
+            Some(offset) => Some((base + offset).max(0) as usize / 4),
+            None => self.label(operand),
+        }
+    }
+
+    /// 在执行阶段读取 size 字节（小端序，不要求对齐），用于 x86 的内存操作数
+    pub fn read_memory(&self, address: u64, size: u8) -> Result<u32, Fault> {
+        let value = self.mmu.read(self.memory, address, size)?;
+        self.reads.borrow_mut().push((address, size));
+        Ok(value as u32)
+    }
+
+    /// 栈中地址处的双字，label 为其位置
+    pub fn stack_slot(&self, address: u64, kind: StackSlotKind, label: String) -> StackSlot {
+        let value = self.mmu.read(self.memory, address, 4).unwrap_or(0) as i32 as i64;
+        StackSlot { address, value, kind, label }
+    }
+}
+
+/// RISC-V 与 MIPS 的访存指令同名：l/s 加上 b、h、w 表示宽度，u 表示零扩展
+pub fn access_size(mnemonic: &str) -> u8 {
+    match &mnemonic[1..] {
+        "b" | "bu" => 1,
+        "h" | "hu" => 2,
+        _ => 4,
+    }
+}
+
+/// 从定长 4 字节指令字反汇编出的一条指令。
+/// target 为跳转目标地址，以及目标不在某条指令起始处时使用的操作数写法。
+pub struct WordInstruction {
+    pub word: u32,
+    pub mnemonic: String,
+    pub operands: Vec<String>,
+    pub target: Option<(i64, String)>,
+}
+
+/// 为跳转目标生成标签 loc_XXXXXXXX，组成指令序列（编码字段由调用者重新编码填写）
+pub fn finish_disassembly(
+    words: Vec<WordInstruction>,
+    classify: impl Fn(&str) -> (InstructionType, u32),
+) -> Vec<Instruction> {
+    let base = CODE_BASE as i64;
+    let end = base + 4 * words.len() as i64;
+    let mut labels: HashMap<usize, String> = HashMap::new();
+    let mut listing = Vec::new();
+    for (i, word) in words.into_iter().enumerate() {
+        let mut operands = word.operands;
+        if let Some((target, fallback)) = word.target {
+            let operand = if target >= base && target < end && (target - base) % 4 == 0 {
+                labels.entry(((target - base) / 4) as usize).or_insert_with(|| format!("loc_{:08X}", target)).clone()
+            } else {
+                fallback
+            };
+            operands.push(operand);
+        }
+        let address = base + 4 * i as i64;
+        let (instruction_type, cycles) = classify(&word.mnemonic);
+        listing.push(Instruction {
+            id: format!("{}_{}", word.mnemonic, i),
+            instruction_type,
+            description: format!("{:08X}: {} {}", address, word.mnemonic, operands.join(", ")).trim_end().to_string(),
+            mnemonic: word.mnemonic,
+            operands,
+            machine_code: word.word.to_le_bytes().iter().map(|b| format!("{:02X}", b)).collect(),
+            cycles,
+            label: None,
+            encoding: Vec::new(),
+        });
+    }
+    for (index, label) in labels {
+        listing[index].label = Some(label);
+    }
+    listing
+}
+
+/// 把机器码切分为小端序的 32 位指令字
+pub fn words(bytes: &[u8]) -> Result<Vec<u32>, String> {
+    if !bytes.len().is_multiple_of(4) {
+        return Err(format!("机器码长度 {} 字节，不是 4 的倍数", bytes.len()));
+    }
+    Ok(bytes.chunks(4).map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]])).collect())
+}
+
+/// 指令集体系结构：描述寄存器文件，并提供译码、执行、编码与反汇编。
+/// 模拟器的流水线驱动只通过这个接口与具体指令集打交道。
+pub trait Isa: Send + 'static {
+    /// 译码阶段的结果，在执行阶段使用
+    type Decoded: Debug + Clone + Send;
+
+    fn architecture(&self) -> Architecture;
+
+    /// 通用寄存器的名称，下标即寄存器编号
+    fn register_names(&self) -> &'static [&'static str];
+
+    /// 特殊寄存器的名称（不含程序计数器）
+    fn special_register_names(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn program_counter_name(&self) -> &'static str;
+
+    /// 是否启用分支延迟槽
+    fn branch_delay_slot(&self) -> bool {
+        false
+    }
+
+    /// 栈指针的寄存器编号
+    fn stack_pointer(&self) -> usize;
+
+    /// 恒为 0 的寄存器编号
+    fn zero_register(&self) -> Option<usize> {
+        None
+    }
+
+    /// 帧指针的寄存器编号，复位时与栈指针一样指向栈顶
+    fn frame_pointer(&self) -> Option<usize> {
+        None
+    }
+
+    /// 访存是否要求地址按宽度对齐；x86 允许非对齐访问
+    fn aligned_access(&self) -> bool {
+        true
+    }
+
+    /// 载入程序；变长指令集据此确定每条指令的地址
+    fn load_program(&mut self, _instructions: &[Instruction]) {}
+
+    /// 第 index 条指令的地址
+    fn instruction_address(&self, index: usize) -> u64 {
+        4 * index as u64
+    }
+
+    /// 进入处理程序时保存返回地址与状态字的特殊寄存器 (pc, status)，如 RISC-V 的 mepc/mstatus、MIPS 的 EPC/Status；
+    /// None 表示与 x86 一样把返回地址与状态字作为中断帧压入栈中
+    fn interrupt_registers(&self) -> Option<(usize, usize)> {
+        None
+    }
+
+    /// 中断现场的状态字：flags 为进入处理程序前的标志位，enabled 为处理程序中是否允许中断。
+    /// 默认为 x86 的 EFLAGS
+    fn interrupt_status(&self, flags: &FlagsState, _enabled: bool) -> u32 {
+        eflags(flags)
+    }
+
+    /// 从处理程序返回时由状态字恢复标志位，同时给出返回后的状态字
+    fn restore_status(&self, status: u32, _flags: &FlagsState) -> (FlagsState, u32) {
+        (flags_from(status), status)
+    }
+
+    /// 地址对应的指令下标
+    fn instruction_index(&self, address: u64) -> usize {
+        (address / 4) as usize
+    }
+
+    fn decode(&self, instruction: &Instruction) -> Result<Self::Decoded, String>;
+
+    /// 执行一条指令；出错时给出异常的种类，由模拟器交给对应的处理程序
+    fn execute(&self, decoded: &Self::Decoded, context: &Context) -> Result<Execution, Fault>;
+
+    /// 指令读写的寄存器，包括隐含的操作数（如 PUSH 的 ESP）
+    fn register_usage(&self, decoded: &Self::Decoded) -> RegisterUsage;
+
+    /// 控制转移指令的类别，用于分支预测；其他指令为 None
+    fn branch_kind(&self, decoded: &Self::Decoded) -> Option<BranchKind>;
+
+    /// 为整段程序编码，填写机器码与编码字段
+    fn encode_program(&self, instructions: &mut [Instruction]) -> Result<(), (usize, String)>;
+
+    fn disassemble(&self, bytes: &[u8]) -> Result<Vec<Instruction>, String>;
+
+    /// 栈帧视图：栈顶到栈指针之间的双字按栈帧划分并标注用途，外层的帧在前。
+    /// 默认不区分栈帧，整个栈作为一帧，各双字按相对栈指针的位置标注
+    fn stack_frames(&self, context: &Context) -> Vec<StackFrame> {
+        let name = self.register_names()[self.stack_pointer()];
+        let sp = context.register(self.stack_pointer()) as u32 as u64;
+        let top = context.memory.stack_top();
+        let slots = (1..=(top.saturating_sub(sp) / 4).min(STACK_VIEW_LIMIT))
+            .map(|k| top - 4 * k)
+            .map(|address| context.stack_slot(address, StackSlotKind::Data, format!("[{}+{}]", name, address - sp)))
+            .collect();
+        vec![StackFrame { function: None, frame_pointer: None, return_address: None, slots }]
+    }
+}
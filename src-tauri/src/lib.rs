@@ -1,16 +1,40 @@
 mod types;
 mod compiler;
+mod semantic;
+mod ir;
+mod optimizer;
+mod regalloc;
+mod cfg;
+mod encoder;
+mod disassembler;
+mod assembler;
+mod isa;
+mod memory;
+mod mmu;
+mod interrupt;
+mod cache;
+mod pipeline;
+mod predictor;
+mod x86;
+mod riscv;
+mod riscv_codegen;
+mod mips;
+mod mips_codegen;
 mod cpu_simulator;
 
 use types::*;
-use compiler::{Lexer, Parser, CodeGenerator};
-use cpu_simulator::{CPUSimulator, ExecutionResult};
+use compiler::compile;
+use assembler::assemble;
+use cfg::build_cfg;
+use disassembler::parse_hex;
+use isa::Isa;
+use cpu_simulator::{create_simulator, ExecutionResult, Simulator};
 use std::sync::Mutex;
 use tauri::State;
 
 // 全局CPU模拟器状态
 struct AppState {
-    cpu_simulator: Mutex<CPUSimulator>,
+    cpu_simulator: Mutex<Box<dyn Simulator>>,
 }
 
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
@@ -20,32 +44,80 @@ fn greet(name: &str) -> String {
 }
 
 #[tauri::command]
-fn compile_code(source_code: String, _language: String) -> Result<CompilationResult, String> {
-    // 词法分析
-    let mut lexer = Lexer::new(source_code.clone());
-    let tokens = lexer.tokenize()?;
+fn compile_code(
+    source_code: String,
+    _language: String,
+    optimization_level: Option<u8>,
+    passes: Option<Vec<OptimizationPass>>,
+    architecture: Option<Architecture>,
+    branch_delay_slot: Option<bool>,
+) -> Result<CompilationResult, String> {
+    // 显式给出的优化遍优先于优化级别；都未给出时不优化
+    let passes = passes.unwrap_or_else(|| OptimizationPass::for_level(optimization_level.unwrap_or(0)));
+    let target = Target {
+        architecture: architecture.unwrap_or_default(),
+        branch_delay_slot: branch_delay_slot.unwrap_or(false),
+    };
+    // 编译错误通过 CompilationResult 中的诊断信息返回；未指定体系结构时生成 x86 代码
+    Ok(compile(&source_code, &passes, target))
+}
 
-    // 语法分析
-    let mut parser = Parser::new(tokens);
-    let ast = parser.parse()?;
+#[tauri::command]
+fn build_control_flow_graph(instructions: Vec<Instruction>) -> Result<ControlFlowGraph, String> {
+    Ok(build_cfg(&instructions))
+}
 
-    // 代码生成
-    let mut generator = CodeGenerator::new(ast);
-    let instructions = generator.generate()?;
+#[tauri::command]
+fn disassemble_machine_code(machine_code: String, architecture: Option<Architecture>) -> Result<Vec<Instruction>, String> {
+    let bytes = parse_hex(&machine_code)?;
+    match architecture.unwrap_or_default() {
+        Architecture::X86 => x86::X86::default().disassemble(&bytes),
+        Architecture::RiscV => riscv::RiscV.disassemble(&bytes),
+        Architecture::Mips => mips::Mips { branch_delay_slot: false }.disassemble(&bytes),
+    }
+}
 
-    Ok(CompilationResult {
-        success: true,
-        instructions,
-        errors: Vec::new(),
-        warnings: Vec::new(),
-        compilation_time: 150, // 模拟编译时间
-    })
+#[tauri::command]
+fn assemble_code(source_code: String) -> Result<AssemblyResult, String> {
+    // 汇编错误通过 AssemblyResult 中的诊断信息返回
+    Ok(assemble(&source_code))
 }
 
 #[tauri::command]
-fn load_instructions(instructions: Vec<Instruction>, state: State<AppState>) -> Result<(), String> {
+fn load_instructions(
+    instructions: Vec<Instruction>,
+    memory_image: Option<MemoryImage>,
+    architecture: Option<Architecture>,
+    branch_delay_slot: Option<bool>,
+    predictor: Option<PredictorKind>,
+    state: State<AppState>,
+) -> Result<(), String> {
     let mut simulator = state.cpu_simulator.lock().unwrap();
-    simulator.load_instructions(instructions);
+    // 体系结构与延迟槽设置须与编译时一致，改变时换用对应指令集的模拟器
+    let target = Target {
+        architecture: architecture.unwrap_or_default(),
+        branch_delay_slot: branch_delay_slot.unwrap_or(false),
+    };
+    if simulator.target() != target {
+        // 换用的模拟器沿用原来的栈、流水线、缓存、MMU 与定时器设置
+        let stack = simulator.state().memory.stack_segment().cloned();
+        let pipeline = simulator.pipeline_config();
+        let cache = simulator.cache_config();
+        let mmu = simulator.mmu_config();
+        let interrupts = simulator.interrupt_config();
+        *simulator = create_simulator(target);
+        if let Some(stack) = stack {
+            simulator.configure_stack(stack.base + stack.size, stack.size)?;
+        }
+        simulator.configure_pipeline(pipeline);
+        simulator.configure_cache(cache)?;
+        simulator.configure_mmu(mmu)?;
+        simulator.configure_interrupts(interrupts);
+    }
+    // 未指定分支预测器时静态预测不转移
+    simulator.set_predictor(predictor.unwrap_or_default());
+    simulator.load_instructions(instructions)?;
+    simulator.load_memory_image(memory_image.unwrap_or_default());
     Ok(())
 }
 
@@ -59,13 +131,123 @@ fn step_execution(state: State<AppState>) -> Result<ExecutionResult, String> {
 fn reset_cpu(state: State<AppState>) -> Result<CPUState, String> {
     let mut simulator = state.cpu_simulator.lock().unwrap();
     simulator.reset();
-    Ok(simulator.state.clone())
+    Ok(simulator.state().clone())
 }
 
 #[tauri::command]
 fn get_cpu_state(state: State<AppState>) -> Result<CPUState, String> {
     let simulator = state.cpu_simulator.lock().unwrap();
-    Ok(simulator.state.clone())
+    Ok(simulator.state().clone())
+}
+
+#[tauri::command]
+fn dump_memory(address: u64, length: u64, state: State<AppState>) -> Result<Vec<HexDumpLine>, String> {
+    let simulator = state.cpu_simulator.lock().unwrap();
+    // 每行 16 字节，一次最多转储 4096 字节
+    Ok(simulator.state().memory.dump(address, length))
+}
+
+#[tauri::command]
+fn configure_stack(stack_top: u64, stack_size: Option<u64>, state: State<AppState>) -> Result<CPUState, String> {
+    let mut simulator = state.cpu_simulator.lock().unwrap();
+    // 未给出大小时保持原来的栈大小；设置后模拟器复位
+    let stack_size = stack_size
+        .or_else(|| simulator.state().memory.stack_segment().map(|segment| segment.size))
+        .unwrap_or(memory::STACK_SIZE);
+    simulator.configure_stack(stack_top, stack_size)?;
+    Ok(simulator.state().clone())
+}
+
+#[tauri::command]
+fn get_stack_frames(state: State<AppState>) -> Result<Vec<StackFrame>, String> {
+    let simulator = state.cpu_simulator.lock().unwrap();
+    Ok(simulator.stack_frames())
+}
+
+#[tauri::command]
+fn configure_pipeline(enabled: bool, forwarding: Option<bool>, state: State<AppState>) -> Result<CPUState, String> {
+    let mut simulator = state.cpu_simulator.lock().unwrap();
+    // 启用后每次单步推进一个时钟周期；前递默认开启；设置后模拟器复位
+    simulator.configure_pipeline(PipelineConfig { enabled, forwarding: forwarding.unwrap_or(true) });
+    Ok(simulator.state().clone())
+}
+
+#[tauri::command]
+fn get_branch_statistics(state: State<AppState>) -> Result<BranchStatistics, String> {
+    let simulator = state.cpu_simulator.lock().unwrap();
+    Ok(simulator.branch_statistics())
+}
+
+#[tauri::command]
+fn configure_cache(config: CacheConfig, state: State<AppState>) -> Result<CPUState, String> {
+    let mut simulator = state.cpu_simulator.lock().unwrap();
+    // 设置后模拟器复位，各级缓存清空
+    simulator.configure_cache(config)?;
+    Ok(simulator.state().clone())
+}
+
+#[tauri::command]
+fn get_cache_view(state: State<AppState>) -> Result<CacheView, String> {
+    let simulator = state.cpu_simulator.lock().unwrap();
+    Ok(simulator.cache_view())
+}
+
+#[tauri::command]
+fn configure_mmu(config: MmuConfig, state: State<AppState>) -> Result<CPUState, String> {
+    let mut simulator = state.cpu_simulator.lock().unwrap();
+    // 设置后模拟器复位，页表重建为恒等映射，TLB 清空
+    simulator.configure_mmu(config)?;
+    Ok(simulator.state().clone())
+}
+
+#[tauri::command]
+fn map_page(
+    virtual_address: u64,
+    physical_address: u64,
+    permissions: PagePermissions,
+    state: State<AppState>,
+) -> Result<PageTableView, String> {
+    let mut simulator = state.cpu_simulator.lock().unwrap();
+    simulator.map_page(virtual_address, physical_address, permissions)?;
+    Ok(simulator.page_table())
+}
+
+#[tauri::command]
+fn unmap_page(virtual_address: u64, state: State<AppState>) -> Result<PageTableView, String> {
+    let mut simulator = state.cpu_simulator.lock().unwrap();
+    simulator.unmap_page(virtual_address)?;
+    Ok(simulator.page_table())
+}
+
+#[tauri::command]
+fn get_page_table(state: State<AppState>) -> Result<PageTableView, String> {
+    let simulator = state.cpu_simulator.lock().unwrap();
+    Ok(simulator.page_table())
+}
+
+#[tauri::command]
+fn configure_interrupts(config: InterruptConfig, state: State<AppState>) -> Result<CPUState, String> {
+    let mut simulator = state.cpu_simulator.lock().unwrap();
+    // 定时器间隔为 0 时关闭定时器中断；设置后模拟器复位
+    simulator.configure_interrupts(config);
+    Ok(simulator.state().clone())
+}
+
+#[tauri::command]
+fn set_interrupt_gate(
+    vector: u8,
+    gate: Option<InterruptGate>,
+    state: State<AppState>,
+) -> Result<InterruptTableView, String> {
+    let mut simulator = state.cpu_simulator.lock().unwrap();
+    simulator.set_interrupt_gate(vector, gate)?;
+    Ok(simulator.interrupt_table())
+}
+
+#[tauri::command]
+fn get_interrupt_table(state: State<AppState>) -> Result<InterruptTableView, String> {
+    let simulator = state.cpu_simulator.lock().unwrap();
+    Ok(simulator.interrupt_table())
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -73,15 +255,32 @@ pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .manage(AppState {
-            cpu_simulator: Mutex::new(CPUSimulator::new()),
+            cpu_simulator: Mutex::new(create_simulator(Target::default())),
         })
         .invoke_handler(tauri::generate_handler![
             greet,
             compile_code,
+            build_control_flow_graph,
+            disassemble_machine_code,
+            assemble_code,
             load_instructions,
             step_execution,
             reset_cpu,
-            get_cpu_state
+            get_cpu_state,
+            dump_memory,
+            configure_stack,
+            get_stack_frames,
+            configure_pipeline,
+            get_branch_statistics,
+            configure_cache,
+            get_cache_view,
+            configure_mmu,
+            map_page,
+            unmap_page,
+            get_page_table,
+            configure_interrupts,
+            set_interrupt_gate,
+            get_interrupt_table
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
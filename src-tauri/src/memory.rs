@@ -0,0 +1,287 @@
+use crate::interrupt::{Fault, IDT_BASE, IDT_SIZE};
+use crate::isa::STACK_TOP;
+use crate::types::{HexDumpLine, MemorySegment, MemoryState, SegmentKind};
+use std::collections::BTreeMap;
+
+/// 代码段的起始地址
+pub const CODE_BASE: u32 = 0;
+/// 数据段的起始地址：全局变量与字符串常量从这里开始分配，其下的 8 KiB 为代码段
+pub const DATA_BASE: u32 = 0x2000;
+/// 代码段的大小：代码段之后紧接着数据段
+pub const CODE_SIZE: u64 = (DATA_BASE - CODE_BASE) as u64;
+/// 堆的起始地址，位于数据段之后
+pub const HEAP_BASE: u64 = 0x4000;
+/// 堆的末尾，也是默认的栈底
+pub const HEAP_END: u64 = 0x6000;
+/// 默认的栈大小：栈从 STACK_TOP 向下增长到 HEAP_END
+pub const STACK_SIZE: u64 = STACK_TOP as u64 - HEAP_END;
+/// 十六进制转储每行的字节数
+const DUMP_WIDTH: u64 = 16;
+/// 一次转储的最大字节数
+const DUMP_LIMIT: u64 = 4096;
+
+impl Default for MemoryState {
+    fn default() -> Self {
+        Self::new(STACK_TOP as u64, STACK_SIZE)
+    }
+}
+
+/// 检查程序的大小：机器码须全部落在代码段内，否则会被截断或与数据段重叠
+pub fn check_code_size(length: u64) -> Result<(), String> {
+    if length > CODE_SIZE {
+        return Err(format!("程序的机器码共 {} 字节，超出了代码段的 {} 字节", length, CODE_SIZE));
+    }
+    Ok(())
+}
+
+/// 检查栈的配置：栈顶按 4 字节对齐且在 32 位地址空间内，栈 [栈顶 - 大小, 栈顶) 不与堆及中断描述符表重叠
+pub fn check_stack(stack_top: u64, stack_size: u64) -> Result<(), String> {
+    if stack_size == 0 || !stack_size.is_multiple_of(4) {
+        return Err(format!("栈的大小 {} 字节不是 4 的正整数倍", stack_size));
+    }
+    if !stack_top.is_multiple_of(4) || stack_top > u32::MAX as u64 {
+        return Err(format!("栈顶地址 0x{:X} 未按 4 字节对齐或超出 32 位地址空间", stack_top));
+    }
+    if stack_size > stack_top || stack_top - stack_size < HEAP_END {
+        return Err(format!(
+            "栈 [0x{:X}, 0x{:X}) 与堆重叠，栈底不能低于 0x{:X}",
+            stack_top.saturating_sub(stack_size),
+            stack_top,
+            HEAP_END
+        ));
+    }
+    if stack_top > IDT_BASE && stack_top - stack_size < IDT_BASE + IDT_SIZE {
+        return Err(format!(
+            "栈 [0x{:X}, 0x{:X}) 与中断描述符表 [0x{:X}, 0x{:X}) 重叠",
+            stack_top - stack_size,
+            stack_top,
+            IDT_BASE,
+            IDT_BASE + IDT_SIZE
+        ));
+    }
+    Ok(())
+}
+
+impl MemoryState {
+    /// 地址空间从低到高依次为代码段、数据段、堆、栈与中断描述符表，代码段只读；栈占据 [stack_top - stack_size, stack_top)
+    pub fn new(stack_top: u64, stack_size: u64) -> Self {
+        let segment = |kind, name: &str, base: u64, end: u64, writable| MemorySegment {
+            kind,
+            name: name.to_string(),
+            base,
+            size: end - base,
+            writable,
+        };
+        Self {
+            segments: vec![
+                segment(SegmentKind::Code, "代码段", CODE_BASE as u64, CODE_BASE as u64 + CODE_SIZE, false),
+                segment(SegmentKind::Data, "数据段", DATA_BASE as u64, HEAP_BASE, true),
+                segment(SegmentKind::Heap, "堆", HEAP_BASE, HEAP_END, true),
+                segment(SegmentKind::Stack, "栈", stack_top - stack_size, stack_top, true),
+                segment(SegmentKind::InterruptTable, "中断描述符表", IDT_BASE, IDT_BASE + IDT_SIZE, true),
+            ],
+            bytes: BTreeMap::new(),
+            stack: Vec::new(),
+        }
+    }
+
+    /// 地址所在的段
+    pub fn segment(&self, address: u64) -> Option<&MemorySegment> {
+        self.segments.iter().find(|segment| address >= segment.base && address - segment.base < segment.size)
+    }
+
+    pub fn stack_segment(&self) -> Option<&MemorySegment> {
+        self.segments.iter().find(|segment| segment.kind == SegmentKind::Stack)
+    }
+
+    /// 栈顶地址，即栈段的末尾
+    pub fn stack_top(&self) -> u64 {
+        self.stack_segment().map_or(STACK_TOP as u64, |segment| segment.base + segment.size)
+    }
+
+    /// 栈指针须位于 [栈底, 栈顶] 之内：低于栈底为栈溢出，高于栈顶为栈下溢
+    pub fn check_stack_pointer(&self, stack_pointer: u64) -> Result<(), Fault> {
+        let Some(segment) = self.stack_segment() else { return Ok(()) };
+        let top = segment.base + segment.size;
+        if stack_pointer < segment.base {
+            let message = format!("栈溢出，栈指针 0x{:X} 低于栈底 0x{:X}", stack_pointer, segment.base);
+            return Err(Fault::general_protection(message));
+        }
+        if stack_pointer > top {
+            return Err(Fault::general_protection(format!("栈下溢，栈指针 0x{:X} 高于栈顶 0x{:X}", stack_pointer, top)));
+        }
+        Ok(())
+    }
+
+    /// 检查一次 size 字节的访问：宽度合法、按需对齐、整个区间落在同一段内，写入时该段可写
+    fn check(&self, address: u64, size: u8, aligned: bool, write: bool) -> Result<(), Fault> {
+        if !matches!(size, 1 | 2 | 4 | 8) {
+            return Err(Fault::general_protection(format!("不支持 {} 字节的访存", size)));
+        }
+        if aligned && !address.is_multiple_of(size as u64) {
+            return Err(Fault::general_protection(format!("地址 0x{:X} 未按 {} 字节对齐", address, size)));
+        }
+        self.check_range(address, size as u64, write)
+    }
+
+    /// 区间 [address, address + length) 须落在同一段内，写入时该段可写
+    fn check_range(&self, address: u64, length: u64, write: bool) -> Result<(), Fault> {
+        let segment = self
+            .segment(address)
+            .ok_or_else(|| Fault::general_protection(format!("访问未映射的地址 0x{:X}", address)))?;
+        if address + length > segment.base + segment.size {
+            return Err(Fault::general_protection(format!(
+                "从地址 0x{:X} 开始的 {} 字节越过了{}的末尾",
+                address, length, segment.name
+            )));
+        }
+        if write && !segment.writable {
+            return Err(Fault::general_protection(format!("不能写入只读的{}（地址 0x{:X}）", segment.name, address)));
+        }
+        Ok(())
+    }
+
+    /// 读取一个字节，不做检查；未写入过的字节为 0
+    pub fn read_byte(&self, address: u64) -> u8 {
+        self.bytes.get(&address).copied().unwrap_or(0)
+    }
+
+    /// 写入一个字节，不做检查；只保存非零的字节
+    pub fn write_byte(&mut self, address: u64, value: u8) {
+        match value {
+            0 => self.bytes.remove(&address),
+            _ => self.bytes.insert(address, value),
+        };
+    }
+
+    /// 按小端序读取 1、2、4 或 8 字节；aligned 为真时要求地址按宽度对齐
+    pub fn read(&self, address: u64, size: u8, aligned: bool) -> Result<u64, Fault> {
+        self.check(address, size, aligned, false)?;
+        Ok((0..size as u64).fold(0, |value, i| value | (self.read_byte(address + i) as u64) << (8 * i)))
+    }
+
+    /// 按小端序写入 value 的低 size 字节
+    pub fn write(&mut self, address: u64, size: u8, value: u64, aligned: bool) -> Result<(), Fault> {
+        self.check(address, size, aligned, true)?;
+        for i in 0..size as u64 {
+            self.write_byte(address + i, (value >> (8 * i)) as u8);
+        }
+        Ok(())
+    }
+
+    /// 连续写入多个字节，先检查整个区间，出错时不写入任何字节
+    pub fn write_bytes(&mut self, address: u64, bytes: &[u8]) -> Result<(), Fault> {
+        self.check_range(address, bytes.len() as u64, true)?;
+        for (i, byte) in bytes.iter().enumerate() {
+            self.write_byte(address + i as u64, *byte);
+        }
+        Ok(())
+    }
+
+    /// 载入代码或数据的初始内容，不检查写权限；落在所有段之外的字节被忽略
+    pub fn fill(&mut self, address: u64, bytes: &[u8]) {
+        for (i, byte) in bytes.iter().enumerate() {
+            let address = address + i as u64;
+            if self.segment(address).is_some() {
+                self.write_byte(address, *byte);
+            }
+        }
+    }
+
+    /// 十六进制转储：从 address 所在的 16 字节行开始，覆盖 length 字节，每行附带可打印字符
+    pub fn dump(&self, address: u64, length: u64) -> Vec<HexDumpLine> {
+        let start = address - address % DUMP_WIDTH;
+        let end = address.saturating_add(length.min(DUMP_LIMIT));
+        (start..end)
+            .step_by(DUMP_WIDTH as usize)
+            .map(|line| {
+                let bytes: Vec<u8> =
+                    (line..line.saturating_add(DUMP_WIDTH)).map(|address| self.read_byte(address)).collect();
+                HexDumpLine {
+                    address: line,
+                    segment: self.segment(line).map(|segment| segment.kind),
+                    hex: bytes.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<_>>().join(" "),
+                    ascii: bytes
+                        .iter()
+                        .map(|byte| if byte.is_ascii_graphic() || *byte == b' ' { *byte as char } else { '.' })
+                        .collect(),
+                    bytes,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segments_are_laid_out_from_code_to_stack() {
+        let memory = MemoryState::default();
+        let kinds: Vec<_> = memory.segments.iter().map(|segment| segment.kind).collect();
+        assert_eq!(
+            kinds,
+            [SegmentKind::Code, SegmentKind::Data, SegmentKind::Heap, SegmentKind::Stack, SegmentKind::InterruptTable]
+        );
+        for pair in memory.segments.windows(2) {
+            assert!(pair[0].base + pair[0].size <= pair[1].base, "{} 与 {} 重叠", pair[0].name, pair[1].name);
+        }
+        assert_eq!(memory.segment(DATA_BASE as u64 - 1).map(|segment| segment.kind), Some(SegmentKind::Code));
+        assert_eq!(memory.segment(DATA_BASE as u64).map(|segment| segment.kind), Some(SegmentKind::Data));
+        assert_eq!(memory.stack_top(), STACK_TOP as u64);
+    }
+
+    #[test]
+    fn reads_and_writes_are_little_endian() {
+        let mut memory = MemoryState::default();
+        let address = DATA_BASE as u64 + 1;
+        memory.write(address, 8, 0x1122_3344_5566_7788, false).unwrap();
+        assert_eq!(memory.read(address, 8, false).unwrap(), 0x1122_3344_5566_7788);
+        assert_eq!(memory.read(address, 2, false).unwrap(), 0x7788);
+        assert_eq!(memory.read_byte(address + 7), 0x11);
+        memory.write(address + 7, 1, 0, false).unwrap();
+        assert!(!memory.bytes.contains_key(&(address + 7)));
+    }
+
+    #[test]
+    fn rejects_bad_accesses() {
+        let mut memory = MemoryState::default();
+        assert!(memory.write(CODE_BASE as u64, 4, 1, false).is_err());
+        assert!(memory.write(DATA_BASE as u64 + 1, 4, 1, true).is_err());
+        assert!(memory.read(DATA_BASE as u64, 3, false).is_err());
+        assert!(memory.read(HEAP_BASE - 2, 4, false).is_err());
+        assert!(memory.read(IDT_BASE + IDT_SIZE, 4, false).is_err());
+        assert!(memory.write_bytes(HEAP_BASE - 2, b"abcd").is_err());
+        assert_eq!(memory.read_byte(HEAP_BASE - 2), 0);
+        // 载入程序时可以写入只读的代码段
+        memory.fill(CODE_BASE as u64, &[0x90, 0xF4]);
+        assert_eq!(memory.read(CODE_BASE as u64, 2, false).unwrap(), 0xF490);
+    }
+
+    #[test]
+    fn checks_program_and_stack_sizes() {
+        assert!(check_code_size(CODE_SIZE).is_ok());
+        assert!(check_code_size(CODE_SIZE + 1).is_err());
+        assert!(check_stack(STACK_TOP as u64, STACK_SIZE).is_ok());
+        assert!(check_stack(STACK_TOP as u64, STACK_SIZE + 4).is_err());
+        assert!(check_stack(STACK_TOP as u64, 6).is_err());
+        assert!(check_stack(IDT_BASE + 0x100, 0x1000).is_err());
+        let memory = MemoryState::default();
+        assert!(memory.check_stack_pointer(STACK_TOP as u64).is_ok());
+        assert!(memory.check_stack_pointer(HEAP_END - 4).is_err());
+        assert!(memory.check_stack_pointer(STACK_TOP as u64 + 4).is_err());
+    }
+
+    #[test]
+    fn dumps_whole_lines() {
+        let mut memory = MemoryState::default();
+        memory.fill(DATA_BASE as u64 + 0x10, b"Hello\n");
+        let lines = memory.dump(DATA_BASE as u64 + 0x15, 20);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].address, DATA_BASE as u64 + 0x10);
+        assert_eq!(lines[0].segment, Some(SegmentKind::Data));
+        assert!(lines[0].hex.starts_with("48 65 6C 6C 6F 0A 00"));
+        assert!(lines[0].ascii.starts_with("Hello."));
+    }
+}
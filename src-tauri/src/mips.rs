@@ -0,0 +1,942 @@
+use crate::encoder::parse_number;
+use crate::interrupt::Fault;
+use crate::isa::{
+    self, Branch, Context, Execution, InterruptRequest, Isa, MemoryOperation, Register, RegisterUsage, WordInstruction,
+};
+use crate::memory::CODE_BASE;
+use crate::types::{Architecture, BranchKind, EncodingField, FlagsState, Instruction, InstructionType};
+use std::collections::HashMap;
+
+/// 寄存器在模拟器状态中的名称 $0–$31
+pub const REGISTERS: [&str; 32] = [
+    "$0", "$1", "$2", "$3", "$4", "$5", "$6", "$7", "$8", "$9", "$10", "$11", "$12", "$13", "$14", "$15", "$16", "$17",
+    "$18", "$19", "$20", "$21", "$22", "$23", "$24", "$25", "$26", "$27", "$28", "$29", "$30", "$31",
+];
+
+/// 乘除法结果寄存器与保存中断现场的协处理器 0 寄存器，下标即特殊寄存器编号
+pub const SPECIAL_REGISTERS: [&str; 4] = ["HI", "LO", "Status", "EPC"];
+const HI: usize = 0;
+const LO: usize = 1;
+const STATUS: usize = 2;
+const EPC: usize = 3;
+/// Status 中的 IE（允许中断）与 EXL（处于异常处理中，屏蔽中断）
+const IE: u32 = 1;
+const EXL: u32 = 1 << 1;
+
+/// 寄存器的约定名称，下标即寄存器编号 $0–$31
+pub const REGISTER_NAMES: [&str; 32] = [
+    "zero", "at", "v0", "v1", "a0", "a1", "a2", "a3", "t0", "t1", "t2", "t3", "t4", "t5", "t6", "t7", "s0", "s1", "s2",
+    "s3", "s4", "s5", "s6", "s7", "t8", "t9", "k0", "k1", "gp", "sp", "fp", "ra",
+];
+
+/// MIPS32 的指令格式（按操作数的写法细分）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// rd, rs, rt
+    R,
+    /// rd, rt, sa
+    Shift,
+    /// rd, rt, rs
+    ShiftVariable,
+    /// rs, rt，结果写入 HI/LO
+    MulDiv,
+    /// rd ← HI/LO
+    MoveFrom,
+    /// rs
+    JumpRegister,
+    /// [rd,] rs
+    JumpLinkRegister,
+    /// 无操作数
+    System,
+    /// rt, rs, 有符号 16 位立即数
+    Immediate,
+    /// rt, rs, 无符号 16 位立即数
+    LogicImmediate,
+    /// rt, 16 位立即数
+    Lui,
+    /// rt, offset(base)
+    Load,
+    Store,
+    /// rs, rt, label
+    Branch,
+    /// rs, label
+    BranchZero,
+    /// label
+    Jump,
+    /// rt, 协处理器 0 的寄存器
+    Coprocessor,
+}
+
+impl Format {
+    /// R 型指令的 opcode 为 0，由 funct 区分
+    pub fn is_r_type(&self) -> bool {
+        matches!(
+            self,
+            Format::R
+                | Format::Shift
+                | Format::ShiftVariable
+                | Format::MulDiv
+                | Format::MoveFrom
+                | Format::JumpRegister
+                | Format::JumpLinkRegister
+                | Format::System
+        )
+    }
+}
+
+/// MIPS32 指令表：(助记符, 格式, opcode, R 型为 funct／bltz、bgez 为 rt 字段／mfc0、mtc0 为 rs 字段)
+pub const INSTRUCTIONS: [(&str, Format, u32, u32); 54] = [
+    ("add", Format::R, 0, 0x20),
+    ("addu", Format::R, 0, 0x21),
+    ("sub", Format::R, 0, 0x22),
+    ("subu", Format::R, 0, 0x23),
+    ("and", Format::R, 0, 0x24),
+    ("or", Format::R, 0, 0x25),
+    ("xor", Format::R, 0, 0x26),
+    ("nor", Format::R, 0, 0x27),
+    ("slt", Format::R, 0, 0x2A),
+    ("sltu", Format::R, 0, 0x2B),
+    ("sll", Format::Shift, 0, 0x00),
+    ("srl", Format::Shift, 0, 0x02),
+    ("sra", Format::Shift, 0, 0x03),
+    ("sllv", Format::ShiftVariable, 0, 0x04),
+    ("srlv", Format::ShiftVariable, 0, 0x06),
+    ("srav", Format::ShiftVariable, 0, 0x07),
+    ("mult", Format::MulDiv, 0, 0x18),
+    ("multu", Format::MulDiv, 0, 0x19),
+    ("div", Format::MulDiv, 0, 0x1A),
+    ("divu", Format::MulDiv, 0, 0x1B),
+    ("mfhi", Format::MoveFrom, 0, 0x10),
+    ("mflo", Format::MoveFrom, 0, 0x12),
+    ("jr", Format::JumpRegister, 0, 0x08),
+    ("jalr", Format::JumpLinkRegister, 0, 0x09),
+    ("nop", Format::System, 0, 0x00),
+    ("syscall", Format::System, 0, 0x0C),
+    ("break", Format::System, 0, 0x0D),
+    ("eret", Format::System, 0x10, 0x18),
+    ("addi", Format::Immediate, 0x08, 0),
+    ("addiu", Format::Immediate, 0x09, 0),
+    ("slti", Format::Immediate, 0x0A, 0),
+    ("sltiu", Format::Immediate, 0x0B, 0),
+    ("andi", Format::LogicImmediate, 0x0C, 0),
+    ("ori", Format::LogicImmediate, 0x0D, 0),
+    ("xori", Format::LogicImmediate, 0x0E, 0),
+    ("lui", Format::Lui, 0x0F, 0),
+    ("lb", Format::Load, 0x20, 0),
+    ("lh", Format::Load, 0x21, 0),
+    ("lw", Format::Load, 0x23, 0),
+    ("lbu", Format::Load, 0x24, 0),
+    ("lhu", Format::Load, 0x25, 0),
+    ("sb", Format::Store, 0x28, 0),
+    ("sh", Format::Store, 0x29, 0),
+    ("sw", Format::Store, 0x2B, 0),
+    ("beq", Format::Branch, 0x04, 0),
+    ("bne", Format::Branch, 0x05, 0),
+    ("blez", Format::BranchZero, 0x06, 0),
+    ("bgtz", Format::BranchZero, 0x07, 0),
+    ("bltz", Format::BranchZero, 0x01, 0),
+    ("bgez", Format::BranchZero, 0x01, 1),
+    ("j", Format::Jump, 0x02, 0),
+    ("jal", Format::Jump, 0x03, 0),
+    ("mfc0", Format::Coprocessor, 0x10, 0x00),
+    ("mtc0", Format::Coprocessor, 0x10, 0x04),
+];
+
+/// 查找指令的格式与编码常量
+pub fn lookup(mnemonic: &str) -> Option<(Format, u32, u32)> {
+    let mnemonic = mnemonic.to_lowercase();
+    INSTRUCTIONS
+        .iter()
+        .find(|(name, ..)| *name == mnemonic)
+        .map(|&(_, format, opcode, function)| (format, opcode, function))
+}
+
+/// 寄存器编号：接受 $0–$31 与约定名称，$ 可以省略，$s8 是 $fp 的别名
+pub fn register_number(name: &str) -> Option<usize> {
+    let name = name.trim().to_lowercase();
+    let name = name.strip_prefix('$').unwrap_or(&name);
+    if name == "s8" {
+        return Some(30);
+    }
+    if let Ok(number) = name.parse::<usize>() {
+        return (number < 32).then_some(number);
+    }
+    REGISTER_NAMES.iter().position(|register| *register == name)
+}
+
+/// 协处理器 0 的寄存器：接受编号 $12、$14 与名称 Status、EPC，返回 (编号, 特殊寄存器下标)
+pub fn coprocessor_register(name: &str) -> Option<(u32, usize)> {
+    let name = name.trim().to_lowercase();
+    match name.strip_prefix('$').unwrap_or(&name) {
+        "12" | "status" => Some((12, STATUS)),
+        "14" | "epc" => Some((14, EPC)),
+        _ => None,
+    }
+}
+
+/// 解析 "offset($base)" 形式的内存操作数
+pub fn parse_memory(text: &str) -> Option<(i64, usize)> {
+    let (offset, base) = text.trim().strip_suffix(')')?.split_once('(')?;
+    let offset = if offset.trim().is_empty() { 0 } else { parse_number(offset)? };
+    Some((offset, register_number(base)?))
+}
+
+/// 指令类别与周期估计：乘法 3 个周期，除法 20 个周期，访存 2 个周期，其余 1 个周期
+pub fn classify(mnemonic: &str) -> (InstructionType, u32) {
+    match lookup(mnemonic).map(|(format, ..)| format) {
+        Some(Format::Load | Format::Store) => (InstructionType::Memory, 2),
+        Some(Format::MulDiv) if mnemonic.starts_with("div") => (InstructionType::Arithmetic, 20),
+        Some(Format::MulDiv) => (InstructionType::Arithmetic, 3),
+        Some(Format::MoveFrom | Format::Lui | Format::Coprocessor) => (InstructionType::DataTransfer, 1),
+        Some(
+            Format::Branch | Format::BranchZero | Format::Jump | Format::JumpRegister | Format::JumpLinkRegister | Format::System,
+        ) => (InstructionType::Control, 1),
+        Some(Format::Shift | Format::ShiftVariable | Format::LogicImmediate) => (InstructionType::Logic, 1),
+        _ => match mnemonic {
+            "and" | "or" | "xor" | "nor" => (InstructionType::Logic, 1),
+            _ => (InstructionType::Arithmetic, 1),
+        },
+    }
+}
+
+/// 整数运算指令的语义（立即数版本与寄存器版本相同），按 32 位补码计算。
+/// add/addi 溢出时返回 None，由调用者报告溢出异常
+pub fn alu(mnemonic: &str, a: i32, b: i32) -> Option<i32> {
+    let shift = (b & 0x1F) as u32;
+    let operation = match mnemonic {
+        "sltiu" => "sltu",
+        "addiu" => "addu",
+        "sllv" | "srlv" | "srav" => &mnemonic[..3],
+        _ => mnemonic.strip_suffix('i').unwrap_or(mnemonic),
+    };
+    let value = match operation {
+        "add" => a.checked_add(b)?,
+        "addu" => a.wrapping_add(b),
+        "sub" => a.checked_sub(b)?,
+        "subu" => a.wrapping_sub(b),
+        "and" => a & b,
+        "or" => a | b,
+        "xor" => a ^ b,
+        "nor" => !(a | b),
+        "slt" => (a < b) as i32,
+        "sltu" => ((a as u32) < (b as u32)) as i32,
+        "sll" => a.wrapping_shl(shift),
+        "srl" => ((a as u32) >> shift) as i32,
+        "sra" => a >> shift,
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// 乘除法的结果 (HI, LO)：乘法为 64 位积的高低 32 位，除法为 (余数, 商)；除数为 0 时返回 None
+pub fn multiply_divide(mnemonic: &str, a: i32, b: i32) -> Option<(i32, i32)> {
+    match mnemonic {
+        "mult" => {
+            let product = a as i64 * b as i64;
+            Some(((product >> 32) as i32, product as i32))
+        }
+        "multu" => {
+            let product = a as u32 as u64 * b as u32 as u64;
+            Some(((product >> 32) as i32, product as i32))
+        }
+        "div" if b != 0 => Some((a.wrapping_rem(b), a.wrapping_div(b))),
+        "divu" if b != 0 => Some((((a as u32) % (b as u32)) as i32, ((a as u32) / (b as u32)) as i32)),
+        _ => None,
+    }
+}
+
+/// 条件分支是否成立，与零比较的分支忽略 b
+pub fn branch_taken(mnemonic: &str, a: i32, b: i32) -> Option<bool> {
+    let taken = match mnemonic {
+        "beq" => a == b,
+        "bne" => a != b,
+        "blez" => a <= 0,
+        "bgtz" => a > 0,
+        "bltz" => a < 0,
+        "bgez" => a >= 0,
+        _ => return None,
+    };
+    Some(taken)
+}
+
+fn register(text: &str) -> Result<u32, String> {
+    register_number(text)
+        .map(|number| number as u32)
+        .ok_or_else(|| format!("无法识别的寄存器 '{}'", text.trim()))
+}
+
+fn immediate(text: &str, min: i64, max: i64) -> Result<i64, String> {
+    let value = parse_number(text).ok_or_else(|| format!("无法解析立即数 '{}'", text.trim()))?;
+    if value < min || value > max {
+        return Err(format!("立即数 {} 超出范围 [{}, {}]", value, min, max));
+    }
+    Ok(value)
+}
+
+/// 描述一个位段：十六进制值、二进制位与含义
+fn field(name: &str, value: u32, width: usize, meaning: String) -> EncodingField {
+    EncodingField {
+        field: name.to_string(),
+        hex: format!("{:X}", value),
+        description: format!("{:0width$b} {}", value, meaning, width = width).trim_end().to_string(),
+    }
+}
+
+fn register_field(name: &str, number: u32) -> EncodingField {
+    field(name, number, 5, format!("${} (${})", number, REGISTER_NAMES[number as usize]))
+}
+
+/// 把一条 MIPS32 指令编码为 32 位指令字，同时给出各位段（从高位到低位）。
+/// target 返回标签的地址，找不到的标签视为外部符号，偏移为 0。
+pub fn encode(
+    mnemonic: &str,
+    operands: &[String],
+    address: i64,
+    target: &dyn Fn(&str) -> Option<i64>,
+) -> Result<(u32, Vec<EncodingField>), String> {
+    let (format, opcode, function) =
+        lookup(mnemonic).ok_or_else(|| format!("无法识别的 MIPS32 指令 '{}'", mnemonic))?;
+    let expected = match format {
+        Format::R | Format::Shift | Format::ShiftVariable | Format::Immediate | Format::LogicImmediate | Format::Branch => 3,
+        Format::MulDiv | Format::Lui | Format::Load | Format::Store | Format::BranchZero | Format::Coprocessor => 2,
+        Format::MoveFrom | Format::JumpRegister | Format::Jump => 1,
+        Format::JumpLinkRegister => operands.len().clamp(1, 2),
+        Format::System => 0,
+    };
+    if operands.len() != expected {
+        return Err(format!("{} 需要 {} 个操作数", mnemonic, expected));
+    }
+    let mnemonic = mnemonic.to_lowercase();
+    let label_note = |label: &str| match target(label) {
+        Some(destination) => (destination, format!("→ {}", label)),
+        None => (address + 4, format!("→ {}（外部符号，链接时重定位）", label)),
+    };
+
+    if format.is_r_type() {
+        // opcode | rs | rt | rd | shamt | funct
+        let (rs, rt, rd, shamt) = match format {
+            Format::R => (register(&operands[1])?, register(&operands[2])?, register(&operands[0])?, 0),
+            Format::Shift => (0, register(&operands[1])?, register(&operands[0])?, immediate(&operands[2], 0, 31)? as u32),
+            Format::ShiftVariable => (register(&operands[2])?, register(&operands[1])?, register(&operands[0])?, 0),
+            Format::MulDiv => (register(&operands[0])?, register(&operands[1])?, 0, 0),
+            Format::MoveFrom => (0, 0, register(&operands[0])?, 0),
+            Format::JumpRegister => (register(&operands[0])?, 0, 0, 0),
+            Format::JumpLinkRegister if operands.len() == 1 => (register(&operands[0])?, 0, 31, 0),
+            Format::JumpLinkRegister => (register(&operands[1])?, 0, register(&operands[0])?, 0),
+            // eret 属于协处理器 0，rs 字段的最高位为 CO 位
+            Format::System if opcode != 0 => (0x10, 0, 0, 0),
+            _ => (0, 0, 0, 0),
+        };
+        let word = (opcode << 26) | (rs << 21) | (rt << 16) | (rd << 11) | (shamt << 6) | function;
+        let fields = vec![
+            field("opcode", opcode, 6, if opcode == 0 { "R 型" } else { "COP0" }.to_string()),
+            register_field("rs", rs),
+            register_field("rt", rt),
+            register_field("rd", rd),
+            field("shamt", shamt, 5, format!("移位量 {}", shamt)),
+            field("funct", function, 6, mnemonic.clone()),
+        ];
+        return Ok((word, fields));
+    }
+
+    let opcode_field = field("opcode", opcode, 6, mnemonic.clone());
+    if format == Format::Coprocessor {
+        // opcode | rs（0 为 mfc0，4 为 mtc0）| rt | rd | 0
+        let (rd, index) = coprocessor_register(&operands[1])
+            .ok_or_else(|| format!("不支持的协处理器 0 寄存器 '{}'", operands[1].trim()))?;
+        let rt = register(&operands[0])?;
+        let word = (opcode << 26) | (function << 21) | (rt << 16) | (rd << 11);
+        let fields = vec![
+            field("opcode", opcode, 6, "COP0".to_string()),
+            field("rs", function, 5, mnemonic.clone()),
+            register_field("rt", rt),
+            field("rd", rd, 5, SPECIAL_REGISTERS[index].to_string()),
+            field("zero", 0, 11, String::new()),
+        ];
+        return Ok((word, fields));
+    }
+    if format == Format::Jump {
+        // 26 位字地址，与 PC+4 的高 4 位拼接
+        let (destination, note) = match parse_number(&operands[0]) {
+            Some(destination) => (destination, String::new()),
+            None => label_note(operands[0].trim()),
+        };
+        let index = ((destination as u32) >> 2) & 0x3FF_FFFF;
+        let word = (opcode << 26) | index;
+        let fields = vec![opcode_field, field("target", index, 26, format!("地址 0x{:08X} {}", destination, note))];
+        return Ok((word, fields));
+    }
+
+    // I 型：opcode | rs | rt | imm16
+    let (rs, rt, imm, meaning) = match format {
+        Format::Immediate => {
+            let imm = immediate(&operands[2], -32768, 32767)?;
+            (register(&operands[1])?, register(&operands[0])?, imm, format!("= {}（符号扩展）", imm))
+        }
+        Format::LogicImmediate => {
+            let imm = immediate(&operands[2], 0, 0xFFFF)?;
+            (register(&operands[1])?, register(&operands[0])?, imm, format!("= {}（零扩展）", imm))
+        }
+        Format::Lui => {
+            let imm = immediate(&operands[1], 0, 0xFFFF)?;
+            (0, register(&operands[0])?, imm, format!("装入高 16 位 0x{:04X}", imm))
+        }
+        Format::Load | Format::Store => {
+            let (offset, base) =
+                parse_memory(&operands[1]).ok_or_else(|| format!("无法解析内存操作数 '{}'", operands[1]))?;
+            if !(-32768..=32767).contains(&offset) {
+                return Err(format!("偏移 {} 超出 16 位范围", offset));
+            }
+            (base as u32, register(&operands[0])?, offset, format!("偏移 = {}", offset))
+        }
+        _ => {
+            // 分支偏移以字为单位，相对于延迟槽（PC+4）
+            let (rs, rt, label) = match format {
+                Format::Branch => (register(&operands[0])?, register(&operands[1])?, operands[2].trim()),
+                _ => (register(&operands[0])?, function, operands[1].trim()),
+            };
+            let (offset, note) = match parse_number(label) {
+                Some(offset) => (offset, String::new()),
+                None => {
+                    let (destination, note) = label_note(label);
+                    (destination - address - 4, note)
+                }
+            };
+            if offset % 4 != 0 || !(-(1 << 17)..(1 << 17)).contains(&offset) {
+                return Err(format!("分支偏移 {} 超出范围或不是 4 的倍数", offset));
+            }
+            (rs, rt, offset >> 2, format!("偏移 {} 字 {}", offset >> 2, note))
+        }
+    };
+    let imm = imm as u32 & 0xFFFF;
+    let word = (opcode << 26) | (rs << 21) | (rt << 16) | imm;
+    let fields = vec![
+        opcode_field,
+        register_field("rs", rs),
+        register_field("rt", rt),
+        field("immediate", imm, 16, meaning),
+    ];
+    Ok((word, fields))
+}
+
+/// 为整段程序编码：每条指令 4 字节，按小端序（与 x86、RISC-V 共用同一内存模型）存放
+pub fn encode_program(instructions: &mut [Instruction]) -> Result<(), (usize, String)> {
+    let labels: HashMap<String, i64> = instructions
+        .iter()
+        .enumerate()
+        .filter_map(|(i, instruction)| {
+            instruction
+                .label
+                .clone()
+                .map(|label| (label, CODE_BASE as i64 + 4 * i as i64))
+        })
+        .collect();
+    let target = |label: &str| labels.get(label).copied();
+
+    for (i, instruction) in instructions.iter_mut().enumerate() {
+        let address = CODE_BASE as i64 + 4 * i as i64;
+        let (word, mut fields) =
+            encode(&instruction.mnemonic, &instruction.operands, address, &target).map_err(|message| (i, message))?;
+        fields.insert(
+            0,
+            EncodingField {
+                field: "指令字".to_string(),
+                hex: format!("{:08X}", word),
+                description: "32 位指令字，在内存中按小端序存放".to_string(),
+            },
+        );
+        instruction.machine_code = word.to_le_bytes().iter().map(|b| format!("{:02X}", b)).collect();
+        instruction.encoding = fields;
+    }
+    Ok(())
+}
+
+/// 译码后的 MIPS32 指令：rd 为写入结果的寄存器（I 型指令中即 rt 字段）
+#[derive(Debug, Clone)]
+pub struct Decoded {
+    pub mnemonic: String,
+    pub format: Format,
+    pub rd: usize,
+    pub rs: usize,
+    pub rt: usize,
+    pub imm: i32,
+    /// 标签，或分支相对延迟槽的字节偏移、跳转的绝对地址
+    pub target: Option<String>,
+}
+
+/// MIPS32：$0 恒为 0，$29 ($sp) 为栈指针，可选分支延迟槽；中断现场保存在 EPC 与 Status 中
+pub struct Mips {
+    pub branch_delay_slot: bool,
+}
+
+impl Isa for Mips {
+    type Decoded = Decoded;
+
+    fn architecture(&self) -> Architecture {
+        Architecture::Mips
+    }
+
+    fn register_names(&self) -> &'static [&'static str] {
+        &REGISTERS
+    }
+
+    fn special_register_names(&self) -> &'static [&'static str] {
+        &SPECIAL_REGISTERS
+    }
+
+    fn program_counter_name(&self) -> &'static str {
+        "PC"
+    }
+
+    fn stack_pointer(&self) -> usize {
+        29
+    }
+
+    fn zero_register(&self) -> Option<usize> {
+        Some(0)
+    }
+
+    fn branch_delay_slot(&self) -> bool {
+        self.branch_delay_slot
+    }
+
+    fn interrupt_registers(&self) -> Option<(usize, usize)> {
+        Some((EPC, STATUS))
+    }
+
+    /// IE 保持进入前的值，处理程序中关中断时置 EXL
+    fn interrupt_status(&self, flags: &FlagsState, enabled: bool) -> u32 {
+        (u32::from(flags.interrupt) * IE) | (u32::from(!enabled) * EXL)
+    }
+
+    /// eret 清除 EXL，按 IE 恢复中断允许
+    fn restore_status(&self, status: u32, flags: &FlagsState) -> (FlagsState, u32) {
+        (FlagsState { interrupt: status & IE != 0, ..flags.clone() }, status & !EXL)
+    }
+
+    fn decode(&self, instruction: &Instruction) -> Result<Decoded, String> {
+        let mnemonic = instruction.mnemonic.to_lowercase();
+        let (format, ..) =
+            lookup(&mnemonic).ok_or_else(|| format!("无法识别的 MIPS32 指令 '{}'", instruction.mnemonic))?;
+        let operand = |index: usize| {
+            instruction
+                .operands
+                .get(index)
+                .map(|operand| operand.as_str())
+                .ok_or_else(|| format!("{} 缺少第 {} 个操作数", mnemonic, index + 1))
+        };
+        let reg = |index: usize| operand(index).and_then(|text| register(text).map(|number| number as usize));
+        let imm = |index: usize| {
+            operand(index).and_then(|text| {
+                parse_number(text).map(|value| value as i32).ok_or_else(|| format!("无法解析立即数 '{}'", text))
+            })
+        };
+        let label = |index: usize| operand(index).map(|text| Some(text.trim().to_string()));
+
+        let mut decoded = Decoded { mnemonic: mnemonic.clone(), format, rd: 0, rs: 0, rt: 0, imm: 0, target: None };
+        match format {
+            Format::R => (decoded.rd, decoded.rs, decoded.rt) = (reg(0)?, reg(1)?, reg(2)?),
+            Format::Shift => (decoded.rd, decoded.rt, decoded.imm) = (reg(0)?, reg(1)?, imm(2)?),
+            Format::ShiftVariable => (decoded.rd, decoded.rt, decoded.rs) = (reg(0)?, reg(1)?, reg(2)?),
+            Format::MulDiv => (decoded.rs, decoded.rt) = (reg(0)?, reg(1)?),
+            Format::MoveFrom => decoded.rd = reg(0)?,
+            Format::JumpRegister => decoded.rs = reg(0)?,
+            Format::JumpLinkRegister if instruction.operands.len() == 1 => (decoded.rd, decoded.rs) = (31, reg(0)?),
+            Format::JumpLinkRegister => (decoded.rd, decoded.rs) = (reg(0)?, reg(1)?),
+            Format::System => {}
+            Format::Immediate => (decoded.rd, decoded.rs, decoded.imm) = (reg(0)?, reg(1)?, imm(2)?),
+            Format::LogicImmediate => (decoded.rd, decoded.rs, decoded.imm) = (reg(0)?, reg(1)?, imm(2)? & 0xFFFF),
+            Format::Lui => (decoded.rd, decoded.imm) = (reg(0)?, imm(1)?),
+            Format::Load | Format::Store => {
+                let text = operand(1)?;
+                let (offset, base) = parse_memory(text).ok_or_else(|| format!("无法解析内存操作数 '{}'", text))?;
+                (decoded.rd, decoded.rt, decoded.rs, decoded.imm) = (reg(0)?, reg(0)?, base, offset as i32);
+            }
+            Format::Branch => (decoded.rs, decoded.rt, decoded.target) = (reg(0)?, reg(1)?, label(2)?),
+            Format::BranchZero => (decoded.rs, decoded.target) = (reg(0)?, label(1)?),
+            Format::Jump => decoded.target = label(0)?,
+            Format::Coprocessor => {
+                let text = operand(1)?;
+                let (_, index) =
+                    coprocessor_register(text).ok_or_else(|| format!("不支持的协处理器 0 寄存器 '{}'", text.trim()))?;
+                (decoded.rd, decoded.rt, decoded.imm) = (reg(0)?, reg(0)?, index as i32);
+            }
+        }
+        Ok(decoded)
+    }
+
+    fn execute(&self, decoded: &Decoded, context: &Context) -> Result<Execution, Fault> {
+        let mnemonic = decoded.mnemonic.as_str();
+        let pc = context.address as i64;
+        let rd = Register::General(decoded.rd);
+        let target = decoded.target.as_deref().unwrap_or("");
+        // 开启延迟槽时返回地址跳过延迟槽
+        let link = (pc + if self.branch_delay_slot { 8 } else { 4 }) as i32;
+        let branch = |index: usize| Some(Branch { target: index, delayed: self.branch_delay_slot });
+
+        let execution = match decoded.format {
+            Format::R | Format::Shift | Format::ShiftVariable | Format::Immediate | Format::LogicImmediate => {
+                let (a, b) = match decoded.format {
+                    Format::R => (context.register(decoded.rs), context.register(decoded.rt)),
+                    Format::Shift => (context.register(decoded.rt), decoded.imm),
+                    Format::ShiftVariable => (context.register(decoded.rt), context.register(decoded.rs)),
+                    _ => (context.register(decoded.rs), decoded.imm),
+                };
+                let value = alu(mnemonic, a, b).ok_or_else(|| format!("算术溢出异常：{} {}, {}", mnemonic, a, b))?;
+                Execution::new(format!("执行：{} {}, {} → {}", mnemonic, a, b, value)).write(rd, value)
+            }
+            Format::MulDiv => {
+                let (a, b) = (context.register(decoded.rs), context.register(decoded.rt));
+                match multiply_divide(mnemonic, a, b) {
+                    Some((hi, lo)) => {
+                        Execution::new(format!("执行：{} {}, {} → HI = {}, LO = {}", mnemonic, a, b, hi, lo))
+                            .write(Register::Special(HI), hi)
+                            .write(Register::Special(LO), lo)
+                    }
+                    None => Execution::new(format!("执行：{} 除数为 0，HI/LO 的值未定义（保持不变）", mnemonic)),
+                }
+            }
+            Format::MoveFrom => {
+                let source = if mnemonic == "mfhi" { HI } else { LO };
+                let value = context.special(source);
+                Execution::new(format!("执行：读取 {} = {}", SPECIAL_REGISTERS[source], value)).write(rd, value)
+            }
+            Format::Lui => {
+                let value = decoded.imm.wrapping_shl(16);
+                Execution::new(format!("执行：lui 得到 0x{:08X}", value)).write(rd, value)
+            }
+            Format::Load | Format::Store => {
+                let base = context.register(decoded.rs);
+                let address = base.wrapping_add(decoded.imm) as u32 as u64;
+                let size = isa::access_size(mnemonic);
+                let mut execution =
+                    Execution::new(format!("执行：计算有效地址 {} + {} = {}", base, decoded.imm, address));
+                execution.memory = Some(match decoded.format {
+                    Format::Load => {
+                        MemoryOperation::Load { address, size, signed: !mnemonic.ends_with('u'), destination: rd }
+                    }
+                    _ => MemoryOperation::Store { address, size, value: context.register(decoded.rt) },
+                });
+                execution
+            }
+            Format::Branch | Format::BranchZero => {
+                let a = context.register(decoded.rs);
+                let b = if decoded.format == Format::Branch { context.register(decoded.rt) } else { 0 };
+                if branch_taken(mnemonic, a, b).unwrap_or(false) {
+                    // 分支偏移相对于延迟槽的地址
+                    let index =
+                        context.word_target(target, pc + 4).ok_or_else(|| format!("找不到跳转目标 '{}'", target))?;
+                    let mut execution =
+                        Execution::new(format!("执行：比较 {} 与 {}，条件成立，跳转到 {}", a, b, target));
+                    execution.branch = branch(index);
+                    execution
+                } else {
+                    Execution::new(format!("执行：比较 {} 与 {}，条件不成立，顺序执行", a, b))
+                }
+            }
+            Format::Jump => {
+                let index = match parse_number(target) {
+                    Some(address) => Some(self.instruction_index(address.max(0) as u64)),
+                    None => context.label(target),
+                };
+                match index {
+                    Some(index) => {
+                        let mut execution = Execution::new(format!("执行：跳转到 {}", target));
+                        if mnemonic == "jal" {
+                            execution = execution.write(Register::General(31), link);
+                        }
+                        execution.branch = branch(index);
+                        execution
+                    }
+                    // 程序中没有的函数视为外部函数，跳过并把返回值 $v0 置 0
+                    None if mnemonic == "jal" => {
+                        Execution::new(format!("执行：调用外部函数 {}（模拟器中跳过，返回值 $v0 = 0）", target))
+                            .write(Register::General(2), 0)
+                    }
+                    None => Execution::new(format!("执行：调用外部函数 {}（模拟器中跳过）", target)),
+                }
+            }
+            Format::JumpRegister | Format::JumpLinkRegister => {
+                let address = context.register(decoded.rs) as u32 as u64;
+                let mut execution =
+                    Execution::new(format!("执行：跳转到 {} 中的地址 0x{:X}", REGISTERS[decoded.rs], address));
+                if decoded.format == Format::JumpLinkRegister {
+                    execution = execution.write(rd, link);
+                }
+                execution.branch = branch(self.instruction_index(address));
+                execution
+            }
+            Format::System => match mnemonic {
+                // eret 从中断处理程序返回，没有延迟槽
+                "eret" => Execution {
+                    interrupt: Some(InterruptRequest::Return),
+                    ..Execution::new("执行：eret，从中断处理程序返回")
+                },
+                "break" => Execution {
+                    halt: true,
+                    ..Execution::new(format!("执行：break，程序停止，$v0 = {}", context.register(2)))
+                },
+                "syscall" if context.register(2) == 10 => {
+                    Execution { halt: true, ..Execution::new("执行：syscall 10，程序退出") }
+                }
+                "syscall" => Execution::new(format!("执行：系统调用 $v0 = {}（模拟器中忽略）", context.register(2))),
+                _ => Execution::new("执行：空操作"),
+            },
+            Format::Coprocessor => {
+                let index = decoded.imm as usize;
+                if mnemonic == "mfc0" {
+                    let value = context.special(index);
+                    Execution::new(format!("执行：读取 {} = 0x{:X}", SPECIAL_REGISTERS[index], value)).write(rd, value)
+                } else {
+                    // 写 Status 时 IE 置位且不在异常处理中才允许中断
+                    let value = context.register(decoded.rt);
+                    let mut execution = Execution::new(format!("执行：{} ← 0x{:X}", SPECIAL_REGISTERS[index], value))
+                        .write(Register::Special(index), value);
+                    if index == STATUS {
+                        let interrupt = value as u32 & (IE | EXL) == IE;
+                        execution.flags = Some(FlagsState { interrupt, ..context.flags.clone() });
+                    }
+                    execution
+                }
+            }
+        };
+        Ok(execution)
+    }
+
+    fn register_usage(&self, decoded: &Decoded) -> RegisterUsage {
+        let (rd, rs, rt) = (decoded.rd, decoded.rs, decoded.rt);
+        match decoded.format {
+            Format::R => RegisterUsage::general(&[rs, rt], &[rd]),
+            Format::Shift => RegisterUsage::general(&[rt], &[rd]),
+            Format::ShiftVariable => RegisterUsage::general(&[rt, rs], &[rd]),
+            Format::MulDiv => RegisterUsage {
+                writes: vec![Register::Special(HI), Register::Special(LO)],
+                ..RegisterUsage::general(&[rs, rt], &[])
+            },
+            Format::MoveFrom => RegisterUsage {
+                reads: vec![Register::Special(if decoded.mnemonic == "mfhi" { HI } else { LO })],
+                ..RegisterUsage::general(&[], &[rd])
+            },
+            Format::JumpRegister | Format::BranchZero => RegisterUsage::general(&[rs], &[]),
+            Format::JumpLinkRegister | Format::Immediate | Format::LogicImmediate | Format::Load => {
+                RegisterUsage::general(&[rs], &[rd])
+            }
+            Format::Lui => RegisterUsage::general(&[], &[rd]),
+            Format::Store | Format::Branch => RegisterUsage::general(&[rs, rt], &[]),
+            // 调用外部函数时还写入返回值 $v0
+            Format::Jump if decoded.mnemonic == "jal" => RegisterUsage::general(&[], &[31, 2]),
+            Format::Jump => RegisterUsage::default(),
+            // eret 由 EPC 与 Status 恢复中断现场
+            Format::System if decoded.mnemonic == "eret" => RegisterUsage {
+                reads: vec![Register::Special(EPC), Register::Special(STATUS)],
+                writes: vec![Register::Special(STATUS)],
+                reads_flags: true,
+                writes_flags: true,
+            },
+            // syscall 与 break 读取 $v0
+            Format::System => RegisterUsage::general(&[2], &[]),
+            Format::Coprocessor if decoded.mnemonic == "mfc0" => RegisterUsage {
+                reads: vec![Register::Special(decoded.imm as usize)],
+                ..RegisterUsage::general(&[], &[rd])
+            },
+            Format::Coprocessor => RegisterUsage {
+                writes: vec![Register::Special(decoded.imm as usize)],
+                writes_flags: decoded.imm as usize == STATUS,
+                ..RegisterUsage::general(&[rt], &[])
+            },
+        }
+    }
+
+    fn branch_kind(&self, decoded: &Decoded) -> Option<BranchKind> {
+        match decoded.format {
+            Format::Branch | Format::BranchZero => Some(BranchKind::Conditional),
+            Format::Jump | Format::JumpRegister | Format::JumpLinkRegister => Some(BranchKind::Unconditional),
+            Format::System if decoded.mnemonic == "eret" => Some(BranchKind::Unconditional),
+            _ => None,
+        }
+    }
+
+    fn encode_program(&self, instructions: &mut [Instruction]) -> Result<(), (usize, String)> {
+        encode_program(instructions)
+    }
+
+    fn disassemble(&self, bytes: &[u8]) -> Result<Vec<Instruction>, String> {
+        disassemble(bytes)
+    }
+}
+
+/// 把 32 位指令字还原为汇编指令；跳转目标为绝对地址
+fn decode_word(word: u32, address: i64) -> Result<WordInstruction, String> {
+    let opcode = word >> 26;
+    let (rs, rt, rd) = ((word >> 21) & 0x1F, (word >> 16) & 0x1F, (word >> 11) & 0x1F);
+    let (shamt, funct) = ((word >> 6) & 0x1F, word & 0x3F);
+    let imm = word as i16 as i32;
+    // sll $0, $0, 0 即 nop
+    let entry = INSTRUCTIONS.iter().find(|&&(name, format, op, function)| {
+        if word == 0 {
+            return name == "nop";
+        }
+        op == opcode
+            && match opcode {
+                0 => format.is_r_type() && function == funct && name != "nop",
+                1 => function == rt,
+                0x10 if format == Format::Coprocessor => function == rs && word & 0x7FF == 0,
+                0x10 => function == funct && rs == 0x10,
+                _ => true,
+            }
+    });
+    let &(name, format, ..) = entry.ok_or_else(|| format!("无法识别的指令字 {:08X}", word))?;
+
+    let reg = |number: u32| format!("${}", REGISTER_NAMES[number as usize]);
+    let branch = || Some((address + 4 + 4 * imm as i64, (4 * imm).to_string()));
+    let mut target = None;
+    let operands = match format {
+        Format::R => vec![reg(rd), reg(rs), reg(rt)],
+        Format::Shift => vec![reg(rd), reg(rt), shamt.to_string()],
+        Format::ShiftVariable => vec![reg(rd), reg(rt), reg(rs)],
+        Format::MulDiv => vec![reg(rs), reg(rt)],
+        Format::MoveFrom => vec![reg(rd)],
+        Format::JumpRegister => vec![reg(rs)],
+        Format::JumpLinkRegister if rd == 31 => vec![reg(rs)],
+        Format::JumpLinkRegister => vec![reg(rd), reg(rs)],
+        Format::System => Vec::new(),
+        Format::Immediate => vec![reg(rt), reg(rs), imm.to_string()],
+        Format::LogicImmediate => vec![reg(rt), reg(rs), (word & 0xFFFF).to_string()],
+        Format::Lui => vec![reg(rt), format!("0x{:X}", word & 0xFFFF)],
+        Format::Load | Format::Store => vec![reg(rt), format!("{}({})", imm, reg(rs))],
+        Format::Branch => {
+            target = branch();
+            vec![reg(rs), reg(rt)]
+        }
+        Format::BranchZero => {
+            target = branch();
+            vec![reg(rs)]
+        }
+        Format::Coprocessor => {
+            let (rd, _) =
+                coprocessor_register(&rd.to_string()).ok_or_else(|| format!("不支持的协处理器 0 寄存器 ${}", rd))?;
+            vec![reg(rt), format!("${}", rd)]
+        }
+        Format::Jump => {
+            let destination = ((address + 4) & !0x0FFF_FFFF) | (((word & 0x3FF_FFFF) << 2) as i64);
+            target = Some((destination, format!("0x{:X}", destination)));
+            Vec::new()
+        }
+    };
+    Ok(WordInstruction { word, mnemonic: name.to_string(), operands, target })
+}
+
+/// 把 MIPS32 机器码反汇编为指令序列，并重新编码以给出各位段
+pub fn disassemble(bytes: &[u8]) -> Result<Vec<Instruction>, String> {
+    let words = isa::words(bytes)?
+        .into_iter()
+        .enumerate()
+        .map(|(i, word)| {
+            let address = CODE_BASE as i64 + 4 * i as i64;
+            decode_word(word, address).map_err(|message| format!("地址 {:08X}: {}", address, message))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut instructions = isa::finish_disassembly(words, classify);
+    encode_program(&mut instructions).map_err(|(i, message)| format!("第 {} 条指令: {}", i + 1, message))?;
+    Ok(instructions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(mnemonic: &str, operands: &[&str]) -> Result<u32, String> {
+        let operands: Vec<String> = operands.iter().map(|operand| operand.to_string()).collect();
+        encode(mnemonic, &operands, 0, &|_| None).map(|(word, _)| word)
+    }
+
+    fn instruction(label: Option<&str>, mnemonic: &str, operands: &[&str]) -> Instruction {
+        let (instruction_type, cycles) = classify(mnemonic);
+        Instruction {
+            id: mnemonic.to_string(),
+            instruction_type,
+            mnemonic: mnemonic.to_string(),
+            operands: operands.iter().map(|operand| operand.to_string()).collect(),
+            machine_code: String::new(),
+            description: String::new(),
+            cycles,
+            label: label.map(str::to_string),
+            encoding: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn encodes_every_format() {
+        let cases: [(&str, &[&str], u32); 14] = [
+            ("add", &["$t0", "$t1", "$t2"], 0x012A_4020),
+            ("sll", &["$t0", "$t1", "4"], 0x0009_4100),
+            ("mult", &["$a0", "$a1"], 0x0085_0018),
+            ("mfhi", &["$v0"], 0x0000_1010),
+            ("addiu", &["$sp", "$sp", "-8"], 0x27BD_FFF8),
+            ("lw", &["$ra", "4($sp)"], 0x8FBF_0004),
+            ("lui", &["$at", "0x1234"], 0x3C01_1234),
+            ("ori", &["$t0", "$t0", "0xFFFF"], 0x3508_FFFF),
+            ("jr", &["$ra"], 0x03E0_0008),
+            ("syscall", &[], 0x0000_000C),
+            ("eret", &[], 0x4200_0018),
+            ("beq", &["$t0", "$zero", "8"], 0x1100_0002),
+            ("j", &["0x40"], 0x0800_0010),
+            ("bgez", &["$a0", "4"], 0x0481_0001),
+        ];
+        for (mnemonic, operands, expected) in cases {
+            assert_eq!(word(mnemonic, operands), Ok(expected), "{} {:?}", mnemonic, operands);
+        }
+        // 数字编号与 ABI 名称等价
+        assert_eq!(word("add", &["$8", "$9", "$10"]), word("add", &["$t0", "$t1", "$t2"]));
+    }
+
+    #[test]
+    fn rejects_out_of_range_operands() {
+        assert!(word("addi", &["$t0", "$t0", "32768"]).is_err());
+        assert!(word("ori", &["$t0", "$t0", "-1"]).is_err());
+        assert!(word("sll", &["$t0", "$t0", "32"]).is_err());
+        assert!(word("add", &["$t0", "$t1", "$32"]).is_err());
+        assert!(word("lw", &["$t0", "$sp"]).is_err());
+        assert!(word("add", &["$t0", "$t1"]).is_err());
+        assert!(word("madd", &["$t0", "$t1"]).is_err());
+    }
+
+    #[test]
+    fn branches_are_relative_to_the_delay_slot() {
+        let mut program = vec![
+            instruction(Some("loop"), "addiu", &["$a0", "$a0", "-1"]),
+            instruction(None, "bne", &["$a0", "$zero", "loop"]),
+            instruction(None, "beq", &["$zero", "$zero", "done"]),
+            instruction(None, "nop", &[]),
+            instruction(Some("done"), "jal", &["loop"]),
+        ];
+        encode_program(&mut program).unwrap();
+        // bne 在 0x4，延迟槽在 0x8，回到 0x0 需偏移 -2 条；beq 在 0x8，到 0x10 偏移 +1 条
+        assert_eq!(program[1].encoding[0].hex, "1480FFFE");
+        assert_eq!(program[2].encoding[0].hex, "10000001");
+        assert_eq!(program[4].encoding[0].hex, "0C000000");
+        // 机器码按小端序存放
+        assert_eq!(program[1].machine_code, "FEFF8014");
+    }
+
+    #[test]
+    fn integer_semantics() {
+        // add 溢出报告异常，addu 回绕
+        assert_eq!(alu("add", i32::MAX, 1), None);
+        assert_eq!(alu("addi", i32::MIN, -1), None);
+        assert_eq!(alu("addu", i32::MAX, 1), Some(i32::MIN));
+        assert_eq!(alu("addiu", 0, -1), Some(-1));
+        assert_eq!(alu("sltu", -1, 0), Some(0));
+        assert_eq!(alu("sltiu", 0, -1), Some(1));
+        assert_eq!(alu("nor", 0, 0), Some(-1));
+        assert_eq!(alu("sra", -16, 2), Some(-4));
+        assert_eq!(alu("srlv", -16, 28), Some(0xF));
+        assert_eq!(multiply_divide("mult", -2, 3), Some((-1, -6)));
+        assert_eq!(multiply_divide("multu", -1, 2), Some((1, -2)));
+        assert_eq!(multiply_divide("div", -7, 2), Some((-1, -3)));
+        assert_eq!(multiply_divide("divu", 7, 0), None);
+        assert_eq!(branch_taken("blez", 0, 5), Some(true));
+        assert_eq!(branch_taken("bgtz", 0, 0), Some(false));
+        assert_eq!(branch_taken("bltz", -1, 0), Some(true));
+    }
+}
@@ -0,0 +1,475 @@
+use crate::ir::{IrFunction, IrInstruction, IrOp, IrOperand, IrProgram};
+use crate::memory::DATA_BASE;
+use crate::mips::classify;
+use crate::types::{Diagnostic, Instruction, SourceSpan};
+use std::collections::HashMap;
+
+/// 参数寄存器 $a0–$a3
+const ARGUMENT_REGISTERS: [&str; 4] = ["$a0", "$a1", "$a2", "$a3"];
+
+/// MIPS 代码生成器：把三地址码翻译为 MIPS32 汇编。
+/// 与 RISC-V 后端一样不做寄存器分配，变量都在栈帧中（相对 $fp 寻址），运算时装入 $t0/$t1；
+/// 乘除法经由 HI/LO 取得结果。开启分支延迟槽时在每条分支和跳转之后填入 nop。
+pub struct MipsCodeGenerator {
+    program: IrProgram,
+    branch_delay_slot: bool,
+    instructions: Vec<Instruction>,
+    memory_offset: usize,
+    globals: HashMap<String, usize>,
+    /// 字符串常量 -> 数据区地址
+    strings: HashMap<String, usize>,
+    /// 当前函数中各槽位相对 $fp 的偏移
+    slots: HashMap<IrOperand, i32>,
+    /// 每个函数的栈帧布局说明
+    layouts: Vec<String>,
+    /// 等待传给下一次调用的参数（按从右向左的顺序给出）
+    pending_params: Vec<IrOperand>,
+    return_label: Option<String>,
+    pending_label: Option<String>,
+    label_aliases: HashMap<String, String>,
+}
+
+impl MipsCodeGenerator {
+    pub fn new(program: IrProgram, branch_delay_slot: bool) -> Self {
+        let mut memory_offset = DATA_BASE as usize;
+        let mut globals = HashMap::new();
+        for name in &program.globals {
+            globals.insert(name.clone(), memory_offset);
+            memory_offset += 4;
+        }
+
+        Self {
+            program,
+            branch_delay_slot,
+            instructions: Vec::new(),
+            memory_offset,
+            globals,
+            strings: HashMap::new(),
+            slots: HashMap::new(),
+            layouts: Vec::new(),
+            pending_params: Vec::new(),
+            return_label: None,
+            pending_label: None,
+            label_aliases: HashMap::new(),
+        }
+    }
+
+    /// 各函数的栈帧布局，生成之后才有内容
+    pub fn layouts(&self) -> &[String] {
+        &self.layouts
+    }
+
+    pub fn generate(&mut self) -> Result<Vec<Instruction>, Diagnostic> {
+        for function in self.program.functions.clone() {
+            self.generate_function(&function)?;
+        }
+
+        if self.pending_label.is_some() {
+            self.emit("nop", &[], "空操作（标签占位）".to_string());
+        }
+
+        // 把跳转目标中的别名替换为实际附着的标签
+        for instruction in &mut self.instructions {
+            if let Some(target) = instruction.operands.last_mut() {
+                if let Some(actual) = self.label_aliases.get(target) {
+                    *target = actual.clone();
+                }
+            }
+        }
+
+        Ok(self.instructions.clone())
+    }
+
+    fn place_label(&mut self, label: String) {
+        match &self.pending_label {
+            Some(pending) => {
+                self.label_aliases.insert(label, pending.clone());
+            }
+            None => self.pending_label = Some(label),
+        }
+    }
+
+    fn emit(&mut self, mnemonic: &str, operands: &[&str], description: String) {
+        let (instruction_type, cycles) = classify(mnemonic);
+        self.instructions.push(Instruction {
+            id: format!("{}_{}", mnemonic, self.instructions.len()),
+            instruction_type,
+            mnemonic: mnemonic.to_string(),
+            operands: operands.iter().map(|operand| operand.to_string()).collect(),
+            machine_code: String::new(),
+            description,
+            cycles,
+            label: self.pending_label.take(),
+            encoding: Vec::new(),
+        });
+    }
+
+    /// 分支与跳转：开启延迟槽时后面跟一条 nop
+    fn emit_branch(&mut self, mnemonic: &str, operands: &[&str], description: String) {
+        self.emit(mnemonic, operands, description);
+        if self.branch_delay_slot {
+            self.emit("nop", &[], "分支延迟槽".to_string());
+        }
+    }
+
+    /// 生成函数：序言保存 $ra、$fp 并建立栈帧，尾声恢复后经 $ra 返回。
+    /// 栈帧按 8 字节对齐，[$fp-4] 保存 $ra，[$fp-8] 保存 $fp，其后依次是局部变量和临时变量；
+    /// 按 O32 约定，第 i 个参数位于调用者栈帧底部的 4i($fp)，前 4 个由序言从 $a0–$a3 存入其中的 16 字节预留区。
+    /// 调用其他函数的栈帧底部留出传参区，至少 16 字节。
+    fn generate_function(&mut self, function: &IrFunction) -> Result<(), Diagnostic> {
+        let name = &function.name;
+        self.slots.clear();
+        let mut layout = Vec::new();
+        for (i, param) in function.params.iter().enumerate() {
+            let offset = 4 * i as i32;
+            layout.push(format!("    {:<12} {}($fp)", param, offset));
+            self.slots.insert(IrOperand::Var(param.clone()), offset);
+        }
+        let operands = function
+            .locals
+            .iter()
+            .map(|name| IrOperand::Var(name.clone()))
+            .chain((1..=function.temp_count).map(IrOperand::Temp));
+        let mut slot_count = 0;
+        for (i, operand) in operands.enumerate() {
+            let offset = -12 - 4 * i as i32;
+            layout.push(format!("    {:<12} {}($fp)", operand.to_string(), offset));
+            self.slots.insert(operand, offset);
+            slot_count += 1;
+        }
+        let outgoing = function
+            .body
+            .iter()
+            .filter_map(|instruction| match instruction.op {
+                IrOp::Call(_, count) => Some(4 * count.max(ARGUMENT_REGISTERS.len()) as i32),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0);
+        let frame_size = (8 + 4 * slot_count + outgoing + 7) / 8 * 8;
+        if frame_size > 32767 {
+            return Err(Diagnostic::error(
+                "E0304",
+                format!("函数 {} 的栈帧有 {} 字节，超出 16 位立即数的寻址范围", name, frame_size),
+                SourceSpan::default(),
+            ));
+        }
+        layout.insert(
+            0,
+            format!(
+                "{}: 栈帧 {} 字节，$ra 在 -4($fp)，$fp 在 -8($fp)，传参区 {} 字节在 0($sp)",
+                name, frame_size, outgoing
+            ),
+        );
+        self.layouts.push(layout.join("\n"));
+
+        let size = frame_size.to_string();
+        let negative = (-frame_size).to_string();
+        let ra_offset = (frame_size - 4).to_string();
+        let fp_offset = (frame_size - 8).to_string();
+        self.place_label(name.clone());
+        self.emit("addiu", &["$sp", "$sp", &negative], format!("{} 序言：分配 {} 字节栈帧", name, frame_size));
+        if !function.is_entry() {
+            self.emit("sw", &["$ra", &format!("{}($sp)", ra_offset)], "保存返回地址 $ra".to_string());
+            self.emit("sw", &["$fp", &format!("{}($sp)", fp_offset)], "保存调用者的 $fp".to_string());
+        }
+        self.emit("addiu", &["$fp", "$sp", &size], "$fp 指向栈帧顶部".to_string());
+        for (register, param) in ARGUMENT_REGISTERS.iter().zip(&function.params) {
+            let offset = self.slots[&IrOperand::Var(param.clone())];
+            self.emit("sw", &[register, &format!("{}($fp)", offset)], format!("把参数 {} 存入预留区", param));
+        }
+
+        self.return_label = (!function.is_entry()).then(|| format!("{}_end", name));
+        for (i, instruction) in function.body.iter().enumerate() {
+            let is_last = i + 1 == function.body.len();
+            self.generate_instruction(instruction, is_last)?;
+        }
+
+        if let Some(return_label) = self.return_label.take() {
+            self.place_label(return_label);
+            self.emit("lw", &["$ra", &format!("{}($sp)", ra_offset)], format!("{} 尾声：恢复 $ra", name));
+            self.emit("lw", &["$fp", &format!("{}($sp)", fp_offset)], "恢复调用者的 $fp".to_string());
+            self.emit("addiu", &["$sp", "$sp", &size], "释放栈帧".to_string());
+            self.emit_branch("jr", &["$ra"], "返回调用者，返回值在 $v0".to_string());
+        }
+        Ok(())
+    }
+
+    fn generate_instruction(&mut self, instruction: &IrInstruction, is_last: bool) -> Result<(), Diagnostic> {
+        let arg1 = instruction.arg1.as_ref();
+        let arg2 = instruction.arg2.as_ref();
+        match &instruction.op {
+            IrOp::Label(label) => self.place_label(label.clone()),
+            IrOp::Jump(label) => self.emit_branch("j", &[label], format!("无条件跳转到 {}", label)),
+            IrOp::Assign => {
+                self.load(arg1, "$t0");
+                self.store_result(instruction, "$t0");
+            }
+            IrOp::Binary(op) => {
+                self.load(arg1, "$t0");
+                self.load(arg2, "$t1");
+                self.generate_binary_op(op)?;
+                self.store_result(instruction, "$t0");
+            }
+            IrOp::Neg => {
+                self.load(arg1, "$t0");
+                self.emit("subu", &["$t0", "$zero", "$t0"], "$t0 = 0 - $t0".to_string());
+                self.store_result(instruction, "$t0");
+            }
+            IrOp::Not => {
+                self.load(arg1, "$t0");
+                self.emit("sltiu", &["$t0", "$t0", "1"], "$t0 为 0 时结果为 1".to_string());
+                self.store_result(instruction, "$t0");
+            }
+            IrOp::Cast(data_type) => {
+                self.load(arg1, "$t0");
+                if data_type == "char" {
+                    self.emit("sll", &["$t0", "$t0", "24"], "左移 24 位，只保留低 8 位".to_string());
+                    self.emit("sra", &["$t0", "$t0", "24"], "算术右移 24 位，截断为 char 并符号扩展".to_string());
+                }
+                self.store_result(instruction, "$t0");
+            }
+            IrOp::CondJump(relop, label) => {
+                self.load(arg1, "$t0");
+                let description = format!("条件 {} 成立时跳转到 {}", relop, label);
+                if let Some(IrOperand::Const(0)) = arg2 {
+                    // 与 0 比较有专门的分支指令
+                    match relop.as_str() {
+                        "==" => self.emit_branch("beq", &["$t0", "$zero", label], description),
+                        "!=" => self.emit_branch("bne", &["$t0", "$zero", label], description),
+                        "<" => self.emit_branch("bltz", &["$t0", label], description),
+                        ">=" => self.emit_branch("bgez", &["$t0", label], description),
+                        ">" => self.emit_branch("bgtz", &["$t0", label], description),
+                        "<=" => self.emit_branch("blez", &["$t0", label], description),
+                        _ => return Err(unsupported(relop)),
+                    }
+                    return Ok(());
+                }
+                self.load(arg2, "$t1");
+                // 只有 beq/bne 比较两个寄存器，其余关系先用 slt 求出
+                let (compare, mnemonic) = match relop.as_str() {
+                    "==" => (None, "beq"),
+                    "!=" => (None, "bne"),
+                    "<" => (Some(["$t0", "$t1"]), "bne"),
+                    ">=" => (Some(["$t0", "$t1"]), "beq"),
+                    ">" => (Some(["$t1", "$t0"]), "bne"),
+                    "<=" => (Some(["$t1", "$t0"]), "beq"),
+                    _ => return Err(unsupported(relop)),
+                };
+                match compare {
+                    Some([a, b]) => {
+                        self.emit("slt", &["$t2", a, b], format!("{} < {} 时 $t2 为 1", a, b));
+                        self.emit_branch(mnemonic, &["$t2", "$zero", label], description);
+                    }
+                    None => self.emit_branch(mnemonic, &["$t0", "$t1", label], description),
+                }
+            }
+            IrOp::Param => {
+                if let Some(operand) = arg1 {
+                    self.pending_params.push(operand.clone());
+                }
+            }
+            IrOp::Call(name, count) => {
+                // 参数按从右向左给出，最后给出的是第一个参数；第 5 个起存入传参区，前 4 个装入 $a0–$a3
+                let start = self.pending_params.len().saturating_sub(*count);
+                let params: Vec<IrOperand> = self.pending_params.drain(start..).rev().collect();
+                for (i, param) in params.iter().enumerate().skip(ARGUMENT_REGISTERS.len()) {
+                    self.load(Some(param), "$t0");
+                    self.emit("sw", &["$t0", &format!("{}($sp)", 4 * i)], format!("第 {} 个参数经栈传递", i + 1));
+                }
+                for (register, param) in ARGUMENT_REGISTERS.iter().zip(&params) {
+                    self.load(Some(param), register);
+                }
+                self.emit_branch("jal", &[name], format!("调用函数 {}，返回地址存入 $ra", name));
+                self.store_result(instruction, "$v0");
+            }
+            IrOp::Return => {
+                self.load(arg1, "$v0");
+                if let (Some(return_label), false) = (self.return_label.clone(), is_last) {
+                    self.emit_branch("j", &[&return_label], "跳转到函数尾声".to_string());
+                }
+            }
+            IrOp::Halt => self.emit("break", &[], "程序结束，停机".to_string()),
+        }
+        Ok(())
+    }
+
+    /// 对 $t0（左操作数）和 $t1（右操作数）执行二元运算，结果写入 $t0
+    fn generate_binary_op(&mut self, op: &str) -> Result<(), Diagnostic> {
+        match op {
+            "+" => self.emit("addu", &["$t0", "$t0", "$t1"], "$t0 = $t0 + $t1".to_string()),
+            "-" => self.emit("subu", &["$t0", "$t0", "$t1"], "$t0 = $t0 - $t1".to_string()),
+            "<<" => self.emit("sllv", &["$t0", "$t0", "$t1"], "$t0 左移 $t1 位".to_string()),
+            "*" => {
+                self.emit("mult", &["$t0", "$t1"], "64 位积写入 HI:LO".to_string());
+                self.emit("mflo", &["$t0"], "取积的低 32 位".to_string());
+            }
+            "/" | "%" => {
+                self.emit("div", &["$t0", "$t1"], "商写入 LO，余数写入 HI".to_string());
+                if op == "/" {
+                    self.emit("mflo", &["$t0"], "取商".to_string());
+                } else {
+                    self.emit("mfhi", &["$t0"], "取余数".to_string());
+                }
+            }
+            "<" => self.emit("slt", &["$t0", "$t0", "$t1"], "$t0 < $t1 时结果为 1".to_string()),
+            ">" => self.emit("slt", &["$t0", "$t1", "$t0"], "$t1 < $t0 时结果为 1".to_string()),
+            "<=" | ">=" => {
+                let (a, b) = if op == "<=" { ("$t1", "$t0") } else { ("$t0", "$t1") };
+                self.emit("slt", &["$t0", a, b], format!("先求 {} 的反面", op));
+                self.emit("xori", &["$t0", "$t0", "1"], format!("取反得到 {} 的结果", op));
+            }
+            "==" | "!=" => {
+                self.emit("subu", &["$t0", "$t0", "$t1"], "两数相等时差为 0".to_string());
+                if op == "==" {
+                    self.emit("sltiu", &["$t0", "$t0", "1"], "差为 0 时结果为 1".to_string());
+                } else {
+                    self.emit("sltu", &["$t0", "$zero", "$t0"], "差不为 0 时结果为 1".to_string());
+                }
+            }
+            _ => return Err(unsupported(op)),
+        }
+        Ok(())
+    }
+
+    /// 字符串常量放在数据区，按 4 字节对齐
+    fn string_address(&mut self, text: &str) -> usize {
+        if let Some(address) = self.strings.get(text) {
+            return *address;
+        }
+        let address = self.memory_offset;
+        self.memory_offset += (text.len() + 1).div_ceil(4) * 4;
+        self.strings.insert(text.to_string(), address);
+        address
+    }
+
+    /// 把 32 位常量装入寄存器：16 位以内用 addiu，否则 lui 装入高 16 位再用 ori 补上低 16 位
+    fn load_immediate(&mut self, register: &str, value: i64, description: String) {
+        let value = value as i32;
+        if (-32768..=32767).contains(&value) {
+            self.emit("addiu", &[register, "$zero", &value.to_string()], description);
+            return;
+        }
+        let (upper, lower) = ((value as u32) >> 16, (value as u32) & 0xFFFF);
+        self.emit("lui", &[register, &upper.to_string()], format!("{}（高 16 位）", description));
+        if lower != 0 {
+            self.emit("ori", &[register, register, &lower.to_string()], "补上低 16 位".to_string());
+        }
+    }
+
+    /// 变量的内存操作数：局部变量相对 $fp，全局变量用绝对地址（超出 16 位时先用 lui 把高位装入 $t2）
+    fn memory_operand(&mut self, operand: &IrOperand) -> String {
+        if let Some(offset) = self.slots.get(operand) {
+            return format!("{}($fp)", offset);
+        }
+        let address = match operand {
+            IrOperand::Var(name) => self.globals[name] as i32,
+            _ => unreachable!("临时变量 {} 没有分配位置", operand),
+        };
+        if address < 32768 {
+            return format!("{}($zero)", address);
+        }
+        let upper = (address + 0x8000) as u32 >> 16;
+        self.emit("lui", &["$t2", &upper.to_string()], format!("全局变量 {} 地址的高 16 位", operand));
+        format!("{}($t2)", address - (upper << 16) as i32)
+    }
+
+    /// 把操作数装入寄存器
+    fn load(&mut self, operand: Option<&IrOperand>, register: &str) {
+        let Some(operand) = operand else { return };
+        match operand {
+            IrOperand::Const(value) => self.load_immediate(register, *value, format!("将值 {} 加载到 {}", value, register)),
+            IrOperand::Str(text) => {
+                let address = self.string_address(text);
+                self.load_immediate(
+                    register,
+                    address as i64,
+                    format!("将字符串 \"{}\" 的地址 {} 加载到 {}", text, address, register),
+                );
+            }
+            IrOperand::Var(_) | IrOperand::Temp(_) => {
+                let memory = self.memory_operand(operand);
+                self.emit("lw", &[register, &memory], format!("从 {} ({}) 加载到 {}", operand, memory, register));
+            }
+        }
+    }
+
+    /// 把寄存器写回四元式的结果操作数
+    fn store_result(&mut self, instruction: &IrInstruction, register: &str) {
+        let Some(result) = &instruction.result else { return };
+        let memory = self.memory_operand(result);
+        self.emit("sw", &[register, &memory], format!("将 {} 存储到 {} ({})", register, result, memory));
+    }
+}
+
+fn unsupported(op: &str) -> Diagnostic {
+    Diagnostic::error("E0301", format!("不支持的运算符 '{}'", op), SourceSpan::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::compile;
+    use crate::cpu_simulator::{CPUSimulator, Simulator};
+    use crate::mips::Mips;
+    use crate::types::{Architecture, OptimizationPass, Target};
+
+    /// 按各优化级别、开关延迟槽编译并运行，返回 $v0
+    fn run(source: &str) -> Vec<i64> {
+        let mut results = Vec::new();
+        for branch_delay_slot in [false, true] {
+            for level in 0..=2 {
+                let target = Target { architecture: Architecture::Mips, branch_delay_slot };
+                let result = compile(source, &OptimizationPass::for_level(level), target);
+                assert!(result.success, "{:?}", result.errors);
+                let mut simulator = CPUSimulator::new(Mips { branch_delay_slot });
+                simulator.load_instructions(result.instructions.clone()).unwrap();
+                while simulator.current_instruction_index < result.instructions.len() {
+                    simulator.step().unwrap();
+                }
+                results.push(simulator.state.registers.general["$2"]);
+            }
+        }
+        results
+    }
+
+    #[test]
+    fn runs_compiled_programs() {
+        assert!(run("int main() { return 2 + 3 * 4; }").iter().all(|value| *value == 14));
+        let fib =
+            "int fib(int n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }\nint main() { return fib(10); }";
+        assert!(run(fib).iter().all(|value| *value == 55));
+        let globals =
+            "int g = 5;\nint bump() { g = g + 1; return g; }\nint main() { int a = g; return a * 10 + bump(); }";
+        assert!(run(globals).iter().all(|value| *value == 56));
+        let arithmetic = "int main() { int a = -17; int b = 5; return (a / b) * 1000 + (a % b) * 10 + (a < b); }";
+        assert!(run(arithmetic).iter().all(|value| *value == -3019));
+    }
+
+    #[test]
+    fn passes_arguments_beyond_four_on_the_stack() {
+        let source = "int f(int a, int b, int c, int d, int e, int g) { return a - b + c * d - e * 10 + g * 100; }\n\
+                      int h(int a, int b, int c, int d, int e) { return f(e, d, c, b, a, e + a); }\n\
+                      int main() { return f(1, 2, 3, 4, 5, 6) * 1000 + h(1, 2, 3, 4, 5); }";
+        // f(1,2,3,4,5,6) = 1 - 2 + 12 - 50 + 600 = 561；h 调用 f(5,4,3,2,1,6) = 5 - 4 + 6 - 10 + 600 = 597
+        assert!(run(source).iter().all(|value| *value == 561_597));
+    }
+
+    #[test]
+    fn reserves_the_home_area_for_calls() {
+        let source = "int f(int a, int b, int c, int d, int e) { return e; }\nint main() { return f(1, 2, 3, 4, 5); }";
+        let target = Target { architecture: Architecture::Mips, branch_delay_slot: false };
+        let result = compile(source, &[], target);
+        assert!(result.success, "{:?}", result.errors);
+        let layout = &result.steps.iter().find(|step| step.id == "register_allocation").unwrap().output;
+        // main 的传参区包含 16 字节预留区和第 5 个参数；f 从 16($fp) 取得第 5 个参数
+        assert!(layout.contains("传参区 20 字节"), "{}", layout);
+        assert!(layout.contains("e            16($fp)"), "{}", layout);
+        let stores: Vec<_> = result
+            .instructions
+            .iter()
+            .filter(|instruction| instruction.operands.contains(&"16($sp)".to_string()))
+            .collect();
+        assert_eq!(stores.len(), 1);
+        assert_eq!(stores[0].mnemonic, "sw");
+    }
+}
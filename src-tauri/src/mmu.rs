@@ -0,0 +1,535 @@
+use crate::interrupt::Fault;
+use crate::types::{
+    AccessKind, MemorySegment, MemoryState, MmuConfig, PageMapping, PagePermissions, PageTableRead, PageTableView,
+    SegmentKind, TlbEntryView, Translation,
+};
+use std::collections::BTreeMap;
+
+/// 页大小 4 KiB：32 位虚拟地址分为 10 位页目录下标、10 位页表下标与 12 位页内偏移
+pub const PAGE_SIZE: u64 = 4096;
+/// 页表区的物理地址：页目录占第一页，页表按需依次分配在其后
+pub const PAGE_TABLE_BASE: u64 = 0x10_0000;
+pub const PAGE_TABLE_SIZE: u64 = 0x1_0000;
+/// TLB 的最大项数
+const MAX_TLB_ENTRIES: usize = 256;
+/// 页目录与页表各有 1024 项
+const ENTRIES: u64 = 1024;
+
+/// 页表项的各位：有效、可读、可写、可执行、用户态可访问、已访问、已修改，高 20 位为页框的物理地址。
+/// 页目录项只使用有效位与页框地址
+const VALID: u32 = 1 << 0;
+const READ: u32 = 1 << 1;
+const WRITE: u32 = 1 << 2;
+const EXECUTE: u32 = 1 << 3;
+const USER: u32 = 1 << 4;
+const ACCESSED: u32 = 1 << 5;
+const DIRTY: u32 = 1 << 6;
+const FRAME: u32 = !(PAGE_SIZE as u32 - 1);
+
+/// 检查 MMU 配置：TLB 至少一项，启用时页表区不能与栈重叠
+pub fn check_config(config: &MmuConfig, stack_top: u64, stack_size: u64) -> Result<(), String> {
+    if config.tlb_entries == 0 || config.tlb_entries > MAX_TLB_ENTRIES {
+        return Err(format!("TLB 的项数 {} 须在 1 到 {} 之间", config.tlb_entries, MAX_TLB_ENTRIES));
+    }
+    if config.enabled && stack_top > PAGE_TABLE_BASE && stack_top - stack_size < PAGE_TABLE_BASE + PAGE_TABLE_SIZE {
+        return Err(format!(
+            "栈 [0x{:X}, 0x{:X}) 与页表区 [0x{:X}, 0x{:X}) 重叠",
+            stack_top - stack_size,
+            stack_top,
+            PAGE_TABLE_BASE,
+            PAGE_TABLE_BASE + PAGE_TABLE_SIZE
+        ));
+    }
+    Ok(())
+}
+
+fn bits(permissions: PagePermissions) -> u32 {
+    [(permissions.read, READ), (permissions.write, WRITE), (permissions.execute, EXECUTE), (permissions.user, USER)]
+        .into_iter()
+        .filter(|(set, _)| *set)
+        .fold(0, |entry, (_, bit)| entry | bit)
+}
+
+fn permissions(entry: u32) -> PagePermissions {
+    PagePermissions {
+        read: entry & READ != 0,
+        write: entry & WRITE != 0,
+        execute: entry & EXECUTE != 0,
+        user: entry & USER != 0,
+    }
+}
+
+fn verb(access: AccessKind) -> &'static str {
+    match access {
+        AccessKind::Read => "读取",
+        AccessKind::Write => "写入",
+        AccessKind::Execute => "取指于",
+    }
+}
+
+/// 页表项在内存中按小端序存放；页表区总在某一段内，读写不做检查
+fn read_entry(memory: &MemoryState, address: u64) -> u32 {
+    (0..4).fold(0, |value, i| value | (memory.read_byte(address + i) as u32) << (8 * i))
+}
+
+fn write_entry(memory: &mut MemoryState, address: u64, value: u32) {
+    for i in 0..4 {
+        memory.write_byte(address + i, (value >> (8 * i)) as u8);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TlbEntry {
+    /// 虚拟页号
+    page: u64,
+    /// 缓存的页表项及其物理地址
+    entry: u32,
+    entry_address: u64,
+    last_used: u64,
+}
+
+/// 内存管理单元：两级页表存放在模拟的内存中，TLB 缓存最近用过的页表项
+pub struct Mmu {
+    config: MmuConfig,
+    tlb: Vec<TlbEntry>,
+    clock: u64,
+    /// 下一个分配给页表的页框
+    next_table: u64,
+    hits: u64,
+    misses: u64,
+    faults: u64,
+}
+
+impl Default for Mmu {
+    fn default() -> Self {
+        Self::new(MmuConfig::default())
+    }
+}
+
+impl Mmu {
+    pub fn new(config: MmuConfig) -> Self {
+        Self {
+            config,
+            tlb: Vec::new(),
+            clock: 0,
+            next_table: PAGE_TABLE_BASE + PAGE_SIZE,
+            hits: 0,
+            misses: 0,
+            faults: 0,
+        }
+    }
+
+    pub fn config(&self) -> MmuConfig {
+        self.config
+    }
+
+    /// 在新建的内存中建立页表：加入页表区，把各段所在的页恒等映射（虚拟地址等于物理地址）。
+    /// 代码段可读、可执行，其余段可读写，页表区只允许内核态访问；两段共用的页取两者权限的并集
+    pub fn install(&mut self, memory: &mut MemoryState) {
+        *self = Self::new(self.config);
+        if !self.config.enabled {
+            return;
+        }
+        memory.segments.push(MemorySegment {
+            kind: SegmentKind::PageTable,
+            name: "页表区".to_string(),
+            base: PAGE_TABLE_BASE,
+            size: PAGE_TABLE_SIZE,
+            writable: true,
+        });
+        let mut pages: BTreeMap<u64, u32> = BTreeMap::new();
+        for segment in &memory.segments {
+            let entry = match segment.kind {
+                SegmentKind::Code => READ | EXECUTE | USER,
+                SegmentKind::PageTable | SegmentKind::InterruptTable => READ | WRITE,
+                _ => READ | WRITE | USER,
+            };
+            for page in segment.base / PAGE_SIZE..(segment.base + segment.size).div_ceil(PAGE_SIZE) {
+                *pages.entry(page).or_default() |= entry;
+            }
+        }
+        for (page, entry) in pages {
+            let address = page * PAGE_SIZE;
+            // 页表区可容纳 15 个页表，足以映射经过检查的各段
+            let _ = self.map(memory, address, address, permissions(entry));
+        }
+    }
+
+    /// 页目录项与页表项的物理地址
+    fn directory_entry_address(address: u64) -> u64 {
+        PAGE_TABLE_BASE + (address >> 22) % ENTRIES * 4
+    }
+
+    fn table_entry_address(directory_entry: u32, address: u64) -> u64 {
+        (directory_entry & FRAME) as u64 + (address >> 12) % ENTRIES * 4
+    }
+
+    /// 把虚拟页 virtual_address 映射到物理页框 physical_address，需要时分配页表；两者均须按页对齐
+    pub fn map(
+        &mut self,
+        memory: &mut MemoryState,
+        virtual_address: u64,
+        physical_address: u64,
+        permissions: PagePermissions,
+    ) -> Result<(), String> {
+        if !self.config.enabled {
+            return Err("未启用 MMU".to_string());
+        }
+        for address in [virtual_address, physical_address] {
+            if !address.is_multiple_of(PAGE_SIZE) || address > u32::MAX as u64 {
+                return Err(format!("地址 0x{:X} 未按 {} 字节的页对齐或超出 32 位地址空间", address, PAGE_SIZE));
+            }
+        }
+        let directory_address = Self::directory_entry_address(virtual_address);
+        let mut directory_entry = read_entry(memory, directory_address);
+        if directory_entry & VALID == 0 {
+            if self.next_table >= PAGE_TABLE_BASE + PAGE_TABLE_SIZE {
+                return Err("页表区已满，无法再分配页表".to_string());
+            }
+            directory_entry = self.next_table as u32 | VALID;
+            self.next_table += PAGE_SIZE;
+            write_entry(memory, directory_address, directory_entry);
+        }
+        let entry = physical_address as u32 | bits(permissions) | VALID;
+        write_entry(memory, Self::table_entry_address(directory_entry, virtual_address), entry);
+        self.flush(virtual_address);
+        Ok(())
+    }
+
+    /// 取消虚拟页 virtual_address 的映射
+    pub fn unmap(&mut self, memory: &mut MemoryState, virtual_address: u64) -> Result<(), String> {
+        if !self.config.enabled {
+            return Err("未启用 MMU".to_string());
+        }
+        let (_, table) = self.walk(memory, virtual_address);
+        match table {
+            Some(table) if table.value & VALID != 0 => {
+                write_entry(memory, table.address, 0);
+                self.flush(virtual_address);
+                Ok(())
+            }
+            _ => Err(format!("虚拟地址 0x{:X} 所在的页没有映射", virtual_address)),
+        }
+    }
+
+    /// 页表改变后清除 TLB 中该页的项
+    fn flush(&mut self, virtual_address: u64) {
+        self.tlb.retain(|entry| entry.page != virtual_address / PAGE_SIZE);
+    }
+
+    /// 遍历页表：读出页目录项，页目录项有效时再读出页表项
+    fn walk(&self, memory: &MemoryState, address: u64) -> (PageTableRead, Option<PageTableRead>) {
+        let directory_address = Self::directory_entry_address(address);
+        let directory = PageTableRead { address: directory_address, value: read_entry(memory, directory_address) };
+        let table = (directory.value & VALID != 0).then(|| {
+            let table_address = Self::table_entry_address(directory.value, address);
+            PageTableRead { address: table_address, value: read_entry(memory, table_address) }
+        });
+        (directory, table)
+    }
+
+    /// 按页表项检查访问权限，违反时给出页错误的说明
+    fn check(&self, entry: u32, address: u64, access: AccessKind) -> Result<(), Fault> {
+        let (bit, what) = match access {
+            AccessKind::Read => (READ, "读"),
+            AccessKind::Write => (WRITE, "写"),
+            AccessKind::Execute => (EXECUTE, "执行"),
+        };
+        if entry & bit == 0 {
+            let message = format!("页保护异常（#PF）：{}虚拟地址 0x{:X} 时该页不可{}", verb(access), address, what);
+            return Err(Fault::page_fault(message));
+        }
+        if self.config.user_mode && entry & USER == 0 {
+            let message = format!("页保护异常（#PF）：用户态不能{}内核页上的虚拟地址 0x{:X}", verb(access), address);
+            return Err(Fault::page_fault(message));
+        }
+        Ok(())
+    }
+
+    /// 不改变 TLB 与页表项的转换，用于执行阶段读取内存操作数与显示栈视图
+    pub fn resolve(&self, memory: &MemoryState, address: u64, access: AccessKind) -> Result<u64, Fault> {
+        if !self.config.enabled {
+            return Ok(address);
+        }
+        let page = address / PAGE_SIZE;
+        let entry = match self.tlb.iter().find(|entry| entry.page == page) {
+            Some(entry) => entry.entry,
+            None => match self.walk(memory, address) {
+                (_, Some(table)) if table.value & VALID != 0 => table.value,
+                (_, table) => {
+                    let level = if table.is_some() { "页表项" } else { "页目录项" };
+                    let message = format!("缺页异常（#PF）：{}虚拟地址 0x{:X} 时{}无效", verb(access), address, level);
+                    return Err(Fault::page_fault(message));
+                }
+            },
+        };
+        self.check(entry, address, access)?;
+        Ok((entry & FRAME) as u64 | (address % PAGE_SIZE))
+    }
+
+    /// 读取虚拟地址处的 size 字节（小端序，不要求对齐），跨页时逐字节转换
+    pub fn read(&self, memory: &MemoryState, address: u64, size: u8) -> Result<u64, Fault> {
+        let last = address + size.max(1) as u64 - 1;
+        if !self.config.enabled || address / PAGE_SIZE == last / PAGE_SIZE {
+            return memory.read(self.resolve(memory, address, AccessKind::Read)?, size, false);
+        }
+        (0..size as u64).try_fold(0, |value, i| {
+            let byte = memory.read(self.resolve(memory, address + i, AccessKind::Read)?, 1, false)?;
+            Ok(value | byte << (8 * i))
+        })
+    }
+
+    /// 转换一个虚拟地址：先查 TLB，不命中时遍历页表并装入 TLB（满时替换最久未用的项）；
+    /// 检查权限后置位页表项的已访问位，写入时再置位已修改位。出现页错误时 physical_address 为 None
+    pub fn translate(&mut self, memory: &mut MemoryState, address: u64, access: AccessKind) -> Translation {
+        let (page, offset) = (address / PAGE_SIZE, address % PAGE_SIZE);
+        let mut translation = Translation {
+            virtual_address: address,
+            access,
+            directory_index: (address >> 22) % ENTRIES,
+            table_index: (address >> 12) % ENTRIES,
+            offset,
+            tlb_hit: false,
+            directory_entry: None,
+            table_entry: None,
+            physical_address: None,
+            fault: None,
+            cycles: 0,
+            steps: Vec::new(),
+        };
+        translation.steps.push(format!(
+            "虚拟地址 0x{:08X}：页目录下标 {}，页表下标 {}，页内偏移 0x{:03X}",
+            address, translation.directory_index, translation.table_index, offset
+        ));
+        self.clock += 1;
+
+        let (entry, entry_address) = match self.tlb.iter_mut().find(|entry| entry.page == page) {
+            Some(cached) => {
+                self.hits += 1;
+                cached.last_used = self.clock;
+                translation.tlb_hit = true;
+                translation.steps.push(format!("TLB 命中：虚拟页 0x{:05X} 的页表项为 0x{:08X}", page, cached.entry));
+                (cached.entry, cached.entry_address)
+            }
+            None => {
+                self.misses += 1;
+                translation.cycles = self.config.walk_latency;
+                let (directory, table) = self.walk(memory, address);
+                translation.directory_entry = Some(directory);
+                translation.table_entry = table;
+                translation.steps.push(format!(
+                    "TLB 不命中，遍历页表：页目录基址 0x{:X}，读取 0x{:X} 处的页目录项 0x{:08X}",
+                    PAGE_TABLE_BASE, directory.address, directory.value
+                ));
+                let Some(table) = table else {
+                    let fault = format!("缺页异常（#PF）：{}虚拟地址 0x{:X} 时页目录项无效", verb(access), address);
+                    return self.fault(translation, fault);
+                };
+                translation.steps.push(format!(
+                    "页表基址 0x{:X}，读取 0x{:X} 处的页表项 0x{:08X}",
+                    directory.value & FRAME,
+                    table.address,
+                    table.value
+                ));
+                if table.value & VALID == 0 {
+                    let fault = format!("缺页异常（#PF）：{}虚拟地址 0x{:X} 时页表项无效", verb(access), address);
+                    return self.fault(translation, fault);
+                }
+                (table.value, table.address)
+            }
+        };
+        if let Err(fault) = self.check(entry, address, access) {
+            return self.fault(translation, fault.message);
+        }
+
+        let updated = entry | ACCESSED | if access == AccessKind::Write { DIRTY } else { 0 };
+        if updated != entry {
+            write_entry(memory, entry_address, updated);
+            translation.steps.push(format!(
+                "置位页表项的已访问{}位",
+                if updated & DIRTY != entry & DIRTY { "与已修改" } else { "" }
+            ));
+        }
+        match self.tlb.iter_mut().find(|cached| cached.page == page) {
+            Some(cached) => cached.entry = updated,
+            None => {
+                let entry = TlbEntry { page, entry: updated, entry_address, last_used: self.clock };
+                if self.tlb.len() < self.config.tlb_entries {
+                    self.tlb.push(entry);
+                    translation.steps.push("装入 TLB".to_string());
+                } else if let Some(victim) = self.tlb.iter_mut().min_by_key(|cached| cached.last_used) {
+                    translation.steps.push(format!("装入 TLB，替换最久未用的虚拟页 0x{:05X}", victim.page));
+                    *victim = entry;
+                }
+            }
+        }
+        let physical = (updated & FRAME) as u64 | offset;
+        translation.physical_address = Some(physical);
+        translation.steps.push(format!(
+            "物理地址 = 页框 0x{:X} + 偏移 0x{:X} = 0x{:X}",
+            updated & FRAME,
+            offset,
+            physical
+        ));
+        translation
+    }
+
+    fn fault(&mut self, mut translation: Translation, fault: String) -> Translation {
+        self.faults += 1;
+        translation.steps.push(fault.clone());
+        translation.fault = Some(fault);
+        translation
+    }
+
+    pub fn view(&self, memory: &MemoryState) -> PageTableView {
+        let mut mappings = Vec::new();
+        for directory_index in 0..ENTRIES {
+            let directory = read_entry(memory, PAGE_TABLE_BASE + directory_index * 4);
+            if directory & VALID == 0 {
+                continue;
+            }
+            for table_index in 0..ENTRIES {
+                let entry_address = (directory & FRAME) as u64 + table_index * 4;
+                let entry = read_entry(memory, entry_address);
+                if entry & VALID != 0 {
+                    mappings.push(PageMapping {
+                        virtual_address: (directory_index << 22) | (table_index << 12),
+                        physical_address: (entry & FRAME) as u64,
+                        permissions: permissions(entry),
+                        accessed: entry & ACCESSED != 0,
+                        dirty: entry & DIRTY != 0,
+                        entry_address,
+                    });
+                }
+            }
+        }
+        PageTableView {
+            config: self.config,
+            directory: PAGE_TABLE_BASE,
+            mappings,
+            tlb: self
+                .tlb
+                .iter()
+                .map(|cached| TlbEntryView {
+                    virtual_address: cached.page * PAGE_SIZE,
+                    physical_address: (cached.entry & FRAME) as u64,
+                    permissions: permissions(cached.entry),
+                    dirty: cached.entry & DIRTY != 0,
+                })
+                .collect(),
+            tlb_hits: self.hits,
+            tlb_misses: self.misses,
+            page_faults: self.faults,
+        }
+    }
+}
+
+/// 一次转换的概要，如 “0x3E8 -> 0x3E8（TLB 命中）”
+pub fn describe(translation: &Translation) -> String {
+    match (&translation.fault, translation.physical_address) {
+        (Some(fault), _) => fault.clone(),
+        (None, Some(physical)) => {
+            let how = if translation.tlb_hit { "TLB 命中" } else { "遍历页表" };
+            format!("0x{:X} -> 0x{:X}（{}）", translation.virtual_address, physical, how)
+        }
+        (None, None) => format!("0x{:X} 未转换", translation.virtual_address),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const READ_WRITE: PagePermissions = PagePermissions { read: true, write: true, execute: false, user: true };
+
+    /// 启用 MMU 并在新建的内存中建立恒等映射的页表
+    fn installed(config: MmuConfig) -> (Mmu, MemoryState) {
+        let (mut mmu, mut memory) = (Mmu::new(MmuConfig { enabled: true, ..config }), MemoryState::default());
+        mmu.install(&mut memory);
+        (mmu, memory)
+    }
+
+    #[test]
+    fn identity_mapping_and_tlb() {
+        let (mut mmu, mut memory) = installed(MmuConfig::default());
+        let first = mmu.translate(&mut memory, 0x10, AccessKind::Execute);
+        assert_eq!(first.physical_address, Some(0x10));
+        assert!(!first.tlb_hit);
+        assert_eq!(first.cycles, 20);
+        let second = mmu.translate(&mut memory, 0x14, AccessKind::Read);
+        assert_eq!(second.physical_address, Some(0x14));
+        assert!(second.tlb_hit);
+        assert_eq!(second.cycles, 0);
+    }
+
+    #[test]
+    fn mapped_page() {
+        let (mut mmu, mut memory) = installed(MmuConfig::default());
+        mmu.map(&mut memory, 0x40_0000, 0x2000, READ_WRITE).unwrap();
+        let translation = mmu.translate(&mut memory, 0x40_0123, AccessKind::Write);
+        assert_eq!((translation.directory_index, translation.table_index, translation.offset), (1, 0, 0x123));
+        assert_eq!(translation.physical_address, Some(0x2123));
+        assert_eq!(mmu.resolve(&memory, 0x40_0FFF, AccessKind::Read), Ok(0x2FFF));
+        // 写入置位了页表项的已访问位与已修改位
+        let view = mmu.view(&memory);
+        let mapping = view.mappings.iter().find(|mapping| mapping.virtual_address == 0x40_0000).unwrap();
+        assert!(mapping.accessed && mapping.dirty);
+        assert!(mmu.map(&mut memory, 0x40_0010, 0x2000, READ_WRITE).is_err());
+    }
+
+    #[test]
+    fn page_not_present() {
+        let (mut mmu, mut memory) = installed(MmuConfig::default());
+        mmu.map(&mut memory, 0x40_0000, 0x2000, READ_WRITE).unwrap();
+        let directory = mmu.translate(&mut memory, 0x80_0000, AccessKind::Read);
+        assert_eq!(directory.physical_address, None);
+        assert!(directory.table_entry.is_none());
+        assert!(directory.fault.unwrap().contains("缺页异常（#PF）"));
+        let table = mmu.translate(&mut memory, 0x40_1000, AccessKind::Read);
+        assert!(table.table_entry.is_some());
+        assert!(table.fault.unwrap().contains("页表项无效"));
+        assert!(mmu.resolve(&memory, 0x40_1000, AccessKind::Read).is_err());
+        assert_eq!(mmu.view(&memory).page_faults, 2);
+    }
+
+    #[test]
+    fn protection_faults() {
+        let (mut mmu, mut memory) = installed(MmuConfig::default());
+        // 只读的页不可写，页表区只允许内核态访问
+        let read_only = PagePermissions { write: false, ..READ_WRITE };
+        mmu.map(&mut memory, 0x40_0000, 0x2000, read_only).unwrap();
+        assert!(mmu.translate(&mut memory, 0x40_0000, AccessKind::Read).fault.is_none());
+        let write = mmu.translate(&mut memory, 0x40_0000, AccessKind::Write);
+        assert!(write.fault.unwrap().contains("页保护异常（#PF）"));
+        let kernel = mmu.translate(&mut memory, PAGE_TABLE_BASE, AccessKind::Read);
+        assert!(kernel.fault.unwrap().contains("用户态"));
+
+        let (mut mmu, mut memory) = installed(MmuConfig { user_mode: false, ..MmuConfig::default() });
+        let kernel = mmu.translate(&mut memory, PAGE_TABLE_BASE, AccessKind::Read);
+        assert_eq!(kernel.physical_address, Some(PAGE_TABLE_BASE));
+    }
+
+    #[test]
+    fn tlb_replaces_least_recently_used() {
+        let (mut mmu, mut memory) = installed(MmuConfig { tlb_entries: 2, ..MmuConfig::default() });
+        for page in [0x0000, 0x1000, 0x0000, 0x2000] {
+            mmu.translate(&mut memory, page, AccessKind::Read);
+        }
+        let cached: Vec<_> = mmu.view(&memory).tlb.iter().map(|entry| entry.virtual_address).collect();
+        assert_eq!(cached, [0x0000, 0x2000]);
+        assert!(mmu.translate(&mut memory, 0x0000, AccessKind::Read).tlb_hit);
+        assert!(!mmu.translate(&mut memory, 0x1000, AccessKind::Read).tlb_hit);
+    }
+
+    #[test]
+    fn unmap_flushes_tlb() {
+        let (mut mmu, mut memory) = installed(MmuConfig::default());
+        mmu.map(&mut memory, 0x40_0000, 0x2000, READ_WRITE).unwrap();
+        assert!(mmu.translate(&mut memory, 0x40_0000, AccessKind::Read).fault.is_none());
+        mmu.unmap(&mut memory, 0x40_0000).unwrap();
+        let translation = mmu.translate(&mut memory, 0x40_0000, AccessKind::Read);
+        assert!(!translation.tlb_hit);
+        assert!(translation.fault.is_some());
+        assert!(mmu.unmap(&mut memory, 0x40_0000).is_err());
+    }
+}
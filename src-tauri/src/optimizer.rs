@@ -0,0 +1,519 @@
+use crate::ir::{IrFunction, IrInstruction, IrOp, IrOperand, IrProgram};
+use crate::types::*;
+use std::collections::{HashMap, HashSet};
+
+/// 各优化遍最多反复执行的轮数
+const MAX_ROUNDS: usize = 5;
+
+/// 中间代码优化器：按固定顺序执行启用的优化遍，反复迭代直到不再产生修改。
+/// 常量传播、复制传播与公共子表达式消除只在基本块内进行，遇到标签（汇合点）即丢弃已知信息。
+pub struct Optimizer {
+    passes: Vec<OptimizationPass>,
+    reports: Vec<OptimizationReport>,
+}
+
+impl Optimizer {
+    pub fn new(passes: &[OptimizationPass]) -> Self {
+        Self {
+            passes: OptimizationPass::ALL
+                .into_iter()
+                .filter(|pass| passes.contains(pass))
+                .collect(),
+            reports: Vec::new(),
+        }
+    }
+
+    pub fn passes(&self) -> &[OptimizationPass] {
+        &self.passes
+    }
+
+    pub fn reports(&self) -> &[OptimizationReport] {
+        &self.reports
+    }
+
+    pub fn optimize(&mut self, program: &mut IrProgram) {
+        let globals: HashSet<String> = program.globals.iter().cloned().collect();
+
+        for round in 1..=MAX_ROUNDS {
+            let mut changed = false;
+            for pass in self.passes.clone() {
+                let before = program.dump();
+                let mut changes = Vec::new();
+                for function in &mut program.functions {
+                    let function_changes = match pass {
+                        OptimizationPass::ConstantPropagation => propagate_constants(function, &globals),
+                        OptimizationPass::CopyPropagation => propagate_copies(function, &globals),
+                        OptimizationPass::ConstantFolding => fold_constants(function),
+                        OptimizationPass::StrengthReduction => reduce_strength(function),
+                        OptimizationPass::CommonSubexpressionElimination => eliminate_common_subexpressions(function, &globals),
+                        OptimizationPass::DeadCodeElimination => eliminate_dead_code(function, &globals),
+                    };
+                    changes.extend(function_changes.into_iter().map(|c| format!("{}: {}", function.name, c)));
+                }
+
+                // 第一轮总是记录，之后只记录产生了修改的执行
+                if round == 1 || !changes.is_empty() {
+                    changed |= !changes.is_empty();
+                    self.reports.push(OptimizationReport {
+                        pass,
+                        round,
+                        changes,
+                        before,
+                        after: program.dump(),
+                    });
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+    }
+}
+
+/// 修改记录：`旧指令` → `新指令`
+fn rewrite(before: &IrInstruction, after: &IrInstruction) -> String {
+    format!("`{}` → `{}`", before.to_string().trim(), after.to_string().trim())
+}
+
+fn is_global(operand: &IrOperand, globals: &HashSet<String>) -> bool {
+    matches!(operand, IrOperand::Var(name) if globals.contains(name))
+}
+
+fn assign(value: IrOperand, result: Option<IrOperand>) -> IrInstruction {
+    IrInstruction {
+        op: IrOp::Assign,
+        arg1: Some(value),
+        arg2: None,
+        result,
+    }
+}
+
+/// 按 32 位整数语义计算二元运算，除数为 0 时不折叠
+fn evaluate(op: &str, a: i64, b: i64) -> Option<i64> {
+    let (a, b) = (a as i32, b as i32);
+    let value = match op {
+        "+" => a.wrapping_add(b),
+        "-" => a.wrapping_sub(b),
+        "*" => a.wrapping_mul(b),
+        "/" if b != 0 => a.wrapping_div(b),
+        "%" if b != 0 => a.wrapping_rem(b),
+        "<<" => a.wrapping_shl(b as u32),
+        "==" => (a == b) as i32,
+        "!=" => (a != b) as i32,
+        "<" => (a < b) as i32,
+        ">" => (a > b) as i32,
+        "<=" => (a <= b) as i32,
+        ">=" => (a >= b) as i32,
+        _ => return None,
+    };
+    Some(value as i64)
+}
+
+/// 常量传播：把已知为常量的变量替换为常量本身
+fn propagate_constants(function: &mut IrFunction, globals: &HashSet<String>) -> Vec<String> {
+    let mut changes = Vec::new();
+    let mut known: HashMap<IrOperand, i64> = HashMap::new();
+
+    for instruction in &mut function.body {
+        if matches!(instruction.op, IrOp::Label(_)) {
+            known.clear();
+            continue;
+        }
+
+        let original = instruction.clone();
+        for arg in [&mut instruction.arg1, &mut instruction.arg2].into_iter().flatten() {
+            if let Some(value) = known.get(arg) {
+                *arg = IrOperand::Const(*value);
+            }
+        }
+        if *instruction != original {
+            changes.push(rewrite(&original, instruction));
+        }
+
+        // 被调函数可能修改全局变量
+        if matches!(instruction.op, IrOp::Call(..)) {
+            known.retain(|operand, _| !is_global(operand, globals));
+        }
+        if let Some(result) = &instruction.result {
+            known.remove(result);
+            if let (IrOp::Assign, Some(IrOperand::Const(value))) = (&instruction.op, &instruction.arg1) {
+                known.insert(result.clone(), *value);
+            }
+        }
+    }
+    changes
+}
+
+/// 复制传播：x = y 之后把对 x 的使用替换为 y，直到 x 或 y 被重新赋值
+fn propagate_copies(function: &mut IrFunction, globals: &HashSet<String>) -> Vec<String> {
+    let mut changes = Vec::new();
+    let mut copies: HashMap<IrOperand, IrOperand> = HashMap::new();
+
+    for instruction in &mut function.body {
+        if matches!(instruction.op, IrOp::Label(_)) {
+            copies.clear();
+            continue;
+        }
+
+        let original = instruction.clone();
+        for arg in [&mut instruction.arg1, &mut instruction.arg2].into_iter().flatten() {
+            if let Some(source) = copies.get(arg) {
+                *arg = source.clone();
+            }
+        }
+        if *instruction != original {
+            changes.push(rewrite(&original, instruction));
+        }
+
+        if matches!(instruction.op, IrOp::Call(..)) {
+            copies.retain(|copy, source| !is_global(copy, globals) && !is_global(source, globals));
+        }
+        if let Some(result) = &instruction.result {
+            copies.retain(|copy, source| copy != result && source != result);
+            if let (IrOp::Assign, Some(source @ (IrOperand::Var(_) | IrOperand::Temp(_)))) =
+                (&instruction.op, &instruction.arg1)
+            {
+                if source != result {
+                    copies.insert(result.clone(), source.clone());
+                }
+            }
+        }
+    }
+    changes
+}
+
+/// 常量折叠：在编译期计算操作数全为常量的运算和条件跳转
+fn fold_constants(function: &mut IrFunction) -> Vec<String> {
+    let mut changes = Vec::new();
+    let mut body = Vec::with_capacity(function.body.len());
+
+    for instruction in function.body.drain(..) {
+        let folded = match (&instruction.op, &instruction.arg1, &instruction.arg2) {
+            (IrOp::Binary(op), Some(IrOperand::Const(a)), Some(IrOperand::Const(b))) => {
+                evaluate(op, *a, *b).map(|value| Some(assign(IrOperand::Const(value), instruction.result.clone())))
+            }
+            (IrOp::Neg, Some(IrOperand::Const(a)), _) => {
+                Some(Some(assign(IrOperand::Const((*a as i32).wrapping_neg() as i64), instruction.result.clone())))
+            }
+            (IrOp::Not, Some(IrOperand::Const(a)), _) => {
+                Some(Some(assign(IrOperand::Const((*a == 0) as i64), instruction.result.clone())))
+            }
+            (IrOp::Cast(data_type), Some(IrOperand::Const(a)), _) => {
+                let value = if data_type == "char" { *a as i8 as i64 } else { *a };
+                Some(Some(assign(IrOperand::Const(value), instruction.result.clone())))
+            }
+            (IrOp::CondJump(relop, label), Some(IrOperand::Const(a)), Some(IrOperand::Const(b))) => {
+                // 条件恒真变为无条件跳转，恒假则删除
+                evaluate(relop, *a, *b).map(|taken| {
+                    (taken != 0).then(|| IrInstruction {
+                        op: IrOp::Jump(label.clone()),
+                        arg1: None,
+                        arg2: None,
+                        result: None,
+                    })
+                })
+            }
+            _ => None,
+        };
+
+        match folded {
+            Some(Some(replacement)) => {
+                changes.push(rewrite(&instruction, &replacement));
+                body.push(replacement);
+            }
+            Some(None) => changes.push(format!("删除恒不成立的跳转 `{}`", instruction.to_string().trim())),
+            None => body.push(instruction),
+        }
+    }
+
+    function.body = body;
+    changes
+}
+
+/// 强度削减：乘以 2 的幂改为左移，并化简乘 0/1、加减 0、除以 1
+fn reduce_strength(function: &mut IrFunction) -> Vec<String> {
+    let mut changes = Vec::new();
+
+    for instruction in &mut function.body {
+        let IrOp::Binary(op) = &instruction.op else { continue };
+        let (Some(a), Some(b)) = (&instruction.arg1, &instruction.arg2) else { continue };
+        let result = instruction.result.clone();
+
+        // 乘法和加法可交换，把常量放到右边统一处理
+        let (x, constant) = match (a, b) {
+            (_, IrOperand::Const(c)) => (a.clone(), Some(*c)),
+            (IrOperand::Const(c), _) if op == "*" || op == "+" => (b.clone(), Some(*c)),
+            _ => (a.clone(), None),
+        };
+        let Some(c) = constant else { continue };
+
+        let replacement = match (op.as_str(), c) {
+            ("*", 0) => assign(IrOperand::Const(0), result),
+            ("*", 1) | ("+", 0) | ("-", 0) | ("/", 1) => assign(x, result),
+            ("*", c) if c > 1 && (c & (c - 1)) == 0 => IrInstruction {
+                op: IrOp::Binary("<<".to_string()),
+                arg1: Some(x),
+                arg2: Some(IrOperand::Const(c.trailing_zeros() as i64)),
+                result,
+            },
+            _ => continue,
+        };
+        changes.push(rewrite(instruction, &replacement));
+        *instruction = replacement;
+    }
+    changes
+}
+
+/// 表达式的规范形式：可交换运算的操作数按文本排序
+fn expression_key(instruction: &IrInstruction) -> Option<(String, Vec<IrOperand>)> {
+    let op = match &instruction.op {
+        IrOp::Binary(op) => op.clone(),
+        IrOp::Neg => "neg".to_string(),
+        IrOp::Not => "not".to_string(),
+        IrOp::Cast(data_type) => format!("cast {}", data_type),
+        _ => return None,
+    };
+    let mut operands: Vec<IrOperand> = instruction.uses().into_iter().cloned().collect();
+    if matches!(op.as_str(), "+" | "*" | "==" | "!=") {
+        operands.sort_by_key(|operand| operand.to_string());
+    }
+    Some((op, operands))
+}
+
+/// 公共子表达式消除：基本块内重复计算的表达式改为复制先前的结果
+fn eliminate_common_subexpressions(function: &mut IrFunction, globals: &HashSet<String>) -> Vec<String> {
+    let mut changes = Vec::new();
+    let mut available: HashMap<(String, Vec<IrOperand>), IrOperand> = HashMap::new();
+
+    for instruction in &mut function.body {
+        if matches!(instruction.op, IrOp::Label(_)) {
+            available.clear();
+            continue;
+        }
+
+        let key = expression_key(instruction);
+        if let Some(key) = &key {
+            if let Some(holder) = available.get(key).filter(|holder| Some(*holder) != instruction.result.as_ref()) {
+                let replacement = assign(holder.clone(), instruction.result.clone());
+                changes.push(rewrite(instruction, &replacement));
+                *instruction = replacement;
+            }
+        }
+
+        if matches!(instruction.op, IrOp::Call(..)) {
+            available.retain(|(_, operands), holder| {
+                !is_global(holder, globals) && !operands.iter().any(|operand| is_global(operand, globals))
+            });
+        }
+        if let Some(result) = &instruction.result {
+            // 结果被重新赋值后，依赖它的表达式都失效
+            available.retain(|(_, operands), holder| holder != result && !operands.contains(result));
+            if let Some(key) = key.filter(|(_, operands)| !operands.contains(result)) {
+                if expression_key(instruction).is_some() {
+                    available.insert(key, result.clone());
+                }
+            }
+        }
+    }
+    changes
+}
+
+/// 死代码消除：删除不可达代码、多余的跳转与标签，以及结果从未被使用的赋值
+fn eliminate_dead_code(function: &mut IrFunction, globals: &HashSet<String>) -> Vec<String> {
+    let mut changes = Vec::new();
+
+    loop {
+        let count = changes.len();
+
+        // 无条件跳转、返回和停机之后直到下一个标签的代码不可达
+        let mut reachable = true;
+        function.body.retain(|instruction| {
+            if matches!(instruction.op, IrOp::Label(_)) {
+                reachable = true;
+            }
+            if !reachable {
+                changes.push(format!("删除不可达代码 `{}`", instruction.to_string().trim()));
+                return false;
+            }
+            if matches!(instruction.op, IrOp::Jump(_) | IrOp::Return | IrOp::Halt) {
+                reachable = false;
+            }
+            true
+        });
+
+        // 跳转到紧随其后的标签
+        let mut index = 0;
+        while index < function.body.len() {
+            if let IrOp::Jump(target) = &function.body[index].op {
+                let falls_through = function.body[index + 1..]
+                    .iter()
+                    .take_while(|next| matches!(next.op, IrOp::Label(_)))
+                    .any(|next| next.op == IrOp::Label(target.clone()));
+                if falls_through {
+                    changes.push(format!("删除多余的跳转 `goto {}`", target));
+                    function.body.remove(index);
+                    continue;
+                }
+            }
+            index += 1;
+        }
+
+        // 没有被任何跳转引用的标签
+        let targets: HashSet<String> = function
+            .body
+            .iter()
+            .filter_map(|instruction| match &instruction.op {
+                IrOp::Jump(label) | IrOp::CondJump(_, label) => Some(label.clone()),
+                _ => None,
+            })
+            .collect();
+        function.body.retain(|instruction| match &instruction.op {
+            IrOp::Label(label) if !targets.contains(label) => {
+                changes.push(format!("删除未使用的标签 {}", label));
+                false
+            }
+            _ => true,
+        });
+
+        // 结果从未被读取的赋值；全局变量对其他函数可见，必须保留
+        let used: HashSet<IrOperand> = function.body.iter().flat_map(|i| i.uses()).cloned().collect();
+        let is_dead = |operand: &IrOperand| !used.contains(operand) && !is_global(operand, globals);
+        let mut body = Vec::with_capacity(function.body.len());
+        for mut instruction in function.body.drain(..) {
+            match &instruction.result {
+                Some(result) if is_dead(result) => {
+                    let text = instruction.to_string().trim().to_string();
+                    if matches!(instruction.op, IrOp::Call(..)) {
+                        // 调用本身可能有副作用，只丢弃返回值
+                        instruction.result = None;
+                        changes.push(format!("丢弃未使用的返回值 `{}`", text));
+                        body.push(instruction);
+                    } else {
+                        changes.push(format!("删除无用赋值 `{}`", text));
+                    }
+                }
+                _ => body.push(instruction),
+            }
+        }
+        function.body = body;
+
+        if changes.len() == count {
+            break;
+        }
+    }
+
+    // 不再出现的局部变量不必分配栈槽
+    let referenced: HashSet<IrOperand> = function
+        .body
+        .iter()
+        .flat_map(|i| i.uses().into_iter().chain(i.result.iter()))
+        .cloned()
+        .collect();
+    function.locals.retain(|name| referenced.contains(&IrOperand::Var(name.clone())));
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{Lexer, Parser};
+    use crate::ir::IrBuilder;
+    use crate::semantic::SemanticAnalyzer;
+
+    /// 生成中间代码并执行给定的优化遍
+    fn optimize(source: &str, passes: &[OptimizationPass]) -> (IrProgram, Vec<OptimizationReport>) {
+        let mut ast = Parser::new(Lexer::new(source.to_string()).tokenize()).parse();
+        SemanticAnalyzer::new().analyze(&mut ast);
+        let mut program = IrBuilder::new().build(&ast).unwrap();
+        let mut optimizer = Optimizer::new(passes);
+        optimizer.optimize(&mut program);
+        (program, optimizer.reports().to_vec())
+    }
+
+    /// 中间代码文本中某个函数的函数体
+    fn body<'a>(dump: &'a str, name: &str) -> &'a str {
+        let start = dump.find(&format!("function {}(", name)).unwrap();
+        let start = start + dump[start..].find('\n').unwrap() + 1;
+        &dump[start..start + dump[start..].find("end\n").unwrap()]
+    }
+
+    #[test]
+    fn folds_constant_expressions() {
+        let (program, reports) = optimize("int main() { return 2 + 3 * 4; }", &[OptimizationPass::ConstantFolding]);
+        // 第二轮没有修改，不再记录
+        assert_eq!(reports.len(), 1);
+        let report = &reports[0];
+        assert_eq!((report.pass, report.round), (OptimizationPass::ConstantFolding, 1));
+        assert_eq!(report.changes, ["main: `t1 = 3 * 4` → `t1 = 12`"]);
+        assert_eq!(body(&report.before, "main"), "    t1 = 3 * 4\n    t2 = 2 + t1\n    return t2\n");
+        assert_eq!(body(&report.after, "main"), "    t1 = 12\n    t2 = 2 + t1\n    return t2\n");
+        assert_eq!(report.after, program.dump());
+    }
+
+    #[test]
+    fn propagates_and_folds_until_nothing_changes() {
+        let passes = [OptimizationPass::ConstantPropagation, OptimizationPass::ConstantFolding];
+        let (program, reports) = optimize("int main() { int x = 3; int y = x * 4; return y + x; }", &passes);
+        let summary: Vec<_> = reports.iter().map(|report| (report.pass, report.round, report.changes.len())).collect();
+        assert_eq!(
+            summary,
+            [
+                (OptimizationPass::ConstantPropagation, 1, 2),
+                (OptimizationPass::ConstantFolding, 1, 1),
+                (OptimizationPass::ConstantPropagation, 2, 2),
+                (OptimizationPass::ConstantFolding, 2, 1),
+                (OptimizationPass::ConstantPropagation, 3, 1),
+            ]
+        );
+        assert_eq!(reports[0].changes, ["main: `t1 = x * 4` → `t1 = 3 * 4`", "main: `t2 = y + x` → `t2 = y + 3`"]);
+        // 每次执行的前后版本首尾相接
+        for pair in reports.windows(2) {
+            assert_eq!(pair[0].after, pair[1].before);
+        }
+        assert_eq!(body(&program.dump(), "main"), "    x = 3\n    t1 = 12\n    y = 12\n    t2 = 15\n    return 15\n");
+    }
+
+    #[test]
+    fn propagates_copies_within_a_block() {
+        let source = "int g = 1;\nint main() { int a = g; int b = a; return b + a; }";
+        let (program, reports) = optimize(source, &[OptimizationPass::CopyPropagation]);
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].changes, ["main: `b = a` → `b = g`", "main: `t1 = b + a` → `t1 = g + g`"]);
+        assert_eq!(body(&program.dump(), "main"), "    a = g\n    b = g\n    t1 = g + g\n    return t1\n");
+
+        // 被调函数可能修改全局变量，调用之后不再用 g 代替 a
+        let source = "int g = 1;\nint f() { g = 2; return 0; }\nint main() { int a = g; f(); return a; }";
+        let (program, _) = optimize(source, &[OptimizationPass::CopyPropagation]);
+        assert!(body(&program.dump(), "main").ends_with("    return a\n"), "{}", program.dump());
+    }
+
+    #[test]
+    fn eliminates_dead_code() {
+        let source = "int main() { int x = 1; int y = 2; return x; y = 3; }";
+        let (program, reports) = optimize(source, &[OptimizationPass::DeadCodeElimination]);
+        assert_eq!(reports.len(), 1);
+        assert_eq!(
+            reports[0].changes,
+            ["main: 删除不可达代码 `y = 3`", "main: 删除不可达代码 `return`", "main: 删除无用赋值 `y = 2`"]
+        );
+        assert_eq!(body(&reports[0].before, "main"), "    x = 1\n    y = 2\n    return x\n    y = 3\n    return\n");
+        assert_eq!(body(&program.dump(), "main"), "    x = 1\n    return x\n");
+        // 不再出现的局部变量不再分配栈槽
+        assert_eq!(program.functions[1].locals, ["x"]);
+    }
+
+    #[test]
+    fn stops_after_the_round_limit() {
+        // 每一轮复制传播只让公共子表达式消除多识别出一层，8 层加法需要的轮数超过上限
+        let source = "int f(int a, int b) { int x = a * b + a + a + a + a + a + a + a; \
+                      int y = a * b + a + a + a + a + a + a + a; return x - y; }\nint main() { return f(1, 2); }";
+        let passes = [OptimizationPass::CopyPropagation, OptimizationPass::CommonSubexpressionElimination];
+        let (_, reports) = optimize(source, &passes);
+        assert_eq!(reports.iter().map(|report| report.round).max(), Some(MAX_ROUNDS));
+        let last = reports.last().unwrap();
+        assert_eq!((last.pass, last.changes.len()), (OptimizationPass::CommonSubexpressionElimination, 1));
+        assert_eq!(last.changes, ["f: `t13 = t4 + a` → `t13 = t5`"]);
+        // 还没有消除完的加法留在结果中
+        assert!(body(&last.after, "f").contains("t14 = t13 + a"), "{}", last.after);
+    }
+}
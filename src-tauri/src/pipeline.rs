@@ -0,0 +1,294 @@
+use crate::interrupt::Fault;
+use crate::isa::{Execution, MemoryOperation, Register, RegisterUsage};
+use crate::predictor::Prediction;
+use crate::types::{ExecutionStage, Forwarding, Hazard, HazardKind};
+
+/// 冒险检测中的存储位置：寄存器或标志位
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Location {
+    Register(Register),
+    Flags,
+}
+
+/// 流水线中的一条指令
+pub struct InFlight<D> {
+    /// 进入流水线的次序
+    pub sequence: u64,
+    pub index: usize,
+    /// 译码结果；错误路径上的指令可能无法译码，因此到进入执行级时才报告错误
+    pub decoded: Option<Result<D, Fault>>,
+    pub usage: RegisterUsage,
+    /// 取指时对这条指令所做的分支预测
+    pub prediction: Prediction,
+    /// 执行级的结果，访存级补上取数的值，写回级提交
+    pub execution: Option<Execution>,
+}
+
+impl<D> InFlight<D> {
+    pub fn new(sequence: u64, index: usize) -> Self {
+        Self {
+            sequence,
+            index,
+            decoded: None,
+            usage: RegisterUsage::default(),
+            prediction: Prediction::default(),
+            execution: None,
+        }
+    }
+
+    /// 读取的位置，不含恒为 0 的寄存器
+    fn reads(&self, zero: Option<usize>) -> Vec<Location> {
+        locations(&self.usage.reads, self.usage.reads_flags, zero)
+    }
+
+    fn writes(&self, zero: Option<usize>) -> Vec<Location> {
+        locations(&self.usage.writes, self.usage.writes_flags, zero)
+    }
+
+    /// 执行级结束时还得不到的结果：取数指令的目的寄存器要到访存级结束才有值
+    fn loads(&self, location: Location) -> bool {
+        let load = self.execution.as_ref().and_then(|execution| match &execution.memory {
+            Some(MemoryOperation::Load { destination, .. }) => Some(*destination),
+            _ => None,
+        });
+        matches!((load, location), (Some(destination), Location::Register(register)) if destination == register)
+    }
+
+    /// 执行后写入该位置的值
+    fn value(&self, location: Location) -> Option<i64> {
+        let Location::Register(register) = location else { return None };
+        let writes = &self.execution.as_ref()?.writes;
+        writes.iter().rev().find(|(written, _)| *written == register).map(|(_, value)| *value as i64)
+    }
+}
+
+fn locations(registers: &[Register], flags: bool, zero: Option<usize>) -> Vec<Location> {
+    let mut locations: Vec<Location> = Vec::new();
+    for register in registers {
+        let location = Location::Register(*register);
+        if Some(*register) != zero.map(Register::General) && !locations.contains(&location) {
+            locations.push(location);
+        }
+    }
+    if flags {
+        locations.push(Location::Flags);
+    }
+    locations
+}
+
+/// 五级流水线的状态：各级中的指令与累计的统计
+pub struct Pipeline<D> {
+    pub fetch: Option<InFlight<D>>,
+    pub decode: Option<InFlight<D>>,
+    pub execute: Option<InFlight<D>>,
+    pub memory: Option<InFlight<D>>,
+    pub write_back: Option<InFlight<D>>,
+    /// 下一条要取的指令下标
+    pub next_fetch: usize,
+    /// 预测转移的带延迟槽指令，取完延迟槽后转到的目标
+    pub slot_target: Option<usize>,
+    pub sequence: u64,
+    pub retired: u64,
+    pub stalls: u64,
+    pub flushes: u64,
+}
+
+impl<D> Default for Pipeline<D> {
+    fn default() -> Self {
+        Self {
+            fetch: None,
+            decode: None,
+            execute: None,
+            memory: None,
+            write_back: None,
+            next_fetch: 0,
+            slot_target: None,
+            sequence: 0,
+            retired: 0,
+            stalls: 0,
+            flushes: 0,
+        }
+    }
+}
+
+/// 一次冒险检测的结果
+#[derive(Default)]
+pub struct Analysis {
+    pub stall: bool,
+    pub hazards: Vec<Hazard>,
+    pub forwardings: Vec<Forwarding>,
+}
+
+/// 检测译码级的指令 consumer 能否在本周期进入执行级。older 为执行级与访存级中的指令（由新到旧），
+/// 本周期它们分别进入访存级与写回级。只有写后读会造成停顿：启用前递时，只有紧跟在取数指令之后
+/// 使用其结果才停顿一个周期；不启用前递时，要等产生结果的指令写回之后才能读取。
+/// 按序流水线按程序顺序读取与写回，读后写与写后写只记录，不会出错
+pub fn analyze<D>(
+    consumer: &InFlight<D>,
+    older: &[(&InFlight<D>, ExecutionStage)],
+    forwarding: bool,
+    zero: Option<usize>,
+    text: impl Fn(usize) -> String,
+    name: impl Fn(Location) -> String,
+) -> Analysis {
+    let mut analysis = Analysis::default();
+    let (reads, writes) = (consumer.reads(zero), consumer.writes(zero));
+    let hazard = |kind, location, producer: &InFlight<D>, stall, description| Hazard {
+        kind,
+        register: Some(name(location)),
+        producer: producer.index,
+        consumer: consumer.index,
+        stall,
+        description,
+    };
+    let (later, earlier) = (text(consumer.index), |producer: &InFlight<D>| text(producer.index));
+
+    // 写后读：同一位置取最近的一次写入
+    for location in &reads {
+        let Some((producer, stage)) = older.iter().find(|(producer, _)| producer.writes(zero).contains(location))
+        else {
+            continue;
+        };
+        let (location, register) = (*location, name(*location));
+        // 位于 MEM/WB 的取数指令已经取到了值
+        let stall = !forwarding || (producer.loads(location) && matches!(stage, ExecutionStage::Execute));
+        let description = if !forwarding {
+            format!("写后读：{} 要读取 {}，须等 {} 写回后才能读取，停顿", later, register, earlier(producer))
+        } else if stall {
+            format!(
+                "写后读：{} 要使用 {} 取出的 {}，取数要到访存级结束才有结果，停顿一个周期",
+                later,
+                earlier(producer),
+                register
+            )
+        } else {
+            let latch = if matches!(stage, ExecutionStage::Execute) { "EX/MEM" } else { "MEM/WB" };
+            analysis.forwardings.push(Forwarding {
+                register: register.clone(),
+                from: stage.clone(),
+                producer: producer.index,
+                consumer: consumer.index,
+                value: producer.value(location),
+            });
+            format!("写后读：{} 的 {} 经 {} 前递给 {}", earlier(producer), register, latch, later)
+        };
+        analysis.stall |= stall;
+        analysis.hazards.push(hazard(HazardKind::ReadAfterWrite, location, producer, stall, description));
+    }
+
+    for (producer, _) in older {
+        for location in &writes {
+            let register = name(*location);
+            if producer.reads(zero).contains(location) {
+                let description =
+                    format!("读后写：{} 写 {} 时，{} 已读取了原来的值，不会出错", later, register, earlier(producer));
+                analysis.hazards.push(hazard(HazardKind::WriteAfterRead, *location, producer, false, description));
+            }
+            if producer.writes(zero).contains(location) {
+                let description =
+                    format!("写后写：{} 与 {} 都写 {}，按程序顺序写回，不会出错", earlier(producer), later, register);
+                analysis.hazards.push(hazard(HazardKind::WriteAfterWrite, *location, producer, false, description));
+            }
+        }
+    }
+    analysis
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble;
+    use crate::cpu_simulator::{CPUSimulator, Simulator};
+    use crate::types::PipelineConfig;
+    use crate::x86::X86;
+
+    fn instruction(index: usize, reads: &[usize], writes: &[usize], load: bool) -> InFlight<()> {
+        let memory = writes.first().filter(|_| load).map(|destination| MemoryOperation::Load {
+            address: 0,
+            size: 4,
+            signed: true,
+            destination: Register::General(*destination),
+        });
+        let writes_back = writes.iter().map(|number| (Register::General(*number), 7)).collect();
+        InFlight {
+            usage: RegisterUsage::general(reads, writes),
+            execution: Some(Execution { writes: writes_back, memory, ..Default::default() }),
+            ..InFlight::new(index as u64, index)
+        }
+    }
+
+    fn check(consumer: &InFlight<()>, older: &[(&InFlight<()>, ExecutionStage)], forwarding: bool) -> Analysis {
+        let name = |location| format!("{:?}", location);
+        analyze(consumer, older, forwarding, Some(0), |index| format!("#{}", index), name)
+    }
+
+    #[test]
+    fn forwarding_only_stalls_on_load_use() {
+        let load = instruction(0, &[2], &[1], true);
+        let alu = instruction(0, &[2], &[1], false);
+        let consumer = instruction(1, &[1], &[3], false);
+
+        let analysis = check(&consumer, &[(&alu, ExecutionStage::Execute)], true);
+        assert!(!analysis.stall);
+        assert_eq!(analysis.forwardings.len(), 1);
+        assert_eq!(analysis.forwardings[0].value, Some(7));
+        assert!(analysis.hazards[0].description.contains("EX/MEM"));
+
+        assert!(check(&consumer, &[(&load, ExecutionStage::Execute)], true).stall);
+        let analysis = check(&consumer, &[(&load, ExecutionStage::MemoryAccess)], true);
+        assert!(!analysis.stall);
+        assert!(analysis.hazards[0].description.contains("MEM/WB"));
+
+        // 不启用前递时，结果写回之前都要停顿
+        let analysis = check(&consumer, &[(&alu, ExecutionStage::MemoryAccess)], false);
+        assert!(analysis.stall && analysis.forwardings.is_empty());
+    }
+
+    #[test]
+    fn reads_the_most_recent_write_and_ignores_the_zero_register() {
+        let newer = instruction(1, &[], &[1], false);
+        let older = instruction(0, &[], &[1], true);
+        let consumer = instruction(2, &[1], &[], false);
+        let analysis =
+            check(&consumer, &[(&newer, ExecutionStage::Execute), (&older, ExecutionStage::MemoryAccess)], true);
+        assert!(!analysis.stall);
+        assert_eq!(analysis.hazards.len(), 1);
+        assert_eq!(analysis.hazards[0].producer, 1);
+
+        let zero = instruction(0, &[], &[0], false);
+        let consumer = instruction(1, &[0], &[0], false);
+        assert!(check(&consumer, &[(&zero, ExecutionStage::Execute)], false).hazards.is_empty());
+    }
+
+    #[test]
+    fn records_write_after_read_and_write_after_write_without_stalling() {
+        let producer = instruction(0, &[1], &[2], false);
+        let consumer = instruction(1, &[], &[1, 2], false);
+        let analysis = check(&consumer, &[(&producer, ExecutionStage::Execute)], false);
+        let kinds: Vec<HazardKind> = analysis.hazards.iter().map(|hazard| hazard.kind).collect();
+        assert_eq!(kinds, [HazardKind::WriteAfterRead, HazardKind::WriteAfterWrite]);
+        assert!(!analysis.stall);
+    }
+
+    /// 流水线运行到结束，返回 (停顿周期数, EBX)
+    fn stalls(source: &str, forwarding: bool) -> (u64, i64) {
+        let result = assemble(source);
+        assert!(result.success, "{:?}", result.errors);
+        let mut simulator = CPUSimulator::new(X86::default());
+        simulator.configure_pipeline(PipelineConfig { enabled: true, forwarding });
+        simulator.load_instructions(result.instructions).unwrap();
+        simulator.load_memory_image(result.memory_image);
+        let mut stalls = 0;
+        while let Some(cycle) = simulator.step().unwrap().pipeline {
+            stalls = cycle.stalls;
+        }
+        (stalls, simulator.state.registers.general["EBX"])
+    }
+
+    #[test]
+    fn counts_stalls_with_and_without_forwarding() {
+        let source = "section .data\nx dd 5\nsection .text\nmov eax, [x]\nadd ebx, eax\nadd ebx, ebx\nhlt";
+        assert_eq!(stalls(source, true), (1, 10));
+        assert_eq!(stalls(source, false), (4, 10));
+    }
+}
@@ -0,0 +1,286 @@
+use crate::types::{BranchKind, BranchStatistic, BranchStatistics, PredictorKind};
+use std::collections::BTreeMap;
+
+/// 方向预测表的项数，以指令地址的低 10 位索引
+const TABLE_SIZE: usize = 1024;
+/// gshare 使用的全局历史位数
+const HISTORY_BITS: u32 = 10;
+/// 转移目标缓冲的项数，直接映射
+pub const BTB_SIZE: usize = 64;
+/// 转移在执行级确定，预测错误时清除取指级与译码级中的两条指令
+pub const MISPREDICT_PENALTY: u64 = 2;
+
+/// 转移目标缓冲的一项：转移指令的地址（完整地址作为标记）与上次转移到的目标
+#[derive(Debug, Clone, Copy)]
+struct BtbEntry {
+    address: u64,
+    target: usize,
+    kind: BranchKind,
+    delayed: bool,
+}
+
+/// 取指时的预测：taken 为方向预测，target 为转移目标缓冲给出的目标（指令下标）
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Prediction {
+    pub taken: bool,
+    pub target: Option<usize>,
+    /// 目标缓冲中记录的转移带延迟槽
+    pub delayed: bool,
+}
+
+impl Prediction {
+    /// 预测的转移目标；方向预测为转移但目标缓冲未命中时只能顺序取指
+    pub fn next(&self) -> Option<usize> {
+        self.target.filter(|_| self.taken)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Record {
+    kind: BranchKind,
+    executed: u64,
+    taken: u64,
+    correct: u64,
+}
+
+/// 分支预测器：方向预测器加转移目标缓冲，并按转移指令统计预测的准确率
+pub struct BranchPredictor {
+    kind: PredictorKind,
+    one_bit: Vec<bool>,
+    /// 两位饱和计数器，0、1 预测不转移，2、3 预测转移；竞争预测器中作为按地址索引的局部预测器
+    counters: Vec<u8>,
+    /// gshare 的计数器，以地址与全局历史的异或索引
+    gshare: Vec<u8>,
+    /// 竞争预测器的选择器，2、3 选择 gshare，0、1 选择局部预测器
+    chooser: Vec<u8>,
+    history: usize,
+    btb: Vec<Option<BtbEntry>>,
+    records: BTreeMap<usize, Record>,
+    btb_hits: u64,
+    btb_misses: u64,
+    penalty_cycles: u64,
+}
+
+fn taken(counter: u8) -> bool {
+    counter >= 2
+}
+
+fn train(counter: &mut u8, taken: bool) {
+    *counter = if taken { (*counter + 1).min(3) } else { counter.saturating_sub(1) };
+}
+
+fn ratio(correct: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        correct as f64 / total as f64
+    }
+}
+
+impl BranchPredictor {
+    /// 新建预测器，计数器初始为弱不转移，选择器初始偏向局部预测器
+    pub fn new(kind: PredictorKind) -> Self {
+        Self {
+            kind,
+            one_bit: vec![false; TABLE_SIZE],
+            counters: vec![1; TABLE_SIZE],
+            gshare: vec![1; TABLE_SIZE],
+            chooser: vec![1; TABLE_SIZE],
+            history: 0,
+            btb: vec![None; BTB_SIZE],
+            records: BTreeMap::new(),
+            btb_hits: 0,
+            btb_misses: 0,
+            penalty_cycles: 0,
+        }
+    }
+
+    pub fn kind(&self) -> PredictorKind {
+        self.kind
+    }
+
+    fn local_index(address: u64) -> usize {
+        address as usize % TABLE_SIZE
+    }
+
+    fn gshare_index(&self, address: u64) -> usize {
+        (address as usize ^ self.history) % TABLE_SIZE
+    }
+
+    /// 条件转移的方向预测
+    fn direction(&self, address: u64) -> bool {
+        let local = Self::local_index(address);
+        match self.kind {
+            PredictorKind::StaticNotTaken => false,
+            PredictorKind::OneBit => self.one_bit[local],
+            PredictorKind::TwoBit => taken(self.counters[local]),
+            PredictorKind::Gshare => taken(self.gshare[self.gshare_index(address)]),
+            PredictorKind::Tournament => match taken(self.chooser[local]) {
+                true => taken(self.gshare[self.gshare_index(address)]),
+                false => taken(self.counters[local]),
+            },
+        }
+    }
+
+    /// 取指时预测地址为 address 的指令之后从哪里取指。静态预测不使用目标缓冲，总是顺序取指；
+    /// 其余预测器在目标缓冲命中时才知道这是转移指令，无条件转移总是预测转移
+    pub fn predict(&self, address: u64) -> Prediction {
+        if self.kind == PredictorKind::StaticNotTaken {
+            return Prediction::default();
+        }
+        match self.btb[address as usize % BTB_SIZE] {
+            Some(entry) if entry.address == address => Prediction {
+                taken: entry.kind == BranchKind::Unconditional || self.direction(address),
+                target: Some(entry.target),
+                delayed: entry.delayed,
+            },
+            _ => Prediction { taken: self.direction(address), target: None, delayed: false },
+        }
+    }
+
+    /// 转移指令执行后按实际结果 actual（转移时为目标下标）训练预测器，返回预测是否正确
+    pub fn update(
+        &mut self,
+        index: usize,
+        address: u64,
+        kind: BranchKind,
+        prediction: Prediction,
+        actual: Option<usize>,
+        delayed: bool,
+    ) -> bool {
+        let correct = prediction.next() == actual;
+        let record = self.records.entry(index).or_insert(Record { kind, executed: 0, taken: 0, correct: 0 });
+        record.executed += 1;
+        record.taken += actual.is_some() as u64;
+        record.correct += correct as u64;
+        if self.kind != PredictorKind::StaticNotTaken {
+            match prediction.target {
+                Some(_) => self.btb_hits += 1,
+                None => self.btb_misses += 1,
+            }
+        }
+
+        let was_taken = actual.is_some();
+        if kind == BranchKind::Conditional {
+            let (local, global) = (Self::local_index(address), self.gshare_index(address));
+            let (bimodal, gshare) = (taken(self.counters[local]), taken(self.gshare[global]));
+            if self.kind == PredictorKind::Tournament && bimodal != gshare {
+                train(&mut self.chooser[local], gshare == was_taken);
+            }
+            self.one_bit[local] = was_taken;
+            train(&mut self.counters[local], was_taken);
+            train(&mut self.gshare[global], was_taken);
+            self.history = ((self.history << 1) | was_taken as usize) & ((1 << HISTORY_BITS) - 1);
+        }
+        if let Some(target) = actual {
+            self.btb[address as usize % BTB_SIZE] = Some(BtbEntry { address, target, kind, delayed });
+        }
+        correct
+    }
+
+    /// 记录预测错误清除流水线损失的周期数
+    pub fn penalize(&mut self, cycles: u64) {
+        self.penalty_cycles += cycles;
+    }
+
+    /// 汇总统计；describe 给出转移指令的地址与写法
+    pub fn statistics(&self, describe: impl Fn(usize) -> (u64, String)) -> BranchStatistics {
+        let branches: Vec<BranchStatistic> = self
+            .records
+            .iter()
+            .map(|(&index, record)| {
+                let (address, text) = describe(index);
+                BranchStatistic {
+                    instruction: index,
+                    address,
+                    text,
+                    kind: record.kind,
+                    executed: record.executed,
+                    taken: record.taken,
+                    correct: record.correct,
+                    accuracy: ratio(record.correct, record.executed),
+                }
+            })
+            .collect();
+        let executed = branches.iter().map(|branch| branch.executed).sum();
+        let correct = branches.iter().map(|branch| branch.correct).sum();
+        BranchStatistics {
+            predictor: self.kind,
+            executed,
+            correct,
+            accuracy: ratio(correct, executed),
+            btb_hits: self.btb_hits,
+            btb_misses: self.btb_misses,
+            penalty_cycles: self.penalty_cycles,
+            branches,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: u64 = 0x10;
+
+    /// 地址 0x10 处转回下标 0 的条件转移依次按 outcomes 执行，返回每次预测是否正确
+    fn run(predictor: &mut BranchPredictor, outcomes: impl IntoIterator<Item = bool>) -> Vec<bool> {
+        outcomes
+            .into_iter()
+            .map(|taken| {
+                let prediction = predictor.predict(ADDRESS);
+                let actual = taken.then_some(0);
+                predictor.update(4, ADDRESS, BranchKind::Conditional, prediction, actual, false)
+            })
+            .collect()
+    }
+
+    /// 循环三次，每次转移 9 次后退出
+    fn loop_branch() -> Vec<bool> {
+        (0..30).map(|i| i % 10 != 9).collect()
+    }
+
+    fn hits(kind: PredictorKind, outcomes: Vec<bool>) -> usize {
+        run(&mut BranchPredictor::new(kind), outcomes).iter().filter(|correct| **correct).count()
+    }
+
+    #[test]
+    fn loop_branch_hit_rates() {
+        assert_eq!(hits(PredictorKind::StaticNotTaken, loop_branch()), 3);
+        // 一位预测在每次退出与重新进入循环时各错一次
+        assert_eq!(hits(PredictorKind::OneBit, loop_branch()), 24);
+        // 两位计数器只在退出循环时出错
+        assert_eq!(hits(PredictorKind::TwoBit, loop_branch()), 26);
+    }
+
+    #[test]
+    fn global_history_learns_alternating_branches() {
+        let alternating: Vec<bool> = (0..40).map(|i| i % 2 == 0).collect();
+        assert_eq!(hits(PredictorKind::TwoBit, alternating.clone()), 0);
+        for kind in [PredictorKind::Gshare, PredictorKind::Tournament] {
+            let mut predictor = BranchPredictor::new(kind);
+            let results = run(&mut predictor, alternating.clone());
+            assert!(results[20..].iter().all(|correct| *correct), "{:?}: {:?}", kind, results);
+        }
+    }
+
+    #[test]
+    fn unconditional_jumps_hit_once_in_the_btb() {
+        let mut predictor = BranchPredictor::new(PredictorKind::TwoBit);
+        for _ in 0..3 {
+            let prediction = predictor.predict(0x20);
+            predictor.update(8, 0x20, BranchKind::Unconditional, prediction, Some(2), false);
+        }
+        // 地址相差 BTB_SIZE 的指令映射到同一项，标记不符时不命中
+        assert_eq!(predictor.predict(0x20 + BTB_SIZE as u64).target, None);
+        assert_eq!(predictor.predict(0x20), Prediction { taken: true, target: Some(2), delayed: false });
+
+        predictor.penalize(MISPREDICT_PENALTY);
+        let statistics = predictor.statistics(|index| (4 * index as u64, format!("#{}", index)));
+        assert_eq!((statistics.executed, statistics.correct), (3, 2));
+        assert_eq!((statistics.btb_hits, statistics.btb_misses), (2, 1));
+        assert_eq!(statistics.penalty_cycles, MISPREDICT_PENALTY);
+        assert_eq!(statistics.branches[0].address, 0x20);
+        assert!((statistics.accuracy - 2.0 / 3.0).abs() < 1e-9);
+    }
+}
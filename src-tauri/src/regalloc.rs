@@ -0,0 +1,358 @@
+use crate::ir::{IrFunction, IrInstruction, IrOp, IrOperand};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// 参与分配的通用寄存器，按优先顺序排列：先用调用者保存的寄存器，再用需要在序言中保存的 EBX/ESI/EDI
+pub const ALLOCATABLE_REGISTERS: [&str; 6] = ["EAX", "ECX", "EDX", "EBX", "ESI", "EDI"];
+
+/// 被调用者保存的寄存器，函数使用它们时必须在序言中保存、尾声中恢复
+pub const CALLEE_SAVED_REGISTERS: [&str; 3] = ["EBX", "ESI", "EDI"];
+
+/// 代码生成器翻译一条四元式时会改写的寄存器。
+/// 操作数先被读入 EAX/ECX，之后才会改写这些寄存器，最后写回结果。
+pub fn clobbers(instruction: &IrInstruction) -> &'static [&'static str] {
+    match &instruction.op {
+        IrOp::Label(_) | IrOp::Jump(_) | IrOp::Halt => &[],
+        IrOp::Binary(op) if op == "/" || op == "%" => &["EAX", "ECX", "EDX"],
+        IrOp::Binary(_) | IrOp::CondJump(..) => &["EAX", "ECX"],
+        IrOp::Call(..) => &["EAX", "ECX", "EDX"],
+        IrOp::Assign | IrOp::Neg | IrOp::Not | IrOp::Cast(_) | IrOp::Param | IrOp::Return => &["EAX"],
+    }
+}
+
+/// 活跃区间，位置按半步计：2i 表示第 i 条四元式读取操作数，2i+1 表示它写入结果
+#[derive(Debug, Clone)]
+pub struct LiveInterval {
+    pub operand: IrOperand,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl LiveInterval {
+    /// 在第 index 条四元式执行期间保持活跃（跨越其对寄存器的改写）
+    fn spans(&self, index: usize) -> bool {
+        self.start <= 2 * index && self.end > 2 * index
+    }
+}
+
+/// 一个函数的寄存器分配结果
+#[derive(Debug, Clone)]
+pub struct RegisterAllocation {
+    pub function: String,
+    pub intervals: Vec<LiveInterval>,
+    pub registers: HashMap<IrOperand, &'static str>,
+    /// 溢出到栈帧中的局部变量和临时变量
+    pub spilled: Vec<IrOperand>,
+}
+
+impl RegisterAllocation {
+    /// 函数中用到的被调用者保存寄存器
+    pub fn callee_saved(&self) -> Vec<&'static str> {
+        CALLEE_SAVED_REGISTERS
+            .into_iter()
+            .filter(|register| self.registers.values().any(|r| r == register))
+            .collect()
+    }
+
+    /// 分配结果的文本形式：每个区间一行
+    pub fn format(&self) -> String {
+        let mut output = format!("function {}:\n", self.function);
+        if self.intervals.is_empty() {
+            output.push_str("    （没有需要分配的变量）\n");
+        }
+        for interval in &self.intervals {
+            let location = match self.registers.get(&interval.operand) {
+                Some(register) => register.to_string(),
+                None => "栈帧（溢出）".to_string(),
+            };
+            output.push_str(&format!(
+                "    {:<8} [{}, {}]  → {}\n",
+                interval.operand.to_string(),
+                interval.start / 2,
+                interval.end / 2,
+                location
+            ));
+        }
+        let saved = self.callee_saved();
+        if !saved.is_empty() {
+            output.push_str(&format!("    序言保存 {}\n", saved.join(", ")));
+        }
+        output
+    }
+}
+
+/// 线性扫描寄存器分配（Poletto & Sarkar）。
+/// 候选对象是临时变量和局部变量；参数留在调用者压入的栈槽中，全局变量留在数据区。
+pub fn allocate_registers(function: &IrFunction) -> RegisterAllocation {
+    let candidates: HashSet<IrOperand> = function
+        .locals
+        .iter()
+        .map(|name| IrOperand::Var(name.clone()))
+        .chain((1..=function.temp_count).map(IrOperand::Temp))
+        .collect();
+    let mut intervals = live_intervals(&function.body, &candidates);
+    intervals.sort_by_key(|interval| (interval.start, interval.end, interval.operand.to_string()));
+
+    let mut registers: HashMap<IrOperand, &'static str> = HashMap::new();
+    let mut spilled = Vec::new();
+    // 当前占用寄存器的区间（下标指向 intervals）
+    let mut active: Vec<usize> = Vec::new();
+
+    for current in 0..intervals.len() {
+        let interval = &intervals[current];
+        active.retain(|&i| intervals[i].end >= interval.start);
+
+        // 区间内被某条指令改写的寄存器不能使用
+        let clobbered: HashSet<&str> = function
+            .body
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| interval.spans(*index))
+            .flat_map(|(_, instruction)| clobbers(instruction).iter().copied())
+            .collect();
+        let allowed: Vec<&'static str> = ALLOCATABLE_REGISTERS
+            .into_iter()
+            .filter(|register| !clobbered.contains(register))
+            .collect();
+        let busy: HashSet<&str> = active.iter().map(|i| registers[&intervals[*i].operand]).collect();
+
+        if let Some(register) = allowed.iter().find(|register| !busy.contains(*register)) {
+            registers.insert(interval.operand.clone(), register);
+            active.push(current);
+            continue;
+        }
+
+        // 没有空闲寄存器：溢出结束得最晚的区间
+        let victim = active
+            .iter()
+            .copied()
+            .filter(|i| allowed.contains(&registers[&intervals[*i].operand]))
+            .max_by_key(|i| intervals[*i].end)
+            .filter(|i| intervals[*i].end > interval.end);
+        match victim {
+            Some(victim) => {
+                let register = registers.remove(&intervals[victim].operand).unwrap();
+                spilled.push(intervals[victim].operand.clone());
+                registers.insert(interval.operand.clone(), register);
+                active.retain(|&i| i != victim);
+                active.push(current);
+            }
+            None => spilled.push(interval.operand.clone()),
+        }
+    }
+
+    RegisterAllocation {
+        function: function.name.clone(),
+        intervals,
+        registers,
+        spilled,
+    }
+}
+
+/// 在四元式序列上做活跃变量分析，再把每个候选对象的活跃位置合并为一个区间
+fn live_intervals(body: &[IrInstruction], candidates: &HashSet<IrOperand>) -> Vec<LiveInterval> {
+    let uses = |instruction: &IrInstruction| -> Vec<IrOperand> {
+        instruction
+            .uses()
+            .into_iter()
+            .filter(|operand| candidates.contains(operand))
+            .cloned()
+            .collect()
+    };
+    let def = |instruction: &IrInstruction| instruction.result.clone().filter(|r| candidates.contains(r));
+
+    // 基本块：标签开始新块，跳转、返回与停机结束当前块
+    let mut leaders = BTreeSet::from([0]);
+    for (i, instruction) in body.iter().enumerate() {
+        match instruction.op {
+            IrOp::Label(_) => {
+                leaders.insert(i);
+            }
+            IrOp::Jump(_) | IrOp::CondJump(..) | IrOp::Return | IrOp::Halt => {
+                leaders.insert(i + 1);
+            }
+            _ => {}
+        }
+    }
+    let starts: Vec<usize> = leaders.into_iter().filter(|&i| i < body.len()).collect();
+    let blocks: Vec<(usize, usize)> = starts
+        .iter()
+        .enumerate()
+        .map(|(b, &start)| (start, starts.get(b + 1).copied().unwrap_or(body.len())))
+        .collect();
+    let label_block: HashMap<&str, usize> = blocks
+        .iter()
+        .enumerate()
+        .filter_map(|(b, (start, _))| match &body[*start].op {
+            IrOp::Label(label) => Some((label.as_str(), b)),
+            _ => None,
+        })
+        .collect();
+    let successors: Vec<Vec<usize>> = blocks
+        .iter()
+        .enumerate()
+        .map(|(b, (_, end))| {
+            let next = (b + 1 < blocks.len()).then_some(b + 1);
+            match &body[end - 1].op {
+                IrOp::Jump(label) => label_block.get(label.as_str()).copied().into_iter().collect(),
+                IrOp::CondJump(_, label) => label_block.get(label.as_str()).copied().into_iter().chain(next).collect(),
+                IrOp::Return | IrOp::Halt => Vec::new(),
+                _ => next.into_iter().collect(),
+            }
+        })
+        .collect();
+
+    // 逆向迭代求解 live_in = use ∪ (live_out - def)
+    let mut live_in: Vec<HashSet<IrOperand>> = vec![HashSet::new(); blocks.len()];
+    let mut live_out: Vec<HashSet<IrOperand>> = vec![HashSet::new(); blocks.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for b in (0..blocks.len()).rev() {
+            let out: HashSet<IrOperand> = successors[b].iter().flat_map(|s| live_in[*s].iter().cloned()).collect();
+            let mut live = out.clone();
+            for instruction in body[blocks[b].0..blocks[b].1].iter().rev() {
+                if let Some(d) = def(instruction) {
+                    live.remove(&d);
+                }
+                live.extend(uses(instruction));
+            }
+            if live != live_in[b] || out != live_out[b] {
+                live_in[b] = live;
+                live_out[b] = out;
+                changed = true;
+            }
+        }
+    }
+
+    // 记录每个候选对象出现活跃的最早与最晚位置
+    let mut ranges: HashMap<IrOperand, (usize, usize)> = HashMap::new();
+    let mut mark = |operand: &IrOperand, position: usize| {
+        let range = ranges.entry(operand.clone()).or_insert((position, position));
+        range.0 = range.0.min(position);
+        range.1 = range.1.max(position);
+    };
+    for (b, (start, end)) in blocks.iter().enumerate() {
+        let mut live = live_out[b].clone();
+        for i in (*start..*end).rev() {
+            for operand in &live {
+                mark(operand, 2 * i + 1);
+            }
+            if let Some(d) = def(&body[i]) {
+                mark(&d, 2 * i + 1);
+                live.remove(&d);
+            }
+            live.extend(uses(&body[i]));
+            for operand in &live {
+                mark(operand, 2 * i);
+            }
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|(operand, (start, end))| LiveInterval { operand, start, end })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{Lexer, Parser};
+    use crate::ir::IrBuilder;
+
+    /// 源程序中名为 name 的函数的中间代码
+    fn function(source: &str, name: &str) -> IrFunction {
+        let ast = Parser::new(Lexer::new(source.to_string()).tokenize()).parse();
+        let program = IrBuilder::new().build(&ast).unwrap();
+        program
+            .functions
+            .into_iter()
+            .find(|function| function.name == name)
+            .unwrap()
+    }
+
+    /// 同时活跃的两个区间不能分到同一个寄存器，区间内被改写的寄存器也不能使用
+    fn assert_consistent(function: &IrFunction, allocation: &RegisterAllocation) {
+        for a in &allocation.intervals {
+            let Some(register) = allocation.registers.get(&a.operand) else {
+                assert!(
+                    allocation.spilled.contains(&a.operand),
+                    "{} 既没有寄存器也没有溢出",
+                    a.operand
+                );
+                continue;
+            };
+            for (index, instruction) in function.body.iter().enumerate() {
+                if a.spans(index) {
+                    assert!(
+                        !clobbers(instruction).contains(register),
+                        "{} 的 {} 被 {} 改写",
+                        a.operand,
+                        register,
+                        instruction
+                    );
+                }
+            }
+            for b in &allocation.intervals {
+                let overlap = a.operand != b.operand && a.start <= b.end && b.start <= a.end;
+                if overlap {
+                    assert_ne!(
+                        allocation.registers.get(&b.operand),
+                        Some(register),
+                        "{} 与 {}",
+                        a.operand,
+                        b.operand
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn loop_variables_stay_live_across_the_back_edge() {
+        let function = function(
+            "int main() { int s = 0; int i; for (i = 0; i < 10; i = i + 1) s = s + i; return s; }",
+            "main",
+        );
+        let allocation = allocate_registers(&function);
+        assert_consistent(&function, &allocation);
+        assert!(allocation.spilled.is_empty());
+
+        // s 与 i 在整个循环中都活跃，直到回跳的 goto
+        let back_edge = function
+            .body
+            .iter()
+            .rposition(|instruction| matches!(instruction.op, IrOp::Jump(_)))
+            .unwrap();
+        for name in ["s", "i"] {
+            let interval = allocation
+                .intervals
+                .iter()
+                .find(|interval| interval.operand == IrOperand::Var(name.to_string()));
+            assert!(interval.unwrap().end >= 2 * back_edge, "{}", name);
+        }
+    }
+
+    #[test]
+    fn values_live_across_calls_use_callee_saved_registers() {
+        let source = "int f(int x) { return x; }\nint main() { int a = f(1); int b = f(2); return a + b; }";
+        let function = function(source, "main");
+        let allocation = allocate_registers(&function);
+        assert_consistent(&function, &allocation);
+
+        let a = &allocation.registers[&IrOperand::Var("a".to_string())];
+        assert!(CALLEE_SAVED_REGISTERS.contains(a), "a 分到了 {}", a);
+        assert!(allocation.callee_saved().contains(a));
+    }
+
+    #[test]
+    fn spills_when_registers_run_out() {
+        let source = "int main() { int a = 1; int b = 2; int c = 3; int d = 4; int e = 5; int f = 6; int g = 7; \
+                      return a + b + c + d + e + f + g + a + b + c + d + e + f + g; }";
+        let function = function(source, "main");
+        let allocation = allocate_registers(&function);
+        assert_consistent(&function, &allocation);
+        assert!(!allocation.spilled.is_empty());
+        assert!(allocation.format().contains("栈帧（溢出）"));
+    }
+}
@@ -0,0 +1,836 @@
+use crate::encoder::parse_number;
+use crate::interrupt::Fault;
+use crate::isa::{
+    self, Branch, Context, Execution, InterruptRequest, Isa, MemoryOperation, Register, RegisterUsage, WordInstruction,
+};
+use crate::memory::CODE_BASE;
+use crate::types::{Architecture, BranchKind, EncodingField, FlagsState, Instruction, InstructionType};
+use std::collections::HashMap;
+
+/// 寄存器在模拟器状态中的名称 x0–x31
+pub const REGISTERS: [&str; 32] = [
+    "x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7", "x8", "x9", "x10", "x11", "x12", "x13", "x14", "x15", "x16", "x17",
+    "x18", "x19", "x20", "x21", "x22", "x23", "x24", "x25", "x26", "x27", "x28", "x29", "x30", "x31",
+];
+
+/// 寄存器的 ABI 名称，下标即寄存器编号 x0–x31
+pub const ABI_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4", "a5", "a6", "a7", "s2",
+    "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4", "t5", "t6",
+];
+
+/// 模拟的控制状态寄存器，下标即特殊寄存器编号：中断现场保存在 mepc 与 mstatus 中
+pub const SPECIAL_REGISTERS: [&str; 2] = ["mstatus", "mepc"];
+/// 控制状态寄存器的编号，与 SPECIAL_REGISTERS 一一对应
+const CSR_NUMBERS: [u32; 2] = [0x300, 0x341];
+const MSTATUS: usize = 0;
+const MEPC: usize = 1;
+/// mstatus 中的 MIE（允许中断）与 MPIE（进入处理程序前的 MIE）
+const MIE: u32 = 1 << 3;
+const MPIE: u32 = 1 << 7;
+
+/// RV32I 的指令格式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    R,
+    I,
+    Shift,
+    Load,
+    S,
+    B,
+    U,
+    J,
+    Jalr,
+    System,
+    /// Zicsr：rd, csr, rs1
+    Csr,
+}
+
+/// RV32I 指令表：(助记符, 格式, opcode, funct3, funct7；System 格式中为 imm)
+pub const INSTRUCTIONS: [(&str, Format, u32, u32, u32); 42] = [
+    ("add", Format::R, 0b0110011, 0, 0),
+    ("sub", Format::R, 0b0110011, 0, 0b0100000),
+    ("sll", Format::R, 0b0110011, 1, 0),
+    ("slt", Format::R, 0b0110011, 2, 0),
+    ("sltu", Format::R, 0b0110011, 3, 0),
+    ("xor", Format::R, 0b0110011, 4, 0),
+    ("srl", Format::R, 0b0110011, 5, 0),
+    ("sra", Format::R, 0b0110011, 5, 0b0100000),
+    ("or", Format::R, 0b0110011, 6, 0),
+    ("and", Format::R, 0b0110011, 7, 0),
+    ("addi", Format::I, 0b0010011, 0, 0),
+    ("slti", Format::I, 0b0010011, 2, 0),
+    ("sltiu", Format::I, 0b0010011, 3, 0),
+    ("xori", Format::I, 0b0010011, 4, 0),
+    ("ori", Format::I, 0b0010011, 6, 0),
+    ("andi", Format::I, 0b0010011, 7, 0),
+    ("slli", Format::Shift, 0b0010011, 1, 0),
+    ("srli", Format::Shift, 0b0010011, 5, 0),
+    ("srai", Format::Shift, 0b0010011, 5, 0b0100000),
+    ("lb", Format::Load, 0b0000011, 0, 0),
+    ("lh", Format::Load, 0b0000011, 1, 0),
+    ("lw", Format::Load, 0b0000011, 2, 0),
+    ("lbu", Format::Load, 0b0000011, 4, 0),
+    ("lhu", Format::Load, 0b0000011, 5, 0),
+    ("sb", Format::S, 0b0100011, 0, 0),
+    ("sh", Format::S, 0b0100011, 1, 0),
+    ("sw", Format::S, 0b0100011, 2, 0),
+    ("beq", Format::B, 0b1100011, 0, 0),
+    ("bne", Format::B, 0b1100011, 1, 0),
+    ("blt", Format::B, 0b1100011, 4, 0),
+    ("bge", Format::B, 0b1100011, 5, 0),
+    ("bltu", Format::B, 0b1100011, 6, 0),
+    ("bgeu", Format::B, 0b1100011, 7, 0),
+    ("lui", Format::U, 0b0110111, 0, 0),
+    ("auipc", Format::U, 0b0010111, 0, 0),
+    ("jal", Format::J, 0b1101111, 0, 0),
+    ("jalr", Format::Jalr, 0b1100111, 0, 0),
+    ("ecall", Format::System, 0b1110011, 0, 0),
+    ("ebreak", Format::System, 0b1110011, 0, 1),
+    ("mret", Format::System, 0b1110011, 0, 0x302),
+    ("csrrw", Format::Csr, 0b1110011, 1, 0),
+    ("csrrs", Format::Csr, 0b1110011, 2, 0),
+];
+
+/// 查找指令的格式与编码常量
+pub fn lookup(mnemonic: &str) -> Option<(Format, u32, u32, u32)> {
+    let mnemonic = mnemonic.to_lowercase();
+    INSTRUCTIONS
+        .iter()
+        .find(|(name, ..)| *name == mnemonic)
+        .map(|&(_, format, opcode, funct3, funct7)| (format, opcode, funct3, funct7))
+}
+
+/// 寄存器编号：接受 x0–x31、ABI 名称以及 fp（s0 的别名）
+pub fn register_number(name: &str) -> Option<usize> {
+    let name = name.trim().to_lowercase();
+    if name == "fp" {
+        return Some(8);
+    }
+    if let Some(number) = name.strip_prefix('x').and_then(|n| n.parse::<usize>().ok()) {
+        return (number < 32).then_some(number);
+    }
+    ABI_NAMES.iter().position(|abi| *abi == name)
+}
+
+/// 控制状态寄存器在特殊寄存器中的下标：接受名称或编号
+pub fn csr_index(text: &str) -> Option<usize> {
+    let text = text.trim().to_lowercase();
+    let number = parse_number(&text).map(|number| number as u32);
+    (0..SPECIAL_REGISTERS.len()).find(|&index| SPECIAL_REGISTERS[index] == text || Some(CSR_NUMBERS[index]) == number)
+}
+
+/// 解析 "offset(base)" 形式的内存操作数
+pub fn parse_memory(text: &str) -> Option<(i64, usize)> {
+    let (offset, base) = text.trim().strip_suffix(')')?.split_once('(')?;
+    let offset = if offset.trim().is_empty() { 0 } else { parse_number(offset)? };
+    Some((offset, register_number(base)?))
+}
+
+/// 指令类别与周期估计：访存 2 个周期，其余 1 个周期
+pub fn classify(mnemonic: &str) -> (InstructionType, u32) {
+    match lookup(mnemonic).map(|(format, ..)| format) {
+        Some(Format::Load | Format::S) => (InstructionType::Memory, 2),
+        Some(Format::B | Format::J | Format::Jalr | Format::System) => (InstructionType::Control, 1),
+        Some(Format::U | Format::Csr) => (InstructionType::DataTransfer, 1),
+        _ => match mnemonic {
+            "and" | "or" | "xor" | "andi" | "ori" | "xori" | "sll" | "srl" | "sra" | "slli" | "srli" | "srai" => {
+                (InstructionType::Logic, 1)
+            }
+            _ => (InstructionType::Arithmetic, 1),
+        },
+    }
+}
+
+/// 整数运算指令的语义（立即数版本与寄存器版本相同），按 32 位补码计算
+pub fn alu(mnemonic: &str, a: i32, b: i32) -> Option<i32> {
+    let shift = (b & 0x1F) as u32;
+    let operation = match mnemonic {
+        "sltiu" => "sltu",
+        _ => mnemonic.strip_suffix('i').unwrap_or(mnemonic),
+    };
+    let value = match operation {
+        "add" => a.wrapping_add(b),
+        "sub" => a.wrapping_sub(b),
+        "sll" => a.wrapping_shl(shift),
+        "slt" => (a < b) as i32,
+        "sltu" => ((a as u32) < (b as u32)) as i32,
+        "xor" => a ^ b,
+        "srl" => ((a as u32) >> shift) as i32,
+        "sra" => a >> shift,
+        "or" => a | b,
+        "and" => a & b,
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// 条件分支是否成立
+pub fn branch_taken(mnemonic: &str, a: i32, b: i32) -> Option<bool> {
+    let taken = match mnemonic {
+        "beq" => a == b,
+        "bne" => a != b,
+        "blt" => a < b,
+        "bge" => a >= b,
+        "bltu" => (a as u32) < (b as u32),
+        "bgeu" => (a as u32) >= (b as u32),
+        _ => return None,
+    };
+    Some(taken)
+}
+
+fn register(text: &str) -> Result<u32, String> {
+    register_number(text)
+        .map(|number| number as u32)
+        .ok_or_else(|| format!("无法识别的寄存器 '{}'", text.trim()))
+}
+
+fn immediate(text: &str, min: i64, max: i64) -> Result<i64, String> {
+    let value = parse_number(text).ok_or_else(|| format!("无法解析立即数 '{}'", text.trim()))?;
+    if value < min || value > max {
+        return Err(format!("立即数 {} 超出范围 [{}, {}]", value, min, max));
+    }
+    Ok(value)
+}
+
+/// 描述一个位段：十六进制值、二进制位与含义
+fn field(name: &str, value: u32, width: usize, meaning: String) -> EncodingField {
+    EncodingField {
+        field: name.to_string(),
+        hex: format!("{:X}", value),
+        description: format!("{:0width$b} {}", value, meaning, width = width).trim_end().to_string(),
+    }
+}
+
+fn register_field(name: &str, number: u32) -> EncodingField {
+    field(name, number, 5, format!("x{} ({})", number, ABI_NAMES[number as usize]))
+}
+
+/// 把一条 RV32I 指令编码为 32 位指令字，同时给出各位段（从高位到低位）。
+/// target 返回标签的地址，找不到的标签视为外部符号，偏移为 0。
+pub fn encode(
+    mnemonic: &str,
+    operands: &[String],
+    address: i64,
+    target: &dyn Fn(&str) -> Option<i64>,
+) -> Result<(u32, Vec<EncodingField>), String> {
+    let (format, opcode, funct3, funct7) =
+        lookup(mnemonic).ok_or_else(|| format!("无法识别的 RV32I 指令 '{}'", mnemonic))?;
+    let expected = match format {
+        Format::R | Format::I | Format::Shift | Format::B | Format::Csr => 3,
+        Format::Load | Format::S | Format::U | Format::J => 2,
+        Format::Jalr => 2,
+        Format::System => 0,
+    };
+    if operands.len() != expected && !(format == Format::Jalr && operands.len() == 3) {
+        return Err(format!("{} 需要 {} 个操作数", mnemonic, expected));
+    }
+    let opcode_field = field("opcode", opcode, 7, format!("{:?} 型", format));
+    let funct3_field = field("funct3", funct3, 3, mnemonic.to_lowercase());
+
+    // 相对跳转的偏移：目标地址 - 本指令地址
+    let relative = |text: &str, bits: u32| -> Result<(i64, String), String> {
+        let label = text.trim();
+        let (offset, note) = match parse_number(label) {
+            Some(offset) => (offset, String::new()),
+            None => match target(label) {
+                Some(destination) => (destination - address, format!("→ {}", label)),
+                None => (0, format!("→ {}（外部符号，链接时重定位）", label)),
+            },
+        };
+        let limit = 1i64 << bits;
+        if offset % 2 != 0 || offset < -limit || offset >= limit {
+            return Err(format!("跳转偏移 {} 超出范围或不是偶数", offset));
+        }
+        Ok((offset, note))
+    };
+
+    let (word, fields) = match format {
+        Format::R => {
+            let (rd, rs1, rs2) = (register(&operands[0])?, register(&operands[1])?, register(&operands[2])?);
+            let word = (funct7 << 25) | (rs2 << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode;
+            let fields = vec![
+                field("funct7", funct7, 7, mnemonic.to_lowercase()),
+                register_field("rs2", rs2),
+                register_field("rs1", rs1),
+                funct3_field,
+                register_field("rd", rd),
+                opcode_field,
+            ];
+            (word, fields)
+        }
+        Format::I | Format::Shift | Format::Load | Format::Jalr => {
+            let rd = register(&operands[0])?;
+            let (rs1, imm) = match (format, operands.len()) {
+                (Format::Load, _) | (Format::Jalr, 2) => {
+                    let (offset, base) =
+                        parse_memory(&operands[1]).ok_or_else(|| format!("无法解析内存操作数 '{}'", operands[1]))?;
+                    if !(-2048..=2047).contains(&offset) {
+                        return Err(format!("偏移 {} 超出 12 位范围", offset));
+                    }
+                    (base as u32, offset)
+                }
+                (Format::Shift, _) => (register(&operands[1])?, immediate(&operands[2], 0, 31)?),
+                _ => (register(&operands[1])?, immediate(&operands[2], -2048, 2047)?),
+            };
+            let imm = ((imm as u32) & 0xFFF) | (funct7 << 5);
+            let word = (imm << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode;
+            let fields = vec![
+                field("imm[11:0]", imm, 12, format!("= {}", ((imm << 20) as i32) >> 20)),
+                register_field("rs1", rs1),
+                funct3_field,
+                register_field("rd", rd),
+                opcode_field,
+            ];
+            (word, fields)
+        }
+        Format::S => {
+            let rs2 = register(&operands[0])?;
+            let (offset, base) =
+                parse_memory(&operands[1]).ok_or_else(|| format!("无法解析内存操作数 '{}'", operands[1]))?;
+            if !(-2048..=2047).contains(&offset) {
+                return Err(format!("偏移 {} 超出 12 位范围", offset));
+            }
+            let (imm, rs1) = (offset as u32 & 0xFFF, base as u32);
+            let word = ((imm >> 5) << 25) | (rs2 << 20) | (rs1 << 15) | (funct3 << 12) | ((imm & 0x1F) << 7) | opcode;
+            let fields = vec![
+                field("imm[11:5]", imm >> 5, 7, format!("偏移 = {}", offset)),
+                register_field("rs2", rs2),
+                register_field("rs1", rs1),
+                funct3_field,
+                field("imm[4:0]", imm & 0x1F, 5, String::new()),
+                opcode_field,
+            ];
+            (word, fields)
+        }
+        Format::B => {
+            let (rs1, rs2) = (register(&operands[0])?, register(&operands[1])?);
+            let (offset, note) = relative(&operands[2], 12)?;
+            let imm = offset as u32;
+            let high = (((imm >> 12) & 1) << 6) | ((imm >> 5) & 0x3F);
+            let low = (((imm >> 1) & 0xF) << 1) | ((imm >> 11) & 1);
+            let word = (high << 25) | (rs2 << 20) | (rs1 << 15) | (funct3 << 12) | (low << 7) | opcode;
+            let fields = vec![
+                field("imm[12|10:5]", high, 7, format!("偏移 = {} {}", offset, note)),
+                register_field("rs2", rs2),
+                register_field("rs1", rs1),
+                funct3_field,
+                field("imm[4:1|11]", low, 5, String::new()),
+                opcode_field,
+            ];
+            (word, fields)
+        }
+        Format::U => {
+            let rd = register(&operands[0])?;
+            let imm = immediate(&operands[1], 0, 0xFFFFF)? as u32;
+            let word = (imm << 12) | (rd << 7) | opcode;
+            let fields = vec![
+                field("imm[31:12]", imm, 20, format!("= 0x{:05X}", imm)),
+                register_field("rd", rd),
+                opcode_field,
+            ];
+            (word, fields)
+        }
+        Format::J => {
+            let rd = register(&operands[0])?;
+            let (offset, note) = relative(&operands[1], 20)?;
+            let imm = offset as u32;
+            let bits = (((imm >> 20) & 1) << 19) | (((imm >> 1) & 0x3FF) << 9) | (((imm >> 11) & 1) << 8) | ((imm >> 12) & 0xFF);
+            let word = (bits << 12) | (rd << 7) | opcode;
+            let fields = vec![
+                field("imm[20|10:1|11|19:12]", bits, 20, format!("偏移 = {} {}", offset, note)),
+                register_field("rd", rd),
+                opcode_field,
+            ];
+            (word, fields)
+        }
+        Format::System => {
+            let word = (funct7 << 20) | opcode;
+            let fields = vec![field("imm[11:0]", funct7, 12, mnemonic.to_lowercase()), opcode_field];
+            (word, fields)
+        }
+        Format::Csr => {
+            let index =
+                csr_index(&operands[1]).ok_or_else(|| format!("不支持的控制状态寄存器 '{}'", operands[1].trim()))?;
+            let (csr, name) = (CSR_NUMBERS[index], SPECIAL_REGISTERS[index]);
+            let (rd, rs1) = (register(&operands[0])?, register(&operands[2])?);
+            let word = (csr << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode;
+            let fields = vec![
+                field("csr", csr, 12, name.to_string()),
+                register_field("rs1", rs1),
+                funct3_field,
+                register_field("rd", rd),
+                opcode_field,
+            ];
+            (word, fields)
+        }
+    };
+    Ok((word, fields))
+}
+
+/// 为整段程序编码：每条指令 4 字节，机器码按内存中的小端字节序存放
+pub fn encode_program(instructions: &mut [Instruction]) -> Result<(), (usize, String)> {
+    let labels: HashMap<String, i64> = instructions
+        .iter()
+        .enumerate()
+        .filter_map(|(i, instruction)| {
+            instruction
+                .label
+                .clone()
+                .map(|label| (label, CODE_BASE as i64 + 4 * i as i64))
+        })
+        .collect();
+    let target = |label: &str| labels.get(label).copied();
+
+    for (i, instruction) in instructions.iter_mut().enumerate() {
+        let address = CODE_BASE as i64 + 4 * i as i64;
+        let (word, mut fields) =
+            encode(&instruction.mnemonic, &instruction.operands, address, &target).map_err(|message| (i, message))?;
+        fields.insert(
+            0,
+            EncodingField {
+                field: "指令字".to_string(),
+                hex: format!("{:08X}", word),
+                description: "32 位指令字，在内存中按小端序存放".to_string(),
+            },
+        );
+        instruction.machine_code = word.to_le_bytes().iter().map(|b| format!("{:02X}", b)).collect();
+        instruction.encoding = fields;
+    }
+    Ok(())
+}
+
+/// 译码后的 RV32I 指令：寄存器编号、立即数与跳转目标
+#[derive(Debug, Clone)]
+pub struct Decoded {
+    pub mnemonic: String,
+    pub format: Format,
+    pub rd: usize,
+    pub rs1: usize,
+    pub rs2: usize,
+    pub imm: i32,
+    /// 标签或相对本指令的字节偏移
+    pub target: Option<String>,
+}
+
+/// RV32I：x0 恒为 0，x2 (sp) 为栈指针；中断现场保存在 mepc 与 mstatus 中
+pub struct RiscV;
+
+impl Isa for RiscV {
+    type Decoded = Decoded;
+
+    fn architecture(&self) -> Architecture {
+        Architecture::RiscV
+    }
+
+    fn register_names(&self) -> &'static [&'static str] {
+        &REGISTERS
+    }
+
+    fn special_register_names(&self) -> &'static [&'static str] {
+        &SPECIAL_REGISTERS
+    }
+
+    fn program_counter_name(&self) -> &'static str {
+        "PC"
+    }
+
+    fn stack_pointer(&self) -> usize {
+        2
+    }
+
+    fn zero_register(&self) -> Option<usize> {
+        Some(0)
+    }
+
+    fn interrupt_registers(&self) -> Option<(usize, usize)> {
+        Some((MEPC, MSTATUS))
+    }
+
+    /// 进入处理程序时 MPIE 记下原来的 MIE
+    fn interrupt_status(&self, flags: &FlagsState, enabled: bool) -> u32 {
+        (u32::from(enabled) * MIE) | (u32::from(flags.interrupt) * MPIE)
+    }
+
+    /// mret 由 MPIE 恢复 MIE，MPIE 置 1
+    fn restore_status(&self, status: u32, flags: &FlagsState) -> (FlagsState, u32) {
+        let interrupt = status & MPIE != 0;
+        (FlagsState { interrupt, ..flags.clone() }, (status & !MIE) | (u32::from(interrupt) * MIE) | MPIE)
+    }
+
+    fn decode(&self, instruction: &Instruction) -> Result<Decoded, String> {
+        let mnemonic = instruction.mnemonic.to_lowercase();
+        let (format, ..) =
+            lookup(&mnemonic).ok_or_else(|| format!("无法识别的 RV32I 指令 '{}'", instruction.mnemonic))?;
+        let operand = |index: usize| {
+            instruction
+                .operands
+                .get(index)
+                .map(|operand| operand.as_str())
+                .ok_or_else(|| format!("{} 缺少第 {} 个操作数", mnemonic, index + 1))
+        };
+        let reg = |index: usize| operand(index).and_then(|text| register(text).map(|number| number as usize));
+        let imm = |index: usize| {
+            operand(index).and_then(|text| {
+                parse_number(text).map(|value| value as i32).ok_or_else(|| format!("无法解析立即数 '{}'", text))
+            })
+        };
+        let memory = |index: usize| {
+            operand(index).and_then(|text| {
+                parse_memory(text)
+                    .map(|(offset, base)| (offset as i32, base))
+                    .ok_or_else(|| format!("无法解析内存操作数 '{}'", text))
+            })
+        };
+
+        let mut decoded = Decoded { mnemonic: mnemonic.clone(), format, rd: 0, rs1: 0, rs2: 0, imm: 0, target: None };
+        match format {
+            Format::R => (decoded.rd, decoded.rs1, decoded.rs2) = (reg(0)?, reg(1)?, reg(2)?),
+            Format::I | Format::Shift => (decoded.rd, decoded.rs1, decoded.imm) = (reg(0)?, reg(1)?, imm(2)?),
+            Format::Load => (decoded.rd, (decoded.imm, decoded.rs1)) = (reg(0)?, memory(1)?),
+            Format::S => (decoded.rs2, (decoded.imm, decoded.rs1)) = (reg(0)?, memory(1)?),
+            Format::B => {
+                (decoded.rs1, decoded.rs2) = (reg(0)?, reg(1)?);
+                decoded.target = Some(operand(2)?.trim().to_string());
+            }
+            Format::U => (decoded.rd, decoded.imm) = (reg(0)?, imm(1)?),
+            Format::J => {
+                decoded.rd = reg(0)?;
+                decoded.target = Some(operand(1)?.trim().to_string());
+            }
+            Format::Jalr if instruction.operands.len() == 3 => {
+                (decoded.rd, decoded.rs1, decoded.imm) = (reg(0)?, reg(1)?, imm(2)?)
+            }
+            Format::Jalr => (decoded.rd, (decoded.imm, decoded.rs1)) = (reg(0)?, memory(1)?),
+            Format::System => {}
+            Format::Csr => {
+                let text = operand(1)?;
+                let index = csr_index(text).ok_or_else(|| format!("不支持的控制状态寄存器 '{}'", text.trim()))?;
+                (decoded.rd, decoded.imm, decoded.rs1) = (reg(0)?, index as i32, reg(2)?);
+            }
+        }
+        Ok(decoded)
+    }
+
+    fn execute(&self, decoded: &Decoded, context: &Context) -> Result<Execution, Fault> {
+        let mnemonic = decoded.mnemonic.as_str();
+        let pc = context.address as i64;
+        let rd = Register::General(decoded.rd);
+        let target = decoded.target.as_deref().unwrap_or("");
+
+        let execution = match decoded.format {
+            Format::R | Format::I | Format::Shift => {
+                let a = context.register(decoded.rs1);
+                let b = match decoded.format {
+                    Format::R => context.register(decoded.rs2),
+                    _ => decoded.imm,
+                };
+                let value = alu(mnemonic, a, b).ok_or_else(|| format!("无法执行 {}", mnemonic))?;
+                Execution::new(format!("执行：{} {}, {} → {}", mnemonic, a, b, value)).write(rd, value)
+            }
+            Format::Load | Format::S => {
+                let base = context.register(decoded.rs1);
+                let address = base.wrapping_add(decoded.imm) as u32 as u64;
+                let size = isa::access_size(mnemonic);
+                let mut execution =
+                    Execution::new(format!("执行：计算有效地址 {} + {} = {}", base, decoded.imm, address));
+                execution.memory = Some(match decoded.format {
+                    Format::Load => {
+                        MemoryOperation::Load { address, size, signed: !mnemonic.ends_with('u'), destination: rd }
+                    }
+                    _ => MemoryOperation::Store { address, size, value: context.register(decoded.rs2) },
+                });
+                execution
+            }
+            Format::B => {
+                let (a, b) = (context.register(decoded.rs1), context.register(decoded.rs2));
+                if branch_taken(mnemonic, a, b).unwrap_or(false) {
+                    let index =
+                        context.word_target(target, pc).ok_or_else(|| format!("找不到跳转目标 '{}'", target))?;
+                    let mut execution =
+                        Execution::new(format!("执行：比较 {} 与 {}，条件成立，跳转到 {}", a, b, target));
+                    execution.branch = Some(Branch { target: index, delayed: false });
+                    execution
+                } else {
+                    Execution::new(format!("执行：比较 {} 与 {}，条件不成立，顺序执行", a, b))
+                }
+            }
+            Format::U => {
+                let upper = decoded.imm.wrapping_shl(12);
+                let value = if mnemonic == "auipc" { (pc as i32).wrapping_add(upper) } else { upper };
+                Execution::new(format!("执行：{} 得到 0x{:08X}", mnemonic, value)).write(rd, value)
+            }
+            Format::J => match context.word_target(target, pc) {
+                Some(index) => {
+                    let mut execution = Execution::new(format!("执行：跳转到 {}，返回地址 0x{:X}", target, pc + 4))
+                        .write(rd, (pc + 4) as i32);
+                    execution.branch = Some(Branch { target: index, delayed: false });
+                    execution
+                }
+                // 程序中没有的函数视为外部函数，跳过并把返回值 a0 置 0；j（rd = x0）不是调用
+                None if decoded.rd != 0 => {
+                    Execution::new(format!("执行：调用外部函数 {}（模拟器中跳过，返回值 a0 = 0）", target))
+                        .write(Register::General(10), 0)
+                }
+                None => Execution::new(format!("执行：调用外部函数 {}（模拟器中跳过）", target)),
+            },
+            Format::Jalr => {
+                let address = (context.register(decoded.rs1).wrapping_add(decoded.imm) & !1) as u32 as u64;
+                let mut execution =
+                    Execution::new(format!("执行：间接跳转到 0x{:X}", address)).write(rd, (pc + 4) as i32);
+                execution.branch = Some(Branch { target: self.instruction_index(address), delayed: false });
+                execution
+            }
+            // mret 从中断处理程序返回，由 mepc 与 mstatus 恢复现场由模拟器完成
+            Format::System if mnemonic == "mret" => Execution {
+                interrupt: Some(InterruptRequest::Return),
+                ..Execution::new("执行：mret，从中断处理程序返回")
+            },
+            Format::System => {
+                // ebreak 或 exit 系统调用（a7 = 93）停止程序
+                if mnemonic == "ebreak" || context.register(17) == 93 {
+                    let mut execution =
+                        Execution::new(format!("执行：{}，程序停止，a0 = {}", mnemonic, context.register(10)));
+                    execution.halt = true;
+                    execution
+                } else {
+                    Execution::new(format!("执行：环境调用 a7 = {}（模拟器中忽略）", context.register(17)))
+                }
+            }
+            // csrrw 写入 rs1，csrrs 按 rs1 置位；rd 得到原值。写 mstatus 时 MIE 即中断允许标志
+            Format::Csr => {
+                let index = decoded.imm as usize;
+                let (old, source) = (context.special(index), context.register(decoded.rs1));
+                let value = if mnemonic == "csrrw" { source } else { old | source };
+                let mut execution = Execution::new(format!(
+                    "执行：读取 {} = 0x{:X}，写入 0x{:X}",
+                    SPECIAL_REGISTERS[index], old, value
+                ))
+                .write(rd, old)
+                .write(Register::Special(index), value);
+                if index == MSTATUS {
+                    execution.flags = Some(FlagsState { interrupt: value as u32 & MIE != 0, ..context.flags.clone() });
+                }
+                execution
+            }
+        };
+        Ok(execution)
+    }
+
+    fn register_usage(&self, decoded: &Decoded) -> RegisterUsage {
+        let (rd, rs1, rs2) = (decoded.rd, decoded.rs1, decoded.rs2);
+        match decoded.format {
+            Format::R => RegisterUsage::general(&[rs1, rs2], &[rd]),
+            Format::I | Format::Shift | Format::Load | Format::Jalr => RegisterUsage::general(&[rs1], &[rd]),
+            Format::S | Format::B => RegisterUsage::general(&[rs1, rs2], &[]),
+            Format::U => RegisterUsage::general(&[], &[rd]),
+            // 调用外部函数时还写入返回值 a0
+            Format::J if rd != 0 => RegisterUsage::general(&[], &[rd, 10]),
+            Format::J => RegisterUsage::general(&[], &[rd]),
+            // mret 由 mepc 与 mstatus 恢复中断现场
+            Format::System if decoded.mnemonic == "mret" => RegisterUsage {
+                reads: vec![Register::Special(MEPC), Register::Special(MSTATUS)],
+                writes: vec![Register::Special(MSTATUS)],
+                reads_flags: true,
+                writes_flags: true,
+            },
+            // ecall 按 a7 判断是否退出，停止时显示 a0
+            Format::System => RegisterUsage::general(&[17, 10], &[]),
+            Format::Csr => {
+                let csr = Register::Special(decoded.imm as usize);
+                let mut usage = RegisterUsage::general(&[rs1], &[rd]);
+                usage.reads.push(csr);
+                usage.writes.push(csr);
+                usage.writes_flags = decoded.imm as usize == MSTATUS;
+                usage
+            }
+        }
+    }
+
+    fn branch_kind(&self, decoded: &Decoded) -> Option<BranchKind> {
+        match decoded.format {
+            Format::B => Some(BranchKind::Conditional),
+            Format::J | Format::Jalr => Some(BranchKind::Unconditional),
+            Format::System if decoded.mnemonic == "mret" => Some(BranchKind::Unconditional),
+            _ => None,
+        }
+    }
+
+    fn encode_program(&self, instructions: &mut [Instruction]) -> Result<(), (usize, String)> {
+        encode_program(instructions)
+    }
+
+    fn disassemble(&self, bytes: &[u8]) -> Result<Vec<Instruction>, String> {
+        disassemble(bytes)
+    }
+}
+
+/// 把 32 位指令字还原为汇编指令；跳转目标为绝对地址
+fn decode_word(word: u32, address: i64) -> Result<WordInstruction, String> {
+    let opcode = word & 0x7F;
+    let funct3 = (word >> 12) & 7;
+    let funct7 = word >> 25;
+    let (rd, rs1, rs2) = ((word >> 7) & 0x1F, (word >> 15) & 0x1F, (word >> 20) & 0x1F);
+    let &(name, format, ..) = INSTRUCTIONS
+        .iter()
+        .find(|&&(_, format, op, f3, f7)| {
+            op == opcode
+                && match format {
+                    Format::R | Format::Shift => f3 == funct3 && f7 == funct7,
+                    Format::I | Format::Load | Format::S | Format::B | Format::Jalr | Format::Csr => f3 == funct3,
+                    Format::U | Format::J => true,
+                    Format::System => word >> 20 == f7 && (word >> 7) & 0x1FFF == 0,
+                }
+        })
+        .ok_or_else(|| format!("无法识别的指令字 {:08X}", word))?;
+
+    let reg = |number: u32| ABI_NAMES[number as usize].to_string();
+    let imm_i = (word as i32) >> 20;
+    let mut target = None;
+    let operands = match format {
+        Format::R => vec![reg(rd), reg(rs1), reg(rs2)],
+        Format::I => vec![reg(rd), reg(rs1), imm_i.to_string()],
+        Format::Shift => vec![reg(rd), reg(rs1), rs2.to_string()],
+        Format::Load | Format::Jalr => vec![reg(rd), format!("{}({})", imm_i, reg(rs1))],
+        Format::S => {
+            let imm = ((word as i32 >> 25) << 5) | ((word >> 7) & 0x1F) as i32;
+            vec![reg(rs2), format!("{}({})", imm, reg(rs1))]
+        }
+        Format::B => {
+            let imm = ((word as i32 >> 31) << 12)
+                | (((word >> 7) & 1) << 11) as i32
+                | (((word >> 25) & 0x3F) << 5) as i32
+                | (((word >> 8) & 0xF) << 1) as i32;
+            target = Some((address + imm as i64, imm.to_string()));
+            vec![reg(rs1), reg(rs2)]
+        }
+        Format::U => vec![reg(rd), format!("0x{:X}", word >> 12)],
+        Format::J => {
+            let imm = ((word as i32 >> 31) << 20)
+                | (((word >> 12) & 0xFF) << 12) as i32
+                | (((word >> 20) & 1) << 11) as i32
+                | (((word >> 21) & 0x3FF) << 1) as i32;
+            target = Some((address + imm as i64, imm.to_string()));
+            vec![reg(rd)]
+        }
+        Format::System => Vec::new(),
+        Format::Csr => {
+            let csr = word >> 20;
+            let index = CSR_NUMBERS
+                .iter()
+                .position(|&number| number == csr)
+                .ok_or_else(|| format!("不支持的控制状态寄存器 0x{:03X}", csr))?;
+            vec![reg(rd), SPECIAL_REGISTERS[index].to_string(), reg(rs1)]
+        }
+    };
+    Ok(WordInstruction { word, mnemonic: name.to_string(), operands, target })
+}
+
+/// 把 RV32I 机器码反汇编为指令序列，并重新编码以给出各位段
+pub fn disassemble(bytes: &[u8]) -> Result<Vec<Instruction>, String> {
+    let words = isa::words(bytes)?
+        .into_iter()
+        .enumerate()
+        .map(|(i, word)| {
+            let address = CODE_BASE as i64 + 4 * i as i64;
+            decode_word(word, address).map_err(|message| format!("地址 {:08X}: {}", address, message))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut instructions = isa::finish_disassembly(words, classify);
+    encode_program(&mut instructions).map_err(|(i, message)| format!("第 {} 条指令: {}", i + 1, message))?;
+    Ok(instructions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(mnemonic: &str, operands: &[&str]) -> Result<u32, String> {
+        let operands: Vec<String> = operands.iter().map(|operand| operand.to_string()).collect();
+        encode(mnemonic, &operands, 0, &|_| None).map(|(word, _)| word)
+    }
+
+    fn instruction(label: Option<&str>, mnemonic: &str, operands: &[&str]) -> Instruction {
+        let (instruction_type, cycles) = classify(mnemonic);
+        Instruction {
+            id: mnemonic.to_string(),
+            instruction_type,
+            mnemonic: mnemonic.to_string(),
+            operands: operands.iter().map(|operand| operand.to_string()).collect(),
+            machine_code: String::new(),
+            description: String::new(),
+            cycles,
+            label: label.map(str::to_string),
+            encoding: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn encodes_every_format() {
+        let cases: [(&str, &[&str], u32); 13] = [
+            ("add", &["a0", "a1", "a2"], 0x00C5_8533),
+            ("sub", &["a0", "a0", "a1"], 0x40B5_0533),
+            ("addi", &["a0", "zero", "-1"], 0xFFF0_0513),
+            ("srai", &["a0", "a0", "3"], 0x4035_5513),
+            ("lw", &["a0", "8(sp)"], 0x0081_2503),
+            ("sw", &["a0", "-4(sp)"], 0xFEA1_2E23),
+            ("beq", &["a0", "a1", "8"], 0x00B5_0463),
+            ("jal", &["ra", "-4"], 0xFFDF_F0EF),
+            ("jalr", &["zero", "0(ra)"], 0x0000_8067),
+            ("lui", &["a0", "0x12345"], 0x1234_5537),
+            ("ecall", &[], 0x0000_0073),
+            ("ebreak", &[], 0x0010_0073),
+            ("mret", &[], 0x3020_0073),
+        ];
+        for (mnemonic, operands, expected) in cases {
+            assert_eq!(word(mnemonic, operands), Ok(expected), "{} {:?}", mnemonic, operands);
+        }
+        // fp 是 s0 的别名，x 编号与 ABI 名称等价
+        assert_eq!(word("addi", &["fp", "x2", "16"]), word("addi", &["s0", "sp", "16"]));
+    }
+
+    #[test]
+    fn rejects_out_of_range_operands() {
+        assert!(word("addi", &["a0", "a0", "2048"]).is_err());
+        assert!(word("slli", &["a0", "a0", "32"]).is_err());
+        assert!(word("beq", &["a0", "a1", "3"]).is_err());
+        assert!(word("add", &["a0", "a1", "x32"]).is_err());
+        assert!(word("lw", &["a0", "sp"]).is_err());
+        assert!(word("mul", &["a0", "a1", "a2"]).is_err());
+    }
+
+    #[test]
+    fn resolves_labels_relative_to_each_instruction() {
+        let mut program = vec![
+            instruction(None, "jal", &["ra", "f"]),
+            instruction(None, "ebreak", &[]),
+            instruction(Some("f"), "addi", &["a0", "a0", "-1"]),
+            instruction(None, "bne", &["a0", "zero", "f"]),
+            instruction(None, "jalr", &["zero", "0(ra)"]),
+        ];
+        encode_program(&mut program).unwrap();
+        // jal 向前 8 字节，bne 向后 4 字节；机器码按小端序存放
+        assert_eq!(program[0].machine_code, "EF008000");
+        assert_eq!(program[3].machine_code, "E31E05FE");
+        assert_eq!(program[0].encoding[0].hex, "008000EF");
+    }
+
+    #[test]
+    fn integer_semantics() {
+        assert_eq!(alu("add", i32::MAX, 1), Some(i32::MIN));
+        assert_eq!(alu("sub", 0, 1), Some(-1));
+        assert_eq!(alu("slt", -1, 0), Some(1));
+        assert_eq!(alu("sltu", -1, 0), Some(0));
+        assert_eq!(alu("sltiu", 0, -1), Some(1));
+        assert_eq!(alu("srai", -16, 2), Some(-4));
+        assert_eq!(alu("srl", -16, 28), Some(0xF));
+        // 移位量只取低 5 位
+        assert_eq!(alu("sll", 1, 33), Some(2));
+        assert_eq!(alu("andi", 0b1100, 0b1010), Some(0b1000));
+        assert_eq!(alu("mul", 2, 3), None);
+        assert_eq!(branch_taken("blt", -1, 0), Some(true));
+        assert_eq!(branch_taken("bltu", -1, 0), Some(false));
+        assert_eq!(branch_taken("bgeu", -1, 0), Some(true));
+        assert_eq!(branch_taken("add", 0, 0), None);
+    }
+}
@@ -0,0 +1,512 @@
+use crate::ir::{IrFunction, IrInstruction, IrOp, IrOperand, IrProgram};
+use crate::memory::DATA_BASE;
+use crate::riscv::classify;
+use crate::types::{Diagnostic, Instruction, SourceSpan};
+use std::collections::{BTreeSet, HashMap};
+
+/// 参数寄存器 a0–a7
+const ARGUMENT_REGISTERS: [&str; 8] = ["a0", "a1", "a2", "a3", "a4", "a5", "a6", "a7"];
+
+/// RISC-V 代码生成器：把三地址码翻译为 RV32I 汇编。
+/// 不做寄存器分配：参数、局部变量和临时变量都在栈帧中占一个槽位（相对 s0 寻址），
+/// 运算时装入 t0/t1，结果从 t0 写回。RV32I 没有乘除法指令，* / % 调用软件例程。
+pub struct RiscvCodeGenerator {
+    program: IrProgram,
+    instructions: Vec<Instruction>,
+    memory_offset: usize,
+    globals: HashMap<String, usize>,
+    /// 字符串常量 -> 数据区地址
+    strings: HashMap<String, usize>,
+    /// 当前函数中各槽位相对 s0 的偏移
+    slots: HashMap<IrOperand, i32>,
+    frame_size: i32,
+    /// 每个函数的栈帧布局说明
+    layouts: Vec<String>,
+    /// 等待传给下一次调用的参数（按从右向左的顺序给出）
+    pending_params: Vec<IrOperand>,
+    /// 程序用到的运行时例程
+    runtime: BTreeSet<&'static str>,
+    return_label: Option<String>,
+    pending_label: Option<String>,
+    label_aliases: HashMap<String, String>,
+}
+
+impl RiscvCodeGenerator {
+    pub fn new(program: IrProgram) -> Self {
+        let mut memory_offset = DATA_BASE as usize;
+        let mut globals = HashMap::new();
+        for name in &program.globals {
+            globals.insert(name.clone(), memory_offset);
+            memory_offset += 4;
+        }
+
+        Self {
+            program,
+            instructions: Vec::new(),
+            memory_offset,
+            globals,
+            strings: HashMap::new(),
+            slots: HashMap::new(),
+            frame_size: 0,
+            layouts: Vec::new(),
+            pending_params: Vec::new(),
+            runtime: BTreeSet::new(),
+            return_label: None,
+            pending_label: None,
+            label_aliases: HashMap::new(),
+        }
+    }
+
+    /// 各函数的栈帧布局，生成之后才有内容
+    pub fn layouts(&self) -> &[String] {
+        &self.layouts
+    }
+
+    pub fn generate(&mut self) -> Result<Vec<Instruction>, Diagnostic> {
+        for function in self.program.functions.clone() {
+            self.generate_function(&function)?;
+        }
+        for routine in self.runtime.clone() {
+            self.generate_runtime(routine);
+        }
+
+        if self.pending_label.is_some() {
+            self.emit("addi", &["zero", "zero", "0"], "空操作（标签占位）".to_string());
+        }
+
+        // 把跳转目标中的别名替换为实际附着的标签
+        for instruction in &mut self.instructions {
+            if let Some(target) = instruction.operands.last_mut() {
+                if let Some(actual) = self.label_aliases.get(target) {
+                    *target = actual.clone();
+                }
+            }
+        }
+
+        Ok(self.instructions.clone())
+    }
+
+    fn place_label(&mut self, label: String) {
+        match &self.pending_label {
+            Some(pending) => {
+                self.label_aliases.insert(label, pending.clone());
+            }
+            None => self.pending_label = Some(label),
+        }
+    }
+
+    fn emit(&mut self, mnemonic: &str, operands: &[&str], description: String) {
+        let (instruction_type, cycles) = classify(mnemonic);
+        self.instructions.push(Instruction {
+            id: format!("{}_{}", mnemonic, self.instructions.len()),
+            instruction_type,
+            mnemonic: mnemonic.to_string(),
+            operands: operands.iter().map(|operand| operand.to_string()).collect(),
+            machine_code: String::new(),
+            description,
+            cycles,
+            label: self.pending_label.take(),
+            encoding: Vec::new(),
+        });
+    }
+
+    /// 生成函数：序言保存 ra、s0 并建立栈帧，尾声恢复后经 ra 返回。
+    /// 栈帧按 16 字节对齐，[s0-4] 保存 ra，[s0-8] 保存 s0，其后依次是前 8 个参数、局部变量和临时变量；
+    /// 第 9 个起的参数由调用者放在其栈帧底部，第 i 个位于 4(i-8)(s0)。调用这类函数的栈帧底部留出传参区。
+    fn generate_function(&mut self, function: &IrFunction) -> Result<(), Diagnostic> {
+        let name = &function.name;
+        self.slots.clear();
+        let (register_params, stack_params) =
+            function.params.split_at(function.params.len().min(ARGUMENT_REGISTERS.len()));
+        let operands = register_params
+            .iter()
+            .chain(&function.locals)
+            .map(|name| IrOperand::Var(name.clone()))
+            .chain((1..=function.temp_count).map(IrOperand::Temp));
+        let mut layout = vec![format!("{}:", name)];
+        let mut slot_count = 0;
+        for (i, operand) in operands.enumerate() {
+            let offset = -12 - 4 * i as i32;
+            layout.push(format!("    {:<12} {}(s0)", operand.to_string(), offset));
+            self.slots.insert(operand, offset);
+            slot_count += 1;
+        }
+        for (i, param) in stack_params.iter().enumerate() {
+            let offset = 4 * i as i32;
+            layout.push(format!("    {:<12} {}(s0)", param, offset));
+            self.slots.insert(IrOperand::Var(param.clone()), offset);
+        }
+        let outgoing = function
+            .body
+            .iter()
+            .filter_map(|instruction| match instruction.op {
+                IrOp::Call(_, count) => Some(4 * count.saturating_sub(ARGUMENT_REGISTERS.len()) as i32),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0);
+        self.frame_size = (8 + 4 * slot_count + outgoing + 15) / 16 * 16;
+        if self.frame_size > 2032 {
+            return Err(Diagnostic::error(
+                "E0304",
+                format!("函数 {} 的栈帧有 {} 字节，超出 12 位立即数的寻址范围", name, self.frame_size),
+                SourceSpan::default(),
+            ));
+        }
+        layout[0] = format!(
+            "{}: 栈帧 {} 字节，ra 在 -4(s0)，s0 在 -8(s0)，传参区 {} 字节在 0(sp)",
+            name, self.frame_size, outgoing
+        );
+        self.layouts.push(layout.join("\n"));
+
+        let size = self.frame_size.to_string();
+        let negative = (-self.frame_size).to_string();
+        let ra_offset = (self.frame_size - 4).to_string();
+        let s0_offset = (self.frame_size - 8).to_string();
+        self.place_label(name.clone());
+        self.emit("addi", &["sp", "sp", &negative], format!("{} 序言：分配 {} 字节栈帧", name, self.frame_size));
+        if !function.is_entry() {
+            self.emit("sw", &["ra", &format!("{}(sp)", ra_offset)], "保存返回地址 ra".to_string());
+            self.emit("sw", &["s0", &format!("{}(sp)", s0_offset)], "保存调用者的 s0".to_string());
+        }
+        self.emit("addi", &["s0", "sp", &size], "s0 指向栈帧顶部".to_string());
+        for (register, param) in ARGUMENT_REGISTERS.iter().zip(register_params) {
+            let offset = self.slots[&IrOperand::Var(param.clone())];
+            self.emit("sw", &[register, &format!("{}(s0)", offset)], format!("把参数 {} 存入栈帧", param));
+        }
+
+        self.return_label = (!function.is_entry()).then(|| format!("{}_end", name));
+        for (i, instruction) in function.body.iter().enumerate() {
+            let is_last = i + 1 == function.body.len();
+            self.generate_instruction(instruction, is_last)?;
+        }
+
+        if let Some(return_label) = self.return_label.take() {
+            self.place_label(return_label);
+            self.emit("lw", &["ra", &format!("{}(sp)", ra_offset)], format!("{} 尾声：恢复 ra", name));
+            self.emit("lw", &["s0", &format!("{}(sp)", s0_offset)], "恢复调用者的 s0".to_string());
+            self.emit("addi", &["sp", "sp", &size], "释放栈帧".to_string());
+            self.emit("jalr", &["zero", "0(ra)"], "返回调用者，返回值在 a0".to_string());
+        }
+        Ok(())
+    }
+
+    fn generate_instruction(&mut self, instruction: &IrInstruction, is_last: bool) -> Result<(), Diagnostic> {
+        let arg1 = instruction.arg1.as_ref();
+        let arg2 = instruction.arg2.as_ref();
+        match &instruction.op {
+            IrOp::Label(label) => self.place_label(label.clone()),
+            IrOp::Jump(label) => self.emit("jal", &["zero", label], format!("无条件跳转到 {}", label)),
+            IrOp::Assign => {
+                self.load(arg1, "t0");
+                self.store_result(instruction, "t0");
+            }
+            IrOp::Binary(op) => {
+                self.load(arg1, "t0");
+                self.load(arg2, "t1");
+                self.generate_binary_op(op)?;
+                self.store_result(instruction, "t0");
+            }
+            IrOp::Neg => {
+                self.load(arg1, "t0");
+                self.emit("sub", &["t0", "zero", "t0"], "t0 = 0 - t0".to_string());
+                self.store_result(instruction, "t0");
+            }
+            IrOp::Not => {
+                self.load(arg1, "t0");
+                self.emit("sltiu", &["t0", "t0", "1"], "t0 为 0 时结果为 1".to_string());
+                self.store_result(instruction, "t0");
+            }
+            IrOp::Cast(data_type) => {
+                self.load(arg1, "t0");
+                if data_type == "char" {
+                    self.emit("slli", &["t0", "t0", "24"], "左移 24 位，只保留低 8 位".to_string());
+                    self.emit("srai", &["t0", "t0", "24"], "算术右移 24 位，截断为 char 并符号扩展".to_string());
+                }
+                self.store_result(instruction, "t0");
+            }
+            IrOp::CondJump(relop, label) => {
+                self.load(arg1, "t0");
+                let right = match arg2 {
+                    Some(IrOperand::Const(0)) => "zero",
+                    _ => {
+                        self.load(arg2, "t1");
+                        "t1"
+                    }
+                };
+                // 没有 bgt/ble 指令：交换两个操作数
+                let (mnemonic, a, b) = match relop.as_str() {
+                    "==" => ("beq", "t0", right),
+                    "!=" => ("bne", "t0", right),
+                    "<" => ("blt", "t0", right),
+                    ">=" => ("bge", "t0", right),
+                    ">" => ("blt", right, "t0"),
+                    "<=" => ("bge", right, "t0"),
+                    _ => return Err(unsupported(relop)),
+                };
+                self.emit(mnemonic, &[a, b, label], format!("条件 {} 成立时跳转到 {}", relop, label));
+            }
+            IrOp::Param => {
+                if let Some(operand) = arg1 {
+                    self.pending_params.push(operand.clone());
+                }
+            }
+            IrOp::Call(name, count) => {
+                // 参数按从右向左给出，最后给出的是第一个参数；前 8 个装入 a0–a7，其余存入传参区
+                let start = self.pending_params.len().saturating_sub(*count);
+                let params: Vec<IrOperand> = self.pending_params.drain(start..).rev().collect();
+                for (i, param) in params.iter().enumerate().skip(ARGUMENT_REGISTERS.len()) {
+                    self.load(Some(param), "t0");
+                    let offset = 4 * (i - ARGUMENT_REGISTERS.len());
+                    self.emit("sw", &["t0", &format!("{}(sp)", offset)], format!("第 {} 个参数经栈传递", i + 1));
+                }
+                for (register, param) in ARGUMENT_REGISTERS.iter().zip(&params) {
+                    self.load(Some(param), register);
+                }
+                self.emit("jal", &["ra", name], format!("调用函数 {}，返回地址存入 ra", name));
+                self.store_result(instruction, "a0");
+            }
+            IrOp::Return => {
+                self.load(arg1, "a0");
+                if let (Some(return_label), false) = (self.return_label.clone(), is_last) {
+                    self.emit("jal", &["zero", &return_label], "跳转到函数尾声".to_string());
+                }
+            }
+            IrOp::Halt => self.emit("ebreak", &[], "程序结束，停机".to_string()),
+        }
+        Ok(())
+    }
+
+    /// 对 t0（左操作数）和 t1（右操作数）执行二元运算，结果写入 t0
+    fn generate_binary_op(&mut self, op: &str) -> Result<(), Diagnostic> {
+        match op {
+            "+" => self.emit("add", &["t0", "t0", "t1"], "t0 = t0 + t1".to_string()),
+            "-" => self.emit("sub", &["t0", "t0", "t1"], "t0 = t0 - t1".to_string()),
+            "<<" => self.emit("sll", &["t0", "t0", "t1"], "t0 左移 t1 位".to_string()),
+            "*" | "/" | "%" => {
+                let routine = match op {
+                    "*" => "__mulsi3",
+                    "/" => "__divsi3",
+                    _ => "__modsi3",
+                };
+                self.runtime.insert(routine);
+                self.emit("addi", &["a0", "t0", "0"], "左操作数放入 a0".to_string());
+                self.emit("addi", &["a1", "t1", "0"], "右操作数放入 a1".to_string());
+                self.emit("jal", &["ra", routine], format!("RV32I 没有 {} 指令，调用运行时例程 {}", op, routine));
+                self.emit("addi", &["t0", "a0", "0"], "取回结果".to_string());
+            }
+            "<" => self.emit("slt", &["t0", "t0", "t1"], "t0 < t1 时结果为 1".to_string()),
+            ">" => self.emit("slt", &["t0", "t1", "t0"], "t1 < t0 时结果为 1".to_string()),
+            "<=" | ">=" => {
+                let (a, b) = if op == "<=" { ("t1", "t0") } else { ("t0", "t1") };
+                self.emit("slt", &["t0", a, b], format!("先求 {} 的反面", op));
+                self.emit("xori", &["t0", "t0", "1"], format!("取反得到 {} 的结果", op));
+            }
+            "==" | "!=" => {
+                self.emit("sub", &["t0", "t0", "t1"], "两数相等时差为 0".to_string());
+                if op == "==" {
+                    self.emit("sltiu", &["t0", "t0", "1"], "差为 0 时结果为 1".to_string());
+                } else {
+                    self.emit("sltu", &["t0", "zero", "t0"], "差不为 0 时结果为 1".to_string());
+                }
+            }
+            _ => return Err(unsupported(op)),
+        }
+        Ok(())
+    }
+
+    /// 字符串常量放在数据区，按 4 字节对齐
+    fn string_address(&mut self, text: &str) -> usize {
+        if let Some(address) = self.strings.get(text) {
+            return *address;
+        }
+        let address = self.memory_offset;
+        self.memory_offset += (text.len() + 1).div_ceil(4) * 4;
+        self.strings.insert(text.to_string(), address);
+        address
+    }
+
+    /// 把 32 位常量装入寄存器：12 位以内用 addi，否则 lui 装入高 20 位再加上低 12 位
+    fn load_immediate(&mut self, register: &str, value: i64, description: String) {
+        let value = value as i32;
+        if (-2048..=2047).contains(&value) {
+            self.emit("addi", &[register, "zero", &value.to_string()], description);
+            return;
+        }
+        let upper = (value.wrapping_add(0x800) as u32) >> 12;
+        let lower = value.wrapping_sub((upper << 12) as i32);
+        self.emit("lui", &[register, &upper.to_string()], format!("{}（高 20 位）", description));
+        if lower != 0 {
+            self.emit("addi", &[register, register, &lower.to_string()], "加上低 12 位".to_string());
+        }
+    }
+
+    /// 变量的内存操作数：局部变量相对 s0，全局变量用绝对地址（超出 12 位时先用 lui 把高位装入 t2）
+    fn memory_operand(&mut self, operand: &IrOperand) -> String {
+        if let Some(offset) = self.slots.get(operand) {
+            return format!("{}(s0)", offset);
+        }
+        let address = match operand {
+            IrOperand::Var(name) => self.globals[name] as i32,
+            _ => unreachable!("临时变量 {} 没有分配位置", operand),
+        };
+        if address < 2048 {
+            return format!("{}(zero)", address);
+        }
+        let upper = (address + 0x800) as u32 >> 12;
+        self.emit("lui", &["t2", &upper.to_string()], format!("全局变量 {} 地址的高 20 位", operand));
+        format!("{}(t2)", address - (upper << 12) as i32)
+    }
+
+    /// 把操作数装入寄存器
+    fn load(&mut self, operand: Option<&IrOperand>, register: &str) {
+        let Some(operand) = operand else { return };
+        match operand {
+            IrOperand::Const(value) => self.load_immediate(register, *value, format!("将值 {} 加载到 {}", value, register)),
+            IrOperand::Str(text) => {
+                let address = self.string_address(text);
+                self.load_immediate(
+                    register,
+                    address as i64,
+                    format!("将字符串 \"{}\" 的地址 {} 加载到 {}", text, address, register),
+                );
+            }
+            IrOperand::Var(_) | IrOperand::Temp(_) => {
+                let memory = self.memory_operand(operand);
+                self.emit("lw", &[register, &memory], format!("从 {} ({}) 加载到 {}", operand, memory, register));
+            }
+        }
+    }
+
+    /// 把寄存器写回四元式的结果操作数
+    fn store_result(&mut self, instruction: &IrInstruction, register: &str) {
+        let Some(result) = &instruction.result else { return };
+        let memory = self.memory_operand(result);
+        self.emit("sw", &[register, &memory], format!("将 {} 存储到 {} ({})", register, result, memory));
+    }
+
+    /// 运行时例程：a0 与 a1 为参数，结果在 a0，只使用调用者保存的寄存器
+    fn generate_runtime(&mut self, routine: &'static str) {
+        let label = |suffix: &str| format!("{}_{}", routine, suffix);
+        self.place_label(routine.to_string());
+        if routine == "__mulsi3" {
+            // 移位相加：逐位检查乘数
+            self.emit("addi", &["a2", "a0", "0"], "__mulsi3：a2 = 被乘数".to_string());
+            self.emit("addi", &["a0", "zero", "0"], "积清零".to_string());
+            self.place_label(label("loop"));
+            self.emit("beq", &["a1", "zero", &label("done")], "乘数为 0 时结束".to_string());
+            self.emit("andi", &["a3", "a1", "1"], "取乘数最低位".to_string());
+            self.emit("beq", &["a3", "zero", &label("skip")], "最低位为 0 时不累加".to_string());
+            self.emit("add", &["a0", "a0", "a2"], "积加上被乘数".to_string());
+            self.place_label(label("skip"));
+            self.emit("slli", &["a2", "a2", "1"], "被乘数左移一位".to_string());
+            self.emit("srli", &["a1", "a1", "1"], "乘数逻辑右移一位".to_string());
+            self.emit("jal", &["zero", &label("loop")], "继续下一位".to_string());
+            self.place_label(label("done"));
+            self.emit("jalr", &["zero", "0(ra)"], "返回，积在 a0".to_string());
+            return;
+        }
+
+        // 恢复余数除法：先对绝对值做无符号除法，再按 C 语义修正符号
+        let remainder = routine == "__modsi3";
+        if remainder {
+            self.emit("addi", &["a5", "a0", "0"], "__modsi3：余数与被除数同号".to_string());
+        } else {
+            self.emit("xor", &["a5", "a0", "a1"], "__divsi3：两数异号时商为负".to_string());
+        }
+        self.emit("bge", &["a0", "zero", &label("dividend")], "被除数非负时不取反".to_string());
+        self.emit("sub", &["a0", "zero", "a0"], "被除数取绝对值".to_string());
+        self.place_label(label("dividend"));
+        self.emit("bge", &["a1", "zero", &label("divisor")], "除数非负时不取反".to_string());
+        self.emit("sub", &["a1", "zero", "a1"], "除数取绝对值".to_string());
+        self.place_label(label("divisor"));
+        self.emit("addi", &["a2", "zero", "0"], "商清零".to_string());
+        self.emit("addi", &["a3", "zero", "0"], "余数清零".to_string());
+        self.emit("addi", &["a4", "zero", "32"], "共 32 位".to_string());
+        self.place_label(label("loop"));
+        self.emit("slli", &["a3", "a3", "1"], "余数左移一位".to_string());
+        self.emit("srli", &["a6", "a0", "31"], "取被除数最高位".to_string());
+        self.emit("or", &["a3", "a3", "a6"], "移入余数最低位".to_string());
+        self.emit("slli", &["a0", "a0", "1"], "被除数左移一位".to_string());
+        self.emit("slli", &["a2", "a2", "1"], "商左移一位".to_string());
+        self.emit("bltu", &["a3", "a1", &label("next")], "余数小于除数时商位为 0".to_string());
+        self.emit("sub", &["a3", "a3", "a1"], "余数减去除数".to_string());
+        self.emit("ori", &["a2", "a2", "1"], "商位为 1".to_string());
+        self.place_label(label("next"));
+        self.emit("addi", &["a4", "a4", "-1"], "剩余位数减一".to_string());
+        self.emit("bne", &["a4", "zero", &label("loop")], "继续下一位".to_string());
+        self.emit("addi", &["a0", if remainder { "a3" } else { "a2" }, "0"], "结果放入 a0".to_string());
+        self.emit("bge", &["a5", "zero", &label("done")], "结果应为非负时直接返回".to_string());
+        self.emit("sub", &["a0", "zero", "a0"], "结果取负".to_string());
+        self.place_label(label("done"));
+        self.emit("jalr", &["zero", "0(ra)"], format!("返回，{}在 a0", if remainder { "余数" } else { "商" }));
+    }
+}
+
+fn unsupported(op: &str) -> Diagnostic {
+    Diagnostic::error("E0301", format!("不支持的运算符 '{}'", op), SourceSpan::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::compile;
+    use crate::cpu_simulator::{CPUSimulator, Simulator};
+    use crate::riscv::RiscV;
+    use crate::types::{Architecture, CompilationResult, OptimizationPass, Target};
+
+    const TARGET: Target = Target { architecture: Architecture::RiscV, branch_delay_slot: false };
+
+    /// 按各优化级别编译并运行，返回 a0
+    fn run(source: &str) -> Vec<i64> {
+        (0..=2)
+            .map(|level| {
+                let result = compile(source, &OptimizationPass::for_level(level), TARGET);
+                assert!(result.success, "{:?}", result.errors);
+                let mut simulator = CPUSimulator::new(RiscV);
+                simulator.load_instructions(result.instructions.clone()).unwrap();
+                while simulator.current_instruction_index < result.instructions.len() {
+                    simulator.step().unwrap();
+                }
+                simulator.state.registers.general["x10"]
+            })
+            .collect()
+    }
+
+    fn layout(result: &CompilationResult) -> &str {
+        &result.steps.iter().find(|step| step.id == "register_allocation").unwrap().output
+    }
+
+    #[test]
+    fn runs_compiled_programs() {
+        assert!(run("int main() { return 2 + 3 * 4; }").iter().all(|value| *value == 14));
+        let fib =
+            "int fib(int n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }\nint main() { return fib(10); }";
+        assert!(run(fib).iter().all(|value| *value == 55));
+        // 乘除法经由软件例程
+        let arithmetic = "int main() { int a = -17; int b = 5; return (a / b) * 1000 + (a % b) * 10 + a * b; }";
+        assert!(run(arithmetic).iter().all(|value| *value == -3105));
+        let big = "int g = 123456789;\nint main() { return g - 123456000; }";
+        assert!(run(big).iter().all(|value| *value == 789));
+    }
+
+    #[test]
+    fn passes_arguments_beyond_eight_on_the_stack() {
+        let source = "int f(int a, int b, int c, int d, int e, int g, int h, int i, int j, int k) \
+                      { return a + b + c + d + e + g + h + i - j * 100 + k * 1000; }\n\
+                      int main() { return f(1, 2, 3, 4, 5, 6, 7, 8, 9, 10); }";
+        assert!(run(source).iter().all(|value| *value == 36 - 900 + 10_000));
+        let result = compile(source, &[], TARGET);
+        // main 为第 9、10 个参数留出 8 字节传参区，f 从调用者栈帧中的 0(s0) 与 4(s0) 取得它们
+        assert!(layout(&result).contains("传参区 8 字节"), "{}", layout(&result));
+        assert!(layout(&result).contains("j            0(s0)"), "{}", layout(&result));
+        assert!(layout(&result).contains("k            4(s0)"), "{}", layout(&result));
+    }
+
+    #[test]
+    fn leaves_no_argument_area_for_register_calls() {
+        let result = compile("int f(int a) { return a; }\nint main() { return f(1); }", &[], TARGET);
+        assert!(result.success, "{:?}", result.errors);
+        assert!(layout(&result).contains("main: 栈帧 16 字节，ra 在 -4(s0)，s0 在 -8(s0)，传参区 0 字节"));
+        assert!(result.instructions.iter().all(|instruction| !instruction.operands.contains(&"0(sp)".to_string())));
+    }
+}
@@ -0,0 +1,710 @@
+use crate::compiler::{ASTNode, ASTNodeType};
+use crate::types::*;
+use std::collections::HashMap;
+use std::fmt;
+
+/// 语义分析使用的数据类型
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DataType {
+    Int,
+    Char,
+    Float,
+    Void,
+    /// 字符串常量（char*），只能作为函数参数使用
+    String,
+}
+
+impl DataType {
+    fn from_keyword(keyword: &str) -> Self {
+        match keyword {
+            "char" => DataType::Char,
+            "float" => DataType::Float,
+            "void" => DataType::Void,
+            _ => DataType::Int,
+        }
+    }
+
+    fn is_arithmetic(&self) -> bool {
+        matches!(self, DataType::Int | DataType::Char | DataType::Float)
+    }
+
+    /// 转换时可能丢失精度（浮点转整数、整数转字符）
+    fn narrows_to(&self, target: DataType) -> bool {
+        matches!(
+            (self, target),
+            (DataType::Float, DataType::Int) | (DataType::Float, DataType::Char) | (DataType::Int, DataType::Char)
+        )
+    }
+}
+
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DataType::Int => "int",
+            DataType::Char => "char",
+            DataType::Float => "float",
+            DataType::Void => "void",
+            DataType::String => "char*",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SymbolKind {
+    Global,
+    Local,
+    Parameter,
+    Function,
+}
+
+/// 符号表中的一项
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub data_type: DataType,
+    /// 函数的参数类型，其他符号为空
+    pub parameters: Vec<DataType>,
+    pub variadic: bool,
+    pub scope_depth: usize,
+    /// 所在函数，全局符号为 None
+    pub function: Option<String>,
+    pub span: SourceSpan,
+    pub used: bool,
+}
+
+/// 语义分析器：构建嵌套作用域，将标识符解析到声明，检查类型并插入隐式类型转换
+pub struct SemanticAnalyzer {
+    symbols: Vec<Symbol>,
+    /// 作用域栈，每层把名字映射到 symbols 中的下标
+    scopes: Vec<HashMap<String, usize>>,
+    functions: HashMap<String, usize>,
+    current_function: Option<(String, DataType)>,
+    conversions: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl SemanticAnalyzer {
+    pub fn new() -> Self {
+        let mut analyzer = Self {
+            symbols: Vec::new(),
+            scopes: vec![HashMap::new()],
+            functions: HashMap::new(),
+            current_function: None,
+            conversions: 0,
+            diagnostics: Vec::new(),
+        };
+
+        // 内置库函数：int printf(char*, ...)
+        let printf = analyzer.add_symbol(Symbol {
+            name: "printf".to_string(),
+            kind: SymbolKind::Function,
+            data_type: DataType::Int,
+            parameters: vec![DataType::String],
+            variadic: true,
+            scope_depth: 0,
+            function: None,
+            span: SourceSpan::default(),
+            used: true,
+        });
+        analyzer.functions.insert("printf".to_string(), printf);
+        analyzer
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    /// 插入的隐式类型转换数量
+    pub fn conversions(&self) -> usize {
+        self.conversions
+    }
+
+    pub fn analyze(&mut self, program: &mut ASTNode) {
+        // 先登记所有函数签名，使函数可以在定义之前被调用
+        for child in &program.children {
+            if matches!(child.node_type, ASTNodeType::Function) {
+                self.declare_function(child);
+            }
+        }
+
+        for child in &mut program.children {
+            if matches!(child.node_type, ASTNodeType::Function) {
+                self.analyze_function(child);
+            } else {
+                self.analyze_statement(child);
+            }
+        }
+    }
+
+    fn add_symbol(&mut self, symbol: Symbol) -> usize {
+        self.symbols.push(symbol);
+        self.symbols.len() - 1
+    }
+
+    fn declare_function(&mut self, node: &ASTNode) {
+        let name = &node.children[0];
+        if let Some(&existing) = self.functions.get(&name.value) {
+            let previous = self.symbols[existing].span;
+            self.diagnostics.push(Diagnostic::error(
+                "E0402",
+                format!("函数 '{}' 重复定义（首次定义于第 {} 行）", name.value, previous.line),
+                name.span,
+            ));
+            return;
+        }
+
+        let parameters = node.children[1..node.children.len() - 1]
+            .iter()
+            .map(|param| DataType::from_keyword(&param.value))
+            .collect();
+        let index = self.add_symbol(Symbol {
+            name: name.value.clone(),
+            kind: SymbolKind::Function,
+            data_type: DataType::from_keyword(&node.value),
+            parameters,
+            variadic: false,
+            scope_depth: 0,
+            function: None,
+            span: name.span,
+            used: name.value == "main",
+        });
+        self.functions.insert(name.value.clone(), index);
+        self.scopes[0].insert(name.value.clone(), index);
+    }
+
+    fn analyze_function(&mut self, node: &mut ASTNode) {
+        let name = node.children[0].value.clone();
+        let return_type = DataType::from_keyword(&node.value);
+        self.current_function = Some((name.clone(), return_type));
+
+        // 参数与函数体共享同一个作用域
+        self.push_scope();
+        let last = node.children.len() - 1;
+        for param in &node.children[1..last] {
+            let param_type = DataType::from_keyword(&param.value);
+            if param_type == DataType::Void {
+                self.diagnostics.push(Diagnostic::error("E0401", "参数不能声明为 void 类型", param.span));
+            }
+            self.declare_variable(&param.children[0], param_type, SymbolKind::Parameter);
+        }
+        let body = &mut node.children[last];
+        for statement in &mut body.children {
+            self.analyze_statement(statement);
+        }
+        self.pop_scope();
+
+        if return_type != DataType::Void && name != "main" && !always_returns(&node.children[last]) {
+            self.diagnostics.push(Diagnostic::warning(
+                "W0401",
+                format!("函数 '{}' 并非所有路径都有返回值", name),
+                node.children[0].span,
+            ));
+        }
+        self.current_function = None;
+    }
+
+    fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    /// 离开作用域时检查未使用的局部变量
+    fn pop_scope(&mut self) {
+        if let Some(scope) = self.scopes.pop() {
+            let mut unused: Vec<&Symbol> = scope
+                .values()
+                .map(|&index| &self.symbols[index])
+                .filter(|symbol| symbol.kind == SymbolKind::Local && !symbol.used)
+                .collect();
+            unused.sort_by_key(|symbol| symbol.span.start);
+            let warnings: Vec<Diagnostic> = unused
+                .into_iter()
+                .map(|symbol| {
+                    Diagnostic::warning("W0402", format!("变量 '{}' 已声明但从未使用", symbol.name), symbol.span)
+                })
+                .collect();
+            self.diagnostics.extend(warnings);
+        }
+    }
+
+    fn declare_variable(&mut self, identifier: &ASTNode, data_type: DataType, kind: SymbolKind) {
+        let depth = self.scopes.len() - 1;
+        if let Some(&existing) = self.scopes[depth].get(&identifier.value) {
+            let previous = self.symbols[existing].span;
+            self.diagnostics.push(Diagnostic::error(
+                "E0410",
+                format!("'{}' 在同一作用域内重复声明（首次声明于第 {} 行）", identifier.value, previous.line),
+                identifier.span,
+            ));
+            return;
+        }
+
+        let index = self.add_symbol(Symbol {
+            name: identifier.value.clone(),
+            kind,
+            data_type,
+            parameters: Vec::new(),
+            variadic: false,
+            scope_depth: depth,
+            function: self.current_function.as_ref().map(|(name, _)| name.clone()),
+            span: identifier.span,
+            used: false,
+        });
+        self.scopes[depth].insert(identifier.value.clone(), index);
+    }
+
+    /// 由内向外查找变量声明
+    fn resolve(&mut self, identifier: &ASTNode) -> Option<usize> {
+        let index = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&identifier.value).copied());
+        match index {
+            Some(index) => {
+                self.symbols[index].used = true;
+                Some(index)
+            }
+            None => {
+                self.diagnostics.push(Diagnostic::error(
+                    "E0403",
+                    format!("未声明的标识符 '{}'", identifier.value),
+                    identifier.span,
+                ));
+                None
+            }
+        }
+    }
+
+    fn analyze_statement(&mut self, node: &mut ASTNode) {
+        match node.node_type {
+            ASTNodeType::Declaration => {
+                let data_type = DataType::from_keyword(&node.value);
+                if data_type == DataType::Void {
+                    self.diagnostics.push(Diagnostic::error("E0401", "变量不能声明为 void 类型", node.span));
+                }
+                // 先分析初始化表达式，使 int x = x; 中的 x 指向外层声明
+                if node.children.len() > 1 {
+                    self.analyze_expression(&mut node.children[1]);
+                    self.coerce(&mut node.children[1], data_type);
+                }
+                let kind = if self.current_function.is_some() { SymbolKind::Local } else { SymbolKind::Global };
+                let identifier = node.children[0].clone();
+                self.declare_variable(&identifier, data_type, kind);
+            }
+            ASTNodeType::Block => {
+                self.push_scope();
+                for child in &mut node.children {
+                    self.analyze_statement(child);
+                }
+                self.pop_scope();
+            }
+            ASTNodeType::Statement => {
+                for child in &mut node.children {
+                    self.analyze_expression(child);
+                }
+            }
+            ASTNodeType::If | ASTNodeType::While => {
+                self.analyze_condition(&mut node.children[0]);
+                for child in &mut node.children[1..] {
+                    self.analyze_statement(child);
+                }
+            }
+            ASTNodeType::For => {
+                // for 的初始化部分有自己的作用域
+                self.push_scope();
+                self.analyze_statement(&mut node.children[0]);
+                if !matches!(node.children[1].node_type, ASTNodeType::Empty) {
+                    self.analyze_condition(&mut node.children[1]);
+                }
+                self.analyze_statement(&mut node.children[2]);
+                self.analyze_statement(&mut node.children[3]);
+                self.pop_scope();
+            }
+            ASTNodeType::Return => self.analyze_return(node),
+            _ => {}
+        }
+    }
+
+    fn analyze_return(&mut self, node: &mut ASTNode) {
+        let return_type = match &self.current_function {
+            Some((_, return_type)) => *return_type,
+            None => {
+                self.diagnostics.push(Diagnostic::error("E0411", "return 不在函数内", node.span));
+                if let Some(value) = node.children.first_mut() {
+                    self.analyze_expression(value);
+                }
+                return;
+            }
+        };
+
+        match node.children.first_mut() {
+            Some(value) => {
+                let value_type = self.analyze_expression(value);
+                if return_type == DataType::Void {
+                    self.diagnostics.push(Diagnostic::error("E0408", "void 函数不能返回值", value.span));
+                } else if value_type != DataType::Void {
+                    self.coerce(value, return_type);
+                }
+            }
+            None if return_type != DataType::Void => {
+                self.diagnostics.push(Diagnostic::error(
+                    "E0409",
+                    format!("函数应返回 {} 类型的值", return_type),
+                    node.span,
+                ));
+            }
+            None => {}
+        }
+    }
+
+    fn analyze_condition(&mut self, condition: &mut ASTNode) {
+        let condition_type = self.analyze_expression(condition);
+        if !condition_type.is_arithmetic() {
+            self.diagnostics.push(Diagnostic::error(
+                "E0404",
+                format!("条件表达式不能是 {} 类型", condition_type),
+                condition.span,
+            ));
+        }
+    }
+
+    /// 分析表达式并返回它的类型
+    fn analyze_expression(&mut self, node: &mut ASTNode) -> DataType {
+        match node.node_type {
+            ASTNodeType::Number if node.value.contains('.') => {
+                // 模拟器没有浮点单元，中间代码把浮点常量截断为整数
+                let truncated = node.value.parse::<f64>().map_or(0, |value| value as i64);
+                self.diagnostics.push(Diagnostic::warning(
+                    "W0404",
+                    format!("浮点常量 {} 将被截断为整数 {}", node.value, truncated),
+                    node.span,
+                ));
+                DataType::Float
+            }
+            ASTNodeType::Number => DataType::Int,
+            ASTNodeType::CharLiteral => DataType::Char,
+            ASTNodeType::StringLiteral => DataType::String,
+            ASTNodeType::Identifier => match self.resolve(node) {
+                Some(index) if self.symbols[index].kind == SymbolKind::Function => {
+                    self.diagnostics.push(Diagnostic::error(
+                        "E0412",
+                        format!("函数 '{}' 不能作为变量使用", node.value),
+                        node.span,
+                    ));
+                    DataType::Int
+                }
+                Some(index) => self.symbols[index].data_type,
+                None => DataType::Int,
+            },
+            ASTNodeType::Assignment => {
+                let target_type = match self.resolve(&node.children[0]) {
+                    Some(index) if self.symbols[index].kind == SymbolKind::Function => {
+                        self.diagnostics.push(Diagnostic::error(
+                            "E0413",
+                            format!("不能给函数 '{}' 赋值", node.children[0].value),
+                            node.children[0].span,
+                        ));
+                        DataType::Int
+                    }
+                    Some(index) => self.symbols[index].data_type,
+                    None => DataType::Int,
+                };
+                let value_type = self.analyze_expression(&mut node.children[1]);
+                self.check_operand(&node.children[1], value_type);
+                self.coerce(&mut node.children[1], target_type);
+                target_type
+            }
+            ASTNodeType::UnaryOp => {
+                let operand_type = self.analyze_expression(&mut node.children[0]);
+                self.check_operand(&node.children[0], operand_type);
+                match node.value.as_str() {
+                    "!" => DataType::Int,
+                    _ => promote(operand_type),
+                }
+            }
+            ASTNodeType::BinaryOp => self.analyze_binary(node),
+            ASTNodeType::Call => self.analyze_call(node),
+            _ => DataType::Int,
+        }
+    }
+
+    fn analyze_binary(&mut self, node: &mut ASTNode) -> DataType {
+        let left = self.analyze_expression(&mut node.children[0]);
+        let right = self.analyze_expression(&mut node.children[1]);
+        self.check_operand(&node.children[0], left);
+        self.check_operand(&node.children[1], right);
+
+        let op = node.value.clone();
+        if matches!(op.as_str(), "&&" | "||") {
+            return DataType::Int;
+        }
+        if op == "%" && (left == DataType::Float || right == DataType::Float) {
+            self.diagnostics.push(Diagnostic::error("E0405", "取模运算的操作数必须是整数", node.span));
+            return DataType::Int;
+        }
+
+        // 常用算术转换：有 float 则转为 float，否则整型提升为 int
+        let common = if left == DataType::Float || right == DataType::Float {
+            DataType::Float
+        } else {
+            DataType::Int
+        };
+        self.coerce(&mut node.children[0], common);
+        self.coerce(&mut node.children[1], common);
+
+        match op.as_str() {
+            "==" | "!=" | "<" | ">" | "<=" | ">=" => DataType::Int,
+            _ => common,
+        }
+    }
+
+    fn analyze_call(&mut self, node: &mut ASTNode) -> DataType {
+        let index = match self.functions.get(&node.value) {
+            Some(&index) => index,
+            None => {
+                self.diagnostics.push(Diagnostic::error(
+                    "E0406",
+                    format!("未声明的函数 '{}'", node.value),
+                    node.span,
+                ));
+                for argument in &mut node.children {
+                    self.analyze_expression(argument);
+                }
+                return DataType::Int;
+            }
+        };
+        self.symbols[index].used = true;
+        let function = self.symbols[index].clone();
+
+        let count_ok = if function.variadic {
+            node.children.len() >= function.parameters.len()
+        } else {
+            node.children.len() == function.parameters.len()
+        };
+        if !count_ok {
+            self.diagnostics.push(Diagnostic::error(
+                "E0407",
+                format!(
+                    "函数 '{}' 需要 {}{} 个参数，实际传入 {} 个",
+                    function.name,
+                    if function.variadic { "至少 " } else { "" },
+                    function.parameters.len(),
+                    node.children.len()
+                ),
+                node.span,
+            ));
+        }
+
+        for (i, argument) in node.children.iter_mut().enumerate() {
+            let argument_type = self.analyze_expression(argument);
+            match function.parameters.get(i) {
+                Some(DataType::String) if argument_type != DataType::String => {
+                    self.diagnostics.push(Diagnostic::error(
+                        "E0414",
+                        format!("第 {} 个参数应为字符串", i + 1),
+                        argument.span,
+                    ));
+                }
+                Some(&parameter_type) if parameter_type != DataType::String => {
+                    self.check_operand(argument, argument_type);
+                    self.coerce(argument, parameter_type);
+                }
+                // 可变参数部分按默认实参提升处理
+                _ if argument_type != DataType::String => {
+                    self.check_operand(argument, argument_type);
+                }
+                _ => {}
+            }
+        }
+
+        function.data_type
+    }
+
+    /// 运算对象必须是算术类型
+    fn check_operand(&mut self, node: &ASTNode, data_type: DataType) {
+        if !data_type.is_arithmetic() {
+            self.diagnostics.push(Diagnostic::error(
+                "E0415",
+                format!("{} 类型的值不能参与运算", data_type),
+                node.span,
+            ));
+        }
+    }
+
+    /// 需要时把表达式包装为 Cast 节点，实现隐式类型转换
+    fn coerce(&mut self, node: &mut ASTNode, target: DataType) {
+        let source = self.expression_type(node);
+        if source == target || !source.is_arithmetic() || !target.is_arithmetic() {
+            return;
+        }
+        if source.narrows_to(target) {
+            self.diagnostics.push(Diagnostic::warning(
+                "W0403",
+                format!("从 {} 隐式转换为 {} 可能丢失精度", source, target),
+                node.span,
+            ));
+        }
+
+        let span = node.span;
+        let inner = std::mem::replace(node, ASTNode::new(ASTNodeType::Empty, "", Vec::new()));
+        *node = ASTNode::new(ASTNodeType::Cast, target.to_string(), vec![inner]).at(span);
+        self.conversions += 1;
+    }
+
+    /// 已分析过的表达式的类型（不重复报告诊断）
+    fn expression_type(&self, node: &ASTNode) -> DataType {
+        match node.node_type {
+            ASTNodeType::Cast => DataType::from_keyword(&node.value),
+            ASTNodeType::Number if node.value.contains('.') => DataType::Float,
+            ASTNodeType::CharLiteral => DataType::Char,
+            ASTNodeType::StringLiteral => DataType::String,
+            ASTNodeType::Identifier => self
+                .scopes
+                .iter()
+                .rev()
+                .find_map(|scope| scope.get(&node.value))
+                .map_or(DataType::Int, |&index| self.symbols[index].data_type),
+            ASTNodeType::Assignment => self.expression_type(&node.children[0]),
+            ASTNodeType::Call => self
+                .functions
+                .get(&node.value)
+                .map_or(DataType::Int, |&index| self.symbols[index].data_type),
+            ASTNodeType::UnaryOp if node.value != "!" => promote(self.expression_type(&node.children[0])),
+            ASTNodeType::BinaryOp if matches!(node.value.as_str(), "+" | "-" | "*" | "/") => {
+                let left = self.expression_type(&node.children[0]);
+                let right = self.expression_type(&node.children[1]);
+                if left == DataType::Float || right == DataType::Float {
+                    DataType::Float
+                } else {
+                    DataType::Int
+                }
+            }
+            _ => DataType::Int,
+        }
+    }
+
+    /// 符号表的文本形式
+    pub fn format_symbol_table(&self) -> String {
+        let mut output = format!("{:<12} {:<16} {:<6} {:<10} {}\n", "名称", "类型", "类别", "作用域", "位置");
+        for symbol in &self.symbols {
+            if symbol.span == SourceSpan::default() {
+                continue; // 内置函数
+            }
+            let data_type = if symbol.kind == SymbolKind::Function {
+                let params: Vec<String> = symbol.parameters.iter().map(|t| t.to_string()).collect();
+                format!("{}({})", symbol.data_type, params.join(", "))
+            } else {
+                symbol.data_type.to_string()
+            };
+            let kind = match symbol.kind {
+                SymbolKind::Global => "全局",
+                SymbolKind::Local => "局部",
+                SymbolKind::Parameter => "参数",
+                SymbolKind::Function => "函数",
+            };
+            let scope = match &symbol.function {
+                Some(function) => format!("{}#{}", function, symbol.scope_depth),
+                None => "全局".to_string(),
+            };
+            output.push_str(&format!(
+                "{:<12} {:<16} {:<6} {:<10} {}:{}\n",
+                symbol.name, data_type, kind, scope, symbol.span.line, symbol.span.column
+            ));
+        }
+        output
+    }
+}
+
+impl Default for SemanticAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 整型提升：char 参与运算时提升为 int
+fn promote(data_type: DataType) -> DataType {
+    match data_type {
+        DataType::Char => DataType::Int,
+        other => other,
+    }
+}
+
+/// 语句是否在所有路径上都执行了 return
+fn always_returns(node: &ASTNode) -> bool {
+    match node.node_type {
+        ASTNodeType::Return => true,
+        ASTNodeType::Block => node.children.iter().any(always_returns),
+        ASTNodeType::If => node.children.len() == 3 && always_returns(&node.children[1]) && always_returns(&node.children[2]),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{Lexer, Parser};
+
+    /// 分析源程序，返回语法树和全部诊断的代码
+    fn analyze(source: &str) -> (ASTNode, Vec<String>) {
+        let tokens = Lexer::new(source.to_string()).tokenize();
+        let mut parser = Parser::new(tokens);
+        let mut ast = parser.parse();
+        assert!(parser.diagnostics().is_empty(), "{:?}", parser.diagnostics());
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.analyze(&mut ast);
+        (ast, analyzer.diagnostics().iter().map(|diagnostic| diagnostic.code.clone()).collect())
+    }
+
+    fn codes(source: &str) -> Vec<String> {
+        analyze(source).1
+    }
+
+    #[test]
+    fn resolves_nested_scopes() {
+        assert!(codes("int x = 1;\nint main() { int x = 2; { int x = 3; x = x + 1; } return x; }").is_empty());
+        assert_eq!(codes("int main() { { int y = 1; y = 2; } return y; }"), ["E0403"]);
+        assert_eq!(codes("int main() { int a = 1; int a = 2; return a; }"), ["E0410"]);
+        assert_eq!(codes("int f(int a) { int a = 1; return a; }\nint main() { return f(1); }"), ["E0410"]);
+        // 函数可以在定义之前调用，局部变量可以遮蔽同名函数
+        assert!(codes("int main() { return f(); }\nint f() { return 1; }").is_empty());
+        assert!(codes("int f() { return 1; }\nint main() { int f = 3; return f; }").is_empty());
+    }
+
+    #[test]
+    fn each_error_has_its_own_code() {
+        assert_eq!(codes("int f() { return 1; }\nint f() { return 2; }\nint main() { return f(); }"), ["E0402"]);
+        assert_eq!(codes("int f(void v) { return 0; }\nint main() { return 0; }"), ["E0401"]);
+        assert_eq!(codes("int i = 0; while (1) { i = i + 1; if (i > 3) return 0; }"), ["E0411"]);
+        assert_eq!(codes("int f() { return 1; }\nint main() { return f + 1; }"), ["E0412"]);
+        assert_eq!(codes("int f() { return 1; }\nint main() { f = 3; return 0; }"), ["E0413"]);
+        assert_eq!(codes("int main() { printf(1); return 0; }"), ["E0414"]);
+        assert_eq!(codes("int main() { return \"a\" + 1; }"), ["E0415"]);
+        assert_eq!(codes("int main() { if (\"a\") return 1; return 0; }"), ["E0404"]);
+        assert_eq!(codes("int main() { return 5 % 1.5; }"), ["W0404", "E0405"]);
+        assert_eq!(codes("int main() { return h(); }"), ["E0406"]);
+        assert_eq!(codes("int f(int a) { return a; }\nint main() { return f(1, 2); }"), ["E0407"]);
+        assert_eq!(codes("void f() { return 1; }\nint main() { f(); return 0; }"), ["E0408"]);
+        assert_eq!(codes("int main() { return; }"), ["E0409"]);
+    }
+
+    #[test]
+    fn warns_without_failing() {
+        assert_eq!(codes("int f(int a) { if (a) return 1; }\nint main() { return f(1); }"), ["W0401"]);
+        assert_eq!(codes("int main() { int unused = 1; return 0; }"), ["W0402"]);
+        assert_eq!(codes("int main() { char c = 300; return c; }"), ["W0403"]);
+    }
+
+    #[test]
+    fn inserts_implicit_conversions() {
+        let (ast, codes) = analyze("int main() { char c = 'a'; int i = c + 1; c = i; return c; }");
+        assert_eq!(codes, ["W0403"]);
+        let tree = ast.pretty();
+        assert!(tree.contains("Cast"), "{}", tree);
+    }
+}
@@ -1,13 +1,63 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CompilationResult {
     pub success: bool,
     pub instructions: Vec<Instruction>,
-    pub errors: Vec<String>,
-    pub warnings: Vec<String>,
+    pub errors: Vec<Diagnostic>,
+    pub warnings: Vec<Diagnostic>,
     pub compilation_time: u64, // 毫秒
+    pub steps: Vec<CompilationStep>,
+    /// 每个优化遍每次执行的记录，按执行顺序排列
+    pub optimizations: Vec<OptimizationReport>,
+}
+
+// 编译诊断相关类型定义
+
+/// 源代码中的一段区间：字节偏移 [start, end)，以及起点的行列号（从 1 开始，列按字符计）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct SourceSpan {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum DiagnosticSeverity {
+    Error,
+    Warning,
+    Info,
+}
+
+/// 编译器各阶段产生的诊断信息，供编辑器标注出错位置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub severity: DiagnosticSeverity,
+    pub code: String,
+    pub message: String,
+    pub span: SourceSpan,
+}
+
+impl Diagnostic {
+    pub fn error(code: &str, message: impl Into<String>, span: SourceSpan) -> Self {
+        Self {
+            severity: DiagnosticSeverity::Error,
+            code: code.to_string(),
+            message: message.into(),
+            span,
+        }
+    }
+
+    pub fn warning(code: &str, message: impl Into<String>, span: SourceSpan) -> Self {
+        Self {
+            severity: DiagnosticSeverity::Warning,
+            code: code.to_string(),
+            message: message.into(),
+            span,
+        }
+    }
 }
 
 // 系统层次结构相关类型定义
@@ -72,6 +122,20 @@ pub struct Instruction {
     pub machine_code: String,
     pub description: String,
     pub cycles: u32,
+    /// 指令前的标签（跳转目标），如 "L1"
+    #[serde(default)]
+    pub label: Option<String>,
+    /// 机器码各字段的含义（操作码、ModR/M、SIB、位移、立即数）
+    #[serde(default)]
+    pub encoding: Vec<EncodingField>,
+}
+
+/// 机器码中的一个字段
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncodingField {
+    pub field: String,
+    pub hex: String,
+    pub description: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -123,11 +187,79 @@ pub struct RegisterState {
     pub special: HashMap<String, i64>,
 }
 
+/// 按字节寻址的内存，读写方法见 memory.rs
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MemoryState {
-    pub data: HashMap<u64, i64>,
+    /// 地址空间的划分：代码段、数据段、堆、栈
+    pub segments: Vec<MemorySegment>,
+    /// 非零的字节：地址 -> 值，其余字节为 0
+    pub bytes: BTreeMap<u64, u8>,
+    /// 栈视图：栈顶到栈指针之间的双字，先压入的在前
     pub stack: Vec<i64>,
-    pub heap: HashMap<u64, i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SegmentKind {
+    Code,
+    Data,
+    Heap,
+    Stack,
+    /// 启用 MMU 时存放页目录与页表的区域
+    PageTable,
+    /// 中断描述符表
+    InterruptTable,
+}
+
+/// 一段连续的地址空间 [base, base + size)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemorySegment {
+    pub kind: SegmentKind,
+    pub name: String,
+    pub base: u64,
+    pub size: u64,
+    pub writable: bool,
+}
+
+/// 十六进制转储的一行（16 字节）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HexDumpLine {
+    pub address: u64,
+    /// 该行起始地址所在的段，不在任何段中时为 None
+    pub segment: Option<SegmentKind>,
+    pub bytes: Vec<u8>,
+    /// 以空格分隔的十六进制字节
+    pub hex: String,
+    /// 可打印字符，其余字节显示为 '.'
+    pub ascii: String,
+}
+
+/// 栈中双字的用途
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StackSlotKind {
+    ReturnAddress,
+    /// 调用者保存的帧指针（x86 的 EBP）
+    SavedFramePointer,
+    Local,
+    /// 用途未知，如不属于任何栈帧的双字
+    Data,
+}
+
+/// 栈中的一个双字，label 为其相对帧指针或栈指针的位置，如 [EBP-8]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StackSlot {
+    pub address: u64,
+    pub value: i64,
+    pub kind: StackSlotKind,
+    pub label: String,
+}
+
+/// 一个栈帧：function 为所属函数，slots 从高地址到低地址排列
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StackFrame {
+    pub function: Option<String>,
+    pub frame_pointer: Option<u64>,
+    pub return_address: Option<u64>,
+    pub slots: Vec<StackSlot>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -137,6 +269,8 @@ pub struct FlagsState {
     pub overflow: bool,
     pub negative: bool,
     pub parity: bool,
+    /// 允许可屏蔽中断（IF），复位后为真
+    pub interrupt: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -149,6 +283,416 @@ pub enum ExecutionStage {
     Complete,
 }
 
+/// 流水线模式的设置：enabled 为假时逐条指令依次经过各阶段，forwarding 控制是否启用前递
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PipelineConfig {
+    pub enabled: bool,
+    pub forwarding: bool,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        Self { enabled: false, forwarding: true }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum HazardKind {
+    ReadAfterWrite,
+    WriteAfterRead,
+    WriteAfterWrite,
+    /// 转移使错误路径上的指令被清除
+    Control,
+}
+
+/// 流水线中两条指令之间的冒险，producer 为较早的指令，consumer 为较晚的指令（均为指令下标）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hazard {
+    pub kind: HazardKind,
+    /// 涉及的寄存器，控制冒险为 None
+    pub register: Option<String>,
+    pub producer: usize,
+    pub consumer: usize,
+    /// 是否因此停顿
+    pub stall: bool,
+    pub description: String,
+}
+
+/// 一次前递：from 为结果所在的流水线寄存器（Execute 为 EX/MEM，MemoryAccess 为 MEM/WB），送往执行级
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Forwarding {
+    pub register: String,
+    pub from: ExecutionStage,
+    pub producer: usize,
+    pub consumer: usize,
+    /// 前递的值，标志位为 None
+    pub value: Option<i64>,
+}
+
+/// 流水线一级在某个周期的占用情况，instruction 为 None 表示气泡
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineSlot {
+    pub stage: ExecutionStage,
+    pub instruction: Option<usize>,
+    /// 指令进入流水线的次序，区分同一条指令的多次执行
+    pub sequence: Option<u64>,
+    pub text: String,
+    /// 因冒险停留在本级
+    pub stalled: bool,
+    /// 位于错误路径上，在本周期末被清除
+    pub flushed: bool,
+}
+
+/// 流水线一个周期的占用表（取指、译码、执行、访存、写回）与本周期检测到的冒险和前递；
+/// 计数均为到本周期为止的累计值
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineCycle {
+    pub cycle: u64,
+    pub slots: Vec<PipelineSlot>,
+    pub hazards: Vec<Hazard>,
+    pub forwardings: Vec<Forwarding>,
+    pub retired: u64,
+    pub stalls: u64,
+    pub flushes: u64,
+}
+
+/// 分支预测器，在载入程序时选择
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum PredictorKind {
+    /// 静态预测不转移
+    #[default]
+    StaticNotTaken,
+    OneBit,
+    /// 两位饱和计数器
+    TwoBit,
+    /// 全局历史与地址异或后索引两位计数器
+    Gshare,
+    /// 竞争预测器：按地址选择局部预测器或 gshare
+    Tournament,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BranchKind {
+    Conditional,
+    /// 无条件转移，包括调用与返回
+    Unconditional,
+}
+
+/// 一条转移指令的预测统计
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BranchStatistic {
+    pub instruction: usize,
+    pub address: u64,
+    pub text: String,
+    pub kind: BranchKind,
+    pub executed: u64,
+    pub taken: u64,
+    /// 预测正确的次数：方向与目标都正确
+    pub correct: u64,
+    pub accuracy: f64,
+}
+
+/// 分支预测的统计：总体准确率、转移目标缓冲的命中情况与预测错误损失的周期数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BranchStatistics {
+    pub predictor: PredictorKind,
+    pub executed: u64,
+    pub correct: u64,
+    pub accuracy: f64,
+    pub btb_hits: u64,
+    pub btb_misses: u64,
+    pub penalty_cycles: u64,
+    pub branches: Vec<BranchStatistic>,
+}
+
+/// 缓存行的替换策略
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum ReplacementPolicy {
+    #[default]
+    Lru,
+    Fifo,
+    Random,
+    /// 树形伪 LRU
+    Plru,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum WritePolicy {
+    /// 写回：只写缓存，行被替换时再写到下一级
+    #[default]
+    WriteBack,
+    /// 写直达：每次写入同时写到下一级
+    WriteThrough,
+}
+
+/// 一级缓存的配置：容量、行大小（字节）与相联度均为 2 的幂；write_allocate 为写不命中时是否调入该行
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CacheLevelConfig {
+    pub size: u64,
+    pub line_size: u64,
+    pub associativity: u64,
+    pub replacement: ReplacementPolicy,
+    pub write_policy: WritePolicy,
+    pub write_allocate: bool,
+    /// 访问这一级所需的周期数
+    pub hit_latency: u64,
+}
+
+/// 缓存层次的配置：L1 之后可以有 L2；enabled 为假时访存直接读写内存，不计延迟
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CacheConfig {
+    pub enabled: bool,
+    pub l1: CacheLevelConfig,
+    pub l2: Option<CacheLevelConfig>,
+    pub memory_latency: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        let level = |size, line_size, associativity, hit_latency| CacheLevelConfig {
+            size,
+            line_size,
+            associativity,
+            replacement: ReplacementPolicy::Lru,
+            write_policy: WritePolicy::WriteBack,
+            write_allocate: true,
+            hit_latency,
+        };
+        Self { enabled: false, l1: level(1024, 16, 2, 1), l2: Some(level(8192, 32, 4, 10)), memory_latency: 100 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum CacheOperation {
+    Read,
+    Write,
+    /// 被替换出的脏行写回这一级
+    WriteBack,
+}
+
+/// 一次访问在某一级缓存中的结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheLevelAccess {
+    pub level: String,
+    pub operation: CacheOperation,
+    /// 所在缓存行的起始地址
+    pub line_address: u64,
+    pub set: usize,
+    pub tag: u64,
+    pub hit: bool,
+    /// 调入新行时被替换出的行的起始地址
+    pub evicted: Option<u64>,
+    /// 被替换出的行是脏行，需要写回下一级
+    pub evicted_dirty: bool,
+}
+
+/// 一次访存经过缓存层次的情况，跨越缓存行的访问按行分开记录；latency 为这次访问所需的周期数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheAccess {
+    pub address: u64,
+    pub size: u64,
+    pub write: bool,
+    pub levels: Vec<CacheLevelAccess>,
+    /// 访问到达了内存
+    pub memory: bool,
+    pub latency: u64,
+}
+
+/// 缓存行的状态；缓存只模拟标记与状态，bytes 取自内存
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheLineView {
+    pub way: usize,
+    pub valid: bool,
+    pub dirty: bool,
+    pub tag: u64,
+    pub address: u64,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheSetView {
+    pub index: usize,
+    pub lines: Vec<CacheLineView>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheLevelView {
+    pub level: String,
+    pub config: CacheLevelConfig,
+    pub hits: u64,
+    pub misses: u64,
+    pub hit_rate: f64,
+    /// 写回下一级的脏行数
+    pub writebacks: u64,
+    pub sets: Vec<CacheSetView>,
+}
+
+/// 各级缓存的内容与命中统计
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheView {
+    pub enabled: bool,
+    pub memory_latency: u64,
+    pub levels: Vec<CacheLevelView>,
+}
+
+/// MMU 的配置：enabled 为假时虚拟地址即物理地址
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MmuConfig {
+    pub enabled: bool,
+    /// TLB 的项数，全相联，按 LRU 替换
+    pub tlb_entries: usize,
+    /// 程序运行在用户态，不能访问只允许内核态访问的页
+    pub user_mode: bool,
+    /// TLB 不命中时遍历页表所需的周期数
+    pub walk_latency: u64,
+}
+
+impl Default for MmuConfig {
+    fn default() -> Self {
+        Self { enabled: false, tlb_entries: 8, user_mode: true, walk_latency: 20 }
+    }
+}
+
+/// 页的访问权限；user 为假的页只允许内核态访问
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PagePermissions {
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+    pub user: bool,
+}
+
+/// 访问内存的方式：取指为执行
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum AccessKind {
+    Read,
+    Write,
+    Execute,
+}
+
+/// 页表遍历读到的一项：所在的物理地址与值
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct PageTableRead {
+    pub address: u64,
+    pub value: u32,
+}
+
+/// 一次虚拟地址到物理地址的转换
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Translation {
+    pub virtual_address: u64,
+    pub access: AccessKind,
+    /// 虚拟地址拆分出的页目录下标、页表下标与页内偏移
+    pub directory_index: u64,
+    pub table_index: u64,
+    pub offset: u64,
+    pub tlb_hit: bool,
+    /// TLB 不命中时读到的页目录项与页表项
+    pub directory_entry: Option<PageTableRead>,
+    pub table_entry: Option<PageTableRead>,
+    /// 出现页错误时为 None
+    pub physical_address: Option<u64>,
+    pub fault: Option<String>,
+    pub cycles: u64,
+    /// 逐步的说明
+    pub steps: Vec<String>,
+}
+
+/// 页表中的一个有效映射
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageMapping {
+    pub virtual_address: u64,
+    pub physical_address: u64,
+    pub permissions: PagePermissions,
+    pub accessed: bool,
+    pub dirty: bool,
+    /// 页表项所在的物理地址
+    pub entry_address: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TlbEntryView {
+    pub virtual_address: u64,
+    pub physical_address: u64,
+    pub permissions: PagePermissions,
+    pub dirty: bool,
+}
+
+/// 页表、TLB 的内容与转换统计
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageTableView {
+    pub config: MmuConfig,
+    /// 页目录的物理地址
+    pub directory: u64,
+    pub mappings: Vec<PageMapping>,
+    pub tlb: Vec<TlbEntryView>,
+    pub tlb_hits: u64,
+    pub tlb_misses: u64,
+    pub page_faults: u64,
+}
+
+/// 中断与异常的种类；Return 为从处理程序返回
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum InterruptKind {
+    DivideError,
+    InvalidOpcode,
+    GeneralProtection,
+    PageFault,
+    /// INT n 等软件中断
+    Software,
+    Timer,
+    Return,
+}
+
+/// 中断描述符表中的一项：处理程序的地址；user 为真时允许用户态以 INT n 调用，trap 为真时进入处理程序不关中断
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct InterruptGate {
+    pub handler: u64,
+    pub user: bool,
+    pub trap: bool,
+}
+
+/// 中断的设置：定时器每隔 timer_interval 个周期请求一次中断，为 0 时关闭
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct InterruptConfig {
+    pub timer_interval: u64,
+}
+
+/// 一次中断、异常或中断返回
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InterruptEvent {
+    pub kind: InterruptKind,
+    /// 中断返回时为 None
+    pub vector: Option<u8>,
+    /// 引发异常、被中断或执行返回的指令
+    pub instruction: Option<usize>,
+    /// 转到的地址：处理程序的入口，或返回的地址
+    pub target: u64,
+    /// 保存或恢复的状态字：x86 为 EFLAGS，RISC-V 为 mstatus，MIPS 为 Status
+    pub flags: u32,
+    /// 保存或恢复中断现场之后的栈指针（RISC-V 与 MIPS 不改变栈指针）
+    pub stack_pointer: u64,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InterruptTableEntry {
+    pub vector: u8,
+    pub gate: InterruptGate,
+    /// 处理程序所在的标签
+    pub label: Option<String>,
+}
+
+/// 中断描述符表中的有效项与中断设置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InterruptTableView {
+    pub base: u64,
+    pub config: InterruptConfig,
+    pub entries: Vec<InterruptTableEntry>,
+    /// 已请求但因关中断尚未响应的定时器中断
+    pub timer_pending: bool,
+}
+
 // 编译过程相关类型定义
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -168,10 +712,184 @@ pub enum CompilationStage {
     SemanticAnalysis,
     IntermediateCode,
     Optimization,
+    RegisterAllocation,
     CodeGeneration,
     Assembly,
 }
 
+/// 代码生成与模拟执行的目标体系结构
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Architecture {
+    #[default]
+    X86,
+    RiscV,
+    Mips,
+}
+
+impl Architecture {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Architecture::X86 => "x86 (IA-32)",
+            Architecture::RiscV => "RISC-V (RV32I)",
+            Architecture::Mips => "MIPS32",
+        }
+    }
+}
+
+/// 目标机器：体系结构及其可选行为
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Target {
+    pub architecture: Architecture,
+    /// 分支延迟槽：紧跟在分支/跳转之后的一条指令总会执行（仅 MIPS）
+    pub branch_delay_slot: bool,
+}
+
+// 代码优化相关类型定义
+
+/// 作用在中间代码上的优化遍
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OptimizationPass {
+    ConstantPropagation,
+    CopyPropagation,
+    ConstantFolding,
+    StrengthReduction,
+    CommonSubexpressionElimination,
+    DeadCodeElimination,
+}
+
+impl OptimizationPass {
+    /// 按执行顺序排列的全部优化遍
+    pub const ALL: [OptimizationPass; 6] = [
+        OptimizationPass::ConstantPropagation,
+        OptimizationPass::CopyPropagation,
+        OptimizationPass::ConstantFolding,
+        OptimizationPass::StrengthReduction,
+        OptimizationPass::CommonSubexpressionElimination,
+        OptimizationPass::DeadCodeElimination,
+    ];
+
+    /// 优化级别对应的优化遍：0 不优化，1 常量传播/折叠与死代码消除，2 及以上全部启用
+    pub fn for_level(level: u8) -> Vec<OptimizationPass> {
+        match level {
+            0 => Vec::new(),
+            1 => vec![
+                OptimizationPass::ConstantPropagation,
+                OptimizationPass::ConstantFolding,
+                OptimizationPass::DeadCodeElimination,
+            ],
+            _ => Self::ALL.to_vec(),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            OptimizationPass::ConstantPropagation => "常量传播",
+            OptimizationPass::CopyPropagation => "复制传播",
+            OptimizationPass::ConstantFolding => "常量折叠",
+            OptimizationPass::StrengthReduction => "强度削减",
+            OptimizationPass::CommonSubexpressionElimination => "公共子表达式消除",
+            OptimizationPass::DeadCodeElimination => "死代码消除",
+        }
+    }
+}
+
+/// 一次优化遍的执行记录：修改列表以及执行前后的中间代码，供界面对比
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OptimizationReport {
+    pub pass: OptimizationPass,
+    /// 第几轮迭代（从 1 开始），各遍反复执行直到不再产生修改
+    pub round: usize,
+    pub changes: Vec<String>,
+    pub before: String,
+    pub after: String,
+}
+
+// 控制流图相关类型定义
+
+/// 基本块：只能从第一条指令进入、从最后一条指令离开的指令序列
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BasicBlock {
+    pub id: usize,
+    /// 块首指令上的标签
+    pub label: Option<String>,
+    /// 所属函数（入口块或 CALL 目标的标签）
+    pub function: String,
+    /// 在指令列表中的下标范围 [start, end]
+    pub start: usize,
+    pub end: usize,
+    pub instructions: Vec<String>,
+    /// 支配该块的所有块（含自身），从函数入口不可达时为空
+    pub dominators: Vec<usize>,
+    pub immediate_dominator: Option<usize>,
+    pub is_loop_header: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum CfgEdgeKind {
+    /// 顺序执行进入下一块
+    Fallthrough,
+    /// 无条件跳转
+    Jump,
+    /// 条件跳转成立
+    Taken,
+    /// 条件跳转不成立
+    NotTaken,
+    /// 函数调用（跨函数，不参与支配关系计算）
+    Call,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CfgEdge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: CfgEdgeKind,
+}
+
+/// 由回边 back_edge_from → header 确定的自然循环
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NaturalLoop {
+    pub header: usize,
+    pub back_edge_from: usize,
+    pub blocks: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ControlFlowGraph {
+    pub blocks: Vec<BasicBlock>,
+    pub edges: Vec<CfgEdge>,
+    pub loops: Vec<NaturalLoop>,
+    /// Graphviz DOT 格式的图
+    pub dot: String,
+}
+
+// 汇编器相关类型定义
+
+/// 汇编结果：指令序列、数据段的初始内存映像以及符号表
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssemblyResult {
+    pub success: bool,
+    pub instructions: Vec<Instruction>,
+    pub memory_image: MemoryImage,
+    pub symbols: Vec<AssemblySymbol>,
+    pub errors: Vec<Diagnostic>,
+}
+
+/// 从 base 开始的一段连续内存的初始内容
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MemoryImage {
+    pub base: u64,
+    pub bytes: Vec<u8>,
+}
+
+/// 汇编源程序中定义的标签
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssemblySymbol {
+    pub name: String,
+    /// "text" 或 "data"
+    pub section: String,
+    pub address: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SourceCode {
     pub id: String,
@@ -212,17 +930,14 @@ impl Default for CPUState {
                 general: HashMap::new(),
                 special: HashMap::new(),
             },
-            memory: MemoryState {
-                data: HashMap::new(),
-                stack: Vec::new(),
-                heap: HashMap::new(),
-            },
+            memory: MemoryState::default(),
             flags: FlagsState {
                 zero: false,
                 carry: false,
                 overflow: false,
                 negative: false,
                 parity: false,
+                interrupt: true,
             },
             program_counter: 0,
             stack_pointer: 0,
@@ -0,0 +1,1171 @@
+use crate::disassembler::{format_operand, parse_hex};
+use crate::encoder::{self, parse_operand, Operand, REGISTERS_32};
+use crate::interrupt::Fault;
+use crate::isa::{
+    Branch, Context, Execution, InterruptRequest, Isa, MemoryOperation, Register, RegisterUsage, STACK_VIEW_LIMIT,
+};
+use crate::memory::CODE_BASE;
+use crate::types::{
+    Architecture, BranchKind, FlagsState, Instruction, StackFrame, StackSlot, StackSlotKind,
+};
+use std::collections::HashMap;
+
+const EAX: usize = 0;
+const ECX: usize = 1;
+const EDX: usize = 2;
+const ESP: usize = 4;
+const EBP: usize = 5;
+
+/// 译码后的 x86 指令：解析好的操作数与操作数长度（字节）
+#[derive(Debug, Clone)]
+pub struct Decoded {
+    pub mnemonic: String,
+    pub operands: Vec<Operand>,
+    pub size: u8,
+}
+
+/// IA-32 的 8 个通用寄存器，ESP 为栈指针。指令变长，地址由载入程序中各条指令的长度决定
+#[derive(Default)]
+pub struct X86 {
+    /// 每条指令的起始地址，最后一项为程序末尾的地址
+    addresses: Vec<u64>,
+    /// CALL 的返回地址 -> 被调函数，用于栈帧视图
+    callees: HashMap<u64, String>,
+    /// 执行到这些指令时 EBP 尚未指向（或已不再指向）本函数的栈帧：指令下标 -> 返回地址相对 ESP 的偏移
+    return_offsets: HashMap<usize, u64>,
+    /// 入口函数的名称
+    entry: Option<String>,
+}
+
+fn bits(size: u8) -> u32 {
+    8 * size as u32
+}
+
+/// 操作数长度对应的掩码
+fn mask(size: u8) -> u32 {
+    u32::MAX >> (32 - bits(size))
+}
+
+fn sign_bit(size: u8) -> u32 {
+    1 << (bits(size) - 1)
+}
+
+/// 把 size 字节的值符号扩展为 32 位
+fn sign_extend(value: u32, size: u8) -> i32 {
+    let shift = 32 - bits(size);
+    ((value << shift) as i32) >> shift
+}
+
+/// 按运算结果设置 ZF、SF、PF（PF 只看最低字节中 1 的个数是否为偶数）
+fn result_flags(flags: &mut FlagsState, result: u32, size: u8) {
+    flags.zero = result & mask(size) == 0;
+    flags.negative = result & sign_bit(size) != 0;
+    flags.parity = (result as u8).count_ones().is_multiple_of(2);
+}
+
+/// 标志位的文字描述，用于执行阶段的提示
+pub fn describe_flags(flags: &FlagsState) -> String {
+    format!(
+        "CF={} ZF={} SF={} OF={} PF={}",
+        flags.carry as u8, flags.zero as u8, flags.negative as u8, flags.overflow as u8, flags.parity as u8
+    )
+}
+
+/// 标志位组成的 EFLAGS：CF、PF、ZF、SF、IF、OF 分别为第 0、2、6、7、9、11 位，第 1 位恒为 1
+pub fn eflags(flags: &FlagsState) -> u32 {
+    2 | flags.carry as u32
+        | (flags.parity as u32) << 2
+        | (flags.zero as u32) << 6
+        | (flags.negative as u32) << 7
+        | (flags.interrupt as u32) << 9
+        | (flags.overflow as u32) << 11
+}
+
+/// 从 EFLAGS 取出标志位
+pub fn flags_from(eflags: u32) -> FlagsState {
+    FlagsState {
+        carry: eflags & 1 != 0,
+        parity: eflags & 1 << 2 != 0,
+        zero: eflags & 1 << 6 != 0,
+        negative: eflags & 1 << 7 != 0,
+        interrupt: eflags & 1 << 9 != 0,
+        overflow: eflags & 1 << 11 != 0,
+    }
+}
+
+/// 栈中位置的写法，如 [EBP-8]、[ESP]
+fn position(register: &str, offset: i64) -> String {
+    match offset {
+        0 => format!("[{}]", register),
+        _ => format!("[{}{:+}]", register, offset),
+    }
+}
+
+/// 双操作数的算术与逻辑运算：返回结果与新的标志位；CMP、TEST 的结果只用于设置标志位
+pub fn alu(mnemonic: &str, a: u32, b: u32, size: u8, flags: &FlagsState) -> Option<(u32, FlagsState)> {
+    let (a, b) = (a & mask(size), b & mask(size));
+    let mut flags = flags.clone();
+    let result = match mnemonic {
+        "ADD" | "ADC" => {
+            let carry_in = (mnemonic == "ADC" && flags.carry) as u64;
+            let wide = a as u64 + b as u64 + carry_in;
+            let result = wide as u32 & mask(size);
+            flags.carry = wide > mask(size) as u64;
+            // 两个加数同号而结果异号时溢出
+            flags.overflow = (a ^ result) & (b ^ result) & sign_bit(size) != 0;
+            result
+        }
+        "SUB" | "SBB" | "CMP" => {
+            let borrow_in = (mnemonic == "SBB" && flags.carry) as u64;
+            let result = (a as u64).wrapping_sub(b as u64 + borrow_in) as u32 & mask(size);
+            flags.carry = (a as u64) < b as u64 + borrow_in;
+            // 被减数与减数异号、且结果与被减数异号时溢出
+            flags.overflow = (a ^ b) & (a ^ result) & sign_bit(size) != 0;
+            result
+        }
+        "AND" | "TEST" => a & b,
+        "OR" => a | b,
+        "XOR" => a ^ b,
+        _ => return None,
+    };
+    if matches!(mnemonic, "AND" | "TEST" | "OR" | "XOR") {
+        flags.carry = false;
+        flags.overflow = false;
+    }
+    result_flags(&mut flags, result, size);
+    Some((result, flags))
+}
+
+/// 单操作数运算 INC、DEC、NEG、NOT；INC、DEC 不改变 CF，NOT 不改变任何标志位
+pub fn unary(mnemonic: &str, a: u32, size: u8, flags: &FlagsState) -> Option<(u32, FlagsState)> {
+    let a = a & mask(size);
+    match mnemonic {
+        "INC" | "DEC" => {
+            let carry = flags.carry;
+            let operation = if mnemonic == "INC" { "ADD" } else { "SUB" };
+            let (result, mut flags) = alu(operation, a, 1, size, flags)?;
+            flags.carry = carry;
+            Some((result, flags))
+        }
+        "NEG" => {
+            let (result, mut flags) = alu("SUB", 0, a, size, flags)?;
+            flags.carry = a != 0;
+            Some((result, flags))
+        }
+        "NOT" => Some((!a & mask(size), flags.clone())),
+        _ => None,
+    }
+}
+
+/// 移位与循环移位。次数取低 5 位，为 0 时结果与标志位都不变；
+/// OF 只在移 1 位时有定义，这里按移 1 位的规则计算
+pub fn shift(mnemonic: &str, a: u32, count: u32, size: u8, flags: &FlagsState) -> Option<(u32, FlagsState)> {
+    let (a, count, width) = (a & mask(size), count & 0x1F, bits(size));
+    let mut flags = flags.clone();
+    if count == 0 {
+        return matches!(mnemonic, "SHL" | "SAL" | "SHR" | "SAR" | "ROL" | "ROR").then_some((a, flags));
+    }
+    let msb = |value: u32| value & sign_bit(size) != 0;
+    let result = match mnemonic {
+        "SHL" | "SAL" => {
+            let result = ((a as u64) << count) as u32 & mask(size);
+            flags.carry = count <= width && (a >> (width - count)) & 1 != 0;
+            flags.overflow = msb(result) != flags.carry;
+            result_flags(&mut flags, result, size);
+            result
+        }
+        "SHR" => {
+            let result = ((a as u64) >> count) as u32;
+            flags.carry = count <= width && (a >> (count - 1)) & 1 != 0;
+            flags.overflow = msb(a);
+            result_flags(&mut flags, result, size);
+            result
+        }
+        "SAR" => {
+            let value = sign_extend(a, size) as i64;
+            let result = (value >> count) as u32 & mask(size);
+            flags.carry = (value >> (count - 1)) & 1 != 0;
+            flags.overflow = false;
+            result_flags(&mut flags, result, size);
+            result
+        }
+        // 循环移位只影响 CF 与 OF
+        "ROL" => {
+            let n = count % width;
+            let result = if n == 0 { a } else { ((a << n) | (a >> (width - n))) & mask(size) };
+            flags.carry = result & 1 != 0;
+            flags.overflow = msb(result) != flags.carry;
+            result
+        }
+        "ROR" => {
+            let n = count % width;
+            let result = if n == 0 { a } else { ((a >> n) | (a << (width - n))) & mask(size) };
+            flags.carry = msb(result);
+            flags.overflow = msb(result) != (result & (sign_bit(size) >> 1) != 0);
+            result
+        }
+        _ => return None,
+    };
+    Some((result, flags))
+}
+
+/// 条件码对应的条件是否成立，Jcc 与 SETcc 共用；条件码最低位为 1 表示取反
+pub fn condition_met(condition: &str, flags: &FlagsState) -> Option<bool> {
+    let code = encoder::condition_code(condition)?;
+    let met = match code >> 1 {
+        0 => flags.overflow,
+        1 => flags.carry,
+        2 => flags.zero,
+        3 => flags.carry || flags.zero,
+        4 => flags.negative,
+        5 => flags.parity,
+        6 => flags.negative != flags.overflow,
+        _ => flags.zero || flags.negative != flags.overflow,
+    };
+    Some(met != (code & 1 == 1))
+}
+
+/// 寄存器操作数对应的 32 位寄存器
+fn register_of(operand: &Operand) -> Option<usize> {
+    match operand {
+        Operand::Reg32(code) | Operand::Reg16(code) => Some(*code as usize),
+        Operand::Reg8(code) => Some(byte_register(*code).0),
+        _ => None,
+    }
+}
+
+fn read_operand(usage: &mut RegisterUsage, operand: &Operand) {
+    usage.reads.extend(register_of(operand).map(Register::General));
+}
+
+/// 写入 8、16 位寄存器时保留其余的位，也算读取了该寄存器
+fn write_operand(usage: &mut RegisterUsage, operand: &Operand) {
+    if let Some(number) = register_of(operand) {
+        usage.writes.push(Register::General(number));
+        if matches!(operand, Operand::Reg16(_) | Operand::Reg8(_)) {
+            usage.reads.push(Register::General(number));
+        }
+    }
+}
+
+fn branch(execution: Execution, target: usize) -> Execution {
+    Execution { branch: Some(Branch { target, delayed: false }), ..execution }
+}
+
+/// 8 位寄存器 AL–BL 是 EAX–EBX 的低字节，AH–BH 是其次低字节
+fn byte_register(code: u8) -> (usize, u32) {
+    ((code & 3) as usize, if code >= 4 { 8 } else { 0 })
+}
+
+/// 把 size 字节的值写入寄存器的低位部分，其余位保持不变
+fn merge(old: i32, value: u32, size: u8, shift: u32) -> i32 {
+    let field = mask(size) << shift;
+    ((old as u32 & !field) | ((value << shift) & field)) as i32
+}
+
+impl X86 {
+    /// 指令长度取自机器码，尚未编码的指令临时编码一次
+    fn instruction_length(instruction: &Instruction) -> u64 {
+        match parse_hex(&instruction.machine_code) {
+            Ok(bytes) if !bytes.is_empty() => bytes.len() as u64,
+            _ => encoder::encode(&instruction.mnemonic, &instruction.operands)
+                .map_or(1, |encoding| encoding.bytes.len() as u64),
+        }
+    }
+
+    /// 地址对应的指令下标；程序末尾的地址对应指令条数，转到该处即结束程序
+    fn index_of(&self, address: u64) -> Result<usize, Fault> {
+        let index = self.addresses.binary_search(&address);
+        index.map_err(|_| Fault::from(format!("地址 0x{:X} 不是指令的起始地址", address)))
+    }
+
+    /// 跳转目标的指令下标：标签、绝对地址，或寄存器、内存中保存的地址（间接跳转）。
+    /// 程序中没有的标签返回 None，由调用者决定如何处理
+    fn jump_target(&self, operand: &Operand, context: &Context) -> Result<Option<usize>, Fault> {
+        match operand {
+            Operand::Label(label) => Ok(context.label(label)),
+            Operand::Imm(address) => self.index_of(*address as u32 as u64).map(Some),
+            _ => self.index_of(Self::value(operand, 4, context)? as u64).map(Some),
+        }
+    }
+
+    /// 转到操作数给出的目标
+    fn jump(&self, description: String, target: &Operand, context: &Context) -> Result<Execution, Fault> {
+        let name = format_operand(target, None);
+        let index = self.jump_target(target, context)?.ok_or_else(|| format!("找不到跳转目标 '{}'", name))?;
+        let message = format!("执行：{}，跳转到 {}（0x{:X}）", description, name, self.instruction_address(index));
+        Ok(branch(Execution::new(message), index))
+    }
+
+    fn address(operand: &Operand, context: &Context) -> Option<u64> {
+        let Operand::Mem { base, index, disp } = operand else { return None };
+        let base = base.map_or(0, |base| context.register(base as usize));
+        let index = index.map_or(0, |(index, scale)| context.register(index as usize).wrapping_mul(scale as i32));
+        Some(base.wrapping_add(index).wrapping_add(*disp as i32) as u32 as u64)
+    }
+
+    /// 读取 size 字节的操作数，立即数截断为 size 字节
+    fn value(operand: &Operand, size: u8, context: &Context) -> Result<u32, Fault> {
+        match operand {
+            Operand::Reg32(code) | Operand::Reg16(code) => Ok(context.register(*code as usize) as u32 & mask(size)),
+            Operand::Reg8(code) => {
+                let (register, shift) = byte_register(*code);
+                Ok((context.register(register) as u32 >> shift) & 0xFF)
+            }
+            Operand::Imm(value) => Ok(*value as u32 & mask(size)),
+            Operand::Mem { .. } => {
+                let address = Self::address(operand, context).unwrap_or(0);
+                context.read_memory(address, size)
+            }
+            Operand::Label(label) => Err(format!("不能把标签 {} 作为数据操作数", label).into()),
+        }
+    }
+
+    /// 压栈：ESP 减小为 esp，在访存阶段把 value 的低 size 字节写入新的栈顶
+    fn push(execution: Execution, esp: i32, value: u32, size: u8) -> Execution {
+        let store = MemoryOperation::Store { address: esp as u32 as u64, size, value: value as i32 };
+        Execution { memory: Some(store), ..execution.write(Register::General(ESP), esp) }
+    }
+
+    /// 在执行阶段出栈 size 字节前检查栈中有足够的数据，返回栈顶地址
+    fn pop_address(size: u8, context: &Context) -> Result<u64, Fault> {
+        let esp = context.register(ESP) as u32 as u64;
+        context.memory.check_stack_pointer(esp + size as u64)?;
+        Ok(esp)
+    }
+
+    /// 栈帧中 [low, high) 的双字，从高地址到低地址。用途按相对帧指针 frame 的位置确定：
+    /// [frame] 为保存的 EBP，[frame+4] 为返回地址，以下为局部变量；位置相对 EBP = base 标注，base 为 None 时相对 ESP
+    fn frame_slots(context: &Context, low: u64, high: u64, frame: Option<u64>, base: Option<u64>) -> Vec<StackSlot> {
+        let (register, base) = match base {
+            Some(base) => ("EBP", base),
+            None => ("ESP", context.register(ESP) as u32 as u64),
+        };
+        (1..=(high.saturating_sub(low) / 4).min(STACK_VIEW_LIMIT))
+            .map(|k| high - 4 * k)
+            .map(|address| {
+                let kind = match frame.map(|frame| address as i64 - frame as i64) {
+                    Some(0) => StackSlotKind::SavedFramePointer,
+                    Some(4) => StackSlotKind::ReturnAddress,
+                    Some(offset) if offset < 0 => StackSlotKind::Local,
+                    _ => StackSlotKind::Data,
+                };
+                context.stack_slot(address, kind, position(register, address as i64 - base as i64))
+            })
+            .collect()
+    }
+
+    /// 寄存器的当前值，包括本条指令中先前的写入
+    fn pending(execution: &Execution, number: usize, context: &Context) -> i32 {
+        execution
+            .writes
+            .iter()
+            .rev()
+            .find(|(register, _)| *register == Register::General(number))
+            .map_or(context.register(number), |(_, value)| *value)
+    }
+
+    /// 把 size 字节的值写入通用寄存器的低位部分
+    fn write_register(execution: Execution, number: usize, value: u32, size: u8, context: &Context) -> Execution {
+        let merged = merge(Self::pending(&execution, number, context), value, size, 0);
+        execution.write(Register::General(number), merged)
+    }
+
+    /// 把结果写入目的操作数：寄存器在写回阶段提交，内存在访存阶段写入
+    fn store(
+        execution: Execution,
+        operand: &Operand,
+        value: u32,
+        size: u8,
+        context: &Context,
+    ) -> Result<Execution, Fault> {
+        match operand {
+            Operand::Reg32(code) | Operand::Reg16(code) => {
+                Ok(Self::write_register(execution, *code as usize, value, size, context))
+            }
+            Operand::Reg8(code) => {
+                let (register, shift) = byte_register(*code);
+                let merged = merge(Self::pending(&execution, register, context), value, 1, shift);
+                Ok(execution.write(Register::General(register), merged))
+            }
+            Operand::Mem { .. } => {
+                let address = Self::address(operand, context).unwrap_or(0);
+                let value = value as i32;
+                Ok(Execution { memory: Some(MemoryOperation::Store { address, size, value }), ..execution })
+            }
+            _ => Err("目的操作数必须是寄存器或内存".into()),
+        }
+    }
+
+    /// MUL、IMUL 与 DIV、IDIV 的单操作数形式：被乘数与被除数隐含在 AL/AX/EAX（及 AH/DX/EDX）中
+    fn multiply_divide(mnemonic: &str, source: u32, size: u8, context: &Context) -> Result<Execution, Fault> {
+        let width = bits(size);
+        let eax = context.register(EAX) as u32;
+        let (low, high) = match size {
+            1 => (eax & 0xFF, (eax >> 8) & 0xFF),
+            _ => (eax & mask(size), context.register(EDX) as u32 & mask(size)),
+        };
+        let (quotient_or_low, remainder_or_high, message, flags) = match mnemonic {
+            "MUL" | "IMUL" => {
+                let (product, fits) = if mnemonic == "MUL" {
+                    let product = low as u64 * source as u64;
+                    (product, product >> width == 0)
+                } else {
+                    let product = sign_extend(low, size) as i64 * sign_extend(source, size) as i64;
+                    (product as u64, product == sign_extend(product as u32 & mask(size), size) as i64)
+                };
+                // 乘积的高半部分有效时 CF = OF = 1，其余标志位未定义（保持不变）
+                let mut flags = context.flags.clone();
+                flags.carry = !fits;
+                flags.overflow = !fits;
+                let (product_low, product_high) = (product as u32 & mask(size), (product >> width) as u32 & mask(size));
+                let message = match mnemonic {
+                    "MUL" => format!("执行：MUL {} × {} = {}", low, source, product),
+                    _ => format!(
+                        "执行：IMUL {} × {} = {}",
+                        sign_extend(low, size),
+                        sign_extend(source, size),
+                        product as i64
+                    ),
+                };
+                (product_low, product_high, message, Some(flags))
+            }
+            _ => {
+                if source == 0 {
+                    return Err(Fault::divide_error(format!("除法错误（#DE）：{} 的除数为 0", mnemonic)));
+                }
+                let dividend = ((high as u64) << width) | low as u64;
+                let (result, message) = if mnemonic == "DIV" {
+                    let (quotient, remainder) = (dividend / source as u64, dividend % source as u64);
+                    let message = format!("执行：DIV {} ÷ {} = {} 余 {}", dividend, source, quotient, remainder);
+                    ((quotient <= mask(size) as u64).then_some((quotient as u32, remainder as u32)), message)
+                } else {
+                    // 被除数为 2 倍长度的有符号数，余数与被除数同号
+                    let dividend = ((dividend << (64 - 2 * width)) as i64) >> (64 - 2 * width);
+                    let divisor = sign_extend(source, size) as i64;
+                    let result = dividend
+                        .checked_div(divisor)
+                        .filter(|quotient| *quotient >= -(sign_bit(size) as i64) && *quotient < sign_bit(size) as i64);
+                    let message = match result {
+                        Some(quotient) => {
+                            format!("执行：IDIV {} ÷ {} = {} 余 {}", dividend, divisor, quotient, dividend % divisor)
+                        }
+                        None => String::new(),
+                    };
+                    let result =
+                        result.map(|quotient| (quotient as u32 & mask(size), (dividend % divisor) as u32 & mask(size)));
+                    (result, message)
+                };
+                let (quotient, remainder) =
+                    result.ok_or_else(|| Fault::divide_error(format!("除法错误（#DE）：{} 的商超出 {} 位范围", mnemonic, width)))?;
+                (quotient, remainder, message, None)
+            }
+        };
+
+        let mut execution = Execution { flags, ..Execution::new(message) };
+        execution = match size {
+            // 8 位：结果在 AX 中，低字节为积的低位或商，高字节为积的高位或余数
+            1 => Self::write_register(execution, EAX, quotient_or_low | (remainder_or_high << 8), 2, context),
+            _ => {
+                let execution = Self::write_register(execution, EAX, quotient_or_low, size, context);
+                Self::write_register(execution, EDX, remainder_or_high, size, context)
+            }
+        };
+        Ok(execution)
+    }
+}
+
+impl Isa for X86 {
+    type Decoded = Decoded;
+
+    fn architecture(&self) -> Architecture {
+        Architecture::X86
+    }
+
+    fn register_names(&self) -> &'static [&'static str] {
+        &REGISTERS_32
+    }
+
+    fn program_counter_name(&self) -> &'static str {
+        "EIP"
+    }
+
+    fn stack_pointer(&self) -> usize {
+        ESP
+    }
+
+    fn frame_pointer(&self) -> Option<usize> {
+        Some(EBP)
+    }
+
+    fn aligned_access(&self) -> bool {
+        false
+    }
+
+    fn load_program(&mut self, instructions: &[Instruction]) {
+        let mut address = CODE_BASE as u64;
+        self.addresses = Vec::with_capacity(instructions.len() + 1);
+        for instruction in instructions {
+            self.addresses.push(address);
+            address += Self::instruction_length(instruction);
+        }
+        self.addresses.push(address);
+
+        // 记录每个 CALL 的返回地址与被调函数；函数入口处返回地址在栈顶，入口为 PUSH EBP 时其后一条指令处在 [ESP+4]，
+        // RET 处又回到栈顶
+        let labels: HashMap<&str, usize> = instructions
+            .iter()
+            .enumerate()
+            .filter_map(|(i, instruction)| instruction.label.as_deref().map(|label| (label, i)))
+            .collect();
+        self.callees.clear();
+        self.return_offsets.clear();
+        for (i, instruction) in instructions.iter().enumerate() {
+            match instruction.mnemonic.to_uppercase().as_str() {
+                "CALL" => {
+                    let Some(name) = instruction.operands.first().map(|operand| operand.trim()) else { continue };
+                    let Some(&target) = labels.get(name) else { continue };
+                    self.callees.insert(self.addresses[i + 1], name.to_string());
+                    self.return_offsets.insert(target, 0);
+                    let first = &instructions[target];
+                    if first.mnemonic.eq_ignore_ascii_case("PUSH")
+                        && matches!(first.operands.as_slice(), [register] if register.trim().eq_ignore_ascii_case("EBP"))
+                    {
+                        self.return_offsets.insert(target + 1, 4);
+                    }
+                }
+                "RET" => {
+                    self.return_offsets.insert(i, 0);
+                }
+                _ => {}
+            }
+        }
+        self.entry = instructions.first().and_then(|instruction| instruction.label.clone());
+    }
+
+    /// 第 index 条指令的地址为之前各条指令的长度之和；超出程序时为程序末尾的地址
+    fn instruction_address(&self, index: usize) -> u64 {
+        self.addresses.get(index).or(self.addresses.last()).copied().unwrap_or(CODE_BASE as u64)
+    }
+
+    fn instruction_index(&self, address: u64) -> usize {
+        self.index_of(address).unwrap_or(self.addresses.len().saturating_sub(1))
+    }
+
+    fn decode(&self, instruction: &Instruction) -> Result<Decoded, String> {
+        // 别名统一为 32 位形式，与编码器一致
+        let mnemonic = match instruction.mnemonic.to_uppercase().as_str() {
+            "IRET" => "IRETD".to_string(),
+            "PUSHA" => "PUSHAD".to_string(),
+            "POPA" => "POPAD".to_string(),
+            "PUSHF" => "PUSHFD".to_string(),
+            "POPF" => "POPFD".to_string(),
+            mnemonic => mnemonic.to_string(),
+        };
+        let operands =
+            instruction.operands.iter().map(|operand| parse_operand(operand)).collect::<Result<Vec<_>, _>>()?;
+        Ok(Decoded {
+            size: encoder::operand_size(&mnemonic, &instruction.operands) as u8,
+            mnemonic,
+            operands,
+        })
+    }
+
+    fn execute(&self, decoded: &Decoded, context: &Context) -> Result<Execution, Fault> {
+        let mnemonic = decoded.mnemonic.as_str();
+        let size = decoded.size;
+        let operand = |index: usize| {
+            decoded.operands.get(index).ok_or_else(|| format!("{} 缺少第 {} 个操作数", mnemonic, index + 1))
+        };
+        let show = |value: u32| sign_extend(value, size);
+
+        match mnemonic {
+            "ADD" | "ADC" | "SUB" | "SBB" | "CMP" | "AND" | "OR" | "XOR" | "TEST" => {
+                let (a, b) = (Self::value(operand(0)?, size, context)?, Self::value(operand(1)?, size, context)?);
+                let (result, flags) = alu(mnemonic, a, b, size, context.flags).ok_or("无法执行运算")?;
+                let message = format!(
+                    "执行：{} {}, {} → {}，{}",
+                    mnemonic,
+                    show(a),
+                    show(b),
+                    show(result),
+                    describe_flags(&flags)
+                );
+                let execution = Execution { flags: Some(flags), ..Execution::new(message) };
+                match mnemonic {
+                    // 只设置标志位，不保存结果
+                    "CMP" | "TEST" => Ok(execution),
+                    _ => Self::store(execution, operand(0)?, result, size, context),
+                }
+            }
+            "INC" | "DEC" | "NEG" | "NOT" => {
+                let a = Self::value(operand(0)?, size, context)?;
+                let (result, flags) = unary(mnemonic, a, size, context.flags).ok_or("无法执行运算")?;
+                let message = format!("执行：{} {} → {}，{}", mnemonic, show(a), show(result), describe_flags(&flags));
+                Self::store(
+                    Execution { flags: Some(flags), ..Execution::new(message) },
+                    operand(0)?,
+                    result,
+                    size,
+                    context,
+                )
+            }
+            "SHL" | "SAL" | "SHR" | "SAR" | "ROL" | "ROR" => {
+                let a = Self::value(operand(0)?, size, context)?;
+                let count = match decoded.operands.get(1) {
+                    Some(count) => Self::value(count, 1, context)?,
+                    None => 1,
+                };
+                let (result, flags) = shift(mnemonic, a, count, size, context.flags).ok_or("无法执行移位")?;
+                let message =
+                    format!("执行：{} {}, {} → {}，{}", mnemonic, show(a), count, show(result), describe_flags(&flags));
+                Self::store(
+                    Execution { flags: Some(flags), ..Execution::new(message) },
+                    operand(0)?,
+                    result,
+                    size,
+                    context,
+                )
+            }
+            // IMUL 的双操作数与三操作数形式只保留与目的寄存器等长的积
+            "IMUL" if decoded.operands.len() > 1 => {
+                let (a, b) = match decoded.operands.as_slice() {
+                    [destination, source] => {
+                        (Self::value(destination, size, context)?, Self::value(source, size, context)?)
+                    }
+                    [_, source, multiplier] => {
+                        (Self::value(source, size, context)?, Self::value(multiplier, size, context)?)
+                    }
+                    _ => return Err("IMUL 的操作数个数不正确".into()),
+                };
+                let product = sign_extend(a, size) as i64 * sign_extend(b, size) as i64;
+                let result = product as u32 & mask(size);
+                let fits = product == sign_extend(result, size) as i64;
+                let mut flags = context.flags.clone();
+                flags.carry = !fits;
+                flags.overflow = !fits;
+                let message =
+                    format!("执行：IMUL {} × {} = {}，{}", show(a), show(b), show(result), describe_flags(&flags));
+                Self::store(
+                    Execution { flags: Some(flags), ..Execution::new(message) },
+                    operand(0)?,
+                    result,
+                    size,
+                    context,
+                )
+            }
+            "MUL" | "IMUL" | "DIV" | "IDIV" => {
+                let source = Self::value(operand(0)?, size, context)?;
+                Self::multiply_divide(mnemonic, source, size, context)
+            }
+            "CDQ" => {
+                let edx = if context.register(EAX) < 0 { -1 } else { 0 };
+                Ok(Execution::new(format!("执行：把 EAX 的符号位扩展到 EDX = {}", edx))
+                    .write(Register::General(EDX), edx))
+            }
+            "MOV" => {
+                let (destination, source) = (operand(0)?, operand(1)?);
+                if let (Operand::Reg32(code), Operand::Mem { .. }) = (destination, source) {
+                    let address = Self::address(source, context).unwrap_or(0);
+                    let mut execution = Execution::new(format!("执行：计算有效地址 0x{:X}", address));
+                    execution.memory = Some(MemoryOperation::Load {
+                        address,
+                        size: 4,
+                        signed: false,
+                        destination: Register::General(*code as usize),
+                    });
+                    return Ok(execution);
+                }
+                let value = Self::value(source, size, context)?;
+                let message = format!("执行：数据传送 MOV ← {}", show(value));
+                Self::store(Execution::new(message), destination, value, size, context)
+            }
+            "PUSH" => {
+                let value = Self::value(operand(0)?, size, context)?;
+                let esp = context.register(ESP).wrapping_sub(size as i32);
+                let message = format!("执行：数据传送 PUSH {}，ESP = 0x{:X}", show(value), esp);
+                Ok(Self::push(Execution::new(message), esp, value, size))
+            }
+            "POP" => {
+                let destination = operand(0)?;
+                if let (4, Operand::Reg32(code)) = (size, destination) {
+                    let esp = context.register(ESP);
+                    let mut execution =
+                        Execution::new(format!("执行：数据传送 POP，ESP = 0x{:X}", esp.wrapping_add(4)))
+                            .write(Register::General(ESP), esp.wrapping_add(4));
+                    execution.memory = Some(MemoryOperation::Load {
+                        address: esp as u32 as u64,
+                        size: 4,
+                        signed: false,
+                        destination: Register::General(*code as usize),
+                    });
+                    return Ok(execution);
+                }
+                // 16 位出栈只改写寄存器的低 16 位；弹出到内存时，以 ESP 为基址的地址按出栈后的 ESP 计算
+                let esp = Self::pop_address(size, context)?;
+                let popped = context.read_memory(esp, size)?;
+                let execution =
+                    Execution::new(format!("执行：数据传送 POP {}，ESP = 0x{:X}", show(popped), esp + size as u64));
+                let execution = match destination {
+                    Operand::Mem { base, .. } => {
+                        let shift = if *base == Some(ESP as u8) { size as u64 } else { 0 };
+                        let address = Self::address(destination, context).unwrap_or(0) + shift;
+                        let store = MemoryOperation::Store { address, size, value: popped as i32 };
+                        Execution { memory: Some(store), ..execution }
+                    }
+                    _ => Self::store(execution, destination, popped, size, context)?,
+                };
+                Ok(execution.write(Register::General(ESP), (esp + size as u64) as i32))
+            }
+            // PUSHAD 依次压入 EAX、ECX、EDX、EBX、执行前的 ESP、EBP、ESI、EDI
+            "PUSHAD" => {
+                let esp = context.register(ESP).wrapping_sub(32);
+                let bytes = (0..8).rev().flat_map(|number| context.register(number).to_le_bytes()).collect();
+                let mut execution = Execution::new(format!("执行：8 个通用寄存器压栈，ESP = 0x{:X}", esp))
+                    .write(Register::General(ESP), esp);
+                execution.memory = Some(MemoryOperation::StoreBytes { address: esp as u32 as u64, bytes });
+                Ok(execution)
+            }
+            // POPAD 按相反的顺序弹出，丢弃保存的 ESP
+            "POPAD" => {
+                let esp = Self::pop_address(32, context)?;
+                let mut execution = Execution::new(format!("执行：弹出 8 个通用寄存器，ESP = 0x{:X}", esp + 32));
+                for number in (0..8).filter(|number| *number != ESP) {
+                    let value = context.read_memory(esp + 4 * (7 - number) as u64, 4)?;
+                    execution = execution.write(Register::General(number), value as i32);
+                }
+                Ok(execution.write(Register::General(ESP), (esp + 32) as i32))
+            }
+            "PUSHFD" => {
+                let value = eflags(context.flags);
+                let esp = context.register(ESP).wrapping_sub(4);
+                let message = format!("执行：EFLAGS = 0x{:X} 压栈，ESP = 0x{:X}", value, esp);
+                Ok(Self::push(Execution::new(message), esp, value, 4))
+            }
+            "POPFD" => {
+                let esp = Self::pop_address(4, context)?;
+                let value = context.read_memory(esp, 4)?;
+                let flags = flags_from(value);
+                let message =
+                    format!("执行：弹出 EFLAGS = 0x{:X}，{}，ESP = 0x{:X}", value, describe_flags(&flags), esp + 4);
+                Ok(Execution { flags: Some(flags), ..Execution::new(message) }
+                    .write(Register::General(ESP), (esp + 4) as i32))
+            }
+            // ENTER size, level：压入 EBP；嵌套层数大于 0 时再压入外层各帧的帧指针与本帧的帧指针。
+            // 随后 EBP 指向保存的 EBP，并为局部变量分配 size 字节
+            "ENTER" => {
+                let locals = Self::value(operand(0)?, 2, context)?;
+                let level = Self::value(operand(1)?, 1, context)? % 32;
+                let (esp, ebp) = (context.register(ESP) as u32, context.register(EBP) as u32);
+                let frame = esp.wrapping_sub(4);
+                let mut pushed = vec![ebp];
+                if level > 0 {
+                    for i in 1..level {
+                        pushed.push(context.read_memory(ebp.wrapping_sub(4 * i) as u64, 4)?);
+                    }
+                    pushed.push(frame);
+                }
+                let address = esp.wrapping_sub(4 * pushed.len() as u32);
+                let bytes = pushed.iter().rev().flat_map(|value| value.to_le_bytes()).collect();
+                let esp = address.wrapping_sub(locals);
+                let message = format!(
+                    "执行：保存 EBP = 0x{:X}，EBP = 0x{:X}，分配 {} 字节局部变量，ESP = 0x{:X}",
+                    ebp, frame, locals, esp
+                );
+                let mut execution = Execution::new(message)
+                    .write(Register::General(EBP), frame as i32)
+                    .write(Register::General(ESP), esp as i32);
+                execution.memory = Some(MemoryOperation::StoreBytes { address: address as u64, bytes });
+                Ok(execution)
+            }
+            // LEAVE 相当于 MOV ESP, EBP; POP EBP
+            "LEAVE" => {
+                let ebp = context.register(EBP);
+                let esp = ebp.wrapping_add(4);
+                let mut execution = Execution::new(format!("执行：释放栈帧，ESP = 0x{:X}，恢复调用者的 EBP", esp))
+                    .write(Register::General(ESP), esp);
+                execution.memory = Some(MemoryOperation::Load {
+                    address: ebp as u32 as u64,
+                    size: 4,
+                    signed: false,
+                    destination: Register::General(EBP),
+                });
+                Ok(execution)
+            }
+            "LEA" => {
+                let source = operand(1)?;
+                let address = Self::address(source, context).ok_or("LEA 的源操作数必须是内存")?;
+                let message = format!("执行：计算有效地址 0x{:X}", address);
+                Self::store(Execution::new(message), operand(0)?, address as u32, size, context)
+            }
+            "MOVZX" | "MOVSX" => {
+                // 源操作数为 r/m8 或 16 位寄存器，扩展为 32 位
+                let source = operand(1)?;
+                let source_size = if matches!(source, Operand::Reg16(_)) { 2 } else { 1 };
+                let value = Self::value(source, source_size, context)?;
+                let value = if mnemonic == "MOVSX" { sign_extend(value, source_size) as u32 } else { value };
+                let message = format!("执行：{} 扩展为 {}", mnemonic, value as i32);
+                Self::store(Execution::new(message), operand(0)?, value, 4, context)
+            }
+            "XCHG" => {
+                let (first, second) = (operand(0)?, operand(1)?);
+                let (a, b) = (Self::value(first, size, context)?, Self::value(second, size, context)?);
+                let execution = Execution::new(format!("执行：交换 {} 与 {}", show(a), show(b)));
+                let execution = Self::store(execution, first, b, size, context)?;
+                Self::store(execution, second, a, size, context)
+            }
+            _ if mnemonic.starts_with("SET") => {
+                let condition = &mnemonic[3..];
+                let met =
+                    condition_met(condition, context.flags).ok_or_else(|| format!("未知的条件码 {}", condition))?;
+                let state = if met { "成立" } else { "不成立" };
+                let message = format!(
+                    "执行：条件 {} {}（{}），结果为 {}",
+                    condition,
+                    state,
+                    describe_flags(context.flags),
+                    met as u8
+                );
+                Self::store(Execution::new(message), operand(0)?, met as u32, 1, context)
+            }
+            "JMP" => self.jump("无条件转移".to_string(), operand(0)?, context),
+            "JECXZ" => {
+                let ecx = context.register(ECX);
+                match ecx {
+                    0 => self.jump("ECX = 0".to_string(), operand(0)?, context),
+                    _ => Ok(Execution::new(format!("执行：ECX = {}，顺序执行", ecx))),
+                }
+            }
+            // LOOP 系列先把 ECX 减 1（不影响标志位），ECX 不为 0 且 ZF 满足要求时继续循环
+            "LOOP" | "LOOPE" | "LOOPZ" | "LOOPNE" | "LOOPNZ" => {
+                let count = context.register(ECX).wrapping_sub(1);
+                let zero = match mnemonic {
+                    "LOOP" => true,
+                    "LOOPE" | "LOOPZ" => context.flags.zero,
+                    _ => !context.flags.zero,
+                };
+                let description = format!("ECX 减 1 得 {}", count);
+                let execution = match count != 0 && zero {
+                    true => self.jump(description, operand(0)?, context)?,
+                    false => Execution::new(format!("执行：{}，结束循环", description)),
+                };
+                Ok(execution.write(Register::General(ECX), count))
+            }
+            _ if mnemonic.starts_with('J') => {
+                let condition = &mnemonic[1..];
+                let met =
+                    condition_met(condition, context.flags).ok_or_else(|| format!("未知的条件码 {}", condition))?;
+                let description = format!(
+                    "条件 {} {}（{}）",
+                    condition,
+                    if met { "成立" } else { "不成立" },
+                    describe_flags(context.flags)
+                );
+                match met {
+                    true => self.jump(description, operand(0)?, context),
+                    false => Ok(Execution::new(format!("执行：{}，顺序执行", description))),
+                }
+            }
+            // CALL 把下一条指令的地址压栈后转移；程序中没有的函数（如 printf）视为外部函数，
+            // 模拟器不执行它，各指令集都把返回值寄存器置 0
+            "CALL" => {
+                let target = operand(0)?;
+                let Some(index) = self.jump_target(target, context)? else {
+                    let name = format_operand(target, None);
+                    let message = format!("执行：调用外部函数 {}（模拟器中跳过，返回值 EAX = 0）", name);
+                    return Ok(Execution::new(message).write(Register::General(EAX), 0));
+                };
+                let return_address = self.instruction_address(context.index + 1);
+                let esp = context.register(ESP).wrapping_sub(4);
+                let message = format!(
+                    "执行：调用 {}（0x{:X}），返回地址 0x{:X} 压栈，ESP = 0x{:X}",
+                    format_operand(target, None),
+                    self.instruction_address(index),
+                    return_address,
+                    esp
+                );
+                Ok(Self::push(branch(Execution::new(message), index), esp, return_address as u32, 4))
+            }
+            // RET 弹出返回地址，RET n 再释放 n 字节参数；栈为空时视为从最外层返回，程序结束
+            "RET" => {
+                if context.register(ESP) as u32 as u64 >= context.memory.stack_top() {
+                    return Ok(Execution {
+                        halt: true, ..Execution::new("执行：栈中没有返回地址，程序结束")
+                    });
+                }
+                let release = match decoded.operands.first() {
+                    Some(size) => Self::value(size, 2, context)?,
+                    None => 0,
+                };
+                let esp = Self::pop_address(4, context)?;
+                let return_address = context.read_memory(esp, 4)? as u64;
+                let index = self.index_of(return_address)?;
+                let esp = (esp + 4 + release as u64) as i32;
+                let message = format!("执行：弹出返回地址 0x{:X}，ESP = 0x{:X}", return_address, esp);
+                Ok(branch(Execution::new(message), index).write(Register::General(ESP), esp))
+            }
+            // INT n 经中断描述符表第 n 项进入处理程序，压栈与转移由模拟器完成
+            "INT" => {
+                let vector = Self::value(operand(0)?, 1, context)? as u8;
+                let mut execution = Execution::new(format!("执行：软件中断 INT {}", vector));
+                execution.interrupt = Some(InterruptRequest::Raise(vector));
+                Ok(execution)
+            }
+            "IRETD" => {
+                let mut execution = Execution::new("执行：IRETD，弹出返回地址与 EFLAGS");
+                execution.interrupt = Some(InterruptRequest::Return);
+                Ok(execution)
+            }
+            "CLI" | "STI" => {
+                let flags = FlagsState { interrupt: mnemonic == "STI", ..context.flags.clone() };
+                let state = if flags.interrupt { "开中断" } else { "关中断" };
+                let message = format!("执行：{}，IF = {}", state, flags.interrupt as u8);
+                Ok(Execution { flags: Some(flags), ..Execution::new(message) })
+            }
+            "HLT" => Ok(Execution { halt: true, ..Execution::new("执行：HLT，处理器停机") }),
+            "NOP" => Ok(Execution::new("执行：空操作")),
+            _ => Err(Fault::invalid_opcode(format!("无效指令（#UD）：x86 模拟器不支持 {}", mnemonic))),
+        }
+    }
+
+    fn register_usage(&self, decoded: &Decoded) -> RegisterUsage {
+        let mnemonic = decoded.mnemonic.as_str();
+        let operands = decoded.operands.as_slice();
+        let mut usage = RegisterUsage::default();
+        // 内存操作数的基址与变址寄存器总是被读取
+        for operand in operands {
+            if let Operand::Mem { base, index, .. } = operand {
+                usage.reads.extend(
+                    base.iter().chain(index.iter().map(|(index, _)| index)).map(|r| Register::General(*r as usize)),
+                );
+            }
+        }
+        let implicit = |usage: &mut RegisterUsage, reads: &[usize], writes: &[usize]| {
+            usage.reads.extend(reads.iter().map(|number| Register::General(*number)));
+            usage.writes.extend(writes.iter().map(|number| Register::General(*number)));
+        };
+        match mnemonic {
+            // 只改变部分标志位的指令（INC、DEC 保留 CF，移位次数为 0 时不变）也要读取原来的标志位
+            "ADD" | "ADC" | "SUB" | "SBB" | "AND" | "OR" | "XOR" | "INC" | "DEC" | "NEG" | "NOT" | "SHL" | "SAL"
+            | "SHR" | "SAR" | "ROL" | "ROR" => {
+                operands.iter().for_each(|operand| read_operand(&mut usage, operand));
+                operands.first().into_iter().for_each(|operand| write_operand(&mut usage, operand));
+                usage.writes_flags = mnemonic != "NOT";
+                usage.reads_flags = !matches!(mnemonic, "ADD" | "SUB" | "AND" | "OR" | "XOR" | "NEG" | "NOT");
+            }
+            "CMP" | "TEST" => {
+                operands.iter().for_each(|operand| read_operand(&mut usage, operand));
+                usage.writes_flags = true;
+            }
+            "IMUL" if operands.len() > 1 => {
+                let sources = if operands.len() == 2 { operands } else { &operands[1..] };
+                sources.iter().for_each(|operand| read_operand(&mut usage, operand));
+                write_operand(&mut usage, &operands[0]);
+                (usage.reads_flags, usage.writes_flags) = (true, true);
+            }
+            // 被乘数与被除数隐含在 EAX（与 EDX）中，8 位形式只用到 AX
+            "MUL" | "IMUL" | "DIV" | "IDIV" => {
+                operands.iter().for_each(|operand| read_operand(&mut usage, operand));
+                let divide = mnemonic.ends_with("DIV");
+                match (decoded.size, divide) {
+                    (1, _) => implicit(&mut usage, &[EAX], &[EAX]),
+                    (_, true) => implicit(&mut usage, &[EAX, EDX], &[EAX, EDX]),
+                    (_, false) => implicit(&mut usage, &[EAX], &[EAX, EDX]),
+                }
+                (usage.reads_flags, usage.writes_flags) = (!divide, !divide);
+            }
+            "CDQ" => implicit(&mut usage, &[EAX], &[EDX]),
+            "MOV" | "MOVZX" | "MOVSX" | "LEA" => {
+                operands.iter().skip(1).for_each(|operand| read_operand(&mut usage, operand));
+                operands.first().into_iter().for_each(|operand| write_operand(&mut usage, operand));
+            }
+            "XCHG" => operands.iter().for_each(|operand| {
+                read_operand(&mut usage, operand);
+                write_operand(&mut usage, operand);
+            }),
+            "PUSH" => {
+                operands.iter().for_each(|operand| read_operand(&mut usage, operand));
+                implicit(&mut usage, &[ESP], &[ESP]);
+            }
+            "POP" => {
+                implicit(&mut usage, &[ESP], &[ESP]);
+                operands.iter().for_each(|operand| write_operand(&mut usage, operand));
+            }
+            "PUSHAD" => implicit(&mut usage, &[0, 1, 2, 3, 4, 5, 6, 7], &[ESP]),
+            "POPAD" => implicit(&mut usage, &[ESP], &[0, 1, 2, 3, 4, 5, 6, 7]),
+            "PUSHFD" => {
+                implicit(&mut usage, &[ESP], &[ESP]);
+                usage.reads_flags = true;
+            }
+            "POPFD" => {
+                implicit(&mut usage, &[ESP], &[ESP]);
+                usage.writes_flags = true;
+            }
+            "ENTER" => implicit(&mut usage, &[ESP, EBP], &[ESP, EBP]),
+            "LEAVE" => implicit(&mut usage, &[EBP], &[ESP, EBP]),
+            // 外部函数的返回值写入 EAX
+            "CALL" => {
+                operands.iter().for_each(|operand| read_operand(&mut usage, operand));
+                implicit(&mut usage, &[ESP], &[ESP, EAX]);
+            }
+            "RET" => implicit(&mut usage, &[ESP], &[ESP]),
+            // 进入与返回处理程序时压入或弹出返回地址与 EFLAGS
+            "INT" | "IRETD" => {
+                implicit(&mut usage, &[ESP], &[ESP]);
+                (usage.reads_flags, usage.writes_flags) = (true, true);
+            }
+            "CLI" | "STI" => (usage.reads_flags, usage.writes_flags) = (true, true),
+            "JMP" => operands.iter().for_each(|operand| read_operand(&mut usage, operand)),
+            "JECXZ" => implicit(&mut usage, &[ECX], &[]),
+            "LOOP" | "LOOPE" | "LOOPZ" | "LOOPNE" | "LOOPNZ" => {
+                implicit(&mut usage, &[ECX], &[ECX]);
+                usage.reads_flags = mnemonic != "LOOP";
+            }
+            _ if mnemonic.starts_with("SET") => {
+                operands.iter().for_each(|operand| write_operand(&mut usage, operand));
+                usage.reads_flags = true;
+            }
+            _ if mnemonic.starts_with('J') => usage.reads_flags = true,
+            _ => {}
+        }
+        usage
+    }
+
+    fn branch_kind(&self, decoded: &Decoded) -> Option<BranchKind> {
+        match decoded.mnemonic.as_str() {
+            "JMP" | "CALL" | "RET" | "INT" | "IRETD" => Some(BranchKind::Unconditional),
+            mnemonic if mnemonic.starts_with('J') || mnemonic.starts_with("LOOP") => Some(BranchKind::Conditional),
+            _ => None,
+        }
+    }
+
+    fn encode_program(&self, instructions: &mut [Instruction]) -> Result<(), (usize, String)> {
+        encoder::encode_program(instructions)
+    }
+
+    fn disassemble(&self, bytes: &[u8]) -> Result<Vec<Instruction>, String> {
+        crate::disassembler::disassemble(bytes)
+    }
+
+    /// 沿 EBP 链划分栈帧：[EBP] 为调用者保存的 EBP，[EBP+4] 为返回地址，EBP 以下到栈指针或内层帧之间为局部变量
+    /// （也包括保存的寄存器与传给被调函数的参数）。函数开头建立栈帧之前以及 RET 处，EBP 仍指向调用者的栈帧，
+    /// 这时本帧按返回地址在栈中的位置划分，位置相对 ESP 标注
+    fn stack_frames(&self, context: &Context) -> Vec<StackFrame> {
+        let top = context.memory.stack_top();
+        let esp = context.register(ESP) as u32 as u64;
+        let ebp = context.register(EBP) as u32 as u64;
+        let (mut frame, mut caller, mut base) = match self.return_offsets.get(&context.index) {
+            Some(offset) => ((esp + offset).wrapping_sub(4), Some(ebp), None),
+            None => (ebp, None, Some(ebp)),
+        };
+        // 由内向外，每一帧占据 [low, 帧指针 + 8)
+        let mut low = esp;
+        let mut frames = Vec::new();
+        while frame.wrapping_add(4) >= low && frame + 8 <= top {
+            let return_address = context.read_memory(frame + 4, 4).unwrap_or(0) as u64;
+            frames.push(StackFrame {
+                function: self.callees.get(&return_address).cloned(),
+                frame_pointer: base,
+                return_address: Some(return_address),
+                slots: Self::frame_slots(context, low, frame + 8, Some(frame), base),
+            });
+            low = frame + 8;
+            frame = caller.take().unwrap_or_else(|| context.read_memory(frame, 4).unwrap_or(0) as u64);
+            base = Some(frame);
+        }
+        // 其余部分属于入口函数
+        let frame = caller.unwrap_or(frame);
+        let frame = (frame >= low && frame <= top).then_some(frame);
+        frames.push(StackFrame {
+            function: self.entry.clone(),
+            frame_pointer: frame,
+            return_address: None,
+            slots: Self::frame_slots(context, low, top, frame, frame),
+        });
+        frames.reverse();
+        frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble;
+    use crate::cpu_simulator::{CPUSimulator, Simulator};
+
+    fn clear() -> FlagsState {
+        FlagsState { zero: false, carry: false, overflow: false, negative: false, parity: false, interrupt: true }
+    }
+
+    fn carry() -> FlagsState {
+        FlagsState { carry: true, ..clear() }
+    }
+
+    /// 运算结果与标志位的文字形式
+    fn show((result, flags): (u32, FlagsState)) -> String {
+        format!("{:#X} {}", result, describe_flags(&flags))
+    }
+
+    #[test]
+    fn add_and_sub_flags() {
+        assert_eq!(show(alu("ADD", 0x7F, 1, 1, &clear()).unwrap()), "0x80 CF=0 ZF=0 SF=1 OF=1 PF=0");
+        assert_eq!(show(alu("ADD", 0xFF, 1, 1, &clear()).unwrap()), "0x0 CF=1 ZF=1 SF=0 OF=0 PF=1");
+        assert_eq!(show(alu("ADC", 0xFFFF, 0, 2, &carry()).unwrap()), "0x0 CF=1 ZF=1 SF=0 OF=0 PF=1");
+        assert_eq!(show(alu("SUB", 0, 1, 4, &clear()).unwrap()), "0xFFFFFFFF CF=1 ZF=0 SF=1 OF=0 PF=1");
+        assert_eq!(show(alu("SUB", 0x8000_0000, 1, 4, &clear()).unwrap()), "0x7FFFFFFF CF=0 ZF=0 SF=0 OF=1 PF=1");
+        assert_eq!(show(alu("SBB", 5, 4, 1, &carry()).unwrap()), "0x0 CF=0 ZF=1 SF=0 OF=0 PF=1");
+        // CMP 与 SUB 的标志位相同
+        let compare = |mnemonic| eflags(&alu(mnemonic, 3, 7, 4, &clear()).unwrap().1);
+        assert_eq!(compare("CMP"), compare("SUB"));
+    }
+
+    #[test]
+    fn logic_and_unary_flags() {
+        // 逻辑运算清除 CF 与 OF
+        let flags = FlagsState { overflow: true, ..carry() };
+        assert_eq!(show(alu("AND", 0xF0, 0x3C, 1, &flags).unwrap()), "0x30 CF=0 ZF=0 SF=0 OF=0 PF=1");
+        assert_eq!(show(alu("XOR", 0x1234, 0x1234, 2, &carry()).unwrap()), "0x0 CF=0 ZF=1 SF=0 OF=0 PF=1");
+        // INC、DEC 不改变 CF，NEG 在操作数非 0 时置 CF，NOT 不改变标志位
+        assert_eq!(show(unary("INC", 0x7FFF_FFFF, 4, &carry()).unwrap()), "0x80000000 CF=1 ZF=0 SF=1 OF=1 PF=1");
+        assert_eq!(show(unary("DEC", 1, 1, &clear()).unwrap()), "0x0 CF=0 ZF=1 SF=0 OF=0 PF=1");
+        assert_eq!(show(unary("NEG", 0, 4, &carry()).unwrap()), "0x0 CF=0 ZF=1 SF=0 OF=0 PF=1");
+        assert_eq!(show(unary("NEG", 0x80, 1, &clear()).unwrap()), "0x80 CF=1 ZF=0 SF=1 OF=1 PF=0");
+        assert_eq!(show(unary("NOT", 0x0F, 1, &carry()).unwrap()), "0xF0 CF=1 ZF=0 SF=0 OF=0 PF=0");
+    }
+
+    #[test]
+    fn shift_and_rotate_flags() {
+        assert_eq!(show(shift("SHL", 0x81, 1, 1, &clear()).unwrap()), "0x2 CF=1 ZF=0 SF=0 OF=1 PF=0");
+        assert_eq!(show(shift("SHR", 0x81, 1, 1, &clear()).unwrap()), "0x40 CF=1 ZF=0 SF=0 OF=1 PF=0");
+        assert_eq!(show(shift("SAR", 0x80, 7, 1, &clear()).unwrap()), "0xFF CF=0 ZF=0 SF=1 OF=0 PF=1");
+        assert_eq!(show(shift("ROL", 0x8000_0000, 1, 4, &clear()).unwrap()), "0x1 CF=1 ZF=0 SF=0 OF=1 PF=0");
+        assert_eq!(show(shift("ROR", 1, 1, 2, &clear()).unwrap()), "0x8000 CF=1 ZF=0 SF=0 OF=1 PF=0");
+        // 次数只取低 5 位，为 0 时标志位不变
+        assert_eq!(show(shift("SHL", 0x12, 32, 4, &carry()).unwrap()), "0x12 CF=1 ZF=0 SF=0 OF=0 PF=0");
+    }
+
+    #[test]
+    fn conditions_and_eflags() {
+        // 3 - 7：有符号小于、无符号低于
+        let (_, flags) = alu("CMP", 3, 7, 4, &clear()).unwrap();
+        let met: Vec<bool> =
+            ["L", "B", "G", "A", "NE", "LE"].iter().map(|c| condition_met(c, &flags).unwrap()).collect();
+        assert_eq!(met, [true, true, false, false, true, true]);
+        // -1 与 1 比较：有符号小于、无符号高于
+        let (_, flags) = alu("CMP", u32::MAX, 1, 4, &clear()).unwrap();
+        assert_eq!((condition_met("L", &flags), condition_met("A", &flags)), (Some(true), Some(true)));
+
+        let flags = FlagsState { carry: true, zero: true, overflow: true, ..clear() };
+        assert_eq!(eflags(&flags), 0xA43);
+        assert_eq!(eflags(&flags_from(0xA43)), 0xA43);
+    }
+
+    #[test]
+    fn divide_error() {
+        let program = assemble("mov eax, 7\nmov edx, 0\nmov ecx, 0\ndiv ecx\n");
+        let mut simulator = CPUSimulator::new(X86::default());
+        simulator.load_instructions(program.instructions).unwrap();
+        let error = (0..20).find_map(|_| simulator.step().err()).unwrap();
+        assert!(error.contains("（#DE）"), "{}", error);
+    }
+}
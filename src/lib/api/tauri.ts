@@ -1,32 +1,429 @@
 import { invoke } from '@tauri-apps/api/core';
-import type { Instruction, CPUState, CompilationStep } from '$lib/types/system';
+import type { Instruction, CPUState, CompilationStep, SegmentKind } from '$lib/types/system';
+
+// 源代码区间（字节偏移，行列号从 1 开始）
+export interface SourceSpan {
+  start: number;
+  end: number;
+  line: number;
+  column: number;
+}
+
+// 编译诊断信息
+export interface Diagnostic {
+  severity: 'Error' | 'Warning' | 'Info';
+  code: string;
+  message: string;
+  span: SourceSpan;
+}
+
+// 后端记录的单个编译阶段（真实的输入与输出）
+export interface CompilationStepRecord {
+  id: string;
+  stage: string;
+  input: string;
+  output: string;
+  description: string;
+  details: string[];
+}
+
+// 代码生成与模拟执行的目标体系结构
+export type Architecture = 'X86' | 'RiscV' | 'Mips';
+
+// 分支预测器：静态预测不转移、1 位、2 位饱和计数器、gshare、竞争预测器
+export type PredictorKind = 'StaticNotTaken' | 'OneBit' | 'TwoBit' | 'Gshare' | 'Tournament';
+
+// 中间代码优化遍
+export type OptimizationPass =
+  | 'ConstantPropagation'
+  | 'CopyPropagation'
+  | 'ConstantFolding'
+  | 'StrengthReduction'
+  | 'CommonSubexpressionElimination'
+  | 'DeadCodeElimination';
+
+// 单个优化遍一次执行的记录，before/after 为执行前后的中间代码
+export interface OptimizationReport {
+  pass: OptimizationPass;
+  round: number;
+  changes: string[];
+  before: string;
+  after: string;
+}
 
 // 编译结果类型
 export interface CompilationResult {
   success: boolean;
   instructions: Instruction[];
-  errors: string[];
-  warnings: string[];
+  errors: Diagnostic[];
+  warnings: Diagnostic[];
   compilation_time: number;
+  steps: CompilationStepRecord[];
+  optimizations: OptimizationReport[];
+}
+
+// 控制流图：基本块、边、支配关系与自然循环
+export interface BasicBlock {
+  id: number;
+  label: string | null;
+  function: string;
+  start: number;
+  end: number;
+  instructions: string[];
+  dominators: number[];
+  immediate_dominator: number | null;
+  is_loop_header: boolean;
+}
+
+export interface CfgEdge {
+  from: number;
+  to: number;
+  kind: 'Fallthrough' | 'Jump' | 'Taken' | 'NotTaken' | 'Call';
+}
+
+export interface NaturalLoop {
+  header: number;
+  back_edge_from: number;
+  blocks: number[];
+}
+
+export interface ControlFlowGraph {
+  blocks: BasicBlock[];
+  edges: CfgEdge[];
+  loops: NaturalLoop[];
+  dot: string;
+}
+
+// 数据段初始内存映像：从 base 开始的连续字节
+export interface MemoryImage {
+  base: number;
+  bytes: number[];
+}
+
+// 汇编源程序中定义的标签
+export interface AssemblySymbol {
+  name: string;
+  section: 'text' | 'data';
+  address: number;
+}
+
+// 汇编结果类型
+export interface AssemblyResult {
+  success: boolean;
+  instructions: Instruction[];
+  memory_image: MemoryImage;
+  symbols: AssemblySymbol[];
+  errors: Diagnostic[];
+}
+
+// 十六进制转储的一行（16 字节）
+export interface HexDumpLine {
+  address: number;
+  segment: SegmentKind | null;
+  bytes: number[];
+  hex: string;
+  ascii: string;
+}
+
+// 栈中的一个双字：kind 为其用途，label 为相对 EBP 或 ESP 的位置，如 [EBP-8]
+export interface StackSlot {
+  address: number;
+  value: number;
+  kind: 'ReturnAddress' | 'SavedFramePointer' | 'Local' | 'Data';
+  label: string;
+}
+
+// 一个栈帧，slots 从高地址到低地址排列
+export interface StackFrame {
+  function: string | null;
+  frame_pointer: number | null;
+  return_address: number | null;
+  slots: StackSlot[];
+}
+
+// 流水线冒险：producer 与 consumer 为指令下标，stall 表示该冒险造成了停顿
+export interface Hazard {
+  kind: 'ReadAfterWrite' | 'WriteAfterRead' | 'WriteAfterWrite' | 'Control';
+  register: string | null;
+  producer: number;
+  consumer: number;
+  stall: boolean;
+  description: string;
+}
+
+// 一次前递：from 为 Execute 时经 EX/MEM，为 MemoryAccess 时经 MEM/WB
+export interface Forwarding {
+  register: string;
+  from: string;
+  producer: number;
+  consumer: number;
+  value: number | null;
+}
+
+// 流水线某一级在本周期末的占用，instruction 为 null 时是气泡
+export interface PipelineSlot {
+  stage: string;
+  instruction: number | null;
+  sequence: number | null;
+  text: string;
+  stalled: boolean;
+  flushed: boolean;
+}
+
+// 一个时钟周期的流水线占用表，slots 依次为 IF、ID、EX、MEM、WB
+export interface PipelineCycle {
+  cycle: number;
+  slots: PipelineSlot[];
+  hazards: Hazard[];
+  forwardings: Forwarding[];
+  retired: number;
+  stalls: number;
+  flushes: number;
+}
+
+// 一条转移指令的预测统计，correct 为方向与目标都预测正确的次数
+export interface BranchStatistic {
+  instruction: number;
+  address: number;
+  text: string;
+  kind: 'Conditional' | 'Unconditional';
+  executed: number;
+  taken: number;
+  correct: number;
+  accuracy: number;
+}
+
+// 分支预测的统计：总体准确率、转移目标缓冲命中情况与预测错误损失的周期数
+export interface BranchStatistics {
+  predictor: PredictorKind;
+  executed: number;
+  correct: number;
+  accuracy: number;
+  btb_hits: number;
+  btb_misses: number;
+  penalty_cycles: number;
+  branches: BranchStatistic[];
+}
+
+// 一级缓存的配置：size、line_size（字节）与 associativity 均为 2 的幂
+export interface CacheLevelConfig {
+  size: number;
+  line_size: number;
+  associativity: number;
+  replacement: 'Lru' | 'Fifo' | 'Random' | 'Plru';
+  write_policy: 'WriteBack' | 'WriteThrough';
+  write_allocate: boolean;
+  hit_latency: number;
+}
+
+// 缓存层次的配置：L1 之后可以有 L2；enabled 为 false 时访存不经过缓存
+export interface CacheConfig {
+  enabled: boolean;
+  l1: CacheLevelConfig;
+  l2: CacheLevelConfig | null;
+  memory_latency: number;
+}
+
+// 一次访问在某一级缓存中的结果
+export interface CacheLevelAccess {
+  level: string;
+  operation: 'Read' | 'Write' | 'WriteBack';
+  line_address: number;
+  set: number;
+  tag: number;
+  hit: boolean;
+  evicted: number | null;
+  evicted_dirty: boolean;
+}
+
+// 一次访存经过缓存层次的情况，latency 为所需的周期数
+export interface CacheAccess {
+  address: number;
+  size: number;
+  write: boolean;
+  levels: CacheLevelAccess[];
+  memory: boolean;
+  latency: number;
+}
+
+// 缓存行的状态，bytes 取自内存
+export interface CacheLineView {
+  way: number;
+  valid: boolean;
+  dirty: boolean;
+  tag: number;
+  address: number;
+  bytes: number[];
+}
+
+export interface CacheSetView {
+  index: number;
+  lines: CacheLineView[];
+}
+
+export interface CacheLevelView {
+  level: string;
+  config: CacheLevelConfig;
+  hits: number;
+  misses: number;
+  hit_rate: number;
+  writebacks: number;
+  sets: CacheSetView[];
+}
+
+// 各级缓存的内容与命中统计
+export interface CacheView {
+  enabled: boolean;
+  memory_latency: number;
+  levels: CacheLevelView[];
+}
+
+// MMU 配置：未启用时虚拟地址即物理地址；TLB 全相联，按 LRU 替换
+export interface MmuConfig {
+  enabled: boolean;
+  tlb_entries: number;
+  user_mode: boolean;
+  walk_latency: number;
+}
+
+// 页的访问权限，user 为假的页只允许内核态访问
+export interface PagePermissions {
+  read: boolean;
+  write: boolean;
+  execute: boolean;
+  user: boolean;
+}
+
+export type AccessKind = 'Read' | 'Write' | 'Execute';
+
+export interface PageTableRead {
+  address: number;
+  value: number;
+}
+
+// 一次虚拟地址到物理地址的转换，steps 为逐步的说明；出现页错误时 physical_address 为 null
+export interface Translation {
+  virtual_address: number;
+  access: AccessKind;
+  directory_index: number;
+  table_index: number;
+  offset: number;
+  tlb_hit: boolean;
+  directory_entry: PageTableRead | null;
+  table_entry: PageTableRead | null;
+  physical_address: number | null;
+  fault: string | null;
+  cycles: number;
+  steps: string[];
+}
+
+export interface PageMapping {
+  virtual_address: number;
+  physical_address: number;
+  permissions: PagePermissions;
+  accessed: boolean;
+  dirty: boolean;
+  entry_address: number;
+}
+
+export interface TlbEntryView {
+  virtual_address: number;
+  physical_address: number;
+  permissions: PagePermissions;
+  dirty: boolean;
+}
+
+// 页表、TLB 的内容与转换统计
+export interface PageTableView {
+  config: MmuConfig;
+  directory: number;
+  mappings: PageMapping[];
+  tlb: TlbEntryView[];
+  tlb_hits: number;
+  tlb_misses: number;
+  page_faults: number;
+}
+
+export type InterruptKind =
+  | 'DivideError'
+  | 'InvalidOpcode'
+  | 'GeneralProtection'
+  | 'PageFault'
+  | 'Software'
+  | 'Timer'
+  | 'Return';
+
+// 中断描述符表的一项；user 为真时允许用户态以 INT n 调用，trap 为真时进入处理程序不关中断
+export interface InterruptGate {
+  handler: number;
+  user: boolean;
+  trap: boolean;
+}
+
+// 定时器每隔 timer_interval 个周期请求一次中断，为 0 时关闭
+export interface InterruptConfig {
+  timer_interval: number;
+}
+
+// 一次异常、中断或中断返回；中断返回时 vector 为 null
+export interface InterruptEvent {
+  kind: InterruptKind;
+  vector: number | null;
+  instruction: number | null;
+  target: number;
+  flags: number;
+  stack_pointer: number;
+  description: string;
 }
 
-// 执行结果类型
+export interface InterruptTableEntry {
+  vector: number;
+  gate: InterruptGate;
+  label: string | null;
+}
+
+// 中断描述符表中的有效项；timer_pending 表示定时器中断因关中断尚未响应
+export interface InterruptTableView {
+  base: number;
+  config: InterruptConfig;
+  entries: InterruptTableEntry[];
+  timer_pending: boolean;
+}
+
+// 执行结果类型；逐条执行时 pipeline 为 null，cache 为本步经过缓存的访存，translations 为本步的地址转换，
+// interrupts 为本步发生的异常、中断与中断返回
 export interface ExecutionResult {
   stage: string;
   instruction: Instruction | null;
   cpu_state: CPUState;
   message: string;
   cycle_count: number;
+  pipeline: PipelineCycle | null;
+  cache: CacheAccess[];
+  translations: Translation[];
+  interrupts: InterruptEvent[];
 }
 
 // API函数
 export const tauriAPI = {
-  // 编译代码
-  async compileCode(sourceCode: string, language: string): Promise<CompilationResult> {
+  // 编译代码：optimizationLevel 为 0 时不优化，passes 给出时优先于优化级别；
+  // architecture 默认为 X86，branchDelaySlot 只对 MIPS 有效
+  async compileCode(
+    sourceCode: string,
+    language: string,
+    optimizationLevel = 0,
+    passes?: OptimizationPass[],
+    architecture?: Architecture,
+    branchDelaySlot?: boolean
+  ): Promise<CompilationResult> {
     try {
       const result = await invoke<CompilationResult>('compile_code', {
         sourceCode,
-        language
+        language,
+        optimizationLevel,
+        passes,
+        architecture,
+        branchDelaySlot
       });
       return result;
     } catch (error) {
@@ -35,10 +432,50 @@ export const tauriAPI = {
     }
   },
 
-  // 加载指令到CPU模拟器
-  async loadInstructions(instructions: Instruction[]): Promise<void> {
+  // 构建指令序列的控制流图
+  async buildControlFlowGraph(instructions: Instruction[]): Promise<ControlFlowGraph> {
+    try {
+      const result = await invoke<ControlFlowGraph>('build_control_flow_graph', { instructions });
+      return result;
+    } catch (error) {
+      console.error('构建控制流图失败:', error);
+      throw error;
+    }
+  },
+
+  // 把十六进制机器码反汇编为指令序列
+  async disassembleMachineCode(machineCode: string, architecture?: Architecture): Promise<Instruction[]> {
+    try {
+      const result = await invoke<Instruction[]>('disassemble_machine_code', { machineCode, architecture });
+      return result;
+    } catch (error) {
+      console.error('反汇编失败:', error);
+      throw error;
+    }
+  },
+
+  // 汇编 Intel 语法的源程序，结果可直接交给 loadInstructions
+  async assembleCode(sourceCode: string): Promise<AssemblyResult> {
+    try {
+      const result = await invoke<AssemblyResult>('assemble_code', { sourceCode });
+      return result;
+    } catch (error) {
+      console.error('汇编失败:', error);
+      throw error;
+    }
+  },
+
+  // 加载指令到CPU模拟器，memoryImage 为数据段的初始内容，architecture 与 branchDelaySlot 须与编译时一致；
+  // predictor 默认为静态预测不转移
+  async loadInstructions(
+    instructions: Instruction[],
+    memoryImage?: MemoryImage,
+    architecture?: Architecture,
+    branchDelaySlot?: boolean,
+    predictor?: PredictorKind
+  ): Promise<void> {
     try {
-      await invoke('load_instructions', { instructions });
+      await invoke('load_instructions', { instructions, memoryImage, architecture, branchDelaySlot, predictor });
     } catch (error) {
       console.error('加载指令失败:', error);
       throw error;
@@ -76,6 +513,158 @@ export const tauriAPI = {
       console.error('获取CPU状态失败:', error);
       throw error;
     }
+  },
+
+  // 以十六进制转储从 address 开始的 length 字节（按 16 字节对齐成行，最多 4096 字节）
+  async dumpMemory(address: number, length: number): Promise<HexDumpLine[]> {
+    try {
+      const result = await invoke<HexDumpLine[]>('dump_memory', { address, length });
+      return result;
+    } catch (error) {
+      console.error('转储内存失败:', error);
+      throw error;
+    }
+  },
+
+  // 设置栈顶地址与栈的大小（字节，默认保持不变），模拟器随即复位
+  async configureStack(stackTop: number, stackSize?: number): Promise<CPUState> {
+    try {
+      const result = await invoke<CPUState>('configure_stack', { stackTop, stackSize });
+      return result;
+    } catch (error) {
+      console.error('设置栈失败:', error);
+      throw error;
+    }
+  },
+
+  // 按栈帧划分的栈视图，外层的帧在前，标注返回地址、保存的 EBP 与局部变量
+  async getStackFrames(): Promise<StackFrame[]> {
+    try {
+      const result = await invoke<StackFrame[]>('get_stack_frames');
+      return result;
+    } catch (error) {
+      console.error('获取栈帧失败:', error);
+      throw error;
+    }
+  },
+
+  // 开启或关闭流水线模式（每次单步推进一个时钟周期），forwarding 默认开启，模拟器随即复位
+  async configurePipeline(enabled: boolean, forwarding?: boolean): Promise<CPUState> {
+    try {
+      const result = await invoke<CPUState>('configure_pipeline', { enabled, forwarding });
+      return result;
+    } catch (error) {
+      console.error('设置流水线失败:', error);
+      throw error;
+    }
+  },
+
+  // 各条转移指令的分支预测统计
+  async getBranchStatistics(): Promise<BranchStatistics> {
+    try {
+      const result = await invoke<BranchStatistics>('get_branch_statistics');
+      return result;
+    } catch (error) {
+      console.error('获取分支预测统计失败:', error);
+      throw error;
+    }
+  },
+
+  // 设置缓存层次，模拟器随即复位
+  async configureCache(config: CacheConfig): Promise<CPUState> {
+    try {
+      const result = await invoke<CPUState>('configure_cache', { config });
+      return result;
+    } catch (error) {
+      console.error('设置缓存失败:', error);
+      throw error;
+    }
+  },
+
+  // 各级缓存每组中的行与命中统计
+  async getCacheView(): Promise<CacheView> {
+    try {
+      const result = await invoke<CacheView>('get_cache_view');
+      return result;
+    } catch (error) {
+      console.error('获取缓存内容失败:', error);
+      throw error;
+    }
+  },
+
+  // 设置 MMU，模拟器随即复位并重建页表
+  async configureMmu(config: MmuConfig): Promise<CPUState> {
+    try {
+      const result = await invoke<CPUState>('configure_mmu', { config });
+      return result;
+    } catch (error) {
+      console.error('设置 MMU 失败:', error);
+      throw error;
+    }
+  },
+
+  // 把虚拟页映射到物理页框，两者均须按 4 KiB 对齐
+  async mapPage(virtualAddress: number, physicalAddress: number, permissions: PagePermissions): Promise<PageTableView> {
+    try {
+      const result = await invoke<PageTableView>('map_page', { virtualAddress, physicalAddress, permissions });
+      return result;
+    } catch (error) {
+      console.error('映射页失败:', error);
+      throw error;
+    }
+  },
+
+  async unmapPage(virtualAddress: number): Promise<PageTableView> {
+    try {
+      const result = await invoke<PageTableView>('unmap_page', { virtualAddress });
+      return result;
+    } catch (error) {
+      console.error('取消映射失败:', error);
+      throw error;
+    }
+  },
+
+  // 页表中的有效映射、TLB 的内容与统计
+  async getPageTable(): Promise<PageTableView> {
+    try {
+      const result = await invoke<PageTableView>('get_page_table');
+      return result;
+    } catch (error) {
+      console.error('获取页表失败:', error);
+      throw error;
+    }
+  },
+
+  // 设置定时器中断，模拟器随即复位
+  async configureInterrupts(config: InterruptConfig): Promise<CPUState> {
+    try {
+      const result = await invoke<CPUState>('configure_interrupts', { config });
+      return result;
+    } catch (error) {
+      console.error('设置中断失败:', error);
+      throw error;
+    }
+  },
+
+  // 设置中断描述符表的一项，gate 为 null 时清除；标签 isr<n> 处的代码在载入时自动装入第 n 项
+  async setInterruptGate(vector: number, gate: InterruptGate | null): Promise<InterruptTableView> {
+    try {
+      const result = await invoke<InterruptTableView>('set_interrupt_gate', { vector, gate });
+      return result;
+    } catch (error) {
+      console.error('设置中断门失败:', error);
+      throw error;
+    }
+  },
+
+  async getInterruptTable(): Promise<InterruptTableView> {
+    try {
+      const result = await invoke<InterruptTableView>('get_interrupt_table');
+      return result;
+    } catch (error) {
+      console.error('获取中断描述符表失败:', error);
+      throw error;
+    }
   }
 };
 
@@ -83,7 +672,8 @@ export const tauriAPI = {
 export async function simulateCompilation(
   sourceCode: string, 
   language: string,
-  onStageUpdate?: (stage: CompilationStep) => void
+  onStageUpdate?: (stage: CompilationStep) => void,
+  optimizationLevel = 0
 ): Promise<CompilationResult> {
   const stages: CompilationStep[] = [
     {
@@ -167,6 +757,21 @@ export async function simulateCompilation(
       ],
       duration: 0
     },
+    {
+      id: 'register_allocation',
+      stage: '寄存器分配',
+      stageEn: 'Register Allocation',
+      status: 'pending',
+      input: '',
+      output: '',
+      description: '为变量和临时变量分配寄存器',
+      details: [
+        '活跃变量分析',
+        '线性扫描分配',
+        '溢出到栈帧'
+      ],
+      duration: 0
+    },
     {
       id: 'code_generation',
       stage: '目标代码生成',
@@ -182,46 +787,53 @@ export async function simulateCompilation(
         '地址计算'
       ],
       duration: 0
+    },
+    {
+      id: 'assembly',
+      stage: '汇编',
+      stageEn: 'Assembly',
+      status: 'pending',
+      input: '',
+      output: '',
+      description: '将汇编代码翻译为机器码',
+      details: [
+        '操作码编码',
+        '操作数编码',
+        '地址分配'
+      ],
+      duration: 0
     }
   ];
 
-  // 模拟逐步编译过程
+  // 先调用后端编译，再逐个展示各阶段的真实输入与输出
+  const result = await tauriAPI.compileCode(sourceCode, language, optimizationLevel);
+
   for (let i = 0; i < stages.length; i++) {
     const stage = stages[i];
     stage.status = 'in-progress';
     onStageUpdate?.(stage);
     
-    // 模拟处理时间
+    // 展示用的处理时间
     await new Promise(resolve => setTimeout(resolve, 500 + Math.random() * 1000));
     
-    stage.status = 'completed';
-    stage.duration = Math.floor(50 + Math.random() * 200);
-    
-    // 设置输出内容
-    switch (stage.id) {
-      case 'lexical_analysis':
-        stage.output = 'TOKEN_INT, TOKEN_IDENTIFIER(a), TOKEN_ASSIGN, TOKEN_NUMBER(5), TOKEN_SEMICOLON, ...';
-        break;
-      case 'syntax_analysis':
-        stage.output = '抽象语法树 (AST)';
-        break;
-      case 'semantic_analysis':
-        stage.output = '带类型信息的AST';
-        break;
-      case 'intermediate_code':
-        stage.output = '三地址码';
-        break;
-      case 'optimization':
-        stage.output = '优化后的中间代码';
-        break;
-      case 'code_generation':
-        stage.output = '汇编代码';
-        break;
+    const record = result.steps.find(step => step.id === stage.id);
+    if (record) {
+      stage.status = 'completed';
+      stage.input = record.input;
+      stage.output = record.output;
+      stage.description = record.description;
+      stage.details = record.details;
+    } else if (result.success) {
+      stage.status = 'completed';
+      stage.output = '（该阶段尚未实现）';
+    } else {
+      stage.status = 'error';
+      stage.output = '（前序阶段出错，该阶段未执行）';
     }
+    stage.duration = Math.floor(result.compilation_time / stages.length);
     
     onStageUpdate?.(stage);
   }
 
-  // 调用后端编译
-  return await tauriAPI.compileCode(sourceCode, language);
+  return result;
 }
@@ -19,16 +19,17 @@ const initialCPUState: CPUState = {
     }
   },
   memory: {
-    data: {},
-    stack: [],
-    heap: {}
+    segments: [],
+    bytes: {},
+    stack: []
   },
   flags: {
     zero: false,
     carry: false,
     overflow: false,
     negative: false,
-    parity: false
+    parity: false,
+    interrupt: true
   },
   programCounter: 2048,
   stackPointer: 1000,
@@ -147,6 +148,21 @@ export const compilationStages = writable<CompilationStep[]>([
     ],
     duration: 0
   },
+  {
+    id: 'register_allocation',
+    stage: '寄存器分配',
+    stageEn: 'Register Allocation',
+    status: 'pending',
+    input: '',
+    output: '',
+    description: '为变量和临时变量分配寄存器',
+    details: [
+      '活跃变量分析',
+      '线性扫描分配',
+      '溢出到栈帧'
+    ],
+    duration: 0
+  },
   {
     id: 'code_generation',
     stage: '目标代码生成',
@@ -162,6 +178,21 @@ export const compilationStages = writable<CompilationStep[]>([
       '地址计算'
     ],
     duration: 0
+  },
+  {
+    id: 'assembly',
+    stage: '汇编',
+    stageEn: 'Assembly',
+    status: 'pending',
+    input: '',
+    output: '',
+    description: '将汇编代码翻译为机器码',
+    details: [
+      '操作码编码',
+      '操作数编码',
+      '地址分配'
+    ],
+    duration: 0
   }
 ]);
 
@@ -48,6 +48,14 @@ export interface Instruction {
   machineCode: string;
   description: string;
   cycles: number;
+  label?: string; // 跳转目标标签
+  encoding?: EncodingField[]; // 机器码各字段的拆解
+}
+
+export interface EncodingField {
+  field: string;
+  hex: string;
+  description: string;
 }
 
 export type InstructionType = 
@@ -86,10 +94,22 @@ export interface RegisterState {
   special: { [key: string]: number };
 }
 
+// 按字节寻址的内存：bytes 只包含非零的字节
 export interface MemoryState {
-  data: { [address: number]: number };
+  segments: MemorySegment[];
+  bytes: { [address: number]: number };
   stack: number[];
-  heap: { [address: number]: number };
+}
+
+export type SegmentKind = 'Code' | 'Data' | 'Heap' | 'Stack' | 'PageTable' | 'InterruptTable';
+
+// 一段连续的地址空间 [base, base + size)
+export interface MemorySegment {
+  kind: SegmentKind;
+  name: string;
+  base: number;
+  size: number;
+  writable: boolean;
 }
 
 export interface FlagsState {
@@ -98,6 +118,8 @@ export interface FlagsState {
   overflow: boolean;
   negative: boolean;
   parity: boolean;
+  // IF：为真时响应定时器等可屏蔽中断
+  interrupt: boolean;
 }
 
 export type ExecutionStage = 
@@ -128,6 +150,7 @@ export type CompilationStage =
   | 'semantic_analysis' 
   | 'intermediate_code' 
   | 'optimization' 
+  | 'register_allocation' 
   | 'code_generation' 
   | 'assembly';
 